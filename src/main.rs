@@ -1,36 +1,591 @@
-mod modules;
+use anyhow::Result;
+#[cfg(windows)]
+use snipping_tool::modules::{
+    backend::WindowType,
+    barcode,
+    capture::{Capture, FrameSource},
+    clipboard,
+    commands::{AppCommand, CommandBus, CommandName, CommandQueue, CommandSource},
+    controller::{Command, WindowController},
+    dxgi_source::DxgiFrameSource,
+    naming::CaptureInfo,
+    ocr::OcrWord,
+    win_fact::{self, Win32Backend},
+};
+use snipping_tool::modules::{
+    cli::{
+        self, CaptureArgs, CliCommand, DecodeArgs, LibraryAction, LibraryArgs, OcrArgs,
+        OverlayArgs, RecordArgs, StitchArgs, TextArgs,
+    },
+    controller::CaptureMode,
+    errorhandler,
+    export::{self, ExportOptions},
+    library::{self, Library, LibraryEntry},
+    ocr, search,
+    table::{Table, TableOptions},
+    text::{self, TextOptions},
+};
 
-use modules::controller::WindowController;
+#[cfg(windows)]
+use anyhow::Context;
+#[cfg(windows)]
+use image::RgbaImage;
+#[cfg(windows)]
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+#[cfg(windows)]
+use windows::Win32::Foundation::{LPARAM, WPARAM};
+#[cfg(windows)]
+use windows::Win32::System::Threading::GetCurrentThreadId;
+#[cfg(windows)]
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    RegisterHotKey, UnregisterHotKey, MOD_NOREPEAT, VK_ESCAPE,
+};
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::*;
 
-use modules::handler::win_proc;
+// Während der Aufnahme ist das Overlay ausgeblendet und bekommt keine Tasten, daher Esc global
+#[cfg(windows)]
+const STOP_RECORDING_HOTKEY: i32 = 1;
 
-use modules::win_fact::{WindowBuilder, WindowType};
-use std::sync::Arc;
+fn open_library(directory: Option<&std::path::Path>) -> Result<Library> {
+    let directory = match directory {
+        Some(directory) => directory.to_path_buf(),
+        None => library::default_directory()
+            .ok_or_else(|| anyhow::anyhow!("No folder for the capture library found"))?,
+    };
+    Ok(Library::open(directory)?)
+}
+
+// Beschreibt die Aufnahme für Dateinamen und Bibliothek
+#[cfg(windows)]
+fn capture_info(
+    controller: &WindowController,
+    image: &RgbaImage,
+    window_title: Option<String>,
+    text: String,
+) -> CaptureInfo {
+    let region = controller.selection_bounds().unwrap_or_default();
+    CaptureInfo {
+        monitor: win_fact::monitor_index(region),
+        window_title,
+        ocr_text: Some(text),
+        ..CaptureInfo::new(image.width(), image.height())
+    }
+}
+
+// Die Bibliothek des Overlays; erst beim ersten Ablegen geöffnet, danach wiederverwendet
+#[cfg(windows)]
+type SharedLibrary = Rc<RefCell<Option<Library>>>;
 
-use windows::{core::*, Win32::UI::WindowsAndMessaging::*};
+// Legt die Aufnahme in der Bibliothek ab, damit sie nach PostQuitMessage nicht verloren ist.
+// Die Aufnahme ist zu diesem Zeitpunkt schon gespeichert oder kopiert, ein Fehler hier wird
+// deshalb nur gemeldet
+#[cfg(windows)]
+fn archive(
+    library: &SharedLibrary,
+    controller: &WindowController,
+    image: &RgbaImage,
+    info: &CaptureInfo,
+    words: &[OcrWord],
+) {
+    let mut library = library.borrow_mut();
+    if library.is_none() {
+        match open_library(None) {
+            Ok(opened) => *library = Some(opened),
+            Err(e) => {
+                errorhandler::report(e.as_ref());
+                return;
+            }
+        }
+    }
+    let region = controller.selection_bounds().unwrap_or_default();
+    if let Some(library) = library.as_mut() {
+        if let Err(e) = library
+            .add(image, region, info, words, &[])
+            .context("Failed to add the capture to the library")
+        {
+            errorhandler::report(e.as_ref());
+        }
+    }
+}
+
+// WM_NULL weckt die Nachrichtenschleife von einem anderen Thread aus, damit sie die
+// Warteschlange abarbeitet; false, wenn es die Schleife nicht mehr gibt
+#[cfg(windows)]
+fn wake(thread: u32) -> bool {
+    unsafe { PostThreadMessageW(thread, WM_NULL, WPARAM(0), LPARAM(0)) }.is_ok()
+}
+
+// Liest Befehle zeilenweise von der Standardeingabe
+#[cfg(windows)]
+fn listen_on_stdin(queue: CommandQueue) {
+    let thread = unsafe { GetCurrentThreadId() };
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else { break };
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Err(e) = queue.post_line(CommandSource::Ipc, &line) {
+                errorhandler::report(e.as_ref());
+                continue;
+            }
+            if !wake(thread) {
+                break;
+            }
+        }
+    });
+}
+
+// Speichert die Aufnahme, sobald der Aufnahme-Thread fertig ist; true, wenn das der Fall war
+#[cfg(windows)]
+fn save_finished_recording(controller: &WindowController, args: &RecordArgs) -> Result<bool> {
+    let finished = controller.finish_recording();
+    if matches!(finished, Ok(None)) {
+        return Ok(false);
+    }
+    let _ = unsafe { UnregisterHotKey(None, STOP_RECORDING_HOTKEY) };
 
-fn main() -> Result<()> {
+    if let Some(recording) = finished? {
+        cli::save_recording(&recording, args)?;
+        println!("{}", args.out.display());
+    }
+    Ok(true)
+}
+
+#[cfg(windows)]
+fn interactive(
+    mode: CaptureMode,
+    text_options: TextOptions,
+    recording: Option<RecordArgs>,
+    output: &OverlayArgs,
+) -> Result<()> {
     unsafe {
-        let controller = Arc::new(WindowController::new());
+        let backend = Win32Backend::new().context("Failed to create resource manager")?;
+        let mut controller = Box::new(WindowController::new(Box::new(backend)));
+        controller.set_mode(mode);
+
+        let source = DxgiFrameSource::new().context("Failed to open desktop duplication")?;
+        controller.set_capture(Capture::new(Box::new(source)))?;
+        // Die Aufnahme öffnet auf ihrem Thread eine eigene Duplizierung
+        controller.set_recording_source(Arc::new(|| {
+            let source = DxgiFrameSource::new().context("Failed to open desktop duplication")?;
+            Ok(Box::new(source) as Box<dyn FrameSource>)
+        }))?;
+
+        // Vor dem Overlay abfragen, danach ist es selbst das Vordergrundfenster
+        let window_title = win_fact::foreground_window_title();
+
+        let policy = output.policy()?;
+
+        // Menüs und Tooltips verschwinden, sobald das Overlay den Fokus bekommt
+        controller
+            .freeze()
+            .context("Failed to freeze the desktop")?;
 
-        let window = WindowBuilder::new()
-            .set_window_type(WindowType::Transparent)
-            .set_window_proc(win_proc)
-            .build()
-            .expect("Failed to create main window");
+        controller
+            .create_window(WindowType::Transparent)
+            .context("Failed to create main window")?;
 
         controller
-            .add_window(window.clone())
-            .expect("Failed to add window");
+            .dispatch(WindowType::Transparent, Command::Show)
+            .context("Failed to show window")?;
+
+        let library: SharedLibrary = Rc::default();
+        let mut bus = CommandBus::new(controller.commands().clone());
+        bus.register(
+            CommandName::Select,
+            Box::new(|controller: &WindowController, queued| {
+                if let AppCommand::Select { region } = queued.command {
+                    controller.select(region)?;
+                }
+                Ok(())
+            }),
+        );
+        let save_policy = policy.clone();
+        let shelf = library.clone();
+        let title = window_title.clone();
+        bus.register(
+            CommandName::Save,
+            Box::new(move |controller: &WindowController, queued| {
+                if let AppCommand::Save { path } = &queued.command {
+                    let image = controller.capture_selection()?;
+                    let info = capture_info(controller, &image, title.clone(), String::new());
+                    let path = match path {
+                        Some(path) => {
+                            export::save(&image, path, &ExportOptions::default())?;
+                            path.clone()
+                        }
+                        None => export::save_with_policy(
+                            &image,
+                            &save_policy,
+                            &info,
+                            &ExportOptions::default(),
+                        )?,
+                    };
+                    println!("{}", path.display());
+                    archive(&shelf, controller, &image, &info, &[]);
+                    PostQuitMessage(0);
+                }
+                Ok(())
+            }),
+        );
+        let shelf = library.clone();
+        let title = window_title.clone();
+        bus.register(
+            CommandName::Copy,
+            Box::new(move |controller: &WindowController, _| {
+                let image = controller.capture_selection()?;
+                clipboard::set_image(&image)?;
+                let info = capture_info(controller, &image, title.clone(), String::new());
+                archive(&shelf, controller, &image, &info, &[]);
+                PostQuitMessage(0);
+                Ok(())
+            }),
+        );
+        let shelf = library.clone();
+        let title = window_title.clone();
+        bus.register(
+            CommandName::Capture,
+            Box::new(move |controller: &WindowController, _| {
+                let image = controller.capture_selection()?;
+                clipboard::set_image(&image)?;
+
+                // Erkannt wird nur, wenn der Dateiname den Text braucht; sonst landet die
+                // Aufnahme ohne Text in der Bibliothek, durchsuchbar sind dann die Aufnahmen
+                // aus Strg+T und Strg+Umschalt+T
+                let words = if policy.template.has_ocr() {
+                    ocr::system_engine()?.recognize_words(&image)?
+                } else {
+                    Vec::new()
+                };
+                let text = text::assemble(&words, &TextOptions::default());
+                let info = capture_info(controller, &image, title.clone(), text);
+                let path =
+                    export::save_with_policy(&image, &policy, &info, &ExportOptions::default())?;
+                println!("{}", path.display());
+
+                archive(&shelf, controller, &image, &info, &words);
+                PostQuitMessage(0);
+                Ok(())
+            }),
+        );
+        let shelf = library.clone();
+        let title = window_title.clone();
+        bus.register(
+            CommandName::Ocr,
+            Box::new(move |controller: &WindowController, _| {
+                let image = controller.capture_selection()?;
+                let words = ocr::system_engine()?.recognize_words(&image)?;
+                let text = text::assemble(&words, &text_options);
+                clipboard::set_text(&text)?;
+                let info = capture_info(controller, &image, title.clone(), text);
+                archive(&shelf, controller, &image, &info, &words);
+                PostQuitMessage(0);
+                Ok(())
+            }),
+        );
+        let shelf = library.clone();
+        bus.register(
+            CommandName::Table,
+            Box::new(move |controller: &WindowController, queued| {
+                if let AppCommand::Table { format } = queued.command {
+                    let image = controller.capture_selection()?;
+                    let words = ocr::system_engine()?.recognize_words(&image)?;
+                    let table = Table::detect(&words, &TableOptions::default());
+                    let text = table.render(format);
+                    clipboard::set_text(&text)?;
+                    let info = capture_info(controller, &image, window_title.clone(), text);
+                    archive(&shelf, controller, &image, &info, &words);
+                    PostQuitMessage(0);
+                }
+                Ok(())
+            }),
+        );
+        bus.register(
+            CommandName::Decode,
+            Box::new(|controller: &WindowController, _| {
+                let codes = barcode::decode(&controller.selection_image()?);
+                let bounds: Vec<_> = codes.iter().map(|code| code.bounds).collect();
+                controller.set_highlights(&bounds)?;
 
-        window.show();
+                // Ohne Treffer bleibt die Zwischenablage unverändert
+                if !codes.is_empty() {
+                    let payloads: Vec<&str> =
+                        codes.iter().map(|code| code.payload.as_str()).collect();
+                    clipboard::set_text(&payloads.join("\n"))?;
+                }
+                Ok(())
+            }),
+        );
+        // Ohne `record --out FILE` fehlt das Ziel der Aufnahme. Gespeichert wird in der
+        // Nachrichtenschleife, sobald die Aufnahme endet.
+        let record_args = recording.clone();
+        bus.register(
+            CommandName::Record,
+            Box::new(move |controller: &WindowController, _| {
+                let args = record_args
+                    .as_ref()
+                    .context("Recording needs the record command with --out")?;
+                let thread = GetCurrentThreadId();
+                if controller.start_recording(&args.options, move || {
+                    wake(thread);
+                })? {
+                    // Ohne Hotkey endet die Aufnahme erst mit der Höchstdauer
+                    if let Err(e) = RegisterHotKey(
+                        None,
+                        STOP_RECORDING_HOTKEY,
+                        MOD_NOREPEAT,
+                        VK_ESCAPE.0 as u32,
+                    ) {
+                        errorhandler::report(&e);
+                    }
+                }
+                Ok(())
+            }),
+        );
+        bus.register(
+            CommandName::Annotate,
+            Box::new(|controller: &WindowController, _| {
+                controller.annotate()?;
+                Ok(())
+            }),
+        );
+        bus.register(
+            CommandName::Undo,
+            Box::new(|controller: &WindowController, _| {
+                controller.undo()?;
+                Ok(())
+            }),
+        );
+        bus.register(
+            CommandName::Redo,
+            Box::new(|controller: &WindowController, _| {
+                controller.redo()?;
+                Ok(())
+            }),
+        );
+        bus.register(
+            CommandName::Cancel,
+            Box::new(|controller: &WindowController, _| {
+                controller.dispatch(WindowType::Transparent, Command::Hide)?;
+                PostQuitMessage(0);
+                Ok(())
+            }),
+        );
+
+        for command in &output.commands {
+            controller
+                .commands()
+                .post(CommandSource::Cli, command.clone());
+        }
+        if output.stdin {
+            listen_on_stdin(controller.commands().clone());
+        }
 
         let mut msg = MSG::default();
         while GetMessageA(&mut msg, None, 0, 0).into() {
+            if msg.message == WM_HOTKEY && msg.wParam.0 == STOP_RECORDING_HOTKEY as usize {
+                controller.stop_recording()?;
+                continue;
+            }
             TranslateMessage(&msg);
             DispatchMessageA(&msg);
+
+            // Ein fehlgeschlagener Befehl beendet das Overlay nicht, der eingefrorene Desktop
+            // bleibt für den nächsten Versuch stehen
+            if let Err(e) = bus.run_pending(&controller) {
+                errorhandler::report(e.as_ref());
+                if !controller.is_recording() {
+                    if let Err(e) = controller.dispatch(WindowType::Transparent, Command::Show) {
+                        errorhandler::report(e.as_ref());
+                        PostQuitMessage(1);
+                    }
+                }
+            }
+            if let Some(args) = &recording {
+                match save_finished_recording(&controller, args) {
+                    Ok(true) => PostQuitMessage(0),
+                    Ok(false) => {}
+                    Err(e) => {
+                        errorhandler::report(e.as_ref());
+                        PostQuitMessage(1);
+                    }
+                }
+            }
         }
 
         Ok(())
     }
 }
+
+#[cfg(not(windows))]
+fn interactive(
+    _mode: CaptureMode,
+    _text_options: TextOptions,
+    _recording: Option<RecordArgs>,
+    _output: &OverlayArgs,
+) -> Result<()> {
+    Err(anyhow::anyhow!(
+        "The interactive snipping overlay is only available on Windows"
+    ))
+}
+
+// Kein Fenster: Frame holen, zuschneiden, speichern
+fn capture(args: &CaptureArgs) -> Result<()> {
+    let engine = if args.searchable || args.redact_pii {
+        Some(ocr::system_engine()?)
+    } else {
+        None
+    };
+    let path = cli::run_capture(
+        args,
+        cli::frame_source(args.source.as_deref())?,
+        engine.as_deref(),
+    )?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+fn recognize(args: &OcrArgs) -> Result<()> {
+    let engine = ocr::system_engine()?;
+    let result = cli::run_ocr(args, cli::frame_source(args.source.as_deref())?, &*engine)?;
+    let words = result.into_words();
+    let output = match args.table {
+        Some(format) => Table::detect(&words, &TableOptions::default()).render(format),
+        None => text::assemble(&words, &TextOptions::default()),
+    };
+    println!("{}", output);
+    Ok(())
+}
+
+fn decode(args: &DecodeArgs) -> Result<()> {
+    let codes = cli::run_decode(args, cli::frame_source(args.source.as_deref())?)?;
+    if codes.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No QR code or barcode found in {}",
+            args.region
+        ));
+    }
+    for code in codes {
+        println!("{}: {}", code.symbology, code.payload);
+    }
+    Ok(())
+}
+
+fn browse(args: &LibraryArgs) -> Result<()> {
+    let mut library = open_library(args.directory.as_deref())?;
+    let hits: Vec<(&LibraryEntry, String)> = match &args.action {
+        LibraryAction::List => library
+            .list()
+            .into_iter()
+            .map(|entry| (entry, String::new()))
+            .collect(),
+        LibraryAction::Search(query) => library
+            .search(query)
+            .into_iter()
+            .map(|hit| {
+                // Fundstellen im Text als Vorschau, auf eine Zeile gebracht
+                let text = hit.entry.text.as_deref().unwrap_or_default();
+                let marked = search::mark(text, &hit.highlights, "[", "]");
+                let snippet = marked.split_whitespace().collect::<Vec<_>>().join(" ");
+                (hit.entry, snippet)
+            })
+            .collect(),
+        LibraryAction::Open { id, out } => {
+            export::save(&library.reopen(*id)?, out, &ExportOptions::default())?;
+            println!("{}", out.display());
+            return Ok(());
+        }
+        LibraryAction::Delete(id) => {
+            let entry = library.delete(*id)?;
+            println!("Deleted capture {}", entry.id);
+            return Ok(());
+        }
+        LibraryAction::Tag { id, tags } => {
+            library.set_tags(*id, tags)?;
+            return Ok(());
+        }
+    };
+
+    for (entry, snippet) in hits {
+        println!(
+            "{}\t{}\t{}x{}\t{}\t{}\t{}",
+            entry.id,
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            entry.region.width,
+            entry.region.height,
+            entry.window_title.as_deref().unwrap_or("-"),
+            entry.tags.join(","),
+            library.path_of(&entry.image).display()
+        );
+        if !snippet.is_empty() {
+            println!("\t{}", snippet);
+        }
+    }
+    Ok(())
+}
+
+fn record(args: &RecordArgs) -> Result<()> {
+    let path = cli::run_record(args, cli::frame_source(args.source.as_deref())?)?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+fn stitch(args: &StitchArgs) -> Result<()> {
+    let path = cli::run_stitch(args)?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+fn main() {
+    // Vor jedem Fenster und jeder Abfrage des Bildschirms, sonst passen Koordinaten und Frames
+    // auf skalierten Monitoren nicht zusammen
+    #[cfg(windows)]
+    win_fact::enable_dpi_awareness();
+
+    let command = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            errorhandler::report(&e);
+            std::process::exit(2);
+        }
+    };
+
+    let result = match command {
+        CliCommand::Interactive(output) => {
+            interactive(CaptureMode::Image, TextOptions::default(), None, &output)
+        }
+        CliCommand::Text(TextArgs { options, table }) => {
+            let mode = table.map_or(CaptureMode::Text, CaptureMode::Table);
+            interactive(mode, options, None, &OverlayArgs::default())
+        }
+        CliCommand::Codes => interactive(
+            CaptureMode::Codes,
+            TextOptions::default(),
+            None,
+            &OverlayArgs::default(),
+        ),
+        CliCommand::Capture(args) => capture(&args),
+        CliCommand::Ocr(args) => recognize(&args),
+        CliCommand::Decode(args) => decode(&args),
+        CliCommand::Record(args) if args.region.is_some() => record(&args),
+        CliCommand::Record(args) => interactive(
+            CaptureMode::Record,
+            TextOptions::default(),
+            Some(args),
+            &OverlayArgs::default(),
+        ),
+        CliCommand::Stitch(args) => stitch(&args),
+        CliCommand::Library(args) => browse(&args),
+        CliCommand::Help => {
+            println!("{}", cli::USAGE);
+            Ok(())
+        }
+    };
+
+    if let Err(e) = result {
+        errorhandler::report(e.as_ref());
+        std::process::exit(1);
+    }
+}
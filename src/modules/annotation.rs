@@ -0,0 +1,877 @@
+use crate::modules::backend::{Color, Rect};
+use crate::modules::history::{Edit, History};
+use crate::modules::{font, raster};
+use image::RgbaImage;
+
+pub const HIGHLIGHT_COLOR: Color = Color::new(1.0, 0.93, 0.2, 1.0);
+pub const MARKER_COLOR: Color = Color::new(0.9, 0.1, 0.1, 1.0);
+pub const MARKER_WIDTH: f32 = 3.0;
+const TEXT_COLOR: Color = Color::new(0.0, 0.0, 0.0, 1.0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stroke {
+    pub color: Color,
+    pub width: f32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Style {
+    pub stroke: Option<Stroke>,
+    pub fill: Option<Color>,
+}
+
+impl Style {
+    pub fn stroked(color: Color, width: f32) -> Self {
+        Style {
+            stroke: Some(Stroke { color, width }),
+            fill: None,
+        }
+    }
+
+    pub fn filled(color: Color) -> Self {
+        Style {
+            stroke: None,
+            fill: Some(color),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShapeKind {
+    Rectangle(Rect),
+    Ellipse(Rect),
+    Line {
+        from: (f32, f32),
+        to: (f32, f32),
+    },
+    // Spitze bei `to`
+    Arrow {
+        from: (f32, f32),
+        to: (f32, f32),
+    },
+    Freehand(Vec<(f32, f32)>),
+    // `origin` ist die linke obere Ecke, `size` die Höhe der Großbuchstaben in Pixeln
+    Text {
+        origin: (f32, f32),
+        text: String,
+        size: f32,
+    },
+    Highlight(Rect),
+}
+
+impl ShapeKind {
+    // Umschließendes Rechteck ohne Strichbreite
+    pub fn bounds(&self) -> Rect {
+        let points = |points: &[(f32, f32)]| {
+            points.iter().fold(
+                Rect::new(f32::MAX, f32::MAX, f32::MIN, f32::MIN),
+                |r, &(x, y)| {
+                    Rect::new(r.left.min(x), r.top.min(y), r.right.max(x), r.bottom.max(y))
+                },
+            )
+        };
+
+        match self {
+            ShapeKind::Rectangle(rect) | ShapeKind::Ellipse(rect) | ShapeKind::Highlight(rect) => {
+                rect.normalized()
+            }
+            ShapeKind::Line { from, to } | ShapeKind::Arrow { from, to } => points(&[*from, *to]),
+            ShapeKind::Freehand(path) if path.is_empty() => Rect::default(),
+            ShapeKind::Freehand(path) => points(path),
+            ShapeKind::Text { origin, text, size } => font::text_bounds(text, *origin, *size),
+        }
+    }
+
+    pub fn translate(&mut self, dx: f32, dy: f32) {
+        let offset = |point: &mut (f32, f32)| {
+            point.0 += dx;
+            point.1 += dy;
+        };
+
+        match self {
+            ShapeKind::Rectangle(rect) | ShapeKind::Ellipse(rect) | ShapeKind::Highlight(rect) => {
+                *rect = rect.offset(dx, dy)
+            }
+            ShapeKind::Line { from, to } | ShapeKind::Arrow { from, to } => {
+                offset(from);
+                offset(to);
+            }
+            ShapeKind::Freehand(path) => path.iter_mut().for_each(offset),
+            ShapeKind::Text { origin, .. } => offset(origin),
+        }
+    }
+}
+
+// Werkzeug, mit dem im Overlay neue Formen aufgezogen werden
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tool {
+    #[default]
+    Rectangle,
+    Ellipse,
+    Line,
+    Arrow,
+    Freehand,
+    Highlight,
+}
+
+impl Tool {
+    // Taste im Overlay, Buchstaben wie bei selection::Key groß
+    pub fn from_key(key: char) -> Option<Tool> {
+        match key {
+            'R' => Some(Tool::Rectangle),
+            'O' => Some(Tool::Ellipse),
+            'L' => Some(Tool::Line),
+            'A' => Some(Tool::Arrow),
+            'F' => Some(Tool::Freehand),
+            'H' => Some(Tool::Highlight),
+            _ => None,
+        }
+    }
+
+    pub fn style(&self) -> Style {
+        match self {
+            Tool::Highlight => Style::filled(HIGHLIGHT_COLOR),
+            _ => Style::stroked(MARKER_COLOR, MARKER_WIDTH),
+        }
+    }
+
+    // Form beim Ziehen von `from` nach `to`
+    pub fn shape(&self, from: (f32, f32), to: (f32, f32)) -> ShapeKind {
+        let rect = Rect::new(from.0, from.1, to.0, to.1).normalized();
+        match self {
+            Tool::Rectangle => ShapeKind::Rectangle(rect),
+            Tool::Ellipse => ShapeKind::Ellipse(rect),
+            Tool::Line => ShapeKind::Line { from, to },
+            Tool::Arrow => ShapeKind::Arrow { from, to },
+            Tool::Freehand => ShapeKind::Freehand(vec![from, to]),
+            Tool::Highlight => ShapeKind::Highlight(rect),
+        }
+    }
+
+    // Freihandlinien wachsen um den neuen Punkt, alle anderen Formen werden neu aufgezogen
+    pub fn extend(&self, kind: &mut ShapeKind, from: (f32, f32), to: (f32, f32)) {
+        match kind {
+            ShapeKind::Freehand(path) if *self == Tool::Freehand => path.push(to),
+            kind => *kind = self.shape(from, to),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShapeId(pub u64);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shape {
+    pub id: ShapeId,
+    pub kind: ShapeKind,
+    pub style: Style,
+    // Größere Werte liegen oben
+    pub z: i32,
+}
+
+// Dreieck der Pfeilspitze; die Linie endet an der Basis, damit sie nicht über die Spitze ragt
+pub fn arrow_head(from: (f32, f32), to: (f32, f32), width: f32) -> [(f32, f32); 3] {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+    let (ux, uy) = (dx / length, dy / length);
+
+    let head = (width * 4.0).max(10.0).min(length);
+    let half = head / 2.0;
+    let base = (to.0 - ux * head, to.1 - uy * head);
+
+    [
+        to,
+        (base.0 - uy * half, base.1 + ux * half),
+        (base.0 + uy * half, base.1 - ux * half),
+    ]
+}
+
+pub fn render_shape(target: &mut RgbaImage, shape: &Shape) {
+    let style = &shape.style;
+
+    match &shape.kind {
+        ShapeKind::Rectangle(rect) => {
+            if let Some(fill) = style.fill {
+                raster::fill_rect(target, rect, fill);
+            }
+            if let Some(stroke) = style.stroke {
+                raster::stroke_rect(target, rect, stroke.color, stroke.width);
+            }
+        }
+        ShapeKind::Ellipse(rect) => {
+            if let Some(fill) = style.fill {
+                raster::fill_ellipse(target, rect, fill);
+            }
+            if let Some(stroke) = style.stroke {
+                raster::stroke_ellipse(target, rect, stroke.color, stroke.width);
+            }
+        }
+        ShapeKind::Line { from, to } => {
+            if let Some(stroke) = style.stroke {
+                raster::stroke_line(target, *from, *to, stroke.color, stroke.width);
+            }
+        }
+        ShapeKind::Arrow { from, to } => {
+            if let Some(stroke) = style.stroke {
+                let head = arrow_head(*from, *to, stroke.width);
+                let base = ((head[1].0 + head[2].0) / 2.0, (head[1].1 + head[2].1) / 2.0);
+                raster::stroke_line(target, *from, base, stroke.color, stroke.width);
+                raster::fill_polygon(target, &head, stroke.color);
+            }
+        }
+        ShapeKind::Freehand(path) => {
+            if let Some(stroke) = style.stroke {
+                raster::stroke_polyline(target, path, stroke.color, stroke.width);
+            }
+        }
+        ShapeKind::Text { origin, text, size } => {
+            let color = text_color(style);
+            for rect in font::text_rects(text, *origin, *size) {
+                raster::fill_rect(target, &rect, color);
+            }
+        }
+        ShapeKind::Highlight(rect) => {
+            raster::darken_rect(target, rect, style.fill.unwrap_or(HIGHLIGHT_COLOR));
+        }
+    }
+}
+
+// Text wird mit der Füllfarbe gezeichnet, ersatzweise mit der Strichfarbe
+pub fn text_color(style: &Style) -> Color {
+    style
+        .fill
+        .or(style.stroke.map(|stroke| stroke.color))
+        .unwrap_or(TEXT_COLOR)
+}
+
+pub fn render_shapes(target: &mut RgbaImage, shapes: &[Shape]) {
+    for shape in shapes {
+        render_shape(target, shape);
+    }
+}
+
+// Die Aufnahme und die darüber liegenden Formen, nach z sortiert
+#[derive(Clone, Debug)]
+pub struct AnnotationDocument {
+    base: RgbaImage,
+    shapes: Vec<Shape>,
+    next_id: u64,
+}
+
+impl AnnotationDocument {
+    pub fn new(base: RgbaImage) -> Self {
+        AnnotationDocument {
+            base,
+            shapes: Vec::new(),
+            next_id: 1,
+        }
+    }
+
+    pub fn base(&self) -> &RgbaImage {
+        &self.base
+    }
+
+    // Von unten nach oben
+    pub fn shapes(&self) -> &[Shape] {
+        &self.shapes
+    }
+
+    pub fn shape(&self, id: ShapeId) -> Option<&Shape> {
+        self.shapes.iter().find(|shape| shape.id == id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shapes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    fn top_z(&self) -> i32 {
+        self.shapes.last().map_or(0, |shape| shape.z)
+    }
+
+    fn sort(&mut self) {
+        // Stabil, bei gleichem z bleibt die Einfügereihenfolge erhalten
+        self.shapes.sort_by_key(|shape| shape.z);
+    }
+
+    // Neue Formen landen ganz oben
+    pub fn add(&mut self, kind: ShapeKind, style: Style) -> ShapeId {
+        let id = ShapeId(self.next_id);
+        self.next_id += 1;
+        let z = if self.shapes.is_empty() {
+            0
+        } else {
+            self.top_z() + 1
+        };
+        self.shapes.push(Shape { id, kind, style, z });
+        id
+    }
+
+    // Fügt eine bereits vorhandene Form wieder ein, z.B. beim Rückgängigmachen
+    pub fn insert(&mut self, shape: Shape) {
+        self.next_id = self.next_id.max(shape.id.0 + 1);
+        self.shapes.retain(|existing| existing.id != shape.id);
+        self.shapes.push(shape);
+        self.sort();
+    }
+
+    pub fn remove(&mut self, id: ShapeId) -> Option<Shape> {
+        let index = self.shapes.iter().position(|shape| shape.id == id)?;
+        Some(self.shapes.remove(index))
+    }
+
+    // Ändert Geometrie oder Stil; z wird über set_z geändert, damit die Reihenfolge stimmt
+    pub fn update<F: FnOnce(&mut ShapeKind, &mut Style)>(&mut self, id: ShapeId, f: F) -> bool {
+        match self.shapes.iter_mut().find(|shape| shape.id == id) {
+            Some(shape) => {
+                f(&mut shape.kind, &mut shape.style);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_z(&mut self, id: ShapeId, z: i32) -> bool {
+        match self.shapes.iter_mut().find(|shape| shape.id == id) {
+            Some(shape) => {
+                shape.z = z;
+                self.sort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn bring_to_front(&mut self, id: ShapeId) -> bool {
+        let z = self.top_z() + 1;
+        self.set_z(id, z)
+    }
+
+    pub fn send_to_back(&mut self, id: ShapeId) -> bool {
+        let z = self.shapes.first().map_or(0, |shape| shape.z) - 1;
+        self.set_z(id, z)
+    }
+
+    // Oberste Form unter dem Punkt
+    pub fn hit_test(&self, x: f32, y: f32) -> Option<ShapeId> {
+        self.shapes
+            .iter()
+            .rev()
+            .find(|shape| shape.kind.bounds().contains(x, y))
+            .map(|shape| shape.id)
+    }
+
+    // Brennt alle Formen in eine Kopie der Aufnahme ein
+    pub fn flatten(&self) -> RgbaImage {
+        let mut image = self.base.clone();
+        render_shapes(&mut image, &self.shapes);
+        image
+    }
+}
+
+// Änderung am Dokument für den Verlauf; Formen werden vollständig gespeichert,
+// damit auch z und Stil beim Rückgängigmachen stimmen
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnnotationEdit {
+    Add(Shape),
+    Remove(Shape),
+    Modify { before: Shape, after: Shape },
+}
+
+fn shape_size(shape: &Shape) -> usize {
+    let extra = match &shape.kind {
+        ShapeKind::Freehand(path) => path.len() * std::mem::size_of::<(f32, f32)>(),
+        ShapeKind::Text { text, .. } => text.len(),
+        _ => 0,
+    };
+    std::mem::size_of::<Shape>() + extra
+}
+
+impl Edit for AnnotationEdit {
+    type Target = AnnotationDocument;
+
+    fn apply(&self, document: &mut AnnotationDocument) {
+        match self {
+            AnnotationEdit::Add(shape) => document.insert(shape.clone()),
+            AnnotationEdit::Remove(shape) => {
+                document.remove(shape.id);
+            }
+            AnnotationEdit::Modify { after, .. } => document.insert(after.clone()),
+        }
+    }
+
+    fn revert(&self, document: &mut AnnotationDocument) {
+        match self {
+            AnnotationEdit::Add(shape) => {
+                document.remove(shape.id);
+            }
+            AnnotationEdit::Remove(shape) => document.insert(shape.clone()),
+            AnnotationEdit::Modify { before, .. } => document.insert(before.clone()),
+        }
+    }
+
+    // Aufeinanderfolgende Änderungen derselben Form, z.B. beim Verschieben
+    fn merge(&mut self, next: &Self) -> bool {
+        match (self, next) {
+            (
+                AnnotationEdit::Modify { after, .. },
+                AnnotationEdit::Modify {
+                    before: next_before,
+                    after: next_after,
+                },
+            ) if after.id == next_before.id => {
+                *after = next_after.clone();
+                true
+            }
+            (AnnotationEdit::Add(shape), AnnotationEdit::Modify { before, after })
+                if shape.id == before.id =>
+            {
+                *shape = after.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn is_noop(&self) -> bool {
+        matches!(self, AnnotationEdit::Modify { before, after } if before == after)
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            AnnotationEdit::Add(shape) | AnnotationEdit::Remove(shape) => shape_size(shape),
+            AnnotationEdit::Modify { before, after } => shape_size(before) + shape_size(after),
+        }
+    }
+}
+
+// Dokument mit Verlauf; alle Änderungen über den Editor lassen sich rückgängig machen
+#[derive(Clone, Debug)]
+pub struct AnnotationEditor {
+    document: AnnotationDocument,
+    history: History<AnnotationEdit>,
+}
+
+impl AnnotationEditor {
+    pub fn new(base: RgbaImage) -> Self {
+        AnnotationEditor::with_history(base, History::new())
+    }
+
+    pub fn with_history(base: RgbaImage, history: History<AnnotationEdit>) -> Self {
+        AnnotationEditor {
+            document: AnnotationDocument::new(base),
+            history,
+        }
+    }
+
+    pub fn document(&self) -> &AnnotationDocument {
+        &self.document
+    }
+
+    pub fn history(&self) -> &History<AnnotationEdit> {
+        &self.history
+    }
+
+    pub fn add(&mut self, kind: ShapeKind, style: Style) -> ShapeId {
+        let id = self.document.add(kind, style);
+        if let Some(shape) = self.document.shape(id) {
+            self.history.record(AnnotationEdit::Add(shape.clone()));
+        }
+        id
+    }
+
+    pub fn remove(&mut self, id: ShapeId) -> bool {
+        match self.document.remove(id) {
+            Some(shape) => {
+                self.history.record(AnnotationEdit::Remove(shape));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn modify<F: FnOnce(&mut AnnotationDocument) -> bool>(&mut self, id: ShapeId, f: F) -> bool {
+        let before = match self.document.shape(id) {
+            Some(shape) => shape.clone(),
+            None => return false,
+        };
+        if !f(&mut self.document) {
+            return false;
+        }
+        if let Some(after) = self.document.shape(id) {
+            let after = after.clone();
+            self.history
+                .record(AnnotationEdit::Modify { before, after });
+        }
+        true
+    }
+
+    pub fn update<F: FnOnce(&mut ShapeKind, &mut Style)>(&mut self, id: ShapeId, f: F) -> bool {
+        self.modify(id, |document| document.update(id, f))
+    }
+
+    pub fn translate(&mut self, id: ShapeId, dx: f32, dy: f32) -> bool {
+        self.update(id, |kind, _| kind.translate(dx, dy))
+    }
+
+    pub fn set_z(&mut self, id: ShapeId, z: i32) -> bool {
+        self.modify(id, |document| document.set_z(id, z))
+    }
+
+    pub fn bring_to_front(&mut self, id: ShapeId) -> bool {
+        self.modify(id, |document| document.bring_to_front(id))
+    }
+
+    pub fn send_to_back(&mut self, id: ShapeId) -> bool {
+        self.modify(id, |document| document.send_to_back(id))
+    }
+
+    // Zwischen begin_gesture und end_gesture wird z.B. ein Verschieben mit der Maus
+    // oder das Zeichnen einer Freihandlinie zu einem einzigen Schritt
+    pub fn begin_gesture(&mut self) {
+        self.history.begin_gesture();
+    }
+
+    pub fn end_gesture(&mut self) {
+        self.history.end_gesture();
+    }
+
+    pub fn undo(&mut self) -> bool {
+        self.history.undo(&mut self.document)
+    }
+
+    pub fn redo(&mut self) -> bool {
+        self.history.redo(&mut self.document)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    const BLUE: Color = Color::new(0.0, 0.0, 1.0, 1.0);
+
+    fn base() -> RgbaImage {
+        RgbaImage::from_pixel(20, 10, Rgba([255, 255, 255, 255]))
+    }
+
+    fn rect(left: f32, top: f32, right: f32, bottom: f32) -> ShapeKind {
+        ShapeKind::Rectangle(Rect::new(left, top, right, bottom))
+    }
+
+    fn order(document: &AnnotationDocument) -> Vec<u64> {
+        document.shapes().iter().map(|shape| shape.id.0).collect()
+    }
+
+    #[test]
+    fn tools_build_shapes() {
+        assert_eq!(Tool::from_key('R'), Some(Tool::Rectangle));
+        assert_eq!(Tool::from_key('F'), Some(Tool::Freehand));
+        assert_eq!(Tool::from_key('r'), None);
+        assert_eq!(Tool::from_key('X'), None);
+
+        // Nach oben links gezogen ergibt trotzdem ein normales Rechteck
+        assert_eq!(
+            Tool::Rectangle.shape((8.0, 6.0), (2.0, 1.0)),
+            rect(2.0, 1.0, 8.0, 6.0)
+        );
+        assert_eq!(
+            Tool::Arrow.shape((8.0, 6.0), (2.0, 1.0)),
+            ShapeKind::Arrow {
+                from: (8.0, 6.0),
+                to: (2.0, 1.0)
+            }
+        );
+        assert_eq!(Tool::Highlight.style(), Style::filled(HIGHLIGHT_COLOR));
+        assert_eq!(
+            Tool::Line.style(),
+            Style::stroked(MARKER_COLOR, MARKER_WIDTH)
+        );
+
+        let mut kind = Tool::Freehand.shape((0.0, 0.0), (1.0, 1.0));
+        Tool::Freehand.extend(&mut kind, (0.0, 0.0), (2.0, 3.0));
+        assert_eq!(
+            kind,
+            ShapeKind::Freehand(vec![(0.0, 0.0), (1.0, 1.0), (2.0, 3.0)])
+        );
+        let mut kind = Tool::Ellipse.shape((0.0, 0.0), (1.0, 1.0));
+        Tool::Ellipse.extend(&mut kind, (0.0, 0.0), (4.0, 2.0));
+        assert_eq!(kind, ShapeKind::Ellipse(Rect::new(0.0, 0.0, 4.0, 2.0)));
+    }
+
+    #[test]
+    fn bounds_and_translation() {
+        let mut line = ShapeKind::Line {
+            from: (5.0, 1.0),
+            to: (2.0, 4.0),
+        };
+        assert_eq!(line.bounds(), Rect::new(2.0, 1.0, 5.0, 4.0));
+        line.translate(1.0, -1.0);
+        assert_eq!(line.bounds(), Rect::new(3.0, 0.0, 6.0, 3.0));
+
+        let mut path = ShapeKind::Freehand(vec![(1.0, 1.0), (3.0, 0.0), (2.0, 5.0)]);
+        assert_eq!(path.bounds(), Rect::new(1.0, 0.0, 3.0, 5.0));
+        path.translate(2.0, 2.0);
+        assert_eq!(path.bounds(), Rect::new(3.0, 2.0, 5.0, 7.0));
+        assert_eq!(ShapeKind::Freehand(Vec::new()).bounds(), Rect::default());
+
+        let mut text = ShapeKind::Text {
+            origin: (1.0, 2.0),
+            text: "AB".to_string(),
+            size: 7.0,
+        };
+        assert_eq!(text.bounds(), font::text_bounds("AB", (1.0, 2.0), 7.0));
+        text.translate(3.0, 3.0);
+        assert_eq!(text.bounds(), font::text_bounds("AB", (4.0, 5.0), 7.0));
+
+        assert_eq!(
+            ShapeKind::Highlight(Rect::new(4.0, 4.0, 1.0, 1.0)).bounds(),
+            Rect::new(1.0, 1.0, 4.0, 4.0)
+        );
+    }
+
+    #[test]
+    fn arrow_head_points_at_the_target() {
+        let [tip, left, right] = arrow_head((0.0, 0.0), (20.0, 0.0), 2.0);
+        assert_eq!(tip, (20.0, 0.0));
+        // Mindestens 10 Pixel lang und halb so breit
+        assert_eq!(left, (10.0, 5.0));
+        assert_eq!(right, (10.0, -5.0));
+
+        // Bei kurzen Pfeilen nie länger als der Pfeil selbst
+        let [_, left, _] = arrow_head((0.0, 0.0), (0.0, 4.0), 3.0);
+        assert_eq!(left.1, 0.0);
+    }
+
+    #[test]
+    fn text_color_falls_back() {
+        assert_eq!(text_color(&Style::filled(BLUE)), BLUE);
+        assert_eq!(text_color(&Style::stroked(BLUE, 1.0)), BLUE);
+        assert_eq!(text_color(&Style::default()), TEXT_COLOR);
+    }
+
+    #[test]
+    fn new_shapes_go_on_top() {
+        let mut document = AnnotationDocument::new(base());
+        assert!(document.is_empty());
+        let a = document.add(rect(0.0, 0.0, 10.0, 10.0), Style::filled(BLUE));
+        let b = document.add(rect(5.0, 0.0, 15.0, 10.0), Style::filled(BLUE));
+        let c = document.add(rect(8.0, 0.0, 20.0, 10.0), Style::filled(BLUE));
+        assert_eq!(document.len(), 3);
+        assert_eq!(order(&document), vec![1, 2, 3]);
+
+        // Der oberste Treffer gewinnt
+        assert_eq!(document.hit_test(9.0, 5.0), Some(c));
+        assert_eq!(document.hit_test(6.0, 5.0), Some(b));
+        assert_eq!(document.hit_test(1.0, 5.0), Some(a));
+        assert_eq!(document.hit_test(1.0, 50.0), None);
+
+        assert!(document.send_to_back(c));
+        assert_eq!(order(&document), vec![3, 1, 2]);
+        assert_eq!(document.hit_test(9.0, 5.0), Some(b));
+        assert!(document.bring_to_front(a));
+        assert_eq!(order(&document), vec![3, 2, 1]);
+        assert!(document.set_z(b, 100));
+        assert_eq!(order(&document), vec![3, 1, 2]);
+
+        assert_eq!(document.remove(a).map(|shape| shape.id), Some(a));
+        assert_eq!(document.remove(a), None);
+        assert!(!document.set_z(a, 1));
+        assert!(!document.update(a, |_, _| {}));
+        assert_eq!(order(&document), vec![3, 2]);
+    }
+
+    #[test]
+    fn inserted_shapes_keep_their_id_and_z() {
+        let mut document = AnnotationDocument::new(base());
+        let a = document.add(rect(0.0, 0.0, 1.0, 1.0), Style::default());
+        let removed = document.remove(a).unwrap();
+        let b = document.add(rect(0.0, 0.0, 1.0, 1.0), Style::default());
+        assert_ne!(a, b);
+
+        document.insert(Shape { z: -5, ..removed });
+        assert_eq!(document.shape(a).map(|shape| shape.z), Some(-5));
+        assert_eq!(order(&document), vec![a.0, b.0]);
+        // Neue IDs kollidieren nicht mit eingefügten
+        document.insert(Shape {
+            id: ShapeId(10),
+            kind: rect(0.0, 0.0, 1.0, 1.0),
+            style: Style::default(),
+            z: 0,
+        });
+        assert_eq!(
+            document.add(rect(0.0, 0.0, 1.0, 1.0), Style::default()),
+            ShapeId(11)
+        );
+    }
+
+    #[test]
+    fn flatten_burns_shapes_in_z_order() {
+        let mut document = AnnotationDocument::new(base());
+        let red = document.add(
+            rect(0.0, 0.0, 10.0, 10.0),
+            Style::filled(Color::new(1.0, 0.0, 0.0, 1.0)),
+        );
+        document.add(rect(5.0, 0.0, 15.0, 10.0), Style::filled(BLUE));
+
+        let image = document.flatten();
+        assert_eq!(image.get_pixel(2, 5), &Rgba([255, 0, 0, 255]));
+        assert_eq!(image.get_pixel(7, 5), &Rgba([0, 0, 255, 255]));
+        assert_eq!(image.get_pixel(17, 5), &Rgba([255, 255, 255, 255]));
+        // Das Original bleibt unverändert
+        assert_eq!(document.base(), &base());
+
+        document.bring_to_front(red);
+        assert_eq!(document.flatten().get_pixel(7, 5), &Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn every_shape_kind_renders() {
+        let stroke = Style::stroked(BLUE, 2.0);
+        let shapes = [
+            (rect(2.0, 2.0, 8.0, 8.0), stroke),
+            (ShapeKind::Ellipse(Rect::new(2.0, 2.0, 8.0, 8.0)), stroke),
+            (
+                ShapeKind::Line {
+                    from: (1.0, 5.0),
+                    to: (9.0, 5.0),
+                },
+                stroke,
+            ),
+            (
+                ShapeKind::Arrow {
+                    from: (1.0, 5.0),
+                    to: (9.0, 5.0),
+                },
+                stroke,
+            ),
+            (ShapeKind::Freehand(vec![(1.0, 1.0), (8.0, 8.0)]), stroke),
+            (
+                ShapeKind::Text {
+                    origin: (1.0, 1.0),
+                    text: "X".to_string(),
+                    size: 7.0,
+                },
+                Style::default(),
+            ),
+            (
+                ShapeKind::Highlight(Rect::new(0.0, 0.0, 10.0, 10.0)),
+                Style::default(),
+            ),
+        ];
+
+        for (kind, style) in shapes {
+            let mut image = RgbaImage::from_pixel(10, 10, Rgba([255, 255, 255, 255]));
+            render_shape(
+                &mut image,
+                &Shape {
+                    id: ShapeId(1),
+                    kind: kind.clone(),
+                    style,
+                    z: 0,
+                },
+            );
+            assert!(
+                image
+                    .pixels()
+                    .any(|p| p[0] < 255 || p[1] < 255 || p[2] < 255),
+                "{:?}",
+                kind
+            );
+        }
+
+        // Ohne Strich zeichnen Linien nichts
+        let mut image = RgbaImage::from_pixel(10, 10, Rgba([255, 255, 255, 255]));
+        render_shape(
+            &mut image,
+            &Shape {
+                id: ShapeId(1),
+                kind: ShapeKind::Line {
+                    from: (1.0, 5.0),
+                    to: (9.0, 5.0),
+                },
+                style: Style::filled(BLUE),
+                z: 0,
+            },
+        );
+        assert!(image.pixels().all(|p| *p == Rgba([255, 255, 255, 255])));
+    }
+
+    #[test]
+    fn editor_undoes_and_redoes_every_change() {
+        let mut editor = AnnotationEditor::new(base());
+        let a = editor.add(rect(0.0, 0.0, 2.0, 2.0), Style::default());
+        let b = editor.add(rect(0.0, 0.0, 4.0, 4.0), Style::default());
+        assert!(editor.translate(a, 3.0, 1.0));
+        assert!(editor.bring_to_front(a));
+        assert!(editor.remove(b));
+        assert!(!editor.remove(b));
+        assert!(!editor.translate(b, 1.0, 1.0));
+        assert_eq!(editor.history().undo_steps(), 5);
+
+        assert!(editor.undo());
+        assert_eq!(order(editor.document()), vec![b.0, a.0]);
+        assert!(editor.undo());
+        assert_eq!(order(editor.document()), vec![a.0, b.0]);
+        assert!(editor.undo());
+        assert_eq!(
+            editor.document().shape(a).unwrap().kind,
+            rect(0.0, 0.0, 2.0, 2.0)
+        );
+        assert!(editor.undo());
+        assert!(editor.undo());
+        assert!(editor.document().is_empty());
+        assert!(!editor.undo());
+
+        while editor.redo() {}
+        assert_eq!(order(editor.document()), vec![a.0]);
+        assert_eq!(
+            editor.document().shape(a).unwrap().kind,
+            rect(3.0, 1.0, 5.0, 3.0)
+        );
+    }
+
+    #[test]
+    fn gestures_become_one_step() {
+        let mut editor = AnnotationEditor::new(base());
+
+        // Aufziehen einer neuen Form: Add und alle Änderungen ergeben einen Schritt
+        editor.begin_gesture();
+        let id = editor.add(
+            Tool::Freehand.shape((0.0, 0.0), (1.0, 0.0)),
+            Style::default(),
+        );
+        for x in 2..6 {
+            editor.update(id, |kind, _| {
+                Tool::Freehand.extend(kind, (0.0, 0.0), (x as f32, 0.0))
+            });
+        }
+        editor.end_gesture();
+        assert_eq!(editor.history().undo_steps(), 1);
+
+        // Verschieben mit der Maus
+        editor.begin_gesture();
+        for _ in 0..4 {
+            editor.translate(id, 1.0, 1.0);
+        }
+        editor.end_gesture();
+        assert_eq!(editor.history().undo_steps(), 2);
+
+        assert!(editor.undo());
+        assert_eq!(
+            editor.document().shape(id).unwrap().kind.bounds(),
+            Rect::new(0.0, 0.0, 5.0, 0.0)
+        );
+        assert!(editor.undo());
+        assert!(editor.document().is_empty());
+        assert!(editor.redo());
+        assert_eq!(
+            editor.document().shape(id).unwrap().kind,
+            ShapeKind::Freehand((0..6).map(|x| (x as f32, 0.0)).collect())
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use crate::modules::annotation::Shape;
+use crate::modules::controller::WindowController;
+use anyhow::{anyhow, Result};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Rect {
+    pub fn new(left: f32, top: f32, right: f32, bottom: f32) -> Self {
+        Rect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.right - self.left
+    }
+
+    pub fn height(&self) -> f32 {
+        self.bottom - self.top
+    }
+
+    pub fn normalized(&self) -> Rect {
+        Rect::new(
+            self.left.min(self.right),
+            self.top.min(self.bottom),
+            self.left.max(self.right),
+            self.top.max(self.bottom),
+        )
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        let rect = self.normalized();
+        x >= rect.left && x <= rect.right && y >= rect.top && y <= rect.bottom
+    }
+
+    pub fn offset(&self, dx: f32, dy: f32) -> Rect {
+        Rect::new(
+            self.left + dx,
+            self.top + dy,
+            self.right + dx,
+            self.bottom + dy,
+        )
+    }
+}
+
+// Ganzzahliger Pixelbereich, z.B. zum Zuschneiden eines Screenshots
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PixelRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PixelRect {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        PixelRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn to_rect(&self) -> Rect {
+        Rect::new(
+            self.x as f32,
+            self.y as f32,
+            self.right() as f32,
+            self.bottom() as f32,
+        )
+    }
+
+    // Bleibt bei Bereichen über i32::MAX hinaus am Rand des Wertebereichs stehen
+    pub fn right(&self) -> i32 {
+        self.x.saturating_add_unsigned(self.width)
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.y.saturating_add_unsigned(self.height)
+    }
+
+    // Kleinstes Rechteck, das beide umschließt
+    pub fn union(&self, other: &PixelRect) -> PixelRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        PixelRect::new(
+            x,
+            y,
+            (self.right().max(other.right()) - x) as u32,
+            (self.bottom().max(other.bottom()) - y) as u32,
+        )
+    }
+
+    // Nur echte Überlappung zählt, bloße Berührung an einer Kante nicht
+    pub fn intersects(&self, other: &PixelRect) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    pub fn intersection(&self, other: &PixelRect) -> Option<PixelRect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        (right > x && bottom > y)
+            .then(|| PixelRect::new(x, y, (right - x) as u32, (bottom - y) as u32))
+    }
+}
+
+// Textform `x,y,w,h`, wie sie auf der Kommandozeile angegeben wird
+impl FromStr for PixelRect {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+        if let [x, y, width, height] = parts[..] {
+            let invalid = |_| anyhow!("Invalid region '{}', expected x,y,w,h", s);
+            let rect = PixelRect::new(
+                x.parse().map_err(invalid)?,
+                y.parse().map_err(invalid)?,
+                width.parse().map_err(invalid)?,
+                height.parse().map_err(invalid)?,
+            );
+            if rect.x.checked_add_unsigned(rect.width).is_none()
+                || rect.y.checked_add_unsigned(rect.height).is_none()
+            {
+                return Err(anyhow!("Region '{}' exceeds the coordinate range", s));
+            }
+            return Ok(rect);
+        }
+        Err(anyhow!("Invalid region '{}', expected x,y,w,h", s))
+    }
+}
+
+impl fmt::Display for PixelRect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Color { r, g, b, a }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowType {
+    Transparent,
+    Opaque,
+    Main,
+    None,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Overlay {
+    pub selection: Option<Rect>,
+    pub handles: Vec<Rect>,
+    // Markierte Bereiche innerhalb der Auswahl, z.B. gefundene QR-Codes
+    pub highlights: Vec<Rect>,
+}
+
+// Beschreibt, was ein Backend in ein Fenster zeichnen soll
+#[derive(Clone, Debug, PartialEq)]
+pub enum Frame {
+    Overlay(Overlay),
+    Background(Color),
+    // Formen über dem Hintergrundbild aus set_backdrop, z.B. im Editor nach der Aufnahme
+    Annotations(Vec<Shape>),
+}
+
+pub trait Surface {
+    fn window_type(&self) -> WindowType;
+    fn show(&self);
+    fn hide(&self);
+    fn redraw(&self);
+    fn set_backdrop(&self, image: &RgbaImage) -> Result<()>;
+    fn paint(&self, frame: &Frame) -> Result<()>;
+}
+
+pub trait Backend {
+    // Virtueller Bildschirm über alle Monitore in Desktop-Koordinaten
+    fn screen_bounds(&self) -> PixelRect;
+
+    fn create_window(
+        &self,
+        window_type: WindowType,
+        controller: &WindowController,
+    ) -> Result<Box<dyn Surface>>;
+}
@@ -0,0 +1,731 @@
+use crate::modules::backend::PixelRect;
+use crate::modules::qr;
+use image::RgbaImage;
+use std::fmt;
+
+// Findet QR-Codes und Strichcodes (EAN-13, Code 128) in einem Bild, ohne externe Bibliotheken.
+// Ausgelegt auf Bildschirminhalte: Codes liegen gerade oder um 90° gedreht, ohne Perspektive.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Symbology {
+    Qr,
+    Ean13,
+    Code128,
+}
+
+impl Symbology {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Symbology::Qr => "QR",
+            Symbology::Ean13 => "EAN-13",
+            Symbology::Code128 => "Code 128",
+        }
+    }
+}
+
+impl fmt::Display for Symbology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// `bounds` in Pixeln relativ zum durchsuchten Bild
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedCode {
+    pub symbology: Symbology,
+    pub payload: String,
+    pub bounds: PixelRect,
+}
+
+// Ein zusammenhängendes Stück gleicher Farbe innerhalb einer Zeile
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Run {
+    pub dark: bool,
+    pub start: u32,
+    pub len: u32,
+}
+
+// Schwarz-Weiß-Bild; außerhalb des Bildes gilt alles als hell
+pub struct Bitmap {
+    width: u32,
+    height: u32,
+    dark: Vec<bool>,
+}
+
+fn luma(pixel: &image::Rgba<u8>) -> u8 {
+    let [r, g, b, a] = pixel.0;
+    let value = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+    // Transparente Pixel wie vor weißem Hintergrund
+    ((value * a as u32 + 255 * (255 - a as u32)) / 255) as u8
+}
+
+// Schwelle nach Otsu: trennt die Helligkeiten so, dass die Varianz zwischen den Klassen maximal wird
+fn otsu_threshold(histogram: &[u64; 256]) -> u8 {
+    let total: u64 = histogram.iter().sum();
+    let sum: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(value, count)| value as f64 * *count as f64)
+        .sum();
+
+    // Bei gleich guten Schwellen, z.B. in reinem Schwarz-Weiß, die Mitte des Bereichs
+    let (mut best, mut low, mut high) = (0.0, 127u8, 127u8);
+    let mut background = 0u64;
+    let mut background_sum = 0.0;
+    for (value, count) in histogram.iter().enumerate() {
+        background += count;
+        if background == 0 {
+            continue;
+        }
+        let foreground = total - background;
+        if foreground == 0 {
+            break;
+        }
+        background_sum += value as f64 * *count as f64;
+        let mean_background = background_sum / background as f64;
+        let mean_foreground = (sum - background_sum) / foreground as f64;
+        let variance =
+            background as f64 * foreground as f64 * (mean_background - mean_foreground).powi(2);
+        if variance > best {
+            best = variance;
+            low = value as u8;
+            high = value as u8;
+        } else if variance == best && variance > 0.0 {
+            high = value as u8;
+        }
+    }
+    ((low as u16 + high as u16) / 2) as u8
+}
+
+impl Bitmap {
+    pub fn from_image(image: &RgbaImage) -> Self {
+        let lumas: Vec<u8> = image.pixels().map(luma).collect();
+        let mut histogram = [0u64; 256];
+        for value in &lumas {
+            histogram[*value as usize] += 1;
+        }
+        let threshold = otsu_threshold(&histogram);
+
+        Bitmap {
+            width: image.width(),
+            height: image.height(),
+            dark: lumas.iter().map(|value| *value <= threshold).collect(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn is_dark(&self, x: i32, y: i32) -> bool {
+        x >= 0
+            && y >= 0
+            && (x as u32) < self.width
+            && (y as u32) < self.height
+            && self.dark[(y as u32 * self.width + x as u32) as usize]
+    }
+
+    pub fn row_runs(&self, y: u32) -> Vec<Run> {
+        let mut runs: Vec<Run> = Vec::new();
+        for x in 0..self.width {
+            let dark = self.is_dark(x as i32, y as i32);
+            match runs.last_mut() {
+                Some(run) if run.dark == dark => run.len += 1,
+                _ => runs.push(Run {
+                    dark,
+                    start: x,
+                    len: 1,
+                }),
+            }
+        }
+        runs
+    }
+}
+
+// Summe der Abweichungen in Modulen, nachdem die Breiten auf `modules` Module normiert wurden
+fn pattern_error(widths: &[u32], pattern: &[u8], modules: u32) -> f32 {
+    let total: u32 = widths.iter().sum();
+    let unit = total as f32 / modules as f32;
+    widths
+        .iter()
+        .zip(pattern)
+        .map(|(width, expected)| (*width as f32 / unit - *expected as f32).abs())
+        .sum()
+}
+
+// Erlaubte Abweichung je Strich bzw. Lücke, gemessen in Modulen
+const TOLERANCE: f32 = 0.25;
+
+fn best_match<const N: usize>(widths: &[u32], patterns: &[[u8; N]], modules: u32) -> Option<usize> {
+    patterns
+        .iter()
+        .enumerate()
+        .map(|(index, pattern)| (index, pattern_error(widths, pattern, modules)))
+        .filter(|(_, error)| *error <= TOLERANCE * N as f32)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(index, _)| index)
+}
+
+fn widths(runs: &[Run]) -> Vec<u32> {
+    runs.iter().map(|run| run.len).collect()
+}
+
+// Vor und hinter einem Strichcode muss eine Ruhezone liegen; der Bildrand zählt als solche
+fn quiet_before(runs: &[Run], index: usize, module: f32, modules: f32) -> bool {
+    index == 0 || runs[index - 1].len as f32 >= module * modules
+}
+
+fn quiet_after(runs: &[Run], index: usize, module: f32, modules: f32) -> bool {
+    !matches!(runs.get(index), Some(run) if (run.len as f32) < module * modules)
+}
+
+// Mindestbreite der Ruhezonen in Modulen; kleiner als in den Normen, da Auswahlen oft knapp sind
+const QUIET_ZONE: f32 = 5.0;
+
+mod ean13 {
+    use super::*;
+
+    // Breiten von Lücke, Strich, Lücke, Strich für die L-Codes; G-Codes sind gespiegelt,
+    // R-Codes haben dieselben Breiten, beginnen aber mit einem Strich
+    pub const L_CODES: [[u8; 4]; 10] = [
+        [3, 2, 1, 1],
+        [2, 2, 2, 1],
+        [2, 1, 2, 2],
+        [1, 4, 1, 1],
+        [1, 1, 3, 2],
+        [1, 2, 3, 1],
+        [1, 1, 1, 4],
+        [1, 3, 1, 2],
+        [1, 2, 1, 3],
+        [3, 1, 1, 2],
+    ];
+
+    // Aus der Folge von L (false) und G (true) der linken Hälfte ergibt sich die erste Ziffer
+    pub const PARITIES: [[bool; 6]; 10] = [
+        [false, false, false, false, false, false],
+        [false, false, true, false, true, true],
+        [false, false, true, true, false, true],
+        [false, false, true, true, true, false],
+        [false, true, false, false, true, true],
+        [false, true, true, false, false, true],
+        [false, true, true, true, false, false],
+        [false, true, false, true, false, true],
+        [false, true, false, true, true, false],
+        [false, true, true, false, true, false],
+    ];
+
+    fn codes() -> [[u8; 4]; 20] {
+        let mut codes = [[0; 4]; 20];
+        for (digit, code) in L_CODES.iter().enumerate() {
+            codes[digit] = *code;
+            let mut mirrored = *code;
+            mirrored.reverse();
+            codes[digit + 10] = mirrored;
+        }
+        codes
+    }
+
+    pub fn check_digit(digits: &[u8]) -> u8 {
+        let sum: u32 = digits
+            .iter()
+            .enumerate()
+            .map(|(index, digit)| *digit as u32 * if index % 2 == 0 { 1 } else { 3 })
+            .sum();
+        ((10 - sum % 10) % 10) as u8
+    }
+
+    fn guard(runs: &[Run], module: f32) -> bool {
+        runs.iter()
+            .all(|run| (run.len as f32 - module).abs() <= module * 0.5 + 0.5)
+    }
+
+    // 3 Randzeichen, 6 × 4 Elemente, 5 Mittelzeichen, 6 × 4 Elemente, 3 Randzeichen
+    const RUNS: usize = 3 + 24 + 5 + 24 + 3;
+
+    // Liefert Ziffern sowie Anfang und Ende in x
+    pub fn decode_at(runs: &[Run], start: usize) -> Option<(String, u32, u32)> {
+        let symbol = runs.get(start..start + RUNS)?;
+        if !symbol[0].dark {
+            return None;
+        }
+        let module = symbol[..3].iter().map(|run| run.len).sum::<u32>() as f32 / 3.0;
+        if !guard(&symbol[..3], module) || !quiet_before(runs, start, module, QUIET_ZONE) {
+            return None;
+        }
+
+        let codes = codes();
+        let mut digits = Vec::with_capacity(13);
+        let mut parity = [false; 6];
+        for (index, chunk) in symbol[3..27].chunks(4).enumerate() {
+            let code = best_match(&widths(chunk), &codes, 7)?;
+            digits.push((code % 10) as u8);
+            parity[index] = code >= 10;
+        }
+        if !guard(&symbol[27..32], module) {
+            return None;
+        }
+        for chunk in symbol[32..56].chunks(4) {
+            digits.push(best_match(&widths(chunk), &L_CODES, 7)? as u8);
+        }
+        if !guard(&symbol[56..59], module) || !quiet_after(runs, start + RUNS, module, QUIET_ZONE) {
+            return None;
+        }
+
+        let first = PARITIES.iter().position(|pattern| *pattern == parity)? as u8;
+        digits.insert(0, first);
+        if check_digit(&digits[..12]) != digits[12] {
+            return None;
+        }
+
+        let end = symbol[RUNS - 1].start + symbol[RUNS - 1].len;
+        let text = digits
+            .iter()
+            .map(|digit| char::from(b'0' + digit))
+            .collect();
+        Some((text, symbol[0].start, end))
+    }
+}
+
+mod code128 {
+    use super::*;
+
+    // Strich, Lücke, Strich, Lücke, Strich, Lücke; 106 ist der Anfang des Stoppzeichens
+    pub const PATTERNS: [[u8; 6]; 107] = [
+        [2, 1, 2, 2, 2, 2],
+        [2, 2, 2, 1, 2, 2],
+        [2, 2, 2, 2, 2, 1],
+        [1, 2, 1, 2, 2, 3],
+        [1, 2, 1, 3, 2, 2],
+        [1, 3, 1, 2, 2, 2],
+        [1, 2, 2, 2, 1, 3],
+        [1, 2, 2, 3, 1, 2],
+        [1, 3, 2, 2, 1, 2],
+        [2, 2, 1, 2, 1, 3],
+        [2, 2, 1, 3, 1, 2],
+        [2, 3, 1, 2, 1, 2],
+        [1, 1, 2, 2, 3, 2],
+        [1, 2, 2, 1, 3, 2],
+        [1, 2, 2, 2, 3, 1],
+        [1, 1, 3, 2, 2, 2],
+        [1, 2, 3, 1, 2, 2],
+        [1, 2, 3, 2, 2, 1],
+        [2, 2, 3, 2, 1, 1],
+        [2, 2, 1, 1, 3, 2],
+        [2, 2, 1, 2, 3, 1],
+        [2, 1, 3, 2, 1, 2],
+        [2, 2, 3, 1, 1, 2],
+        [3, 1, 2, 1, 3, 1],
+        [3, 1, 1, 2, 2, 2],
+        [3, 2, 1, 1, 2, 2],
+        [3, 2, 1, 2, 2, 1],
+        [3, 1, 2, 2, 1, 2],
+        [3, 2, 2, 1, 1, 2],
+        [3, 2, 2, 2, 1, 1],
+        [2, 1, 2, 1, 2, 3],
+        [2, 1, 2, 3, 2, 1],
+        [2, 3, 2, 1, 2, 1],
+        [1, 1, 1, 3, 2, 3],
+        [1, 3, 1, 1, 2, 3],
+        [1, 3, 1, 3, 2, 1],
+        [1, 1, 2, 3, 1, 3],
+        [1, 3, 2, 1, 1, 3],
+        [1, 3, 2, 3, 1, 1],
+        [2, 1, 1, 3, 1, 3],
+        [2, 3, 1, 1, 1, 3],
+        [2, 3, 1, 3, 1, 1],
+        [1, 1, 2, 1, 3, 3],
+        [1, 1, 2, 3, 3, 1],
+        [1, 3, 2, 1, 3, 1],
+        [1, 1, 3, 1, 2, 3],
+        [1, 1, 3, 3, 2, 1],
+        [1, 3, 3, 1, 2, 1],
+        [3, 1, 3, 1, 2, 1],
+        [2, 1, 1, 3, 3, 1],
+        [2, 3, 1, 1, 3, 1],
+        [2, 1, 3, 1, 1, 3],
+        [2, 1, 3, 3, 1, 1],
+        [2, 1, 3, 1, 3, 1],
+        [3, 1, 1, 1, 2, 3],
+        [3, 1, 1, 3, 2, 1],
+        [3, 3, 1, 1, 2, 1],
+        [3, 1, 2, 1, 1, 3],
+        [3, 1, 2, 3, 1, 1],
+        [3, 3, 2, 1, 1, 1],
+        [3, 1, 4, 1, 1, 1],
+        [2, 2, 1, 4, 1, 1],
+        [4, 3, 1, 1, 1, 1],
+        [1, 1, 1, 2, 2, 4],
+        [1, 1, 1, 4, 2, 2],
+        [1, 2, 1, 1, 2, 4],
+        [1, 2, 1, 4, 2, 1],
+        [1, 4, 1, 1, 2, 2],
+        [1, 4, 1, 2, 2, 1],
+        [1, 1, 2, 2, 1, 4],
+        [1, 1, 2, 4, 1, 2],
+        [1, 2, 2, 1, 1, 4],
+        [1, 2, 2, 4, 1, 1],
+        [1, 4, 2, 1, 1, 2],
+        [1, 4, 2, 2, 1, 1],
+        [2, 4, 1, 2, 1, 1],
+        [2, 2, 1, 1, 1, 4],
+        [4, 1, 3, 1, 1, 1],
+        [2, 4, 1, 1, 1, 2],
+        [1, 3, 4, 1, 1, 1],
+        [1, 1, 1, 2, 4, 2],
+        [1, 2, 1, 1, 4, 2],
+        [1, 2, 1, 2, 4, 1],
+        [1, 1, 4, 2, 1, 2],
+        [1, 2, 4, 1, 1, 2],
+        [1, 2, 4, 2, 1, 1],
+        [4, 1, 1, 2, 1, 2],
+        [4, 2, 1, 1, 1, 2],
+        [4, 2, 1, 2, 1, 1],
+        [2, 1, 2, 1, 4, 1],
+        [2, 1, 4, 1, 2, 1],
+        [4, 1, 2, 1, 2, 1],
+        [1, 1, 1, 1, 4, 3],
+        [1, 1, 1, 3, 4, 1],
+        [1, 3, 1, 1, 4, 1],
+        [1, 1, 4, 1, 1, 3],
+        [1, 1, 4, 3, 1, 1],
+        [4, 1, 1, 1, 1, 3],
+        [4, 1, 1, 3, 1, 1],
+        [1, 1, 3, 1, 4, 1],
+        [1, 1, 4, 1, 3, 1],
+        [3, 1, 1, 1, 4, 1],
+        [4, 1, 1, 1, 3, 1],
+        [2, 1, 1, 4, 1, 2],
+        [2, 1, 1, 2, 1, 4],
+        [2, 1, 1, 2, 3, 2],
+        [2, 3, 3, 1, 1, 1],
+    ];
+
+    pub const START_A: usize = 103;
+    pub const START_B: usize = 104;
+    pub const START_C: usize = 105;
+    pub const STOP: usize = 106;
+
+    const SHIFT: usize = 98;
+    const CODE_C: usize = 99;
+    const FNC1: usize = 102;
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum CodeSet {
+        A,
+        B,
+        C,
+    }
+
+    fn push_char(text: &mut String, set: CodeSet, value: usize) {
+        let byte = match set {
+            CodeSet::A if value < 64 => value as u8 + 32,
+            CodeSet::A => value as u8 - 64,
+            _ => value as u8 + 32,
+        };
+        text.push(char::from(byte));
+    }
+
+    // Wandelt die Symbolwerte zwischen Start- und Prüfzeichen in Text um
+    fn text(start: usize, values: &[usize]) -> Option<String> {
+        let mut set = match start {
+            START_A => CodeSet::A,
+            START_B => CodeSet::B,
+            _ => CodeSet::C,
+        };
+        let mut text = String::new();
+        let mut shifted = false;
+
+        for (index, &value) in values.iter().enumerate() {
+            let current = match (shifted, set) {
+                (true, CodeSet::A) => CodeSet::B,
+                (true, CodeSet::B) => CodeSet::A,
+                _ => set,
+            };
+            shifted = false;
+
+            match (current, value) {
+                (CodeSet::C, 0..=99) => text.push_str(&format!("{:02}", value)),
+                (_, 0..=95) if current != CodeSet::C => push_char(&mut text, current, value),
+                // FNC1 am Anfang kennzeichnet GS1-Daten, danach trennt es Felder
+                (_, FNC1) => {
+                    if index > 0 {
+                        text.push('\u{1d}');
+                    }
+                }
+                (CodeSet::A | CodeSet::B, SHIFT) => shifted = true,
+                (CodeSet::A | CodeSet::B, CODE_C) => set = CodeSet::C,
+                (CodeSet::A, 100) | (CodeSet::C, 100) => set = CodeSet::B,
+                (CodeSet::B, 101) | (CodeSet::C, 101) => set = CodeSet::A,
+                // FNC2 bis FNC4 tragen keinen Text
+                (CodeSet::A | CodeSet::B, 96 | 97) | (CodeSet::A, 101) | (CodeSet::B, 100) => {}
+                _ => return None,
+            }
+        }
+        Some(text)
+    }
+
+    pub fn decode_at(runs: &[Run], start: usize) -> Option<(String, u32, u32)> {
+        let first = runs.get(start..start + 6)?;
+        if !first[0].dark {
+            return None;
+        }
+        let module = first.iter().map(|run| run.len).sum::<u32>() as f32 / 11.0;
+        if !quiet_before(runs, start, module, QUIET_ZONE) {
+            return None;
+        }
+        let start_code = best_match(&widths(first), &PATTERNS, 11)?;
+        if !(START_A..=START_C).contains(&start_code) {
+            return None;
+        }
+
+        let mut values = Vec::new();
+        let mut index = start + 6;
+        loop {
+            let symbol = runs.get(index..index + 6)?;
+            let value = best_match(&widths(symbol), &PATTERNS, 11)?;
+            if value == STOP {
+                // Das Stoppzeichen endet mit einem zusätzlichen Strich von zwei Modulen
+                let bar = runs.get(index + 6)?;
+                let unit = symbol.iter().map(|run| run.len).sum::<u32>() as f32 / 11.0;
+                if (bar.len as f32 / unit - 2.0).abs() > 1.0
+                    || !quiet_after(runs, index + 7, unit, QUIET_ZONE)
+                {
+                    return None;
+                }
+                let end = bar.start + bar.len;
+                let (check, data) = values.split_last()?;
+                let sum = data
+                    .iter()
+                    .enumerate()
+                    .fold(start_code, |sum, (position, value)| {
+                        sum + (position + 1) * value
+                    });
+                if data.is_empty() || sum % 103 != *check {
+                    return None;
+                }
+                return Some((text(start_code, data)?, runs[start].start, end));
+            }
+            if value >= START_A {
+                return None;
+            }
+            values.push(value);
+            index += 6;
+        }
+    }
+}
+
+fn decode_linear(bitmap: &Bitmap) -> Vec<DecodedCode> {
+    let mut found: Vec<DecodedCode> = Vec::new();
+    for y in 0..bitmap.height() {
+        let runs = bitmap.row_runs(y);
+        let mut index = 0;
+        while index < runs.len() {
+            let decoded = ean13::decode_at(&runs, index)
+                .map(|result| (Symbology::Ean13, result))
+                .or_else(|| {
+                    code128::decode_at(&runs, index).map(|result| (Symbology::Code128, result))
+                });
+
+            let (symbology, (payload, left, right)) = match decoded {
+                Some(decoded) => decoded,
+                None => {
+                    index += 1;
+                    continue;
+                }
+            };
+
+            // Dieselbe Codezeile in den Zeilen darunter vergrößert nur den Bereich
+            let row = PixelRect::new(left as i32, y as i32, right - left, 1);
+            let existing = found.iter_mut().find(|code| {
+                code.symbology == symbology
+                    && code.payload == payload
+                    && code.bounds.bottom() >= row.y
+                    && code.bounds.x < row.right()
+                    && row.x < code.bounds.right()
+            });
+            match existing {
+                Some(code) => code.bounds = code.bounds.union(&row),
+                None => found.push(DecodedCode {
+                    symbology,
+                    payload,
+                    bounds: row,
+                }),
+            }
+
+            // Hinter dem Code weitersuchen
+            index = runs
+                .iter()
+                .position(|run| run.start >= right)
+                .unwrap_or(runs.len());
+        }
+    }
+    found
+}
+
+// QR-Codes zuerst, dann Strichcodes, jeweils von oben nach unten
+pub fn decode(image: &RgbaImage) -> Vec<DecodedCode> {
+    let bitmap = Bitmap::from_image(image);
+    let mut codes: Vec<DecodedCode> = qr::decode(&bitmap)
+        .into_iter()
+        .map(|code| DecodedCode {
+            symbology: Symbology::Qr,
+            payload: code.text(),
+            bounds: code.bounds,
+        })
+        .collect();
+    codes.extend(decode_linear(&bitmap));
+    codes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    // Zeichnet Module (true = Strich) mit fester Breite und Ruhezonen
+    fn render_bars(modules: &[bool], module_width: u32, height: u32) -> RgbaImage {
+        let quiet = 10 * module_width;
+        let width = modules.len() as u32 * module_width + 2 * quiet;
+        RgbaImage::from_fn(width, height, |x, _| {
+            let dark = x >= quiet
+                && ((x - quiet) / module_width) < modules.len() as u32
+                && modules[((x - quiet) / module_width) as usize];
+            if dark {
+                Rgba([0, 0, 0, 255])
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
+        })
+    }
+
+    fn push_widths(modules: &mut Vec<bool>, widths: &[u8], dark_first: bool) {
+        let mut dark = dark_first;
+        for width in widths {
+            modules.extend(std::iter::repeat_n(dark, *width as usize));
+            dark = !dark;
+        }
+    }
+
+    fn ean13_modules(digits: &str) -> Vec<bool> {
+        let digits: Vec<u8> = digits.bytes().map(|b| b - b'0').collect();
+        let parity = ean13::PARITIES[digits[0] as usize];
+        let mut modules = Vec::new();
+        push_widths(&mut modules, &[1, 1, 1], true);
+        for (index, digit) in digits[1..7].iter().enumerate() {
+            let mut code = ean13::L_CODES[*digit as usize];
+            if parity[index] {
+                code.reverse();
+            }
+            push_widths(&mut modules, &code, false);
+        }
+        push_widths(&mut modules, &[1, 1, 1, 1, 1], false);
+        for digit in &digits[7..] {
+            push_widths(&mut modules, &ean13::L_CODES[*digit as usize], true);
+        }
+        push_widths(&mut modules, &[1, 1, 1], true);
+        modules
+    }
+
+    // Codiert in Zeichensatz B, Ziffernpaare wie "123456" ganz in C
+    fn code128_modules(text: &str) -> Vec<bool> {
+        let numeric = text.len().is_multiple_of(2) && text.bytes().all(|b| b.is_ascii_digit());
+        let (start, values): (usize, Vec<usize>) = if numeric {
+            (
+                code128::START_C,
+                text.as_bytes()
+                    .chunks(2)
+                    .map(|pair| ((pair[0] - b'0') * 10 + pair[1] - b'0') as usize)
+                    .collect(),
+            )
+        } else {
+            (
+                code128::START_B,
+                text.bytes().map(|b| (b - 32) as usize).collect(),
+            )
+        };
+        let check = values
+            .iter()
+            .enumerate()
+            .fold(start, |sum, (position, value)| sum + (position + 1) * value)
+            % 103;
+
+        let mut modules = Vec::new();
+        for value in std::iter::once(start)
+            .chain(values)
+            .chain([check, code128::STOP])
+        {
+            push_widths(&mut modules, &code128::PATTERNS[value], true);
+        }
+        modules.extend([true, true]);
+        modules
+    }
+
+    #[test]
+    fn code128_table_is_consistent() {
+        for (value, pattern) in code128::PATTERNS.iter().enumerate() {
+            assert_eq!(
+                pattern.iter().map(|w| *w as u32).sum::<u32>(),
+                11,
+                "{}",
+                value
+            );
+            // Striche belegen immer eine gerade Zahl von Modulen
+            assert_eq!((pattern[0] + pattern[2] + pattern[4]) % 2, 0, "{}", value);
+        }
+        let mut unique = code128::PATTERNS.to_vec();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), code128::PATTERNS.len());
+    }
+
+    #[test]
+    fn decodes_ean13() {
+        let image = render_bars(&ean13_modules("4006381333931"), 2, 40);
+        let codes = decode(&image);
+        assert_eq!(codes.len(), 1);
+        assert_eq!(codes[0].symbology, Symbology::Ean13);
+        assert_eq!(codes[0].payload, "4006381333931");
+        assert_eq!(codes[0].bounds, PixelRect::new(20, 0, 95 * 2, 40));
+    }
+
+    #[test]
+    fn ean13_with_wrong_check_digit_is_rejected() {
+        let image = render_bars(&ean13_modules("4006381333932"), 2, 20);
+        assert!(decode(&image).is_empty());
+    }
+
+    #[test]
+    fn decodes_code128_in_sets_b_and_c() {
+        for text in ["Snip-128 ok!", "12345678"] {
+            let image = render_bars(&code128_modules(text), 3, 30);
+            let codes = decode(&image);
+            assert_eq!(codes.len(), 1, "{}", text);
+            assert_eq!(codes[0].symbology, Symbology::Code128);
+            assert_eq!(codes[0].payload, text);
+        }
+    }
+
+    #[test]
+    fn decodes_bars_scaled_by_a_fraction() {
+        let image = render_bars(&code128_modules("scaled"), 2, 30);
+        let image = image::imageops::resize(
+            &image,
+            image.width() * 3 / 2,
+            30,
+            image::imageops::FilterType::Triangle,
+        );
+        let codes = decode(&image);
+        assert_eq!(codes.len(), 1);
+        assert_eq!(codes[0].payload, "scaled");
+    }
+
+    #[test]
+    fn blank_image_has_no_codes() {
+        let image = RgbaImage::from_pixel(50, 50, Rgba([255, 255, 255, 255]));
+        assert!(decode(&image).is_empty());
+    }
+}
@@ -0,0 +1,203 @@
+use crate::modules::backend::PixelRect;
+use anyhow::{anyhow, Result};
+use image::RgbaImage;
+use std::path::Path;
+use std::sync::Arc;
+
+// Quelle für ein Standbild des gesamten virtuellen Bildschirms
+pub trait FrameSource {
+    fn grab(&mut self) -> Result<RgbaImage>;
+}
+
+// Erzeugt eine eigene Quelle auf dem Thread, der sie benutzt, z.B. für Aufnahmen im Hintergrund
+pub type FrameSourceFactory = Arc<dyn Fn() -> Result<Box<dyn FrameSource>> + Send + Sync>;
+
+// Liefert immer dasselbe Bild, z.B. aus einer Datei oder einem Puffer in Tests
+pub struct ImageFrameSource {
+    image: RgbaImage,
+}
+
+impl ImageFrameSource {
+    pub fn new(image: RgbaImage) -> Self {
+        ImageFrameSource { image }
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .map_err(|e| anyhow!("Failed to load frame from {}: {}", path.display(), e))?
+            .to_rgba8();
+        Ok(ImageFrameSource { image })
+    }
+}
+
+impl FrameSource for ImageFrameSource {
+    fn grab(&mut self) -> Result<RgbaImage> {
+        Ok(self.image.clone())
+    }
+}
+
+// Schneidet den Bereich aus dem Frame; Teile außerhalb des Frames werden abgeschnitten
+pub fn crop(frame: &RgbaImage, region: PixelRect) -> Result<RgbaImage> {
+    let left = region.x.max(0) as u32;
+    let top = region.y.max(0) as u32;
+    let right = region.right().clamp(0, frame.width() as i32) as u32;
+    let bottom = region.bottom().clamp(0, frame.height() as i32) as u32;
+
+    if right <= left || bottom <= top {
+        return Err(anyhow!(
+            "Selection {:?} lies outside the captured frame",
+            region
+        ));
+    }
+
+    Ok(image::imageops::crop_imm(frame, left, top, right - left, bottom - top).to_image())
+}
+
+pub type CaptureConsumer = Box<dyn FnMut(&RgbaImage) -> Result<()>>;
+
+pub struct Capture {
+    source: Box<dyn FrameSource>,
+    frozen: Option<RgbaImage>,
+    consumers: Vec<CaptureConsumer>,
+}
+
+impl Capture {
+    pub fn new(source: Box<dyn FrameSource>) -> Self {
+        Capture {
+            source,
+            frozen: None,
+            consumers: Vec::new(),
+        }
+    }
+
+    // Wird mit jedem fertigen Ausschnitt aufgerufen
+    pub fn subscribe(&mut self, consumer: CaptureConsumer) {
+        self.consumers.push(consumer);
+    }
+
+    // Hält den aktuellen Bildschirminhalt fest; folgende Captures schneiden aus diesem Bild aus
+    pub fn freeze(&mut self) -> Result<&RgbaImage> {
+        let frame = self.source.grab()?;
+        Ok(self.frozen.insert(frame))
+    }
+
+    pub fn thaw(&mut self) {
+        self.frozen = None;
+    }
+
+    pub fn frozen(&self) -> Option<&RgbaImage> {
+        self.frozen.as_ref()
+    }
+
+    pub fn capture(&mut self, region: PixelRect) -> Result<RgbaImage> {
+        let image = match &self.frozen {
+            Some(frame) => crop(frame, region)?,
+            None => crop(&self.source.grab()?, region)?,
+        };
+
+        for consumer in self.consumers.iter_mut() {
+            consumer(&image)?;
+        }
+
+        Ok(image)
+    }
+
+    // Aktueller Inhalt des Bereichs, unabhängig vom eingefrorenen Bild und ohne Abonnenten;
+    // für Aufnahmen, die viele Frames hintereinander brauchen
+    pub fn sample(&mut self, region: PixelRect) -> Result<RgbaImage> {
+        crop(&self.source.grab()?, region)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::testing::TempDir;
+    use image::Rgba;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn frame(shade: u8) -> RgbaImage {
+        RgbaImage::from_fn(8, 6, |x, y| Rgba([x as u8 * 10, y as u8 * 10, shade, 255]))
+    }
+
+    // Liefert bei jedem Aufruf ein anderes Bild, wie ein Bildschirm, der sich ändert
+    struct CountingSource {
+        grabs: u8,
+    }
+
+    impl FrameSource for CountingSource {
+        fn grab(&mut self) -> Result<RgbaImage> {
+            self.grabs += 1;
+            Ok(frame(self.grabs))
+        }
+    }
+
+    #[test]
+    fn png_fixture_is_read_as_the_frame() {
+        let dir = TempDir::new("capture");
+        std::fs::create_dir_all(&dir.0).unwrap();
+        let path = dir.join("desktop.png");
+        frame(7).save(&path).unwrap();
+
+        let mut source = ImageFrameSource::open(&path).unwrap();
+        assert_eq!(source.grab().unwrap(), frame(7));
+        assert_eq!(source.grab().unwrap(), frame(7));
+
+        let error = ImageFrameSource::open(dir.join("missing.png"))
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("missing.png"));
+    }
+
+    #[test]
+    fn crop_clips_to_the_frame() {
+        let frame = frame(0);
+        let inside = crop(&frame, PixelRect::new(2, 1, 3, 2)).unwrap();
+        assert_eq!(inside.dimensions(), (3, 2));
+        assert_eq!(inside.get_pixel(0, 0), frame.get_pixel(2, 1));
+
+        let overhanging = crop(&frame, PixelRect::new(-2, 4, 5, 10)).unwrap();
+        assert_eq!(overhanging.dimensions(), (3, 2));
+        assert_eq!(overhanging.get_pixel(0, 0), frame.get_pixel(0, 4));
+
+        assert!(crop(&frame, PixelRect::new(8, 0, 4, 4)).is_err());
+        assert!(crop(&frame, PixelRect::new(-5, -5, 5, 5)).is_err());
+        assert!(crop(&frame, PixelRect::new(1, 1, 0, 3)).is_err());
+    }
+
+    #[test]
+    fn frozen_frame_is_used_until_thawed() {
+        let mut capture = Capture::new(Box::new(CountingSource { grabs: 0 }));
+        let region = PixelRect::new(0, 0, 8, 6);
+        assert_eq!(capture.capture(region).unwrap(), frame(1));
+
+        capture.freeze().unwrap();
+        assert_eq!(capture.frozen(), Some(&frame(2)));
+        assert_eq!(capture.capture(region).unwrap(), frame(2));
+        assert_eq!(capture.capture(region).unwrap(), frame(2));
+        // Aufnahmen über mehrere Frames lesen immer live
+        assert_eq!(capture.sample(region).unwrap(), frame(3));
+
+        capture.thaw();
+        assert_eq!(capture.frozen(), None);
+        assert_eq!(capture.capture(region).unwrap(), frame(4));
+    }
+
+    #[test]
+    fn subscribers_receive_each_capture() {
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let mut capture = Capture::new(Box::new(ImageFrameSource::new(frame(0))));
+        let sink = received.clone();
+        capture.subscribe(Box::new(move |image| {
+            sink.borrow_mut().push(image.dimensions());
+            Ok(())
+        }));
+
+        capture.capture(PixelRect::new(0, 0, 2, 2)).unwrap();
+        capture.capture(PixelRect::new(1, 1, 4, 3)).unwrap();
+        capture.sample(PixelRect::new(0, 0, 8, 6)).unwrap();
+        assert_eq!(*received.borrow(), vec![(2, 2), (4, 3)]);
+    }
+}
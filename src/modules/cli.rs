@@ -0,0 +1,1231 @@
+use crate::modules::backend::PixelRect;
+use crate::modules::barcode::{self, DecodedCode};
+use crate::modules::capture::{Capture, FrameSource, ImageFrameSource};
+use crate::modules::commands::AppCommand;
+use crate::modules::errorhandler::SnipError;
+use crate::modules::export::{self, ExportFormat, ExportOptions};
+use crate::modules::naming::{self, CaptureInfo, FilenameTemplate, OutputPolicy};
+use crate::modules::ocr::{OcrEngine, OcrResult};
+use crate::modules::pii::{self, PiiRules};
+use crate::modules::recording::{self, AnimationFormat, RecordingOptions};
+use crate::modules::redact::Redaction;
+use crate::modules::stitch::{self, StitchOptions};
+use crate::modules::table::TableFormat;
+use crate::modules::text::TextOptions;
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "Usage:
+  snipping_tool [--dir DIR] [--template TEMPLATE] [--command COMMAND]... [--stdin]
+                                     interactive selection overlay; captures are saved to DIR,
+                                     by default Pictures\\Screenshots (Windows)
+  snipping_tool text [--join-hyphens | --table FORMAT]
+                                     select an area and copy the text recognized in it (Windows)
+  snipping_tool codes                select areas and copy the QR codes and barcodes found in them (Windows)
+  snipping_tool capture --region x,y,w,h (--out FILE | --dir DIR [--template TEMPLATE])
+                        [--source IMAGE] [--format FORMAT] [--redact x,y,w,h[=STYLE]]...
+                        [--searchable] [--redact-pii]
+  snipping_tool ocr --region x,y,w,h [--source IMAGE] [--table FORMAT]
+                                     print the text recognized in the region (Windows)
+  snipping_tool decode --region x,y,w,h [--source IMAGE]
+                                     print the QR codes and barcodes found in the region
+  snipping_tool record --out FILE [--region x,y,w,h [--source IMAGE]] [--format FORMAT]
+                       [--fps N] [--duration SECONDS] [--max-size SIZE]
+                                     record an animation of the area; without --region select it first (Windows)
+  snipping_tool stitch --out FILE FRAME...
+                                     join screenshots taken while scrolling down into one tall image
+  snipping_tool library (list | search QUERY | open ID --out FILE | delete ID | tag ID [TAG]...)
+                        [--dir DIR]  browse the captures taken with the overlay
+
+Options:
+  --region x,y,w,h     area to capture, relative to the top-left corner of the frame
+  --out FILE           output file; the format follows the extension unless --format is given
+  --dir DIR            output directory, created if missing; for library the library folder
+  --template TEMPLATE  file name template for captures saved to a directory, e.g. {date:%Y-%m-%d}_{time}_{w}x{h}_{counter}.png
+  --source IMAGE       read the frame from an image instead of the screen
+  --command COMMAND    run an overlay command once it is open, may be repeated: select [x,y,w,h],
+                       capture, save [FILE], copy, ocr, table [FORMAT], decode, record, annotate,
+                       undo, redo or cancel
+  --stdin              read further overlay commands from standard input, one per line
+  --format FORMAT      png, jpg, bmp, tiff, webp or pdf; for record gif or apng
+  --redact x,y,w,h[=STYLE]
+                       area of the captured image to redact, may be repeated;
+                       STYLE is pixelate[:BLOCK], blur[:SIGMA] or fill[:RRGGBB], default pixelate:12
+  --searchable         add the recognized text as an invisible layer to PDF output; characters
+                       outside Western European scripts are written as '?' (Windows)
+  --redact-pii         redact e-mail addresses, IBANs, card numbers, IP addresses and API keys
+                       found by text recognition (Windows)
+  --join-hyphens       join words hyphenated at the end of a line
+  --table FORMAT       output the text as a table: csv, tsv or markdown
+  --fps N              frames per second for record, default 10, at most 50
+  --duration SECONDS   length of the recording, default 10
+  --max-size SIZE      size budget of the animation, e.g. 500K or 8M; frames are dropped to fit";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Output {
+    File(PathBuf),
+    Directory {
+        directory: PathBuf,
+        template: FilenameTemplate,
+    },
+}
+
+// Wohin das Overlay Aufnahmen speichert und was es ohne Eingabe ausführt
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OverlayArgs {
+    // Ohne Angabe der Standardordner aus naming::default_directory
+    pub directory: Option<PathBuf>,
+    pub template: FilenameTemplate,
+    // Läuft nach dem Öffnen der Reihe nach ab, z.B. `select 10,20,300,200` und `save`
+    pub commands: Vec<AppCommand>,
+    // Weitere Befehle zeilenweise von der Standardeingabe lesen
+    pub stdin: bool,
+}
+
+impl OverlayArgs {
+    pub fn policy(&self) -> Result<OutputPolicy, SnipError> {
+        let directory = match &self.directory {
+            Some(directory) => directory.clone(),
+            None => naming::default_directory().ok_or_else(|| {
+                SnipError::Config("No folder for screenshots found, use --dir".to_string())
+            })?,
+        };
+        Ok(OutputPolicy::new(directory, self.template.clone()))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaptureArgs {
+    pub region: PixelRect,
+    pub output: Output,
+    pub source: Option<PathBuf>,
+    pub format: Option<ExportFormat>,
+    pub redactions: Vec<Redaction>,
+    // OCR-Text als unsichtbare Ebene ins PDF schreiben
+    pub searchable: bool,
+    // Per OCR gefundene persönliche Daten zusätzlich schwärzen
+    pub redact_pii: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OcrArgs {
+    pub region: PixelRect,
+    pub source: Option<PathBuf>,
+    pub table: Option<TableFormat>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodeArgs {
+    pub region: PixelRect,
+    pub source: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordArgs {
+    // Ohne Bereich wird er im Overlay ausgewählt
+    pub region: Option<PixelRect>,
+    pub out: PathBuf,
+    pub format: AnimationFormat,
+    pub source: Option<PathBuf>,
+    pub options: RecordingOptions,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StitchArgs {
+    // In Scrollreihenfolge, von oben nach unten
+    pub frames: Vec<PathBuf>,
+    pub out: PathBuf,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LibraryAction {
+    List,
+    Search(String),
+    Open { id: u64, out: PathBuf },
+    Delete(u64),
+    // Ersetzt alle Tags; ohne Tags werden sie entfernt
+    Tag { id: u64, tags: Vec<String> },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LibraryArgs {
+    // Ohne Angabe der Standardordner aus library::default_directory
+    pub directory: Option<PathBuf>,
+    pub action: LibraryAction,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextArgs {
+    pub options: TextOptions,
+    // Statt Fließtext eine Tabelle in diesem Format kopieren
+    pub table: Option<TableFormat>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CliCommand {
+    // Ohne Unterbefehl startet das Overlay
+    Interactive(OverlayArgs),
+    // Overlay, das den erkannten Text statt des Bildes kopiert
+    Text(TextArgs),
+    // Overlay, das QR- und Barcodes erkennt und markiert
+    Codes,
+    Capture(CaptureArgs),
+    Ocr(OcrArgs),
+    Decode(DecodeArgs),
+    Record(RecordArgs),
+    Stitch(StitchArgs),
+    Library(LibraryArgs),
+    Help,
+}
+
+// Unterbefehle ohne Fenster, die alle einen Bereich brauchen
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Headless {
+    Capture,
+    Ocr,
+    Decode,
+}
+
+impl Headless {
+    fn name(self) -> &'static str {
+        match self {
+            Headless::Capture => "capture",
+            Headless::Ocr => "ocr",
+            Headless::Decode => "decode",
+        }
+    }
+
+    fn accepts(self, flag: &str) -> bool {
+        match self {
+            Headless::Capture => flag != "--table",
+            Headless::Ocr => matches!(flag, "--region" | "--source" | "--table"),
+            Headless::Decode => matches!(flag, "--region" | "--source"),
+        }
+    }
+}
+
+fn usage_error(message: String) -> SnipError {
+    SnipError::Config(format!("{}\n\n{}", message, USAGE))
+}
+
+// Erwartet die Argumente ohne den Programmnamen
+pub fn parse_args<I, S>(args: I) -> Result<CliCommand, SnipError>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let mut args = args.into_iter().map(Into::into);
+
+    let command = match args.next().as_deref() {
+        None => return Ok(CliCommand::Interactive(OverlayArgs::default())),
+        Some("-h" | "--help" | "help") => return Ok(CliCommand::Help),
+        Some(flag) if flag.starts_with("--") => {
+            return parse_overlay_args(std::iter::once(flag.to_string()).chain(args))
+        }
+        Some("text") => return parse_text_args(args),
+        Some("codes") => return parse_codes_args(args),
+        Some("record") => return parse_record_args(args),
+        Some("stitch") => return parse_stitch_args(args),
+        Some("library") => return parse_library_args(args),
+        Some("capture") => Headless::Capture,
+        Some("ocr") => Headless::Ocr,
+        Some("decode") => Headless::Decode,
+        Some(other) => return Err(usage_error(format!("Unknown command '{}'", other))),
+    };
+
+    let mut region = None;
+    let mut out = None;
+    let mut directory = None;
+    let mut template = None;
+    let mut source = None;
+    let mut format = None;
+    let mut redactions = Vec::new();
+    let mut table = None;
+    let mut searchable = false;
+    let mut redact_pii = false;
+
+    while let Some(flag) = args.next() {
+        if flag == "-h" || flag == "--help" {
+            return Ok(CliCommand::Help);
+        }
+
+        // Schalter ohne Wert
+        if command == Headless::Capture {
+            match flag.as_str() {
+                "--searchable" => {
+                    searchable = true;
+                    continue;
+                }
+                "--redact-pii" => {
+                    redact_pii = true;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| usage_error(format!("Missing value for {}", flag)))?;
+
+        if !command.accepts(&flag) {
+            return Err(usage_error(format!(
+                "Unknown option '{}' for {}",
+                flag,
+                command.name()
+            )));
+        }
+
+        match flag.as_str() {
+            "--region" => {
+                region = Some(
+                    value
+                        .parse::<PixelRect>()
+                        .map_err(|e| usage_error(e.to_string()))?,
+                )
+            }
+            "--out" => out = Some(PathBuf::from(value)),
+            "--dir" => directory = Some(PathBuf::from(value)),
+            "--template" => template = Some(value.parse::<FilenameTemplate>()?),
+            "--source" => source = Some(PathBuf::from(value)),
+            "--format" => {
+                format = Some(
+                    ExportFormat::from_extension(&value)
+                        .ok_or(SnipError::UnsupportedFormat(value))?,
+                )
+            }
+            "--redact" => redactions.push(value.parse::<Redaction>()?),
+            "--table" => table = Some(value.parse::<TableFormat>()?),
+            _ => return Err(usage_error(format!("Unknown option '{}'", flag))),
+        }
+    }
+
+    let region = region.ok_or_else(|| usage_error("--region is required".to_string()))?;
+    if region.width == 0 || region.height == 0 {
+        return Err(usage_error(format!("Region {} is empty", region)));
+    }
+
+    match command {
+        Headless::Ocr => {
+            return Ok(CliCommand::Ocr(OcrArgs {
+                region,
+                source,
+                table,
+            }))
+        }
+        Headless::Decode => return Ok(CliCommand::Decode(DecodeArgs { region, source })),
+        Headless::Capture => {}
+    }
+
+    let output = match (out, directory, template) {
+        (Some(file), None, None) => Output::File(file),
+        (None, Some(directory), template) => Output::Directory {
+            directory,
+            template: template.unwrap_or_default(),
+        },
+        (None, None, _) => return Err(usage_error("--out or --dir is required".to_string())),
+        (Some(_), Some(_), _) => {
+            return Err(usage_error(
+                "--out and --dir cannot be combined".to_string(),
+            ))
+        }
+        (Some(_), None, Some(_)) => {
+            return Err(usage_error("--template requires --dir".to_string()))
+        }
+    };
+
+    Ok(CliCommand::Capture(CaptureArgs {
+        region,
+        output,
+        source,
+        format,
+        redactions,
+        searchable,
+        redact_pii,
+    }))
+}
+
+fn parse_overlay_args<I: Iterator<Item = String>>(mut args: I) -> Result<CliCommand, SnipError> {
+    let mut overlay = OverlayArgs::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(CliCommand::Help),
+            "--stdin" => overlay.stdin = true,
+            "--dir" | "--template" | "--command" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| usage_error(format!("Missing value for {}", flag)))?;
+                match flag.as_str() {
+                    "--dir" => overlay.directory = Some(PathBuf::from(value)),
+                    "--template" => overlay.template = value.parse()?,
+                    _ => overlay.commands.push(
+                        value
+                            .parse::<AppCommand>()
+                            .map_err(|e| usage_error(e.to_string()))?,
+                    ),
+                }
+            }
+            _ => return Err(usage_error(format!("Unknown option '{}'", flag))),
+        }
+    }
+    Ok(CliCommand::Interactive(overlay))
+}
+
+fn parse_text_args<I: Iterator<Item = String>>(mut args: I) -> Result<CliCommand, SnipError> {
+    let mut text = TextArgs::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(CliCommand::Help),
+            "--join-hyphens" => text.options.join_hyphenated = true,
+            "--table" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| usage_error(format!("Missing value for {}", flag)))?;
+                text.table = Some(value.parse()?);
+            }
+            _ => return Err(usage_error(format!("Unknown option '{}' for text", flag))),
+        }
+    }
+    Ok(CliCommand::Text(text))
+}
+
+fn parse_codes_args<I: Iterator<Item = String>>(mut args: I) -> Result<CliCommand, SnipError> {
+    match args.next().as_deref() {
+        None => Ok(CliCommand::Codes),
+        Some("-h" | "--help") => Ok(CliCommand::Help),
+        Some(flag) => Err(usage_error(format!("Unknown option '{}' for codes", flag))),
+    }
+}
+
+// Bytes, optional mit K oder M (1024 bzw. 1024²)
+fn parse_size(value: &str) -> Option<usize> {
+    let upper = value.trim().to_ascii_uppercase();
+    let (number, factor) = match upper.strip_suffix('M') {
+        Some(number) => (number, 1024 * 1024),
+        None => match upper.strip_suffix('K') {
+            Some(number) => (number, 1024),
+            None => (upper.as_str(), 1),
+        },
+    };
+    let number: f64 = number.trim().parse().ok()?;
+    (number.is_finite() && number >= 1.0).then_some((number * factor as f64) as usize)
+}
+
+fn parse_record_args<I: Iterator<Item = String>>(mut args: I) -> Result<CliCommand, SnipError> {
+    let mut region = None;
+    let mut out: Option<PathBuf> = None;
+    let mut format = None;
+    let mut source = None;
+    let mut options = RecordingOptions::default();
+
+    while let Some(flag) = args.next() {
+        if flag == "-h" || flag == "--help" {
+            return Ok(CliCommand::Help);
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| usage_error(format!("Missing value for {}", flag)))?;
+        let invalid = || usage_error(format!("Invalid value '{}' for {}", value, flag));
+
+        match flag.as_str() {
+            "--region" => {
+                region = Some(
+                    value
+                        .parse::<PixelRect>()
+                        .map_err(|e| usage_error(e.to_string()))?,
+                )
+            }
+            "--out" => out = Some(PathBuf::from(&value)),
+            "--source" => source = Some(PathBuf::from(&value)),
+            "--format" => {
+                format = Some(
+                    AnimationFormat::from_extension(&value)
+                        .ok_or_else(|| SnipError::UnsupportedFormat(value.clone()))?,
+                )
+            }
+            "--fps" => options.fps = value.parse().map_err(|_| invalid())?,
+            "--duration" => {
+                let seconds: f64 = value.parse().map_err(|_| invalid())?;
+                options.max_duration =
+                    std::time::Duration::try_from_secs_f64(seconds).map_err(|_| invalid())?;
+            }
+            "--max-size" => options.max_bytes = parse_size(&value).ok_or_else(invalid)?,
+            _ => return Err(usage_error(format!("Unknown option '{}' for record", flag))),
+        }
+    }
+
+    let out = out.ok_or_else(|| usage_error("--out is required".to_string()))?;
+    let format = format
+        .or_else(|| AnimationFormat::from_path(&out))
+        .ok_or_else(|| SnipError::UnsupportedFormat(out.display().to_string()))?;
+    if region.is_none() && source.is_some() {
+        return Err(usage_error("--source requires --region".to_string()));
+    }
+    if let Some(region) = region {
+        if region.width == 0 || region.height == 0 {
+            return Err(usage_error(format!("Region {} is empty", region)));
+        }
+    }
+    options.validate()?;
+
+    Ok(CliCommand::Record(RecordArgs {
+        region,
+        out,
+        format,
+        source,
+        options,
+    }))
+}
+
+fn parse_stitch_args<I: Iterator<Item = String>>(mut args: I) -> Result<CliCommand, SnipError> {
+    let mut out = None;
+    let mut frames = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(CliCommand::Help),
+            "--out" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| usage_error(format!("Missing value for {}", arg)))?;
+                out = Some(PathBuf::from(value));
+            }
+            flag if flag.starts_with("--") => {
+                return Err(usage_error(format!("Unknown option '{}' for stitch", flag)))
+            }
+            _ => frames.push(PathBuf::from(arg)),
+        }
+    }
+
+    let out = out.ok_or_else(|| usage_error("--out is required".to_string()))?;
+    if frames.is_empty() {
+        return Err(usage_error("stitch needs at least one frame".to_string()));
+    }
+    Ok(CliCommand::Stitch(StitchArgs { frames, out }))
+}
+
+fn parse_library_args<I: Iterator<Item = String>>(args: I) -> Result<CliCommand, SnipError> {
+    let mut directory = None;
+    let mut out = None;
+    let mut positional = Vec::new();
+
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(CliCommand::Help),
+            "--dir" | "--out" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| usage_error(format!("Missing value for {}", arg)))?;
+                if arg == "--dir" {
+                    directory = Some(PathBuf::from(value));
+                } else {
+                    out = Some(PathBuf::from(value));
+                }
+            }
+            flag if flag.starts_with("--") => {
+                return Err(usage_error(format!(
+                    "Unknown option '{}' for library",
+                    flag
+                )))
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let (action, rest) = positional
+        .split_first()
+        .ok_or_else(|| usage_error("library needs an action".to_string()))?;
+    let id = || -> Result<u64, SnipError> {
+        let value = rest
+            .first()
+            .ok_or_else(|| usage_error(format!("library {} needs an id", action)))?;
+        value
+            .parse()
+            .map_err(|_| usage_error(format!("Invalid capture id '{}'", value)))
+    };
+
+    // Anzahl der Positionsargumente nach der Aktion, die verbraucht werden
+    let (action, consumed) = match action.as_str() {
+        "list" => (LibraryAction::List, 0),
+        "search" if !rest.is_empty() => (LibraryAction::Search(rest.join(" ")), rest.len()),
+        "search" => return Err(usage_error("library search needs a query".to_string())),
+        "open" => {
+            let out = out
+                .take()
+                .ok_or_else(|| usage_error("library open needs --out".to_string()))?;
+            (LibraryAction::Open { id: id()?, out }, 1)
+        }
+        "delete" => (LibraryAction::Delete(id()?), 1),
+        "tag" => (
+            LibraryAction::Tag {
+                id: id()?,
+                tags: rest[1..].to_vec(),
+            },
+            rest.len(),
+        ),
+        other => return Err(usage_error(format!("Unknown library action '{}'", other))),
+    };
+
+    if let Some(extra) = rest.get(consumed) {
+        return Err(usage_error(format!("Unexpected argument '{}'", extra)));
+    }
+    if out.is_some() {
+        return Err(usage_error(
+            "--out is only used by library open".to_string(),
+        ));
+    }
+    Ok(CliCommand::Library(LibraryArgs { directory, action }))
+}
+
+#[cfg(windows)]
+fn screen_source() -> Result<Box<dyn FrameSource>, SnipError> {
+    let source = crate::modules::dxgi_source::DxgiFrameSource::new()
+        .map_err(|e| SnipError::capture("Failed to open desktop duplication").caused_by(e))?;
+    Ok(Box::new(source))
+}
+
+#[cfg(not(windows))]
+fn screen_source() -> Result<Box<dyn FrameSource>, SnipError> {
+    Err(SnipError::Config(
+        "Screen capture is only available on Windows, use --source".to_string(),
+    ))
+}
+
+// Ohne Bilddatei wird der Bildschirm aufgenommen
+pub fn frame_source(source: Option<&Path>) -> Result<Box<dyn FrameSource>, SnipError> {
+    match source {
+        Some(path) => {
+            let source = ImageFrameSource::open(path)
+                .map_err(|e| SnipError::capture("Failed to open the source image").caused_by(e))?;
+            Ok(Box::new(source))
+        }
+        None => screen_source(),
+    }
+}
+
+fn capture_region(
+    region: PixelRect,
+    source: Box<dyn FrameSource>,
+) -> Result<image::RgbaImage, SnipError> {
+    Capture::new(source)
+        .capture(region)
+        .map_err(|e| SnipError::capture(format!("Region {}", region)).caused_by(e))
+}
+
+// Zuschneiden und Speichern ohne Fenster; gibt den geschriebenen Pfad zurück.
+// Mit einer OCR-Engine bekommen PDFs einen durchsuchbaren Textlayer.
+pub fn run_capture(
+    args: &CaptureArgs,
+    source: Box<dyn FrameSource>,
+    engine: Option<&dyn OcrEngine>,
+) -> Result<PathBuf, SnipError> {
+    if args.redact_pii && engine.is_none() {
+        return Err(SnipError::Config(
+            "--redact-pii needs text recognition".to_string(),
+        ));
+    }
+    let image = capture_region(args.region, source)?;
+
+    // Erkannt wird auf dem ungeschwärzten Bild, der Export verwirft die geschwärzten Wörter
+    let words = match engine {
+        Some(engine) => engine.recognize_words(&image)?,
+        None => Vec::new(),
+    };
+
+    let mut redactions = args.redactions.clone();
+    if args.redact_pii {
+        let rules = PiiRules::default();
+        redactions.extend(pii::redactions(&pii::find_pii(&words, &rules), &rules));
+    }
+
+    let options = ExportOptions {
+        format: args.format,
+        redactions,
+        text_layer: if args.searchable { words } else { Vec::new() },
+    };
+
+    match &args.output {
+        Output::File(path) => {
+            export::save(&image, path, &options)?;
+            Ok(path.clone())
+        }
+        Output::Directory {
+            directory,
+            template,
+        } => {
+            let mut policy = OutputPolicy::new(directory.clone(), template.clone());
+            if let Some(format) = args.format {
+                policy = policy.with_extension(format.extension());
+            }
+            let info = CaptureInfo::new(image.width(), image.height());
+            export::save_with_policy(&image, &policy, &info, &options)
+        }
+    }
+}
+
+pub fn run_ocr(
+    args: &OcrArgs,
+    source: Box<dyn FrameSource>,
+    engine: &dyn OcrEngine,
+) -> Result<OcrResult, SnipError> {
+    let image = capture_region(args.region, source)?;
+    engine.recognize(&image)
+}
+
+// Nimmt den Bereich ohne Fenster auf und speichert die Animation; gibt den Pfad zurück
+pub fn run_record(args: &RecordArgs, source: Box<dyn FrameSource>) -> Result<PathBuf, SnipError> {
+    let region = args
+        .region
+        .ok_or_else(|| usage_error("--region is required without the overlay".to_string()))?;
+    let mut capture = Capture::new(source);
+    let recording = recording::record(|| capture.sample(region), &args.options, || false)
+        .map_err(|e| e.context(format!("Failed to record region {}", region)))?;
+    save_recording(&recording, args)?;
+    Ok(args.out.clone())
+}
+
+pub fn save_recording(
+    recording: &recording::Recording,
+    args: &RecordArgs,
+) -> Result<(), SnipError> {
+    let data = recording.encode(args.format, &args.options)?;
+    std::fs::write(&args.out, data)?;
+    Ok(())
+}
+
+pub fn run_stitch(args: &StitchArgs) -> Result<PathBuf, SnipError> {
+    let frames = args
+        .frames
+        .iter()
+        .map(|path| {
+            image::open(path)
+                .map(|image| image.to_rgba8())
+                .map_err(|e| {
+                    SnipError::from(e).context(format!("Failed to open {}", path.display()))
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let image = stitch::stitch(&frames, &StitchOptions::default())?;
+    export::save(&image, &args.out, &ExportOptions::default())?;
+    Ok(args.out.clone())
+}
+
+pub fn run_decode(
+    args: &DecodeArgs,
+    source: Box<dyn FrameSource>,
+) -> Result<Vec<DecodedCode>, SnipError> {
+    let image = capture_region(args.region, source)?;
+    Ok(barcode::decode(&image))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::ocr::StubOcrEngine;
+    use crate::modules::redact::RedactionStyle;
+    use crate::modules::testing::TempDir;
+    use image::{Rgba, RgbaImage};
+    use std::fs;
+    use std::time::Duration;
+
+    fn parse(args: &[&str]) -> Result<CliCommand, SnipError> {
+        parse_args(args.iter().copied())
+    }
+
+    fn capture_args(args: &[&str]) -> CaptureArgs {
+        match parse(args).unwrap() {
+            CliCommand::Capture(args) => args,
+            other => panic!("{:?}", other),
+        }
+    }
+
+    // Jeder Pixel kodiert seine Position, so lässt sich der Ausschnitt nachprüfen
+    fn fixture(dir: &TempDir) -> (PathBuf, RgbaImage) {
+        let image = RgbaImage::from_fn(120, 40, |x, y| Rgba([x as u8, y as u8, 200, 255]));
+        fs::create_dir_all(&dir.0).unwrap();
+        let path = dir.join("frame.png");
+        image.save(&path).unwrap();
+        (path, image)
+    }
+
+    fn run(args: &[&str], engine: Option<&dyn OcrEngine>) -> Result<PathBuf, SnipError> {
+        let args = capture_args(args);
+        run_capture(&args, frame_source(args.source.as_deref())?, engine)
+    }
+
+    #[test]
+    fn help_and_unknown_commands() {
+        assert_eq!(parse(&["--help"]).unwrap(), CliCommand::Help);
+        assert_eq!(parse(&["help"]).unwrap(), CliCommand::Help);
+        assert_eq!(
+            parse(&["capture", "--region", "0,0,1,1", "-h"]).unwrap(),
+            CliCommand::Help
+        );
+        assert!(matches!(parse(&["shoot"]), Err(SnipError::Config(_))));
+    }
+
+    #[test]
+    fn capture_arguments() {
+        let args = capture_args(&[
+            "capture",
+            "--region",
+            "-10, 20,300,200",
+            "--out",
+            "a.png",
+            "--format",
+            "webp",
+            "--redact",
+            "1,2,3,4=fill:ff0000",
+            "--redact",
+            "5,6,7,8",
+            "--searchable",
+            "--source",
+            "frame.png",
+        ]);
+        assert_eq!(
+            args,
+            CaptureArgs {
+                region: PixelRect::new(-10, 20, 300, 200),
+                output: Output::File(PathBuf::from("a.png")),
+                source: Some(PathBuf::from("frame.png")),
+                format: Some(ExportFormat::WebP),
+                redactions: vec![
+                    Redaction::new(
+                        PixelRect::new(1, 2, 3, 4),
+                        RedactionStyle::Fill(crate::modules::backend::Color::new(
+                            1.0, 0.0, 0.0, 1.0
+                        )),
+                    ),
+                    Redaction::new(PixelRect::new(5, 6, 7, 8), RedactionStyle::default()),
+                ],
+                searchable: true,
+                redact_pii: false,
+            }
+        );
+
+        let args = capture_args(&["capture", "--region", "0,0,1,1", "--dir", "out"]);
+        assert_eq!(
+            args.output,
+            Output::Directory {
+                directory: PathBuf::from("out"),
+                template: FilenameTemplate::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn capture_argument_errors() {
+        for args in [
+            &["capture", "--out", "a.png"][..],
+            &["capture", "--region", "0,0,1,1"],
+            &["capture", "--region", "0,0,0,1", "--out", "a.png"],
+            &["capture", "--region", "0,0,1", "--out", "a.png"],
+            &[
+                "capture", "--region", "0,0,1,1", "--out", "a.png", "--dir", "d",
+            ],
+            &[
+                "capture",
+                "--region",
+                "0,0,1,1",
+                "--out",
+                "a.png",
+                "--template",
+                "x",
+            ],
+            &[
+                "capture", "--region", "0,0,1,1", "--out", "a.png", "--table", "csv",
+            ],
+            &[
+                "capture", "--region", "0,0,1,1", "--out", "a.png", "--format", "gif",
+            ],
+            &["capture", "--region", "0,0,1,1", "--out"],
+            &[
+                "capture",
+                "--region",
+                "0,0,1,1",
+                "--dir",
+                "d",
+                "--template",
+                "{x}",
+            ],
+        ] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn regions_beyond_the_coordinate_range_are_rejected() {
+        for region in [
+            "2147483647,0,10,10",
+            "0,2147483000,1,1000",
+            "-1,0,4294967295,1",
+        ] {
+            let error = parse(&["capture", "--region", region, "--out", "a.png"]).unwrap_err();
+            assert!(error.to_string().contains("coordinate range"), "{}", error);
+        }
+        // Genau bis an den Rand ist erlaubt
+        let args = capture_args(&[
+            "capture",
+            "--region",
+            "2147483637,0,10,10",
+            "--out",
+            "a.png",
+        ]);
+        assert_eq!(args.region.right(), i32::MAX);
+    }
+
+    #[test]
+    fn ocr_and_decode_arguments() {
+        assert_eq!(
+            parse(&["ocr", "--region", "1,2,3,4", "--table", "markdown"]).unwrap(),
+            CliCommand::Ocr(OcrArgs {
+                region: PixelRect::new(1, 2, 3, 4),
+                source: None,
+                table: Some(TableFormat::Markdown),
+            })
+        );
+        assert_eq!(
+            parse(&["decode", "--source", "a.png", "--region", "1,2,3,4"]).unwrap(),
+            CliCommand::Decode(DecodeArgs {
+                region: PixelRect::new(1, 2, 3, 4),
+                source: Some(PathBuf::from("a.png")),
+            })
+        );
+        assert!(parse(&["ocr", "--region", "1,2,3,4", "--out", "a.png"]).is_err());
+        assert!(parse(&["decode", "--region", "1,2,3,4", "--table", "csv"]).is_err());
+    }
+
+    #[test]
+    fn text_and_codes_arguments() {
+        assert_eq!(
+            parse(&["text"]).unwrap(),
+            CliCommand::Text(TextArgs::default())
+        );
+        match parse(&["text", "--join-hyphens", "--table", "tsv"]).unwrap() {
+            CliCommand::Text(args) => {
+                assert!(args.options.join_hyphenated);
+                assert_eq!(args.table, Some(TableFormat::Tsv));
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(parse(&["text", "--table"]).is_err());
+        assert_eq!(parse(&["codes"]).unwrap(), CliCommand::Codes);
+        assert!(parse(&["codes", "--region", "1,1,1,1"]).is_err());
+    }
+
+    #[test]
+    fn record_arguments() {
+        match parse(&[
+            "record",
+            "--out",
+            "a.gif",
+            "--region",
+            "0,0,10,10",
+            "--fps",
+            "20",
+            "--duration",
+            "2.5",
+            "--max-size",
+            "1.5M",
+        ])
+        .unwrap()
+        {
+            CliCommand::Record(args) => {
+                assert_eq!(args.region, Some(PixelRect::new(0, 0, 10, 10)));
+                assert_eq!(args.format, AnimationFormat::Gif);
+                assert_eq!(args.options.fps, 20.0);
+                assert_eq!(args.options.max_duration, Duration::from_millis(2500));
+                assert_eq!(args.options.max_bytes, 1024 * 1024 * 3 / 2);
+            }
+            other => panic!("{:?}", other),
+        }
+        match parse(&["record", "--out", "a.bin", "--format", "apng"]).unwrap() {
+            CliCommand::Record(args) => {
+                assert_eq!(args.region, None);
+                assert_eq!(args.format, AnimationFormat::Apng);
+            }
+            other => panic!("{:?}", other),
+        }
+
+        assert_eq!(parse_size("500K"), Some(500 * 1024));
+        assert_eq!(parse_size("12"), Some(12));
+        assert_eq!(parse_size("0.5"), None);
+        assert!(parse(&["record", "--region", "0,0,1,1"]).is_err());
+        assert!(parse(&["record", "--out", "a.mp4"]).is_err());
+        assert!(parse(&["record", "--out", "a.gif", "--source", "a.png"]).is_err());
+        assert!(parse(&["record", "--out", "a.gif", "--duration", "-1"]).is_err());
+        assert!(parse(&["record", "--out", "a.gif", "--max-size", "viel"]).is_err());
+    }
+
+    #[test]
+    fn stitch_and_library_arguments() {
+        assert_eq!(
+            parse(&["stitch", "a.png", "--out", "all.png", "b.png"]).unwrap(),
+            CliCommand::Stitch(StitchArgs {
+                frames: vec![PathBuf::from("a.png"), PathBuf::from("b.png")],
+                out: PathBuf::from("all.png"),
+            })
+        );
+        assert!(parse(&["stitch", "--out", "all.png"]).is_err());
+        assert!(parse(&["stitch", "a.png"]).is_err());
+
+        let library = |args: &[&str]| match parse(args).unwrap() {
+            CliCommand::Library(args) => args,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(library(&["library", "list"]).action, LibraryAction::List);
+        assert_eq!(
+            library(&["library", "search", "rechnung", "märz", "--dir", "lib"]),
+            LibraryArgs {
+                directory: Some(PathBuf::from("lib")),
+                action: LibraryAction::Search("rechnung märz".to_string()),
+            }
+        );
+        assert_eq!(
+            library(&["library", "open", "3", "--out", "a.png"]).action,
+            LibraryAction::Open {
+                id: 3,
+                out: PathBuf::from("a.png")
+            }
+        );
+        assert_eq!(
+            library(&["library", "tag", "4", "a", "b"]).action,
+            LibraryAction::Tag {
+                id: 4,
+                tags: vec!["a".to_string(), "b".to_string()]
+            }
+        );
+        assert_eq!(
+            library(&["library", "delete", "5"]).action,
+            LibraryAction::Delete(5)
+        );
+        for args in [
+            &["library"][..],
+            &["library", "search"],
+            &["library", "open", "3"],
+            &["library", "delete", "x"],
+            &["library", "list", "extra"],
+            &["library", "list", "--out", "a.png"],
+            &["library", "rename", "1"],
+        ] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn capture_crops_the_source_image() {
+        let dir = TempDir::new("cli_capture");
+        let (source, frame) = fixture(&dir);
+        let out = dir.join("out.png");
+
+        let path = run(
+            &[
+                "capture",
+                "--region",
+                "10,5,30,20",
+                "--out",
+                out.to_str().unwrap(),
+                "--source",
+                source.to_str().unwrap(),
+            ],
+            None,
+        )
+        .unwrap();
+        assert_eq!(path, out);
+
+        let saved = image::open(&out).unwrap().to_rgba8();
+        assert_eq!(saved.dimensions(), (30, 20));
+        for (x, y, pixel) in saved.enumerate_pixels() {
+            assert_eq!(pixel, frame.get_pixel(x + 10, y + 5));
+        }
+    }
+
+    #[test]
+    fn capture_into_directory_with_format_and_redaction() {
+        let dir = TempDir::new("cli_capture_dir");
+        let (source, frame) = fixture(&dir);
+        let out = dir.join("shots");
+        let args = [
+            "capture",
+            "--region",
+            "100,30,50,50",
+            "--dir",
+            out.to_str().unwrap(),
+            "--template",
+            "{w}x{h}.png",
+            "--format",
+            "bmp",
+            "--redact",
+            "0,0,5,5=fill:000000",
+            "--source",
+            source.to_str().unwrap(),
+        ];
+
+        // Der Bereich ragt über das Bild hinaus und wird auf 20x10 beschnitten
+        let first = run(&args, None).unwrap();
+        let second = run(&args, None).unwrap();
+        assert_eq!(first, out.join("20x10.bmp"));
+        assert_eq!(second, out.join("20x10_2.bmp"));
+
+        let saved = image::open(&first).unwrap().to_rgba8();
+        assert_eq!(saved.dimensions(), (20, 10));
+        assert_eq!(saved.get_pixel(4, 4), &Rgba([0, 0, 0, 255]));
+        assert_eq!(saved.get_pixel(5, 5), frame.get_pixel(105, 35));
+    }
+
+    #[test]
+    fn capture_outside_the_frame_fails() {
+        let dir = TempDir::new("cli_capture_outside");
+        let (source, _) = fixture(&dir);
+        let out = dir.join("out.png");
+        let error = run(
+            &[
+                "capture",
+                "--region",
+                "2147483637,0,10,10",
+                "--out",
+                out.to_str().unwrap(),
+                "--source",
+                source.to_str().unwrap(),
+            ],
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(error, SnipError::Capture { .. }));
+        assert!(!out.exists());
+    }
+
+    #[test]
+    fn searchable_pdf_skips_redacted_words() {
+        let dir = TempDir::new("cli_capture_pdf");
+        let (source, _) = fixture(&dir);
+        let out = dir.join("out.pdf");
+        let engine = StubOcrEngine::from_text("Hallo Welt");
+
+        run(
+            &[
+                "capture",
+                "--region",
+                "0,0,100,20",
+                "--out",
+                out.to_str().unwrap(),
+                "--redact",
+                "0,0,40,12",
+                "--searchable",
+                "--source",
+                source.to_str().unwrap(),
+            ],
+            Some(&engine),
+        )
+        .unwrap();
+
+        let pdf = fs::read(&out).unwrap();
+        let contains = |needle: &[u8]| pdf.windows(needle.len()).any(|window| window == needle);
+        assert!(pdf.starts_with(b"%PDF-"));
+        // Hex-kodiert: "Welt" bleibt, "Hallo" liegt unter der Schwärzung
+        assert!(contains(b"<57656C74>"));
+        assert!(!contains(b"<48616C6C6F>"));
+    }
+
+    #[test]
+    fn redact_pii_covers_found_addresses() {
+        let dir = TempDir::new("cli_capture_pii");
+        let (source, frame) = fixture(&dir);
+        let out = dir.join("out.pdf");
+        let args = [
+            "capture",
+            "--region",
+            "0,0,120,20",
+            "--out",
+            out.to_str().unwrap(),
+            "--redact-pii",
+            "--searchable",
+            "--source",
+            source.to_str().unwrap(),
+        ];
+        assert!(capture_args(&args).redact_pii);
+        assert!(matches!(run(&args, None), Err(SnipError::Config(_))));
+
+        let engine = StubOcrEngine::from_text("An a@b.de");
+        run(&args, Some(&engine)).unwrap();
+        let pdf = fs::read(&out).unwrap();
+        let contains = |needle: &[u8]| pdf.windows(needle.len()).any(|window| window == needle);
+        // "An" bleibt durchsuchbar, die Adresse nicht
+        assert!(contains(b"<416E>"));
+        assert!(!contains(b"<6140622E6465>"));
+
+        let png = dir.join("out.png");
+        let args = [
+            "capture",
+            "--region",
+            "0,0,120,20",
+            "--out",
+            png.to_str().unwrap(),
+            "--redact-pii",
+            "--source",
+            source.to_str().unwrap(),
+        ];
+        run(&args, Some(&engine)).unwrap();
+        let saved = image::open(&png).unwrap().to_rgba8();
+        assert_eq!(saved.get_pixel(10, 5), frame.get_pixel(10, 5));
+        assert_ne!(saved.get_pixel(30, 5), frame.get_pixel(30, 5));
+    }
+
+    #[test]
+    fn overlay_takes_directory_and_template() {
+        assert_eq!(
+            parse(&[]).unwrap(),
+            CliCommand::Interactive(OverlayArgs::default())
+        );
+        assert_eq!(
+            parse(&["--template", "{w}x{h}.png", "--dir", "shots"]).unwrap(),
+            CliCommand::Interactive(OverlayArgs {
+                directory: Some(PathBuf::from("shots")),
+                template: "{w}x{h}.png".parse().unwrap(),
+                ..OverlayArgs::default()
+            })
+        );
+        assert!(parse(&["--dir"]).is_err());
+        assert!(parse(&["--template", "{nope}"]).is_err());
+        assert!(parse(&["--out", "a.png"]).is_err());
+    }
+
+    #[test]
+    fn overlay_takes_commands() {
+        assert_eq!(
+            parse(&[
+                "--command",
+                "select 10,20,300,200",
+                "--stdin",
+                "--command",
+                "save shot.png"
+            ])
+            .unwrap(),
+            CliCommand::Interactive(OverlayArgs {
+                commands: vec![
+                    AppCommand::Select {
+                        region: Some(PixelRect::new(10, 20, 300, 200))
+                    },
+                    AppCommand::Save {
+                        path: Some(PathBuf::from("shot.png"))
+                    },
+                ],
+                stdin: true,
+                ..OverlayArgs::default()
+            })
+        );
+        assert!(parse(&["--command"]).is_err());
+        assert!(parse(&["--command", "paste"]).is_err());
+        assert!(parse(&["--command", "capture now"]).is_err());
+    }
+
+    #[test]
+    fn overlay_policy_uses_the_directory() {
+        let args = OverlayArgs {
+            directory: Some(PathBuf::from("shots")),
+            ..OverlayArgs::default()
+        };
+        let policy = args.policy().unwrap();
+        assert_eq!(policy.directory, PathBuf::from("shots"));
+        assert_eq!(policy.template, FilenameTemplate::default());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::modules::errorhandler::{ErrorContext, SnipError};
+use image::RgbaImage;
+use windows::Win32::Foundation::{GlobalFree, HANDLE, HWND};
+use windows::Win32::System::DataExchange::{
+    CloseClipboard, EmptyClipboard, OpenClipboard, SetClipboardData,
+};
+use windows::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+use windows::Win32::System::Ole::{CF_DIB, CF_UNICODETEXT, CLIPBOARD_FORMAT};
+
+// Schließt die Zwischenablage auch auf dem Fehlerpfad wieder
+struct OpenedClipboard;
+
+impl OpenedClipboard {
+    unsafe fn open() -> Result<Self, SnipError> {
+        OpenClipboard(HWND::default()).context("Failed to open the clipboard")?;
+        Ok(OpenedClipboard)
+    }
+}
+
+impl Drop for OpenedClipboard {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CloseClipboard();
+        }
+    }
+}
+
+// Kopiert die Daten in globalen Speicher und übergibt ihn der bereits geöffneten Zwischenablage
+unsafe fn put(format: CLIPBOARD_FORMAT, data: &[u8]) -> Result<(), SnipError> {
+    let memory =
+        GlobalAlloc(GMEM_MOVEABLE, data.len()).context("Failed to allocate clipboard memory")?;
+
+    let target = GlobalLock(memory) as *mut u8;
+    if target.is_null() {
+        let _ = GlobalFree(memory);
+        return Err(SnipError::from(windows::core::Error::from_win32())
+            .context("Failed to lock clipboard memory"));
+    }
+    std::ptr::copy_nonoverlapping(data.as_ptr(), target, data.len());
+    // Meldet beim letzten Entsperren immer einen "Fehler" ohne Fehlercode
+    let _ = GlobalUnlock(memory);
+
+    // Bei Erfolg gehört der Speicher dem System
+    if let Err(e) = SetClipboardData(format.0 as u32, HANDLE(memory.0 as isize)) {
+        let _ = GlobalFree(memory);
+        return Err(SnipError::from(e).context("Failed to set the clipboard data"));
+    }
+    Ok(())
+}
+
+// Ersetzt den Inhalt der Zwischenablage durch den Text; Zeilenumbrüche werden zu CRLF
+pub fn set_text(text: &str) -> Result<(), SnipError> {
+    let bytes: Vec<u8> = text
+        .replace("\r\n", "\n")
+        .replace('\n', "\r\n")
+        .encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(u16::to_le_bytes)
+        .collect();
+
+    unsafe {
+        let _clipboard = OpenedClipboard::open()?;
+        EmptyClipboard().context("Failed to clear the clipboard")?;
+        put(CF_UNICODETEXT, &bytes)
+    }
+}
+
+// Geräteunabhängige Bitmap: BITMAPINFOHEADER, danach die Zeilen von unten nach oben als BGRA
+fn dib(image: &RgbaImage) -> Vec<u8> {
+    const HEADER_SIZE: u32 = 40;
+    let (width, height) = image.dimensions();
+    let pixels = width * height * 4;
+
+    let mut data = Vec::with_capacity((HEADER_SIZE + pixels) as usize);
+    data.extend_from_slice(&HEADER_SIZE.to_le_bytes());
+    data.extend_from_slice(&(width as i32).to_le_bytes());
+    data.extend_from_slice(&(height as i32).to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&32u16.to_le_bytes());
+    // BI_RGB, Bildgröße, Auflösung und Farbtabelle
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&pixels.to_le_bytes());
+    data.extend_from_slice(&[0; 16]);
+
+    for row in image.rows().rev() {
+        for p in row {
+            data.extend_from_slice(&[p[2], p[1], p[0], p[3]]);
+        }
+    }
+    data
+}
+
+// Ersetzt den Inhalt der Zwischenablage durch das Bild
+pub fn set_image(image: &RgbaImage) -> Result<(), SnipError> {
+    let data = dib(image);
+    unsafe {
+        let _clipboard = OpenedClipboard::open()?;
+        EmptyClipboard().context("Failed to clear the clipboard")?;
+        put(CF_DIB, &data)
+    }
+}
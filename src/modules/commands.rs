@@ -1 +1,601 @@
+use crate::modules::backend::PixelRect;
+use crate::modules::selection::{Key, Modifiers};
+use crate::modules::table::TableFormat;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandName {
+    Select,
+    Capture,
+    Save,
+    Copy,
+    Ocr,
+    Table,
+    Decode,
+    Record,
+    Annotate,
+    Undo,
+    Redo,
+    Cancel,
+}
+
+impl CommandName {
+    pub const ALL: [CommandName; 12] = [
+        CommandName::Select,
+        CommandName::Capture,
+        CommandName::Save,
+        CommandName::Copy,
+        CommandName::Ocr,
+        CommandName::Table,
+        CommandName::Decode,
+        CommandName::Record,
+        CommandName::Annotate,
+        CommandName::Undo,
+        CommandName::Redo,
+        CommandName::Cancel,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommandName::Select => "select",
+            CommandName::Capture => "capture",
+            CommandName::Save => "save",
+            CommandName::Copy => "copy",
+            CommandName::Ocr => "ocr",
+            CommandName::Table => "table",
+            CommandName::Decode => "decode",
+            CommandName::Record => "record",
+            CommandName::Annotate => "annotate",
+            CommandName::Undo => "undo",
+            CommandName::Redo => "redo",
+            CommandName::Cancel => "cancel",
+        }
+    }
+}
+
+impl FromStr for CommandName {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        CommandName::ALL
+            .iter()
+            .find(|name| name.as_str().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| anyhow!("Unknown command '{}'", s))
+    }
+}
+
+impl fmt::Display for CommandName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Alles, was Tastenkürzel, Kommandozeile oder IPC auslösen können
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum AppCommand {
+    // Ohne Bereich wird die Auswahl zurückgesetzt
+    Select { region: Option<PixelRect> },
+    Capture,
+    // Ohne Pfad entscheidet die Ausgaberichtlinie über den Dateinamen
+    Save { path: Option<PathBuf> },
+    Copy,
+    Ocr,
+    // Erkannte Tabelle in die Zwischenablage
+    Table { format: TableFormat },
+    // QR- und Barcodes in der Auswahl erkennen und markieren
+    Decode,
+    // Die Auswahl als Animation aufnehmen
+    Record,
+    Annotate,
+    Undo,
+    Redo,
+    Cancel,
+}
+
+impl AppCommand {
+    pub fn name(&self) -> CommandName {
+        match self {
+            AppCommand::Select { .. } => CommandName::Select,
+            AppCommand::Capture => CommandName::Capture,
+            AppCommand::Save { .. } => CommandName::Save,
+            AppCommand::Copy => CommandName::Copy,
+            AppCommand::Ocr => CommandName::Ocr,
+            AppCommand::Table { .. } => CommandName::Table,
+            AppCommand::Decode => CommandName::Decode,
+            AppCommand::Record => CommandName::Record,
+            AppCommand::Annotate => CommandName::Annotate,
+            AppCommand::Undo => CommandName::Undo,
+            AppCommand::Redo => CommandName::Redo,
+            AppCommand::Cancel => CommandName::Cancel,
+        }
+    }
+
+    // Befehle, die ohne Auswahl nichts zu tun haben
+    pub fn needs_selection(&self) -> bool {
+        !matches!(
+            self,
+            AppCommand::Select { .. } | AppCommand::Undo | AppCommand::Redo | AppCommand::Cancel
+        )
+    }
+}
+
+// Textform für Kommandozeile und IPC, z.B. `select 10,20,300,200` oder `save shot.png`
+impl FromStr for AppCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (name, argument) = match s.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, Some(argument.trim())),
+            None => (s, None),
+        };
+
+        let command = match (name.parse()?, argument) {
+            (CommandName::Select, region) => AppCommand::Select {
+                region: region.map(str::parse).transpose()?,
+            },
+            (CommandName::Save, path) => AppCommand::Save {
+                path: path.map(PathBuf::from),
+            },
+            (CommandName::Capture, None) => AppCommand::Capture,
+            (CommandName::Copy, None) => AppCommand::Copy,
+            (CommandName::Ocr, None) => AppCommand::Ocr,
+            (CommandName::Table, format) => AppCommand::Table {
+                format: format.map(str::parse).transpose()?.unwrap_or_default(),
+            },
+            (CommandName::Decode, None) => AppCommand::Decode,
+            (CommandName::Record, None) => AppCommand::Record,
+            (CommandName::Annotate, None) => AppCommand::Annotate,
+            (CommandName::Undo, None) => AppCommand::Undo,
+            (CommandName::Redo, None) => AppCommand::Redo,
+            (CommandName::Cancel, None) => AppCommand::Cancel,
+            (name, Some(argument)) => {
+                return Err(anyhow!(
+                    "Command '{}' takes no argument, got '{}'",
+                    name,
+                    argument
+                ))
+            }
+        };
+        Ok(command)
+    }
+}
+
+impl fmt::Display for AppCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppCommand::Select {
+                region: Some(region),
+            } => write!(f, "select {}", region),
+            AppCommand::Save { path: Some(path) } => write!(f, "save {}", path.display()),
+            AppCommand::Table { format } => write!(f, "table {}", format),
+            command => f.write_str(command.name().as_str()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandSource {
+    // Folgebefehle aus der Auswahl, z.B. Capture nach Enter
+    Input,
+    Shortcut,
+    Cli,
+    Ipc,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueuedCommand {
+    pub source: CommandSource,
+    pub command: AppCommand,
+}
+
+// Threadsichere Warteschlange; alle Klone teilen sich denselben Puffer
+#[derive(Clone, Default)]
+pub struct CommandQueue {
+    pending: Arc<Mutex<VecDeque<QueuedCommand>>>,
+}
+
+impl CommandQueue {
+    pub fn new() -> Self {
+        CommandQueue::default()
+    }
+
+    pub fn post(&self, source: CommandSource, command: AppCommand) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.push_back(QueuedCommand { source, command });
+        }
+    }
+
+    pub fn post_line(&self, source: CommandSource, line: &str) -> Result<()> {
+        self.post(source, line.parse()?);
+        Ok(())
+    }
+
+    pub fn pop(&self) -> Option<QueuedCommand> {
+        self.pending
+            .lock()
+            .ok()
+            .and_then(|mut pending| pending.pop_front())
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending
+            .lock()
+            .map(|pending| pending.len())
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+pub type CommandHandler<T> = Box<dyn FnMut(&T, &QueuedCommand) -> Result<()>>;
+
+// Verteilt Befehle an die registrierten Handler; T ist der Kontext, z.B. der WindowController
+pub struct CommandBus<T> {
+    queue: CommandQueue,
+    handlers: HashMap<CommandName, Vec<CommandHandler<T>>>,
+}
+
+impl<T> CommandBus<T> {
+    pub fn new(queue: CommandQueue) -> Self {
+        CommandBus {
+            queue,
+            handlers: HashMap::new(),
+        }
+    }
+
+    pub fn queue(&self) -> &CommandQueue {
+        &self.queue
+    }
+
+    // Mehrere Handler pro Befehl laufen in der Reihenfolge der Registrierung
+    pub fn register(&mut self, name: CommandName, handler: CommandHandler<T>) {
+        self.handlers.entry(name).or_default().push(handler);
+    }
+
+    pub fn is_handled(&self, name: CommandName) -> bool {
+        self.handlers
+            .get(&name)
+            .is_some_and(|handlers| !handlers.is_empty())
+    }
+
+    // Gibt false zurück, wenn für den Befehl kein Handler registriert ist
+    pub fn execute(&mut self, context: &T, queued: &QueuedCommand) -> Result<bool> {
+        let handlers = match self.handlers.get_mut(&queued.command.name()) {
+            Some(handlers) if !handlers.is_empty() => handlers,
+            _ => return Ok(false),
+        };
+
+        for handler in handlers.iter_mut() {
+            handler(context, queued)
+                .map_err(|e| anyhow!("Command '{}' failed: {}", queued.command, e))?;
+        }
+        Ok(true)
+    }
+
+    // Arbeitet die Warteschlange ab, einschließlich der Befehle, die Handler dabei neu einreihen.
+    // Bei einem Befehl ohne Handler oder einem Fehler bleibt der Rest für den nächsten Aufruf liegen.
+    pub fn run_pending(&mut self, context: &T) -> Result<usize> {
+        let mut handled = 0;
+        while let Some(queued) = self.queue.pop() {
+            if !self.execute(context, &queued)? {
+                return Err(anyhow!(
+                    "Command '{}' is not available here",
+                    queued.command
+                ));
+            }
+            handled += 1;
+        }
+        Ok(handled)
+    }
+}
+
+// Ordnet Tastenkombinationen Befehle zu
+#[derive(Clone, Debug, Default)]
+pub struct Shortcuts {
+    bindings: HashMap<(Key, Modifiers), AppCommand>,
+}
+
+impl Shortcuts {
+    pub fn new() -> Self {
+        Shortcuts::default()
+    }
+
+    pub fn bind(&mut self, key: Key, modifiers: Modifiers, command: AppCommand) {
+        self.bindings.insert((key, modifiers), command);
+    }
+
+    pub fn lookup(&self, key: Key, modifiers: Modifiers) -> Option<&AppCommand> {
+        self.bindings.get(&(key, modifiers))
+    }
+}
+
+// Enter und Escape gehören der Auswahl, die daraus selbst Capture bzw. Cancel erzeugt
+pub fn default_shortcuts() -> Shortcuts {
+    let ctrl = Modifiers {
+        shift: false,
+        ctrl: true,
+    };
+
+    let ctrl_shift = Modifiers {
+        shift: true,
+        ctrl: true,
+    };
+
+    let mut shortcuts = Shortcuts::new();
+    shortcuts.bind(Key::Char('C'), ctrl, AppCommand::Copy);
+    shortcuts.bind(Key::Char('S'), ctrl, AppCommand::Save { path: None });
+    shortcuts.bind(Key::Char('T'), ctrl, AppCommand::Ocr);
+    shortcuts.bind(
+        Key::Char('T'),
+        ctrl_shift,
+        AppCommand::Table {
+            format: TableFormat::default(),
+        },
+    );
+    shortcuts.bind(Key::Char('D'), ctrl, AppCommand::Decode);
+    shortcuts.bind(Key::Char('E'), ctrl, AppCommand::Annotate);
+    shortcuts.bind(Key::Char('Z'), ctrl, AppCommand::Undo);
+    shortcuts.bind(Key::Char('Y'), ctrl, AppCommand::Redo);
+    shortcuts.bind(Key::Char('Z'), ctrl_shift, AppCommand::Redo);
+    shortcuts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    fn ctrl() -> Modifiers {
+        Modifiers {
+            shift: false,
+            ctrl: true,
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for name in CommandName::ALL {
+            assert_eq!(name.to_string().parse::<CommandName>().unwrap(), name);
+        }
+        assert_eq!("UNDO".parse::<CommandName>().unwrap(), CommandName::Undo);
+        assert!("paste".parse::<CommandName>().is_err());
+    }
+
+    fn all_commands() -> Vec<AppCommand> {
+        vec![
+            AppCommand::Select { region: None },
+            AppCommand::Select {
+                region: Some(PixelRect::new(10, 20, 300, 200)),
+            },
+            AppCommand::Capture,
+            AppCommand::Save { path: None },
+            AppCommand::Save {
+                path: Some(PathBuf::from("shot.png")),
+            },
+            AppCommand::Copy,
+            AppCommand::Ocr,
+            AppCommand::Table {
+                format: TableFormat::Markdown,
+            },
+            AppCommand::Decode,
+            AppCommand::Record,
+            AppCommand::Annotate,
+            AppCommand::Undo,
+            AppCommand::Redo,
+            AppCommand::Cancel,
+        ]
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        for command in all_commands() {
+            assert_eq!(command.to_string().parse::<AppCommand>().unwrap(), command);
+        }
+        assert_eq!(
+            AppCommand::Select {
+                region: Some(PixelRect::new(10, 20, 300, 200))
+            }
+            .to_string(),
+            "select 10,20,300,200"
+        );
+    }
+
+    #[test]
+    fn commands_round_trip_through_json() {
+        for command in all_commands() {
+            let json = serde_json::to_string(&command).unwrap();
+            assert_eq!(serde_json::from_str::<AppCommand>(&json).unwrap(), command);
+        }
+        assert_eq!(
+            serde_json::to_string(&AppCommand::Table {
+                format: TableFormat::Csv
+            })
+            .unwrap(),
+            r#"{"command":"table","format":"csv"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<AppCommand>(r#"{"command":"save","path":"shot.png"}"#).unwrap(),
+            AppCommand::Save {
+                path: Some(PathBuf::from("shot.png"))
+            }
+        );
+        assert!(serde_json::from_str::<AppCommand>(r#"{"command":"paste"}"#).is_err());
+
+        let source = serde_json::to_string(&CommandSource::Ipc).unwrap();
+        assert_eq!(source, r#""ipc""#);
+        assert_eq!(
+            serde_json::from_str::<CommandSource>(&source).unwrap(),
+            CommandSource::Ipc
+        );
+    }
+
+    #[test]
+    fn parsing_handles_whitespace_and_defaults() {
+        assert_eq!(
+            "  save   my shot.png ".parse::<AppCommand>().unwrap(),
+            AppCommand::Save {
+                path: Some(PathBuf::from("my shot.png"))
+            }
+        );
+        assert_eq!(
+            "table".parse::<AppCommand>().unwrap(),
+            AppCommand::Table {
+                format: TableFormat::default()
+            }
+        );
+        assert!("capture now".parse::<AppCommand>().is_err());
+        assert!("select 10,20".parse::<AppCommand>().is_err());
+        assert!("".parse::<AppCommand>().is_err());
+    }
+
+    #[test]
+    fn queue_keeps_the_order_across_clones() {
+        let queue = CommandQueue::new();
+        let other = queue.clone();
+        queue.post(CommandSource::Shortcut, AppCommand::Copy);
+        other.post_line(CommandSource::Ipc, "undo").unwrap();
+        assert!(other.post_line(CommandSource::Ipc, "paste").is_err());
+        assert_eq!(queue.len(), 2);
+
+        assert_eq!(
+            other.pop(),
+            Some(QueuedCommand {
+                source: CommandSource::Shortcut,
+                command: AppCommand::Copy
+            })
+        );
+        assert_eq!(
+            queue.pop(),
+            Some(QueuedCommand {
+                source: CommandSource::Ipc,
+                command: AppCommand::Undo
+            })
+        );
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn handlers_run_in_registration_order() {
+        let queue = CommandQueue::new();
+        let mut bus: CommandBus<RefCell<Vec<String>>> = CommandBus::new(queue.clone());
+        bus.register(
+            CommandName::Copy,
+            Box::new(|log, _| {
+                log.borrow_mut().push("first".to_string());
+                Ok(())
+            }),
+        );
+        bus.register(
+            CommandName::Copy,
+            Box::new(|log, queued| {
+                log.borrow_mut().push(format!("second {:?}", queued.source));
+                Ok(())
+            }),
+        );
+        assert!(bus.is_handled(CommandName::Copy));
+        assert!(!bus.is_handled(CommandName::Save));
+
+        let log = RefCell::new(Vec::new());
+        queue.post(CommandSource::Cli, AppCommand::Copy);
+        assert_eq!(bus.run_pending(&log).unwrap(), 1);
+        assert_eq!(log.into_inner(), vec!["first", "second Cli"]);
+    }
+
+    #[test]
+    fn handlers_can_queue_follow_up_commands() {
+        let queue = CommandQueue::new();
+        let mut bus: CommandBus<RefCell<Vec<AppCommand>>> = CommandBus::new(queue.clone());
+        let follow_up = queue.clone();
+        bus.register(
+            CommandName::Capture,
+            Box::new(move |log, queued| {
+                log.borrow_mut().push(queued.command.clone());
+                follow_up.post(CommandSource::Input, AppCommand::Copy);
+                Ok(())
+            }),
+        );
+        bus.register(
+            CommandName::Copy,
+            Box::new(|log, queued| {
+                log.borrow_mut().push(queued.command.clone());
+                Ok(())
+            }),
+        );
+
+        let log = RefCell::new(Vec::new());
+        queue.post(CommandSource::Shortcut, AppCommand::Capture);
+        assert_eq!(bus.run_pending(&log).unwrap(), 2);
+        assert_eq!(
+            log.into_inner(),
+            vec![AppCommand::Capture, AppCommand::Copy]
+        );
+    }
+
+    #[test]
+    fn unhandled_and_failing_commands_are_errors() {
+        let queue = CommandQueue::new();
+        let mut bus: CommandBus<()> = CommandBus::new(queue.clone());
+        bus.register(CommandName::Undo, Box::new(|_, _| Ok(())));
+        bus.register(
+            CommandName::Redo,
+            Box::new(|_, _| Err(anyhow!("nothing to redo"))),
+        );
+
+        // Was nach dem Fehler kommt, bleibt für den nächsten Durchlauf
+        queue.post(CommandSource::Cli, AppCommand::Decode);
+        queue.post(CommandSource::Cli, AppCommand::Undo);
+        let error = bus.run_pending(&()).unwrap_err();
+        assert!(error.to_string().contains("decode"));
+        assert_eq!(queue.len(), 1);
+        assert_eq!(bus.run_pending(&()).unwrap(), 1);
+
+        queue.post(CommandSource::Cli, AppCommand::Redo);
+        let error = bus.run_pending(&()).unwrap_err();
+        assert_eq!(error.to_string(), "Command 'redo' failed: nothing to redo");
+    }
+
+    #[test]
+    fn shortcuts_are_looked_up_by_key_and_modifiers() {
+        let shortcuts = default_shortcuts();
+        let ctrl_shift = Modifiers {
+            shift: true,
+            ctrl: true,
+        };
+        assert_eq!(
+            shortcuts.lookup(Key::Char('S'), ctrl()),
+            Some(&AppCommand::Save { path: None })
+        );
+        assert_eq!(
+            shortcuts.lookup(Key::Char('Z'), ctrl()),
+            Some(&AppCommand::Undo)
+        );
+        assert_eq!(
+            shortcuts.lookup(Key::Char('Z'), ctrl_shift),
+            Some(&AppCommand::Redo)
+        );
+        assert_eq!(shortcuts.lookup(Key::Char('Z'), Modifiers::default()), None);
+        assert_eq!(shortcuts.lookup(Key::Enter, Modifiers::default()), None);
+
+        let mut shortcuts = Shortcuts::new();
+        shortcuts.bind(Key::Char('C'), ctrl(), AppCommand::Copy);
+        shortcuts.bind(Key::Char('C'), ctrl(), AppCommand::Capture);
+        assert_eq!(
+            shortcuts.lookup(Key::Char('C'), ctrl()),
+            Some(&AppCommand::Capture)
+        );
+    }
+}
@@ -1,38 +1,112 @@
-use crate::modules::drawing::Drawing;
-
-use crate::modules::resource_manager::ResourceManager;
-use crate::modules::win_fact::{Window, WindowType};
+use crate::modules::annotation::{self, AnnotationEditor, Shape, ShapeId, Tool};
+use crate::modules::backend::{
+    Backend, Color, Frame, Overlay, PixelRect, Rect, Surface, WindowType,
+};
+use crate::modules::capture::{self, Capture, FrameSourceFactory};
+use crate::modules::commands::{
+    default_shortcuts, AppCommand, CommandQueue, CommandSource, Shortcuts,
+};
+use crate::modules::history::History;
+use crate::modules::recording::{self, Recording, RecordingOptions};
+use crate::modules::selection::{
+    InputEvent, Key, Modifiers, SelectionEdit, SelectionSession, SelectionState,
+};
+use crate::modules::table::TableFormat;
 use anyhow::{anyhow, Result};
+use image::RgbaImage;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
 use std::sync::{Arc, Mutex, MutexGuard};
-use windows::Win32::Graphics::Direct2D::Common::*;
-use windows::Win32::UI::WindowsAndMessaging::*;
+use std::thread;
+use std::time::Duration;
+
+const OVERLAY_HIDE_DELAY: Duration = Duration::from_millis(100);
 
 pub enum Command {
     Show,
-    DrawOverlay(Option<D2D_RECT_F>),
-    FillBackground(D2D1_COLOR_F),
+    Hide,
+    DrawOverlay(Overlay),
+    FillBackground(Color),
+    DrawAnnotations(Vec<Shape>),
     RedrawWindow,
 }
 
+// Bearbeitung der Auswahl nach Annotate; die Formen liegen in Bildkoordinaten der Auswahl
+struct Annotating {
+    editor: AnnotationEditor,
+    // Linke obere Ecke der Auswahl im Overlay
+    origin: (f32, f32),
+    tool: Tool,
+    // Startpunkt und, sobald sich die Maus bewegt hat, die aufgezogene Form
+    drag: Option<((f32, f32), Option<ShapeId>)>,
+}
+
+// Aufnahme auf einem eigenen Thread, damit die Nachrichtenschleife weiterläuft
+struct ActiveRecording {
+    stop: Arc<AtomicBool>,
+    result: Receiver<Result<Recording>>,
+}
+
+// Was nach dem Abschluss der Auswahl passiert
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CaptureMode {
+    #[default]
+    Image,
+    // Text bzw. Tabelle erkennen und in die Zwischenablage legen;
+    // die Auswahl endet hier schon beim Loslassen der Maus
+    Text,
+    Table(TableFormat),
+    // QR- und Barcodes nach jedem Aufziehen erkennen und markieren; Enter schließt das Overlay
+    Codes,
+    // Die bestätigte Auswahl als Animation aufnehmen
+    Record,
+}
+
 pub struct WindowController {
-    transparent_window: Mutex<Option<Window>>,
-    opaque_window: Mutex<Option<Window>>,
-    main_window: Mutex<Option<Window>>,
-    resource_manager: Arc<ResourceManager>,
+    transparent_window: Mutex<Option<Rc<dyn Surface>>>,
+    opaque_window: Mutex<Option<Rc<dyn Surface>>>,
+    main_window: Mutex<Option<Rc<dyn Surface>>>,
+    selection: Mutex<SelectionSession>,
+    history: Mutex<History<SelectionEdit>>,
+    capture: Mutex<Option<Capture>>,
+    recording_source: Mutex<Option<FrameSourceFactory>>,
+    recording: Mutex<Option<ActiveRecording>>,
+    last_capture: Mutex<Option<RgbaImage>>,
+    highlights: Mutex<Vec<Rect>>,
+    annotation: Mutex<Option<Annotating>>,
+    commands: CommandQueue,
+    shortcuts: Shortcuts,
+    mode: CaptureMode,
+    backend: Box<dyn Backend>,
 }
 
 impl WindowController {
-    pub fn new() -> Self {
-        let resource_manager = ResourceManager::new().expect("Failed to create resource manager");
+    pub fn new(backend: Box<dyn Backend>) -> Self {
+        // Die Mauskoordinaten beziehen sich auf das Overlay, das den ganzen virtuellen Bildschirm abdeckt
+        let screen = backend.screen_bounds();
+        let bounds = PixelRect::new(0, 0, screen.width, screen.height).to_rect();
+
         WindowController {
             transparent_window: Mutex::new(None),
             opaque_window: Mutex::new(None),
             main_window: Mutex::new(None),
-            resource_manager: resource_manager,
+            selection: Mutex::new(SelectionSession::new(bounds)),
+            history: Mutex::new(History::new()),
+            capture: Mutex::new(None),
+            recording_source: Mutex::new(None),
+            recording: Mutex::new(None),
+            last_capture: Mutex::new(None),
+            highlights: Mutex::new(Vec::new()),
+            annotation: Mutex::new(None),
+            commands: CommandQueue::new(),
+            shortcuts: default_shortcuts(),
+            mode: CaptureMode::default(),
+            backend,
         }
     }
 
-    fn window_ref(&self, window_type: WindowType) -> Option<&Mutex<Option<Window>>> {
+    fn window_ref(&self, window_type: WindowType) -> Option<&Mutex<Option<Rc<dyn Surface>>>> {
         match window_type {
             WindowType::Transparent => Some(&self.transparent_window),
             WindowType::Opaque => Some(&self.opaque_window),
@@ -41,7 +115,10 @@ impl WindowController {
         }
     }
 
-    fn locked_window(&self, window_type: WindowType) -> Result<MutexGuard<Option<Window>>> {
+    fn locked_window(
+        &self,
+        window_type: WindowType,
+    ) -> Result<MutexGuard<'_, Option<Rc<dyn Surface>>>> {
         self.window_ref(window_type)
             .ok_or_else(|| anyhow!("Invalid window type"))
             .and_then(|mutex| {
@@ -51,30 +128,602 @@ impl WindowController {
             })
     }
 
-    pub fn add_window(&self, mut window: Window) -> Result<()> {
-        let hwnd = window.get_hwnd();
-        let drawing = Drawing::new(hwnd, &self.resource_manager);
+    pub fn create_window(&self, window_type: WindowType) -> Result<()> {
+        let window = self.backend.create_window(window_type, self)?;
+
+        if window_type == WindowType::Transparent {
+            if let Some(frame) = self.locked_capture()?.as_ref().and_then(|c| c.frozen()) {
+                window.set_backdrop(frame)?;
+            }
+        }
+
+        let mut locked_window = self.locked_window(window_type)?;
+        *locked_window = Some(Rc::from(window));
+
+        Ok(())
+    }
+
+    fn locked_selection(&self) -> Result<MutexGuard<'_, SelectionSession>> {
+        self.selection
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock selection mutex"))
+    }
+
+    pub fn selection(&self) -> Option<Rect> {
+        self.locked_selection()
+            .ok()
+            .and_then(|session| session.rect())
+    }
+
+    pub fn selection_bounds(&self) -> Option<PixelRect> {
+        self.locked_selection()
+            .ok()
+            .and_then(|session| session.pixel_bounds())
+    }
+
+    pub fn overlay(&self) -> Overlay {
+        let highlights = self
+            .highlights
+            .lock()
+            .map(|highlights| highlights.clone())
+            .unwrap_or_default();
+
+        self.locked_selection()
+            .map(|session| Overlay {
+                selection: session.rect(),
+                handles: session.handles(),
+                highlights,
+            })
+            .unwrap_or_default()
+    }
+
+    // Bereiche relativ zum Bild aus selection_image; ersetzt die bisherigen Markierungen
+    pub fn set_highlights(&self, regions: &[PixelRect]) -> Result<()> {
+        let origin = self
+            .selection_bounds()
+            .map(|bounds| (bounds.x.max(0), bounds.y.max(0)))
+            .unwrap_or_default();
 
-        window.set_drawing(drawing);
+        let highlights = regions
+            .iter()
+            .map(|region| {
+                PixelRect::new(
+                    region.x + origin.0,
+                    region.y + origin.1,
+                    region.width,
+                    region.height,
+                )
+                .to_rect()
+            })
+            .collect();
+        *self
+            .highlights
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock highlights mutex"))? = highlights;
 
-        let mut locked_window = self.locked_window(window.window_type)?;
-        *locked_window = Some(window);
+        self.dispatch(WindowType::Transparent, Command::RedrawWindow)
+    }
 
-        // Set the WindowController pointer to the window
-        unsafe {
-            SetWindowLongPtrA(hwnd, GWLP_USERDATA, self as *const _ as isize);
+    pub fn reset_selection(&self) -> Result<()> {
+        self.locked_selection()?.reset();
+        self.locked_history()?.clear();
+        *self.locked_annotation()? = None;
+        Ok(())
+    }
+
+    pub fn select(&self, region: Option<PixelRect>) -> Result<()> {
+        {
+            let mut session = self.locked_selection()?;
+            let edit = SelectionEdit {
+                before: session.rect(),
+                after: region.map(|region| region.to_rect()),
+            };
+            self.locked_history()?.execute(edit, &mut session);
         }
+        self.dispatch(WindowType::Transparent, Command::RedrawWindow)
+    }
+
+    fn locked_history(&self) -> Result<MutexGuard<'_, History<SelectionEdit>>> {
+        self.history
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock history mutex"))
+    }
+
+    // Gibt false zurück, wenn es nichts rückgängig zu machen gibt oder gerade gezogen wird.
+    // Beim Annotieren betrifft das die Formen, nicht die Auswahl.
+    pub fn undo(&self) -> Result<bool> {
+        if let Some(changed) = self.step_annotation(AnnotationEditor::undo)? {
+            return Ok(changed);
+        }
+        self.step_history(|history, session| history.undo(session))
+    }
 
+    pub fn redo(&self) -> Result<bool> {
+        if let Some(changed) = self.step_annotation(AnnotationEditor::redo)? {
+            return Ok(changed);
+        }
+        self.step_history(|history, session| history.redo(session))
+    }
+
+    // None, wenn gerade nicht annotiert wird
+    fn step_annotation<F>(&self, step: F) -> Result<Option<bool>>
+    where
+        F: FnOnce(&mut AnnotationEditor) -> bool,
+    {
+        let changed = {
+            let mut annotation = self.locked_annotation()?;
+            match annotation.as_mut() {
+                Some(annotating) if annotating.drag.is_some() => false,
+                Some(annotating) => step(&mut annotating.editor),
+                None => return Ok(None),
+            }
+        };
+
+        if changed {
+            self.dispatch(WindowType::Transparent, Command::RedrawWindow)?;
+        }
+        Ok(Some(changed))
+    }
+
+    fn step_history<F>(&self, step: F) -> Result<bool>
+    where
+        F: FnOnce(&mut History<SelectionEdit>, &mut SelectionSession) -> bool,
+    {
+        let changed = {
+            let mut session = self.locked_selection()?;
+            if session.in_gesture() || session.is_finished() {
+                return Ok(false);
+            }
+            step(&mut *self.locked_history()?, &mut session)
+        };
+
+        if changed {
+            self.dispatch(WindowType::Transparent, Command::RedrawWindow)?;
+        }
+        Ok(changed)
+    }
+
+    // Über diese Warteschlange erreichen Tastenkürzel und Auswahl den CommandBus
+    pub fn commands(&self) -> &CommandQueue {
+        &self.commands
+    }
+
+    pub fn set_shortcuts(&mut self, shortcuts: Shortcuts) {
+        self.shortcuts = shortcuts;
+    }
+
+    pub fn mode(&self) -> CaptureMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: CaptureMode) {
+        self.mode = mode;
+    }
+
+    fn locked_capture(&self) -> Result<MutexGuard<'_, Option<Capture>>> {
+        self.capture
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock capture mutex"))
+    }
+
+    pub fn set_capture(&self, capture: Capture) -> Result<()> {
+        *self.locked_capture()? = Some(capture);
+        Ok(())
+    }
+
+    // Quelle für start_recording; die Aufnahme läuft auf einem eigenen Thread
+    pub fn set_recording_source(&self, factory: FrameSourceFactory) -> Result<()> {
+        *self
+            .recording_source
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock recording source mutex"))? = Some(factory);
+        Ok(())
+    }
+
+    fn locked_recording(&self) -> Result<MutexGuard<'_, Option<ActiveRecording>>> {
+        self.recording
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock recording mutex"))
+    }
+
+    // Muss vor create_window aufgerufen werden, damit das Overlay selbst nicht im Bild ist
+    pub fn freeze(&self) -> Result<()> {
+        let mut locked_capture = self.locked_capture()?;
+        let capture = locked_capture
+            .as_mut()
+            .ok_or_else(|| anyhow!("No capture source configured"))?;
+        capture.freeze()?;
         Ok(())
     }
 
+    pub fn take_capture(&self) -> Option<RgbaImage> {
+        self.last_capture
+            .lock()
+            .ok()
+            .and_then(|mut image| image.take())
+    }
+
+    // Leitet ein Eingabeereignis an die Auswahl weiter und fordert bei Bedarf ein Neuzeichnen an.
+    // Tastenkürzel und das Ende der Auswahl werden als Befehle eingereiht.
+    pub fn handle_input(&self, event: InputEvent) -> Result<SelectionState> {
+        if let InputEvent::KeyDown(key, modifiers) = event {
+            if self.handle_shortcut(key, modifiers) {
+                return Ok(self.locked_selection()?.state());
+            }
+        }
+        if self.handle_annotation_input(event)? {
+            return Ok(self.locked_selection()?.state());
+        }
+
+        let (redraw, previous, state) = {
+            let mut session = self.locked_selection()?;
+            let previous = session.state();
+            let was_in_gesture = session.in_gesture();
+            let before = session.rect();
+
+            let redraw = session.handle(event);
+
+            // Ein Ziehen mit der Maus wird zu einem einzigen Schritt im Verlauf
+            let mut history = self.locked_history()?;
+            if session.in_gesture() && !was_in_gesture {
+                history.begin_gesture();
+                // Markierungen gehören zur alten Auswahl
+                if let Ok(mut highlights) = self.highlights.lock() {
+                    highlights.clear();
+                }
+            }
+            history.record(SelectionEdit {
+                before,
+                after: session.rect(),
+            });
+            if !session.in_gesture() {
+                history.end_gesture();
+            }
+
+            // Beim Erkennen von Text gibt es kein Nachjustieren, das Loslassen schließt die Auswahl ab
+            let finished_gesture = was_in_gesture && !session.in_gesture();
+            if matches!(self.mode, CaptureMode::Text | CaptureMode::Table(_)) && was_in_gesture {
+                session.commit();
+            }
+
+            if self.mode == CaptureMode::Codes
+                && finished_gesture
+                && session.state() == SelectionState::Selected
+            {
+                self.commands.post(CommandSource::Input, AppCommand::Decode);
+            }
+
+            (redraw, previous, session.state())
+        };
+
+        if state != previous {
+            match state {
+                SelectionState::Committed => {
+                    let command = match self.mode {
+                        CaptureMode::Image => AppCommand::Capture,
+                        CaptureMode::Text => AppCommand::Ocr,
+                        CaptureMode::Table(format) => AppCommand::Table { format },
+                        // Die Codes wurden schon beim Loslassen erkannt und kopiert
+                        CaptureMode::Codes => AppCommand::Cancel,
+                        CaptureMode::Record => AppCommand::Record,
+                    };
+                    self.commands.post(CommandSource::Input, command)
+                }
+                SelectionState::Cancelled => {
+                    self.commands.post(CommandSource::Input, AppCommand::Cancel)
+                }
+                _ => {}
+            }
+        }
+
+        if redraw {
+            self.dispatch(WindowType::Transparent, Command::RedrawWindow)?;
+        }
+
+        Ok(state)
+    }
+
+    fn locked_annotation(&self) -> Result<MutexGuard<'_, Option<Annotating>>> {
+        self.annotation
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock annotation mutex"))
+    }
+
+    // Hält die Auswahl fest und leitet die Maus ab jetzt an den Editor weiter;
+    // gibt false zurück, wenn schon annotiert wird
+    pub fn annotate(&self) -> Result<bool> {
+        if self.is_annotating() {
+            return Ok(false);
+        }
+        let bounds = self
+            .selection_bounds()
+            .ok_or_else(|| anyhow!("Nothing selected"))?;
+        let image = self.selection_image()?;
+
+        *self.locked_annotation()? = Some(Annotating {
+            editor: AnnotationEditor::new(image),
+            origin: (bounds.x.max(0) as f32, bounds.y.max(0) as f32),
+            tool: Tool::default(),
+            drag: None,
+        });
+        self.dispatch(WindowType::Transparent, Command::RedrawWindow)?;
+        Ok(true)
+    }
+
+    pub fn is_annotating(&self) -> bool {
+        self.annotation
+            .lock()
+            .map(|annotation| annotation.is_some())
+            .unwrap_or(false)
+    }
+
+    pub fn set_tool(&self, tool: Tool) -> Result<()> {
+        if let Some(annotating) = self.locked_annotation()?.as_mut() {
+            annotating.tool = tool;
+        }
+        Ok(())
+    }
+
+    // Formen in Overlay-Koordinaten, zum Zeichnen über dem eingefrorenen Bild
+    pub fn annotation_shapes(&self) -> Vec<Shape> {
+        let annotation = match self.annotation.lock() {
+            Ok(annotation) => annotation,
+            Err(_) => return Vec::new(),
+        };
+        annotation
+            .as_ref()
+            .map(|annotating| {
+                let (dx, dy) = annotating.origin;
+                annotating
+                    .editor
+                    .document()
+                    .shapes()
+                    .iter()
+                    .cloned()
+                    .map(|mut shape| {
+                        shape.kind.translate(dx, dy);
+                        shape
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // Maus und Werkzeugtasten gehen beim Annotieren an den Editor; Enter und Escape
+    // schließen weiterhin die Auswahl ab. Gibt false zurück, wenn das Ereignis nicht
+    // verbraucht wurde.
+    fn handle_annotation_input(&self, event: InputEvent) -> Result<bool> {
+        let redraw = {
+            let mut annotation = self.locked_annotation()?;
+            let annotating = match annotation.as_mut() {
+                Some(annotating) => annotating,
+                None => return Ok(false),
+            };
+            let (dx, dy) = annotating.origin;
+            let tool = annotating.tool;
+
+            match event {
+                InputEvent::MouseDown { x, y } => {
+                    annotating.editor.begin_gesture();
+                    annotating.drag = Some(((x - dx, y - dy), None));
+                    false
+                }
+                InputEvent::MouseMove {
+                    x,
+                    y,
+                    pressed: true,
+                }
+                | InputEvent::MouseUp { x, y } => {
+                    let point = (x - dx, y - dy);
+                    if let Some((start, id)) = annotating.drag {
+                        // Ein bloßer Klick legt keine leere Form an
+                        let id = match id {
+                            Some(id) => {
+                                annotating
+                                    .editor
+                                    .update(id, |kind, _| tool.extend(kind, start, point));
+                                Some(id)
+                            }
+                            None if point != start => Some(
+                                annotating
+                                    .editor
+                                    .add(tool.shape(start, point), tool.style()),
+                            ),
+                            None => None,
+                        };
+                        annotating.drag = Some((start, id));
+                    }
+                    if let InputEvent::MouseUp { .. } = event {
+                        annotating.drag = None;
+                        annotating.editor.end_gesture();
+                    }
+                    true
+                }
+                InputEvent::MouseMove { .. } => false,
+                InputEvent::KeyDown(Key::Enter | Key::Escape, _) => return Ok(false),
+                InputEvent::KeyDown(Key::Char(key), modifiers) => {
+                    if modifiers == Modifiers::default() {
+                        if let Some(tool) = Tool::from_key(key) {
+                            annotating.tool = tool;
+                        }
+                    }
+                    false
+                }
+                // Pfeiltasten verschieben die Auswahl nicht mehr
+                InputEvent::KeyDown(..) => false,
+            }
+        };
+
+        if redraw {
+            self.dispatch(WindowType::Transparent, Command::RedrawWindow)?;
+        }
+        Ok(true)
+    }
+
+    // Reiht den Befehl zur Tastenkombination ein; gibt false zurück, wenn keiner gebunden ist
+    // oder der Befehl eine Auswahl braucht, aber noch nichts ausgewählt wurde
+    pub fn handle_shortcut(&self, key: Key, modifiers: Modifiers) -> bool {
+        match self.shortcuts.lookup(key, modifiers) {
+            Some(command) if command.needs_selection() && self.selection_bounds().is_none() => {
+                false
+            }
+            Some(command) => {
+                self.commands.post(CommandSource::Shortcut, command.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Das Overlay wird vorher ausgeblendet, damit es nicht im Screenshot landet
+    pub fn capture_selection(&self) -> Result<RgbaImage> {
+        let region = self
+            .selection_bounds()
+            .ok_or_else(|| anyhow!("Nothing selected"))?;
+
+        let mut image = {
+            let mut locked_capture = self.locked_capture()?;
+            let capture = locked_capture
+                .as_mut()
+                .ok_or_else(|| anyhow!("No capture source configured"))?;
+
+            self.dispatch(WindowType::Transparent, Command::Hide)?;
+            match capture.capture(region) {
+                Ok(image) => image,
+                Err(e) => {
+                    // Fehlgeschlagen: das Overlay bleibt, damit man es erneut versuchen kann
+                    self.dispatch(WindowType::Transparent, Command::Show)?;
+                    return Err(e);
+                }
+            }
+        };
+        // Die Formen werden in die Aufnahme eingebrannt
+        if let Some(annotating) = self.locked_annotation()?.as_ref() {
+            annotation::render_shapes(&mut image, annotating.editor.document().shapes());
+        }
+
+        if let Ok(mut last_capture) = self.last_capture.lock() {
+            *last_capture = Some(image.clone());
+        }
+
+        Ok(image)
+    }
+
+    // Blendet das Overlay aus und nimmt den Bereich im Hintergrund live auf, bis stop_recording
+    // aufgerufen oder die Höchstdauer erreicht wird. `finished` läuft danach auf dem
+    // Aufnahme-Thread, z.B. um die Nachrichtenschleife zu wecken. False, wenn schon aufgenommen wird.
+    pub fn start_recording<F>(&self, options: &RecordingOptions, finished: F) -> Result<bool>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut recording = self.locked_recording()?;
+        if recording.is_some() {
+            return Ok(false);
+        }
+
+        let region = self
+            .selection_bounds()
+            .ok_or_else(|| anyhow!("Nothing selected"))?;
+        options.validate()?;
+        let factory = self
+            .recording_source
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock recording source mutex"))?
+            .clone()
+            .ok_or_else(|| anyhow!("No recording source configured"))?;
+
+        self.dispatch(WindowType::Transparent, Command::Hide)?;
+        // Der Compositor braucht einen Moment, bis das Overlay wirklich verschwunden ist
+        thread::sleep(OVERLAY_HIDE_DELAY);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let (sender, result) = mpsc::channel();
+        let options = *options;
+        thread::spawn(move || {
+            let recorded = factory().and_then(|source| {
+                let mut capture = Capture::new(source);
+                Ok(recording::record(
+                    || capture.sample(region),
+                    &options,
+                    || stopped.load(Ordering::SeqCst),
+                )?)
+            });
+            // Erst das Ergebnis, dann die Benachrichtigung, sonst findet finish_recording nichts
+            let _ = sender.send(recorded);
+            finished();
+        });
+
+        *recording = Some(ActiveRecording { stop, result });
+        Ok(true)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+            .lock()
+            .map(|recording| recording.is_some())
+            .unwrap_or(false)
+    }
+
+    // Beendet die Aufnahme nach dem aktuellen Frame; das Ergebnis liefert finish_recording
+    pub fn stop_recording(&self) -> Result<bool> {
+        Ok(match self.locked_recording()?.as_ref() {
+            Some(active) => {
+                active.stop.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        })
+    }
+
+    // Die fertige Aufnahme, sobald der Thread durch ist; blockiert nie
+    pub fn finish_recording(&self) -> Result<Option<Recording>> {
+        let mut recording = self.locked_recording()?;
+        let received = match recording.as_ref() {
+            Some(active) => active.result.try_recv(),
+            None => return Ok(None),
+        };
+
+        match received {
+            Ok(result) => {
+                *recording = None;
+                result.map(Some)
+            }
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => {
+                *recording = None;
+                Err(anyhow!("The recording thread stopped unexpectedly"))
+            }
+        }
+    }
+
+    // Ausschnitt aus dem eingefrorenen Bild, ohne das Overlay auszublenden
+    pub fn selection_image(&self) -> Result<RgbaImage> {
+        let region = self
+            .selection_bounds()
+            .ok_or_else(|| anyhow!("Nothing selected"))?;
+
+        let locked_capture = self.locked_capture()?;
+        let frame = locked_capture
+            .as_ref()
+            .and_then(|capture| capture.frozen())
+            .ok_or_else(|| anyhow!("The desktop is not frozen"))?;
+        capture::crop(frame, region)
+    }
+
     pub fn dispatch(&self, window_type: WindowType, command: Command) -> Result<(), anyhow::Error> {
-        if let Some(window) = &*self.locked_window(window_type)? {
+        // Das Fenster wird vor dem Aufruf aus dem Mutex geholt, da z.B. ShowWindow
+        // synchron Nachrichten sendet, die wiederum dispatch aufrufen
+        let window = self.locked_window(window_type)?.clone();
+
+        if let Some(window) = window {
             match command {
                 Command::Show => window.show(),
-                Command::DrawOverlay(rect) => window.draw_overlay(rect)?,
-                Command::FillBackground(color) => window.fill_background(color)?,
-                Command::RedrawWindow => window.redraw_window(),
+                Command::Hide => window.hide(),
+                Command::DrawOverlay(overlay) => window.paint(&Frame::Overlay(overlay))?,
+                Command::FillBackground(color) => window.paint(&Frame::Background(color))?,
+                Command::DrawAnnotations(shapes) => window.paint(&Frame::Annotations(shapes))?,
+                Command::RedrawWindow => window.redraw(),
             }
         }
         Ok(())
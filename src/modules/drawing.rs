@@ -1,8 +1,13 @@
+use crate::modules::annotation::{self, Shape, ShapeKind, HIGHLIGHT_COLOR};
+use crate::modules::backend::{Color, Overlay};
+use crate::modules::errorhandler::SnipError;
+use crate::modules::font;
 use crate::modules::renderer::Render;
 use crate::modules::resource_manager::ResourceManager;
+use image::RgbaImage;
+use std::cell::RefCell;
 use std::mem::ManuallyDrop;
 
-
 use windows::core::Result;
 use windows::Foundation::Numerics::Matrix3x2;
 use windows::Win32::Graphics::Direct2D::Common::D2D_RECT_F;
@@ -12,7 +17,7 @@ use windows::{
     core::*,
     Win32::{
         Foundation::*,
-        Graphics::{Direct2D::Common::*, Direct2D::*},
+        Graphics::{Direct2D::Common::*, Direct2D::*, Dxgi::Common::*},
     },
 };
 
@@ -20,12 +25,53 @@ use windows::{
 
 pub struct Drawing {
     render: Render,
+    backdrop: RefCell<Option<ID2D1Bitmap1>>,
 }
 
 impl Drawing {
-    pub fn new(hwnd: HWND, resource_manager: &ResourceManager) -> Self {
-        let render = Render::new(hwnd, resource_manager).unwrap();
-        Drawing { render }
+    pub fn new(
+        hwnd: HWND,
+        resource_manager: &ResourceManager,
+    ) -> std::result::Result<Self, SnipError> {
+        let render = Render::new(hwnd, resource_manager)?;
+        Ok(Drawing {
+            render,
+            backdrop: RefCell::new(None),
+        })
+    }
+
+    // Eingefrorenes Bild des Desktops, das unter die abgedunkelte Ebene gezeichnet wird
+    pub fn set_backdrop(&self, image: &RgbaImage) -> Result<()> {
+        let bgra: Vec<u8> = image
+            .pixels()
+            .flat_map(|p| [p[2], p[1], p[0], p[3]])
+            .collect();
+
+        let bitmap_properties = D2D1_BITMAP_PROPERTIES1 {
+            pixelFormat: D2D1_PIXEL_FORMAT {
+                format: DXGI_FORMAT_B8G8R8A8_UNORM,
+                alphaMode: D2D1_ALPHA_MODE_PREMULTIPLIED,
+            },
+            dpiX: self.render.dpi_x,
+            dpiY: self.render.dpi_y,
+            bitmapOptions: D2D1_BITMAP_OPTIONS_NONE,
+            colorContext: ManuallyDrop::new(None),
+        };
+
+        let bitmap = unsafe {
+            self.render.d2d_context.CreateBitmap(
+                D2D_SIZE_U {
+                    width: image.width(),
+                    height: image.height(),
+                },
+                Some(bgra.as_ptr() as *const _),
+                image.width() * 4,
+                &bitmap_properties,
+            )?
+        };
+
+        *self.backdrop.borrow_mut() = Some(bitmap);
+        Ok(())
     }
 
     fn provide_env<F>(&self, hwnd: HWND, render_fn: F) -> Result<()>
@@ -45,10 +91,13 @@ impl Drawing {
         result
     }
 
-    pub fn draw_overlay(&self, hwnd: HWND, rect: Option<D2D_RECT_F>) -> Result<()> {
+    pub fn draw_overlay(&self, hwnd: HWND, overlay: &Overlay) -> Result<()> {
         self.provide_env(hwnd, |_hdc| {
             self.render.with_render_context(|d2d_context| {
-                if let Some(rect) = rect {
+                let backdrop = self.backdrop.borrow();
+                unsafe { self.draw_backdrop(d2d_context) };
+
+                if let Some(rect) = overlay.selection.map(D2D_RECT_F::from) {
                     unsafe {
                         // Hier fügt man die Zeichenlogik ein, die das Overlay zeichnet
                         let brush_color = D2D1_COLOR_F {
@@ -57,9 +106,7 @@ impl Drawing {
                             b: 0.0,
                             a: 1.0,
                         };
-                        let brush = d2d_context
-                            .CreateSolidColorBrush(&brush_color, None)
-                            .unwrap();
+                        let brush = d2d_context.CreateSolidColorBrush(&brush_color, None)?;
 
                         let size = d2d_context.GetSize();
 
@@ -88,9 +135,8 @@ impl Drawing {
                         )?;
                         sink.Close()?;
 
-                        let geom_mask = ManuallyDrop::new(Some(
-                            combined_geometry.cast::<ID2D1Geometry>().unwrap(),
-                        ));
+                        let geom_mask =
+                            ManuallyDrop::new(Some(combined_geometry.cast::<ID2D1Geometry>()?));
                         let layer_parameters = D2D1_LAYER_PARAMETERS1 {
                             contentBounds: D2D_RECT_F {
                                 left: 0.0,
@@ -102,9 +148,7 @@ impl Drawing {
                             maskAntialiasMode: D2D1_ANTIALIAS_MODE_PER_PRIMITIVE,
                             maskTransform: Matrix3x2::identity(),
                             opacity: 0.6,
-                            opacityBrush: ManuallyDrop::new(Some(
-                                brush.cast::<ID2D1Brush>().unwrap(),
-                            )),
+                            opacityBrush: ManuallyDrop::new(Some(brush.cast::<ID2D1Brush>()?)),
                             layerOptions: D2D1_LAYER_OPTIONS1_NONE,
                         };
 
@@ -121,6 +165,32 @@ impl Drawing {
                             &brush,
                         );
                         d2d_context.PopLayer();
+
+                        self.draw_handles(d2d_context, overlay, &brush)?;
+                        self.draw_highlights(d2d_context, overlay)?;
+                    }
+                } else if backdrop.is_some() {
+                    // Ohne Auswahl wird das eingefrorene Bild komplett abgedunkelt
+                    unsafe {
+                        let brush = d2d_context.CreateSolidColorBrush(
+                            &D2D1_COLOR_F {
+                                r: 0.0,
+                                g: 0.0,
+                                b: 0.0,
+                                a: 0.6,
+                            },
+                            None,
+                        )?;
+                        let size = d2d_context.GetSize();
+                        d2d_context.FillRectangle(
+                            &D2D_RECT_F {
+                                left: 0.0,
+                                top: 0.0,
+                                right: size.width,
+                                bottom: size.height,
+                            },
+                            &brush,
+                        );
                     }
                 }
 
@@ -128,11 +198,229 @@ impl Drawing {
             })
         })
     }
+    unsafe fn draw_backdrop(&self, d2d_context: &ID2D1DeviceContext) {
+        if let Some(bitmap) = self.backdrop.borrow().as_ref() {
+            let size = d2d_context.GetSize();
+            d2d_context.DrawBitmap(
+                bitmap,
+                Some(&D2D_RECT_F {
+                    left: 0.0,
+                    top: 0.0,
+                    right: size.width,
+                    bottom: size.height,
+                }),
+                1.0,
+                D2D1_INTERPOLATION_MODE_LINEAR,
+                None,
+                None,
+            );
+        }
+    }
+
+    // Zeichnet die Aufnahme mit allen Formen; muss zum CPU-Pfad in annotation::render_shape passen
+    pub fn draw_annotations(&self, hwnd: HWND, shapes: &[Shape]) -> Result<()> {
+        self.provide_env(hwnd, |_hdc| {
+            self.render.with_render_context(|d2d_context| unsafe {
+                self.draw_backdrop(d2d_context);
+
+                let round = self.render.d2d_factory.CreateStrokeStyle(
+                    &D2D1_STROKE_STYLE_PROPERTIES1 {
+                        startCap: D2D1_CAP_STYLE_ROUND,
+                        endCap: D2D1_CAP_STYLE_ROUND,
+                        dashCap: D2D1_CAP_STYLE_ROUND,
+                        lineJoin: D2D1_LINE_JOIN_ROUND,
+                        miterLimit: 10.0,
+                        dashStyle: D2D1_DASH_STYLE_SOLID,
+                        dashOffset: 0.0,
+                        transformType: D2D1_STROKE_TRANSFORM_TYPE_NORMAL,
+                    },
+                    None,
+                )?;
+
+                for shape in shapes {
+                    self.draw_shape(d2d_context, shape, &round)?;
+                }
+                Ok(())
+            })
+        })
+    }
+
+    unsafe fn draw_shape(
+        &self,
+        d2d_context: &ID2D1DeviceContext,
+        shape: &Shape,
+        round: &ID2D1StrokeStyle1,
+    ) -> Result<()> {
+        let style = &shape.style;
+        let brush =
+            |color: Color| d2d_context.CreateSolidColorBrush(&D2D1_COLOR_F::from(color), None);
+        let point = |(x, y): (f32, f32)| D2D_POINT_2F { x, y };
+
+        match &shape.kind {
+            ShapeKind::Rectangle(rect) => {
+                let rect = D2D_RECT_F::from(rect.normalized());
+                if let Some(fill) = style.fill {
+                    d2d_context.FillRectangle(&rect, &brush(fill)?);
+                }
+                if let Some(stroke) = style.stroke {
+                    d2d_context.DrawRectangle(
+                        &rect,
+                        &brush(stroke.color)?,
+                        stroke.width,
+                        None::<&ID2D1StrokeStyle>,
+                    );
+                }
+            }
+            ShapeKind::Ellipse(rect) => {
+                let rect = rect.normalized();
+                let ellipse = D2D1_ELLIPSE {
+                    point: point((
+                        (rect.left + rect.right) / 2.0,
+                        (rect.top + rect.bottom) / 2.0,
+                    )),
+                    radiusX: (rect.right - rect.left) / 2.0,
+                    radiusY: (rect.bottom - rect.top) / 2.0,
+                };
+                if let Some(fill) = style.fill {
+                    d2d_context.FillEllipse(&ellipse, &brush(fill)?);
+                }
+                if let Some(stroke) = style.stroke {
+                    d2d_context.DrawEllipse(
+                        &ellipse,
+                        &brush(stroke.color)?,
+                        stroke.width,
+                        None::<&ID2D1StrokeStyle>,
+                    );
+                }
+            }
+            ShapeKind::Line { from, to } => {
+                if let Some(stroke) = style.stroke {
+                    d2d_context.DrawLine(
+                        point(*from),
+                        point(*to),
+                        &brush(stroke.color)?,
+                        stroke.width,
+                        round,
+                    );
+                }
+            }
+            ShapeKind::Arrow { from, to } => {
+                if let Some(stroke) = style.stroke {
+                    let color = brush(stroke.color)?;
+                    let head = annotation::arrow_head(*from, *to, stroke.width);
+                    let base = ((head[1].0 + head[2].0) / 2.0, (head[1].1 + head[2].1) / 2.0);
+                    d2d_context.DrawLine(point(*from), point(base), &color, stroke.width, round);
+
+                    let geometry = self.path_geometry(&head, true)?;
+                    d2d_context.FillGeometry(&geometry, &color, None::<&ID2D1Brush>);
+                }
+            }
+            ShapeKind::Freehand(path) => {
+                if let (Some(stroke), false) = (style.stroke, path.is_empty()) {
+                    let geometry = self.path_geometry(path, false)?;
+                    d2d_context.DrawGeometry(&geometry, &brush(stroke.color)?, stroke.width, round);
+                }
+            }
+            ShapeKind::Text { origin, text, size } => {
+                let color = brush(annotation::text_color(style))?;
+                for rect in font::text_rects(text, *origin, *size) {
+                    d2d_context.FillRectangle(&D2D_RECT_F::from(rect), &color);
+                }
+            }
+            ShapeKind::Highlight(rect) => {
+                // Wie ein Textmarker: nur abdunkeln, nie aufhellen
+                let color = brush(style.fill.unwrap_or(HIGHLIGHT_COLOR))?;
+                d2d_context.SetPrimitiveBlend(D2D1_PRIMITIVE_BLEND_MIN);
+                d2d_context.FillRectangle(&D2D_RECT_F::from(rect.normalized()), &color);
+                d2d_context.SetPrimitiveBlend(D2D1_PRIMITIVE_BLEND_SOURCE_OVER);
+            }
+        }
+
+        Ok(())
+    }
+
+    unsafe fn path_geometry(
+        &self,
+        points: &[(f32, f32)],
+        closed: bool,
+    ) -> Result<ID2D1PathGeometry> {
+        let points: Vec<D2D_POINT_2F> =
+            points.iter().map(|&(x, y)| D2D_POINT_2F { x, y }).collect();
+
+        let geometry = self.render.d2d_factory.CreatePathGeometry()?;
+        let sink = geometry.Open()?;
+        let (begin, end) = if closed {
+            (D2D1_FIGURE_BEGIN_FILLED, D2D1_FIGURE_END_CLOSED)
+        } else {
+            (D2D1_FIGURE_BEGIN_HOLLOW, D2D1_FIGURE_END_OPEN)
+        };
+        sink.BeginFigure(points[0], begin);
+        sink.AddLines(&points[1..]);
+        sink.EndFigure(end);
+        sink.Close()?;
+        Ok(geometry)
+    }
+
+    unsafe fn draw_handles(
+        &self,
+        d2d_context: &ID2D1DeviceContext,
+        overlay: &Overlay,
+        outline: &ID2D1SolidColorBrush,
+    ) -> Result<()> {
+        if overlay.handles.is_empty() {
+            return Ok(());
+        }
+
+        let fill = d2d_context.CreateSolidColorBrush(
+            &D2D1_COLOR_F {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+                a: 1.0,
+            },
+            None,
+        )?;
+
+        for handle in overlay.handles.iter().copied().map(D2D_RECT_F::from) {
+            d2d_context.FillRectangle(&handle, &fill);
+            d2d_context.DrawRectangle(&handle, outline, 1.0, None::<&ID2D1StrokeStyle>);
+        }
+
+        Ok(())
+    }
+
+    // Rahmen um erkannte Bereiche, in derselben Farbe wie OverlayStyle::highlight
+    unsafe fn draw_highlights(
+        &self,
+        d2d_context: &ID2D1DeviceContext,
+        overlay: &Overlay,
+    ) -> Result<()> {
+        if overlay.highlights.is_empty() {
+            return Ok(());
+        }
+
+        let brush = d2d_context.CreateSolidColorBrush(
+            &D2D1_COLOR_F {
+                r: 0.0,
+                g: 0.8,
+                b: 0.3,
+                a: 1.0,
+            },
+            None,
+        )?;
+
+        for highlight in overlay.highlights.iter().copied().map(D2D_RECT_F::from) {
+            d2d_context.DrawRectangle(&highlight, &brush, 2.0, None::<&ID2D1StrokeStyle>);
+        }
+
+        Ok(())
+    }
+
     pub fn fill_background(&self, hwnd: HWND, color: D2D1_COLOR_F) -> Result<()> {
         self.provide_env(hwnd, |_hdc| {
             self.render.with_render_context(|d2d_context| {
                 unsafe {
-                    let brush = d2d_context.CreateSolidColorBrush(&color, None).unwrap();
+                    let brush = d2d_context.CreateSolidColorBrush(&color, None)?;
 
                     let size = d2d_context.GetSize();
 
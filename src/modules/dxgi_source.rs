@@ -0,0 +1,253 @@
+use crate::modules::backend::PixelRect;
+use crate::modules::capture::FrameSource;
+use crate::modules::win_fact::{monitors, virtual_screen};
+use anyhow::{anyhow, Result};
+use image::RgbaImage;
+use std::time::{Duration, Instant};
+use windows::{
+    core::*,
+    Win32::{
+        Foundation::*,
+        Graphics::{Direct3D::*, Direct3D11::*, Dxgi::Common::*, Dxgi::*},
+    },
+};
+
+// Wartezeit auf den ersten Frame eines Monitors. Danach kommt ein neuer Frame nur, wenn sich
+// der Bildschirm geändert hat; ein Timeout liefert dann den zuletzt gelesenen Frame.
+const FIRST_FRAME_TIMEOUT: Duration = Duration::from_secs(1);
+const FRAME_TIMEOUT_MS: u32 = 16;
+
+struct DuplicatedOutput {
+    // Gerät der Grafikkarte, an der der Monitor hängt
+    device: ID3D11Device,
+    context: ID3D11DeviceContext,
+    duplication: IDXGIOutputDuplication,
+    desktop: RECT,
+    rotation: DXGI_MODE_ROTATION,
+}
+
+// Desktop Duplication: jeder Monitor wird einzeln gelesen und in den virtuellen Bildschirm kopiert
+pub struct DxgiFrameSource {
+    outputs: Vec<DuplicatedOutput>,
+    // Letzter Frame je Monitor, bereits in Desktop-Ausrichtung
+    frames: Vec<Option<RgbaImage>>,
+    screen: PixelRect,
+}
+
+// Die Textur liegt in der Ausrichtung des Panels vor, gedrehte Monitore werden zurückgedreht
+fn to_desktop_orientation(image: RgbaImage, rotation: DXGI_MODE_ROTATION) -> RgbaImage {
+    match rotation {
+        DXGI_MODE_ROTATION_ROTATE90 => image::imageops::rotate270(&image),
+        DXGI_MODE_ROTATION_ROTATE180 => image::imageops::rotate180(&image),
+        DXGI_MODE_ROTATION_ROTATE270 => image::imageops::rotate90(&image),
+        _ => image,
+    }
+}
+
+// Ein Monitor lässt sich nur über das Gerät der Grafikkarte duplizieren, an der er hängt
+unsafe fn create_device(adapter: &IDXGIAdapter1) -> Result<(ID3D11Device, ID3D11DeviceContext)> {
+    let mut device: Option<ID3D11Device> = None;
+    let mut context: Option<ID3D11DeviceContext> = None;
+
+    let feature_levels = [D3D_FEATURE_LEVEL_11_0];
+    D3D11CreateDevice(
+        adapter,
+        D3D_DRIVER_TYPE_UNKNOWN,
+        HMODULE::default(),
+        D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+        Some(&feature_levels),
+        D3D11_SDK_VERSION,
+        Some(&mut device),
+        None,
+        Some(&mut context),
+    )?;
+
+    let device = device.ok_or_else(|| anyhow!("Failed to create D3D11 device"))?;
+    let context = context.ok_or_else(|| anyhow!("Failed to create D3D11 context"))?;
+    Ok((device, context))
+}
+
+impl DxgiFrameSource {
+    pub fn new() -> Result<Self> {
+        unsafe {
+            let factory: IDXGIFactory1 = CreateDXGIFactory1()?;
+
+            let mut outputs = Vec::new();
+            let mut adapter_index = 0;
+            while let Ok(adapter) = factory.EnumAdapters1(adapter_index) {
+                adapter_index += 1;
+
+                let mut attached = Vec::new();
+                let mut index = 0;
+                while let Ok(output) = adapter.EnumOutputs(index) {
+                    index += 1;
+
+                    let mut desc = DXGI_OUTPUT_DESC::default();
+                    output.GetDesc(&mut desc)?;
+                    if desc.AttachedToDesktop.as_bool() {
+                        attached.push((output, desc));
+                    }
+                }
+                // Grafikkarten ohne Monitor bekommen kein Gerät
+                if attached.is_empty() {
+                    continue;
+                }
+
+                let (device, context) = create_device(&adapter)?;
+                for (output, desc) in attached {
+                    let duplication = output.cast::<IDXGIOutput1>()?.DuplicateOutput(&device)?;
+                    outputs.push(DuplicatedOutput {
+                        device: device.clone(),
+                        context: context.clone(),
+                        duplication,
+                        desktop: desc.DesktopCoordinates,
+                        rotation: desc.Rotation,
+                    });
+                }
+            }
+
+            if outputs.is_empty() {
+                return Err(anyhow!("No desktop output available for duplication"));
+            }
+            // Ein fehlender Monitor bliebe sonst stillschweigend schwarz
+            for monitor in monitors() {
+                let covered = outputs.iter().any(|output| {
+                    output.desktop.left == monitor.x
+                        && output.desktop.top == monitor.y
+                        && output.desktop.right == monitor.right()
+                        && output.desktop.bottom == monitor.bottom()
+                });
+                if !covered {
+                    return Err(anyhow!(
+                        "The monitor at {},{} ({}x{}) cannot be duplicated",
+                        monitor.x,
+                        monitor.y,
+                        monitor.width,
+                        monitor.height
+                    ));
+                }
+            }
+
+            Ok(DxgiFrameSource {
+                frames: vec![None; outputs.len()],
+                outputs,
+                screen: virtual_screen(),
+            })
+        }
+    }
+}
+
+impl DuplicatedOutput {
+    // Gibt None zurück, wenn sich das Bild seit dem letzten Frame nicht geändert hat.
+    // Ohne bisherigen Frame wird bis FIRST_FRAME_TIMEOUT gewartet.
+    unsafe fn acquire(&self, has_frame: bool) -> Result<Option<RgbaImage>> {
+        let deadline = Instant::now() + FIRST_FRAME_TIMEOUT;
+
+        loop {
+            let mut frame_info = DXGI_OUTDUPL_FRAME_INFO::default();
+            let mut resource: Option<IDXGIResource> = None;
+
+            match self.duplication.AcquireNextFrame(
+                FRAME_TIMEOUT_MS,
+                &mut frame_info,
+                &mut resource,
+            ) {
+                Ok(()) => {
+                    // Ohne LastPresentTime hat sich nur der Mauszeiger bewegt
+                    let image = match resource {
+                        Some(resource) if frame_info.LastPresentTime != 0 => {
+                            let texture = resource.cast::<ID3D11Texture2D>();
+                            texture
+                                .map_err(anyhow::Error::from)
+                                .and_then(|texture| self.read_texture(&texture))
+                                .map(Some)
+                        }
+                        _ => Ok(None),
+                    };
+                    self.duplication.ReleaseFrame()?;
+
+                    if let Some(image) = image? {
+                        return Ok(Some(to_desktop_orientation(image, self.rotation)));
+                    }
+                }
+                Err(e) if e.code() == DXGI_ERROR_WAIT_TIMEOUT => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            if has_frame {
+                return Ok(None);
+            }
+            if Instant::now() >= deadline {
+                return Err(anyhow!("Timed out waiting for the first desktop frame"));
+            }
+        }
+    }
+
+    // Kopiert die BGRA-Textur in ein Bild in der Ausrichtung des Panels
+    unsafe fn read_texture(&self, texture: &ID3D11Texture2D) -> Result<RgbaImage> {
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        texture.GetDesc(&mut desc);
+
+        let staging_desc = D3D11_TEXTURE2D_DESC {
+            Usage: D3D11_USAGE_STAGING,
+            BindFlags: 0,
+            CPUAccessFlags: D3D11_CPU_ACCESS_READ.0 as u32,
+            MiscFlags: 0,
+            MipLevels: 1,
+            ArraySize: 1,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            ..desc
+        };
+
+        let mut staging: Option<ID3D11Texture2D> = None;
+        self.device
+            .CreateTexture2D(&staging_desc, None, Some(&mut staging))?;
+        let staging = staging.ok_or_else(|| anyhow!("Failed to create staging texture"))?;
+
+        self.context.CopyResource(&staging, texture);
+
+        let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+        self.context
+            .Map(&staging, 0, D3D11_MAP_READ, 0, Some(&mut mapped))?;
+
+        let mut image = RgbaImage::new(desc.Width, desc.Height);
+        let data = mapped.pData as *const u8;
+        for (row, pixels) in image.rows_mut().enumerate() {
+            let line = std::slice::from_raw_parts(
+                data.add(row * mapped.RowPitch as usize),
+                (desc.Width * 4) as usize,
+            );
+            for (pixel, bgra) in pixels.zip(line.chunks_exact(4)) {
+                *pixel = image::Rgba([bgra[2], bgra[1], bgra[0], 255]);
+            }
+        }
+
+        self.context.Unmap(&staging, 0);
+        Ok(image)
+    }
+}
+
+impl FrameSource for DxgiFrameSource {
+    fn grab(&mut self) -> Result<RgbaImage> {
+        let mut image = RgbaImage::new(self.screen.width, self.screen.height);
+        for index in 0..self.outputs.len() {
+            let output = &self.outputs[index];
+            let has_frame = self.frames[index].is_some();
+            if let Some(frame) = unsafe { output.acquire(has_frame)? } {
+                self.frames[index] = Some(frame);
+            }
+
+            // Position relativ zum virtuellen Bildschirm
+            if let Some(frame) = &self.frames[index] {
+                let output = &self.outputs[index];
+                let x = (output.desktop.left - self.screen.x).max(0) as u32;
+                let y = (output.desktop.top - self.screen.y).max(0) as u32;
+                image::imageops::replace(&mut image, frame, x, y);
+            }
+        }
+        Ok(image)
+    }
+}
@@ -0,0 +1,331 @@
+use image::ImageError;
+use std::error::Error;
+use std::fmt;
+use std::sync::RwLock;
+
+pub type BoxedError = Box<dyn Error + Send + Sync>;
+
+#[derive(Debug)]
+pub enum SnipError {
+    BackendInit {
+        message: String,
+        source: Option<BoxedError>,
+    },
+    Capture {
+        message: String,
+        source: Option<BoxedError>,
+    },
+    Encode {
+        format: &'static str,
+        message: String,
+    },
+    UnsupportedFormat(String),
+    Ocr {
+        message: String,
+        source: Option<BoxedError>,
+    },
+    Config(String),
+    Io(std::io::Error),
+    Image(ImageError),
+    #[cfg(windows)]
+    Windows(windows::core::Error),
+    // Beschreibt, wobei ein Fehler aufgetreten ist, z.B. "Failed to save shot.png"
+    Context {
+        context: String,
+        source: Box<SnipError>,
+    },
+}
+
+impl SnipError {
+    pub fn backend_init<S: Into<String>>(message: S) -> Self {
+        SnipError::BackendInit {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub fn capture<S: Into<String>>(message: S) -> Self {
+        SnipError::Capture {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub fn ocr<S: Into<String>>(message: S) -> Self {
+        SnipError::Ocr {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    // Hängt die eigentliche Ursache an; nur für Varianten mit freiem Fehlerfeld
+    pub fn caused_by<E: Into<BoxedError>>(mut self, cause: E) -> Self {
+        if let SnipError::BackendInit { source, .. }
+        | SnipError::Capture { source, .. }
+        | SnipError::Ocr { source, .. } = &mut self
+        {
+            *source = Some(cause.into());
+        }
+        self
+    }
+
+    pub fn context<S: Into<String>>(self, context: S) -> Self {
+        SnipError::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    // Der innerste Fehler ohne Kontext, z.B. um die Art des Fehlers zu prüfen
+    pub fn root(&self) -> &SnipError {
+        match self {
+            SnipError::Context { source, .. } => source.root(),
+            error => error,
+        }
+    }
+}
+
+impl fmt::Display for SnipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnipError::BackendInit { message, .. } => {
+                write!(f, "Failed to initialize the graphics backend: {}", message)
+            }
+            SnipError::Capture { message, .. } => write!(f, "Capture failed: {}", message),
+            SnipError::Encode { format, message } => {
+                write!(f, "Failed to encode {} image: {}", format, message)
+            }
+            SnipError::UnsupportedFormat(format) => {
+                write!(f, "Unsupported image format: {}", format)
+            }
+            SnipError::Ocr { message, .. } => write!(f, "Text recognition failed: {}", message),
+            SnipError::Config(message) => write!(f, "Invalid configuration: {}", message),
+            SnipError::Io(e) => write!(f, "I/O error: {}", e),
+            SnipError::Image(e) => write!(f, "Image error: {}", e),
+            #[cfg(windows)]
+            SnipError::Windows(e) => write!(f, "Windows error: {}", e),
+            SnipError::Context { context, .. } => f.write_str(context),
+        }
+    }
+}
+
+impl Error for SnipError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SnipError::BackendInit { source, .. }
+            | SnipError::Capture { source, .. }
+            | SnipError::Ocr { source, .. } => source
+                .as_ref()
+                .map(|source| source.as_ref() as &(dyn Error + 'static)),
+            SnipError::Io(e) => Some(e),
+            SnipError::Image(e) => Some(e),
+            #[cfg(windows)]
+            SnipError::Windows(e) => Some(e),
+            SnipError::Context { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SnipError {
+    fn from(e: std::io::Error) -> Self {
+        SnipError::Io(e)
+    }
+}
+
+impl From<ImageError> for SnipError {
+    fn from(e: ImageError) -> Self {
+        SnipError::Image(e)
+    }
+}
+
+#[cfg(windows)]
+impl From<windows::core::Error> for SnipError {
+    fn from(e: windows::core::Error) -> Self {
+        SnipError::Windows(e)
+    }
+}
+
+// Kontext direkt am Result anbringen: `fs::write(..).context("Failed to save")?`
+pub trait ErrorContext<T> {
+    fn context<S: Into<String>>(self, context: S) -> Result<T, SnipError>;
+}
+
+impl<T, E: Into<SnipError>> ErrorContext<T> for Result<T, E> {
+    fn context<S: Into<String>>(self, context: S) -> Result<T, SnipError> {
+        self.map_err(|e| e.into().context(context))
+    }
+}
+
+// Aufbereiteter Fehler für die Anzeige in UI oder CLI
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorReport {
+    // Die oberste Meldung, kurz genug für einen Dialog
+    pub message: String,
+    // Die Ursachen von außen nach innen
+    pub causes: Vec<String>,
+}
+
+impl ErrorReport {
+    pub fn new(error: &(dyn Error + 'static)) -> Self {
+        let message = error.to_string();
+        let mut causes: Vec<String> = Vec::new();
+        let mut source = error.source();
+        while let Some(cause) = source {
+            // Io, Image usw. nennen ihre Ursache bereits in der eigenen Meldung
+            let cause_message = cause.to_string();
+            let previous = causes.last().unwrap_or(&message);
+            if !previous.contains(&cause_message) {
+                causes.push(cause_message);
+            }
+            source = cause.source();
+        }
+
+        ErrorReport { message, causes }
+    }
+}
+
+impl fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        for cause in &self.causes {
+            write!(f, "\n  caused by: {}", cause)?;
+        }
+        Ok(())
+    }
+}
+
+pub type Reporter = Box<dyn Fn(&ErrorReport) + Send + Sync>;
+
+static REPORTER: RwLock<Option<Reporter>> = RwLock::new(None);
+
+// Ersetzt die Ausgabe auf stderr, z.B. durch eine MessageBox in der UI
+pub fn set_reporter(reporter: Reporter) {
+    if let Ok(mut current) = REPORTER.write() {
+        *current = Some(reporter);
+    }
+}
+
+// Meldet einen Fehler über den registrierten Reporter, ohne Reporter auf stderr.
+// Mit anyhow: `report(error.as_ref())`
+pub fn report(error: &(dyn Error + 'static)) -> ErrorReport {
+    let report = ErrorReport::new(error);
+    match REPORTER.read().as_deref() {
+        Ok(Some(reporter)) => reporter(&report),
+        _ => eprintln!("{}", report),
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::ErrorKind;
+    use std::sync::{Arc, Mutex};
+
+    fn not_found() -> std::io::Error {
+        std::io::Error::new(ErrorKind::NotFound, "shot.png fehlt")
+    }
+
+    #[test]
+    fn variants_display_their_message() {
+        assert_eq!(
+            SnipError::backend_init("no device").to_string(),
+            "Failed to initialize the graphics backend: no device"
+        );
+        assert_eq!(
+            SnipError::capture("Region 0,0,1,1").to_string(),
+            "Capture failed: Region 0,0,1,1"
+        );
+        assert_eq!(
+            SnipError::Encode {
+                format: "PNG",
+                message: "zu groß".to_string()
+            }
+            .to_string(),
+            "Failed to encode PNG image: zu groß"
+        );
+        assert_eq!(
+            SnipError::UnsupportedFormat("gif".to_string()).to_string(),
+            "Unsupported image format: gif"
+        );
+        assert_eq!(
+            SnipError::ocr("leer").to_string(),
+            "Text recognition failed: leer"
+        );
+        assert_eq!(
+            SnipError::Config("--region".to_string()).to_string(),
+            "Invalid configuration: --region"
+        );
+        assert_eq!(
+            SnipError::from(not_found()).to_string(),
+            "I/O error: shot.png fehlt"
+        );
+    }
+
+    #[test]
+    fn sources_form_a_chain() {
+        let error = SnipError::capture("Region 1,2,3,4")
+            .caused_by(not_found())
+            .context("Failed to save")
+            .context("Capture command");
+
+        assert_eq!(error.to_string(), "Capture command");
+        let first = error.source().unwrap();
+        assert_eq!(first.to_string(), "Failed to save");
+        let second = first.source().unwrap();
+        assert_eq!(second.to_string(), "Capture failed: Region 1,2,3,4");
+        let third = second.source().unwrap();
+        assert_eq!(third.to_string(), "shot.png fehlt");
+        assert!(third.source().is_none());
+
+        assert!(matches!(error.root(), SnipError::Capture { .. }));
+    }
+
+    #[test]
+    fn caused_by_ignores_variants_without_source() {
+        let error = SnipError::Config("x".to_string()).caused_by(not_found());
+        assert!(error.source().is_none());
+        assert!(SnipError::Config("x".to_string()).source().is_none());
+    }
+
+    #[test]
+    fn context_on_results() {
+        let result: Result<(), std::io::Error> = Err(not_found());
+        let error = result.context("Failed to open shot.png").unwrap_err();
+        assert_eq!(error.to_string(), "Failed to open shot.png");
+        assert!(matches!(error.root(), SnipError::Io(_)));
+    }
+
+    #[test]
+    fn report_lists_causes_without_repeating_them() {
+        let error = SnipError::from(not_found()).context("Failed to save");
+        let report = ErrorReport::new(&error);
+        // "I/O error: shot.png fehlt" enthält die Meldung des io::Error schon
+        assert_eq!(report.message, "Failed to save");
+        assert_eq!(report.causes, vec!["I/O error: shot.png fehlt".to_string()]);
+        assert_eq!(
+            report.to_string(),
+            "Failed to save\n  caused by: I/O error: shot.png fehlt"
+        );
+
+        let report = ErrorReport::new(&SnipError::Config("x".to_string()));
+        assert!(report.causes.is_empty());
+        assert_eq!(report.to_string(), "Invalid configuration: x");
+    }
+
+    #[test]
+    fn reporter_receives_reports() {
+        let received: Arc<Mutex<Vec<ErrorReport>>> = Arc::default();
+        let sink = Arc::clone(&received);
+        set_reporter(Box::new(move |report| {
+            sink.lock().unwrap().push(report.clone())
+        }));
+
+        let error = SnipError::capture("Bildschirm gesperrt").context("Reporter-Test");
+        let report = report(&error);
+        assert_eq!(report.message, "Reporter-Test");
+        // Andere Tests können parallel ebenfalls melden
+        assert!(received.lock().unwrap().contains(&report));
+    }
+}
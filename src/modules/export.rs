@@ -0,0 +1,364 @@
+use crate::modules::errorhandler::SnipError;
+use crate::modules::naming::{CaptureInfo, OutputPolicy};
+use crate::modules::ocr::OcrWord;
+use crate::modules::pdf::{self, PdfOptions};
+use crate::modules::redact::{self, Redaction};
+use crate::modules::webp;
+use image::codecs::{bmp::BmpEncoder, jpeg::JpegEncoder, png::PngEncoder, tiff::TiffEncoder};
+use image::{ColorType, DynamicImage, ImageError, RgbaImage};
+use std::borrow::Cow;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Png,
+    Jpeg { quality: u8 },
+    Bmp,
+    Tiff,
+    WebP,
+    // Bild als Seite mit unsichtbarem OCR-Text darüber
+    Pdf,
+}
+
+impl ExportFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(ExportFormat::Png),
+            "jpg" | "jpeg" => Some(ExportFormat::Jpeg {
+                quality: DEFAULT_JPEG_QUALITY,
+            }),
+            "bmp" => Some(ExportFormat::Bmp),
+            "tif" | "tiff" => Some(ExportFormat::Tiff),
+            "webp" => Some(ExportFormat::WebP),
+            "pdf" => Some(ExportFormat::Pdf),
+            _ => None,
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        path.as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(ExportFormat::from_extension)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Png => "png",
+            ExportFormat::Jpeg { .. } => "jpg",
+            ExportFormat::Bmp => "bmp",
+            ExportFormat::Tiff => "tiff",
+            ExportFormat::WebP => "webp",
+            ExportFormat::Pdf => "pdf",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Png => "PNG",
+            ExportFormat::Jpeg { .. } => "JPEG",
+            ExportFormat::Bmp => "BMP",
+            ExportFormat::Tiff => "TIFF",
+            ExportFormat::WebP => "WebP",
+            ExportFormat::Pdf => "PDF",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExportOptions {
+    // Hat Vorrang vor der Dateiendung
+    pub format: Option<ExportFormat>,
+    // Werden vor dem Kodieren in das Bild eingebrannt
+    pub redactions: Vec<Redaction>,
+    // Erkannter Text in Bildkoordinaten; wird nur in PDFs geschrieben
+    pub text_layer: Vec<OcrWord>,
+}
+
+impl ExportOptions {
+    pub fn with_format(format: ExportFormat) -> Self {
+        ExportOptions {
+            format: Some(format),
+            ..ExportOptions::default()
+        }
+    }
+
+    // Das übergebene Bild bleibt unverändert, geschrieben wird eine geschwärzte Kopie
+    pub fn prepare<'a>(&self, image: &'a RgbaImage) -> Cow<'a, RgbaImage> {
+        if self.redactions.is_empty() {
+            return Cow::Borrowed(image);
+        }
+        let mut redacted = image.clone();
+        redact::apply_all(&mut redacted, &self.redactions);
+        Cow::Owned(redacted)
+    }
+
+    // Wörter unter einer Schwärzung dürfen auch im Textlayer nicht auftauchen
+    pub fn visible_text(&self) -> Vec<OcrWord> {
+        self.text_layer
+            .iter()
+            .filter(|word| {
+                !self
+                    .redactions
+                    .iter()
+                    .any(|redaction| redaction.region.intersects(&word.bounds))
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn resolve<P: AsRef<Path>>(&self, path: P) -> Result<ExportFormat, SnipError> {
+        let path = path.as_ref();
+        self.format
+            .or_else(|| ExportFormat::from_path(path))
+            .ok_or_else(|| SnipError::UnsupportedFormat(path.display().to_string()))
+    }
+}
+
+fn encode_error(format: ExportFormat) -> impl Fn(ImageError) -> SnipError {
+    move |e| SnipError::Encode {
+        format: format.name(),
+        message: e.to_string(),
+    }
+}
+
+pub fn encode(image: &RgbaImage, format: ExportFormat) -> Result<Vec<u8>, SnipError> {
+    encode_with_text(image, format, &[])
+}
+
+// Andere Formate als PDF ignorieren den Text
+pub fn encode_with_text(
+    image: &RgbaImage,
+    format: ExportFormat,
+    words: &[OcrWord],
+) -> Result<Vec<u8>, SnipError> {
+    let (width, height) = image.dimensions();
+    let mut buffer = Vec::new();
+
+    match format {
+        ExportFormat::Png => PngEncoder::new(&mut buffer)
+            .encode(image.as_raw(), width, height, ColorType::Rgba8)
+            .map_err(encode_error(format))?,
+        ExportFormat::Jpeg { quality } => {
+            // JPEG kennt keinen Alphakanal
+            let rgb = DynamicImage::ImageRgba8(image.clone()).to_rgb8();
+            JpegEncoder::new_with_quality(&mut buffer, quality.clamp(1, 100))
+                .encode(rgb.as_raw(), width, height, ColorType::Rgb8)
+                .map_err(encode_error(format))?
+        }
+        ExportFormat::Bmp => BmpEncoder::new(&mut buffer)
+            .encode(image.as_raw(), width, height, ColorType::Rgba8)
+            .map_err(encode_error(format))?,
+        ExportFormat::Tiff => TiffEncoder::new(Cursor::new(&mut buffer))
+            .encode(image.as_raw(), width, height, ColorType::Rgba8)
+            .map_err(encode_error(format))?,
+        ExportFormat::WebP => buffer = webp::encode_lossless(image)?,
+        ExportFormat::Pdf => buffer = pdf::encode(image, words, &PdfOptions::default())?,
+    }
+
+    Ok(buffer)
+}
+
+pub fn save<P: AsRef<Path>>(
+    image: &RgbaImage,
+    path: P,
+    options: &ExportOptions,
+) -> Result<ExportFormat, SnipError> {
+    let path = path.as_ref();
+    let format = options.resolve(path)?;
+    let data = encode_with_text(&options.prepare(image), format, &options.visible_text())?;
+    fs::write(path, data)?;
+    Ok(format)
+}
+
+// Speichert unter dem nächsten freien Namen, den die Ausgaberichtlinie vergibt
+pub fn save_with_policy(
+    image: &RgbaImage,
+    policy: &OutputPolicy,
+    info: &CaptureInfo,
+    options: &ExportOptions,
+) -> Result<PathBuf, SnipError> {
+    let path = policy.reserve(info)?;
+    if let Err(e) = save(image, &path, options) {
+        // Die reservierte, leere Datei nicht liegen lassen
+        let _ = fs::remove_file(&path);
+        return Err(e);
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::backend::{Color, PixelRect};
+    use crate::modules::naming::FilenameTemplate;
+    use crate::modules::redact::RedactionStyle;
+    use crate::modules::testing::TempDir;
+    use image::Rgba;
+
+    // Verlauf mit wechselndem Alpha, damit vertauschte Kanäle auffallen
+    fn sample() -> RgbaImage {
+        RgbaImage::from_fn(23, 17, |x, y| {
+            Rgba([
+                (x * 11) as u8,
+                (y * 15) as u8,
+                ((x + y) * 7) as u8,
+                255 - (x * y % 200) as u8,
+            ])
+        })
+    }
+
+    fn decode(data: &[u8]) -> RgbaImage {
+        image::load_from_memory(data).unwrap().to_rgba8()
+    }
+
+    #[test]
+    fn lossless_formats_round_trip() {
+        let image = sample();
+        for format in [ExportFormat::Png, ExportFormat::Bmp, ExportFormat::Tiff] {
+            let decoded = decode(&encode(&image, format).unwrap());
+            assert_eq!(decoded, image, "{}", format.name());
+        }
+    }
+
+    #[test]
+    fn webp_round_trips() {
+        let image = sample();
+        let data = encode(&image, ExportFormat::WebP).unwrap();
+        let mut decoder = image_webp::WebPDecoder::new(Cursor::new(&data)).unwrap();
+        assert_eq!(decoder.dimensions(), image.dimensions());
+        assert!(decoder.has_alpha());
+        let mut decoded = vec![0; decoder.output_buffer_size().unwrap()];
+        decoder.read_image(&mut decoded).unwrap();
+        assert_eq!(decoded, image.into_raw());
+    }
+
+    #[test]
+    fn jpeg_stays_close_to_the_original() {
+        // Flächen statt Rauschen, damit die Kompression kaum Spielraum braucht
+        let image = RgbaImage::from_fn(32, 32, |x, _| {
+            if x < 16 {
+                Rgba([200, 40, 40, 255])
+            } else {
+                Rgba([30, 90, 220, 255])
+            }
+        });
+        let data = encode(&image, ExportFormat::Jpeg { quality: 95 }).unwrap();
+        let decoded = decode(&data);
+        assert_eq!(decoded.dimensions(), image.dimensions());
+        for (x, y, pixel) in decoded.enumerate_pixels() {
+            if x == 15 || x == 16 {
+                continue;
+            }
+            let original = image.get_pixel(x, y);
+            for channel in 0..3 {
+                let delta = (pixel[channel] as i32 - original[channel] as i32).abs();
+                assert!(delta <= 12, "({}, {}) weicht um {} ab", x, y, delta);
+            }
+            assert_eq!(pixel[3], 255);
+        }
+    }
+
+    #[test]
+    fn pdf_output_is_a_pdf() {
+        let data = encode(&sample(), ExportFormat::Pdf).unwrap();
+        assert!(data.starts_with(b"%PDF-"));
+    }
+
+    #[test]
+    fn format_follows_option_then_extension() {
+        let options = ExportOptions::default();
+        assert_eq!(options.resolve("a.PNG").unwrap(), ExportFormat::Png);
+        assert_eq!(
+            options.resolve("a.jpeg").unwrap(),
+            ExportFormat::Jpeg {
+                quality: DEFAULT_JPEG_QUALITY
+            }
+        );
+        assert_eq!(options.resolve("a.tif").unwrap(), ExportFormat::Tiff);
+        assert!(matches!(
+            options.resolve("a.gif"),
+            Err(SnipError::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            options.resolve("a"),
+            Err(SnipError::UnsupportedFormat(_))
+        ));
+
+        let options = ExportOptions::with_format(ExportFormat::Bmp);
+        assert_eq!(options.resolve("a.png").unwrap(), ExportFormat::Bmp);
+        assert_eq!(options.resolve("a").unwrap(), ExportFormat::Bmp);
+    }
+
+    #[test]
+    fn redactions_are_burned_into_the_copy() {
+        let image = sample();
+        let options = ExportOptions {
+            redactions: vec![Redaction::new(
+                PixelRect::new(2, 3, 5, 4),
+                RedactionStyle::Fill(Color::new(0.0, 0.0, 0.0, 1.0)),
+            )],
+            text_layer: vec![
+                OcrWord::new("geheim", PixelRect::new(3, 4, 2, 2)),
+                OcrWord::new("sichtbar", PixelRect::new(15, 10, 4, 4)),
+            ],
+            ..ExportOptions::default()
+        };
+
+        let dir = TempDir::new("export_redact");
+        fs::create_dir_all(&dir.0).unwrap();
+        let path = dir.join("out.png");
+        assert_eq!(save(&image, &path, &options).unwrap(), ExportFormat::Png);
+
+        let saved = image::open(&path).unwrap().to_rgba8();
+        for (x, y, pixel) in saved.enumerate_pixels() {
+            let inside = (2..7).contains(&x) && (3..7).contains(&y);
+            if inside {
+                assert_eq!(*pixel, Rgba([0, 0, 0, 255]));
+            } else {
+                assert_eq!(pixel, image.get_pixel(x, y));
+            }
+        }
+        // Das Original bleibt unberührt
+        assert_eq!(image, sample());
+
+        let words = options.visible_text();
+        assert_eq!(words.len(), 1);
+        assert_eq!(words[0].text, "sichtbar");
+    }
+
+    #[test]
+    fn policy_saves_under_free_names() {
+        let dir = TempDir::new("export_policy");
+        let policy = OutputPolicy::new(&dir.0, "shot.png".parse::<FilenameTemplate>().unwrap());
+        let info = CaptureInfo::new(23, 17);
+        let image = sample();
+
+        let first = save_with_policy(&image, &policy, &info, &ExportOptions::default()).unwrap();
+        let second = save_with_policy(&image, &policy, &info, &ExportOptions::default()).unwrap();
+        assert_eq!(first, dir.join("shot.png"));
+        assert_eq!(second, dir.join("shot_2.png"));
+        assert_eq!(image::open(&second).unwrap().to_rgba8(), image);
+
+        let policy = policy.with_extension("webp");
+        let third = save_with_policy(&image, &policy, &info, &ExportOptions::default()).unwrap();
+        assert_eq!(third, dir.join("shot.webp"));
+        assert!(fs::read(&third).unwrap().starts_with(b"RIFF"));
+    }
+
+    #[test]
+    fn failed_encoding_releases_the_reserved_name() {
+        let dir = TempDir::new("export_failed");
+        let policy = OutputPolicy::new(&dir.0, "shot.webp".parse::<FilenameTemplate>().unwrap());
+        let info = CaptureInfo::new(webp::MAX_DIMENSION + 1, 1);
+
+        // WebP erlaubt höchstens 16384 Pixel pro Seite
+        let image = RgbaImage::new(webp::MAX_DIMENSION + 1, 1);
+        assert!(save_with_policy(&image, &policy, &info, &ExportOptions::default()).is_err());
+        assert!(!dir.join("shot.webp").exists());
+    }
+}
@@ -0,0 +1,244 @@
+use crate::modules::backend::Rect;
+
+// 5x7-Bitmapschrift für ASCII 32..=126, spaltenweise, Bit 0 ist die oberste Zeile.
+// Export und Vorschau zeichnen Text mit denselben Rechtecken, damit beides gleich aussieht.
+
+pub const GLYPH_WIDTH: u32 = 5;
+pub const GLYPH_HEIGHT: u32 = 7;
+// Abstand in Glyphpixeln bis zum nächsten Zeichen bzw. zur nächsten Zeile
+const ADVANCE: u32 = 6;
+const LINE_HEIGHT: u32 = 9;
+
+const FIRST: char = ' ';
+const GLYPHS: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x01, 0x01], // F
+    [0x3E, 0x41, 0x41, 0x51, 0x32], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x04, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x7F, 0x20, 0x18, 0x20, 0x7F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x03, 0x04, 0x78, 0x04, 0x03], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+// Umlaute werden auf den Grundbuchstaben abgebildet, alles andere auf '?'
+fn glyph(c: char) -> &'static [u8; 5] {
+    let c = match c {
+        'ä' => 'a',
+        'ö' => 'o',
+        'ü' => 'u',
+        'Ä' => 'A',
+        'Ö' => 'O',
+        'Ü' => 'U',
+        'ß' => 's',
+        '\t' => ' ',
+        c => c,
+    };
+    let index = (c as u32).wrapping_sub(FIRST as u32) as usize;
+    GLYPHS
+        .get(index)
+        .unwrap_or(&GLYPHS[('?' as u32 - FIRST as u32) as usize])
+}
+
+// Größe eines Glyphpixels bei der gegebenen Schrifthöhe
+fn scale(size: f32) -> f32 {
+    size.max(1.0) / GLYPH_HEIGHT as f32
+}
+
+// Alle gesetzten Pixel des Textes als Rechtecke; `origin` ist die linke obere Ecke
+pub fn text_rects(text: &str, origin: (f32, f32), size: f32) -> Vec<Rect> {
+    let scale = scale(size);
+    let mut rects = Vec::new();
+
+    for (line_index, line) in text.lines().enumerate() {
+        let top = origin.1 + (line_index as u32 * LINE_HEIGHT) as f32 * scale;
+        for (char_index, c) in line.chars().enumerate() {
+            let left = origin.0 + (char_index as u32 * ADVANCE) as f32 * scale;
+            for (column, bits) in glyph(c).iter().enumerate() {
+                for row in 0..GLYPH_HEIGHT {
+                    if bits & (1 << row) != 0 {
+                        let x = left + column as f32 * scale;
+                        let y = top + row as f32 * scale;
+                        rects.push(Rect::new(x, y, x + scale, y + scale));
+                    }
+                }
+            }
+        }
+    }
+
+    rects
+}
+
+pub fn text_bounds(text: &str, origin: (f32, f32), size: f32) -> Rect {
+    let scale = scale(size);
+    let columns = text
+        .lines()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0) as u32;
+    let lines = text.lines().count().max(1) as u32;
+
+    let width = (columns * ADVANCE).saturating_sub(ADVANCE - GLYPH_WIDTH) as f32 * scale;
+    let height = ((lines - 1) * LINE_HEIGHT + GLYPH_HEIGHT) as f32 * scale;
+    Rect::new(origin.0, origin.1, origin.0 + width, origin.1 + height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_bits(c: char) -> usize {
+        glyph(c).iter().map(|bits| bits.count_ones() as usize).sum()
+    }
+
+    #[test]
+    fn glyphs_fall_back() {
+        assert_eq!(glyph('ä'), glyph('a'));
+        assert_eq!(glyph('Ü'), glyph('U'));
+        assert_eq!(glyph('\t'), glyph(' '));
+        assert_eq!(glyph('€'), glyph('?'));
+        assert_eq!(glyph('\u{7f}'), glyph('?'));
+        assert_eq!(glyph('\u{1}'), glyph('?'));
+        assert_eq!(glyph('~'), &GLYPHS[94]);
+    }
+
+    #[test]
+    fn one_rect_per_set_pixel() {
+        assert!(text_rects(" ", (0.0, 0.0), 7.0).is_empty());
+        assert_eq!(text_rects("I", (0.0, 0.0), 7.0).len(), set_bits('I'));
+        assert_eq!(
+            text_rects("Hi\nA", (0.0, 0.0), 7.0).len(),
+            set_bits('H') + set_bits('i') + set_bits('A')
+        );
+
+        // Das I: mittlere Spalte durchgehend, oben und unten je ein Querstrich
+        let rects = text_rects("I", (10.0, 20.0), 7.0);
+        assert!(rects.contains(&Rect::new(12.0, 20.0, 13.0, 21.0)));
+        assert!(rects.contains(&Rect::new(12.0, 26.0, 13.0, 27.0)));
+        assert!(rects.contains(&Rect::new(11.0, 26.0, 12.0, 27.0)));
+        assert!(!rects.contains(&Rect::new(10.0, 20.0, 11.0, 21.0)));
+    }
+
+    #[test]
+    fn size_scales_the_glyph_pixels() {
+        let small = text_rects("A", (0.0, 0.0), 7.0);
+        let large = text_rects("A", (0.0, 0.0), 14.0);
+        assert_eq!(small.len(), large.len());
+        for (small, large) in small.iter().zip(&large) {
+            assert_eq!(large.left, small.left * 2.0);
+            assert_eq!(large.width(), 2.0);
+        }
+        // Sehr kleine Größen werden auf einen Pixel Schrifthöhe begrenzt
+        assert_eq!(text_bounds("A", (0.0, 0.0), 0.0).height(), 1.0);
+    }
+
+    #[test]
+    fn bounds_cover_lines_and_columns() {
+        assert_eq!(
+            text_bounds("AB", (1.0, 2.0), 7.0),
+            Rect::new(1.0, 2.0, 12.0, 9.0)
+        );
+        // Die längste Zeile bestimmt die Breite
+        assert_eq!(
+            text_bounds("A\nBCD", (0.0, 0.0), 14.0),
+            Rect::new(0.0, 0.0, 34.0, 32.0)
+        );
+        assert_eq!(
+            text_bounds("", (5.0, 5.0), 7.0),
+            Rect::new(5.0, 5.0, 5.0, 12.0)
+        );
+
+        let bounds = text_bounds("Wg|\n_~", (3.0, 4.0), 21.0);
+        for rect in text_rects("Wg|\n_~", (3.0, 4.0), 21.0) {
+            assert!(rect.left >= bounds.left && rect.right <= bounds.right);
+            assert!(rect.top >= bounds.top && rect.bottom <= bounds.bottom);
+        }
+    }
+}
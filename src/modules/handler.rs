@@ -1,29 +1,15 @@
+use crate::modules::backend::{Color, WindowType};
 use crate::modules::controller::{Command, WindowController};
-use crate::modules::win_fact::WindowType;
+use crate::modules::errorhandler;
+use crate::modules::selection::{InputEvent, Key, Modifiers};
 
 use windows::Win32::{
     Foundation::*,
-    Graphics::{Direct2D::Common::*, Gdi::*},
+    Graphics::Gdi::*,
     System::SystemServices::*,
     UI::{Input::KeyboardAndMouse::*, WindowsAndMessaging::*},
 };
 
-fn update_rect(rect: &mut D2D_RECT_F, x: f32, y: f32) {
-    if x == rect.left {
-        std::mem::swap(&mut rect.left, &mut rect.right);
-        rect.left = x;
-    } else {
-        rect.right = x;
-    }
-
-    if y == rect.top {
-        std::mem::swap(&mut rect.top, &mut rect.bottom);
-        rect.top = y;
-    } else {
-        rect.bottom = y;
-    }
-}
-
 macro_rules! get_x_lparam {
     ($lparam:expr) => {
         ($lparam & 0xFFFF) as i16 as i32 // Cast to i16 first to handle negative coordinates correctly
@@ -36,6 +22,38 @@ macro_rules! get_y_lparam {
     };
 }
 
+fn key_from_vk(vk: usize) -> Option<Key> {
+    match VIRTUAL_KEY(vk as u16) {
+        VK_ESCAPE => Some(Key::Escape),
+        VK_RETURN => Some(Key::Enter),
+        VK_LEFT => Some(Key::Left),
+        VK_RIGHT => Some(Key::Right),
+        VK_UP => Some(Key::Up),
+        VK_DOWN => Some(Key::Down),
+        // Die virtuellen Tastencodes von A-Z und 0-9 entsprechen ihren ASCII-Werten
+        VIRTUAL_KEY(code @ (0x30..=0x39 | 0x41..=0x5A)) => Some(Key::Char(code as u8 as char)),
+        _ => None,
+    }
+}
+
+fn modifiers() -> Modifiers {
+    // Das höchste Bit von GetKeyState ist gesetzt, solange die Taste gedrückt ist
+    let pressed = |vk: VIRTUAL_KEY| unsafe { GetKeyState(vk.0 as i32) } < 0;
+    Modifiers {
+        shift: pressed(VK_SHIFT),
+        ctrl: pressed(VK_CONTROL),
+    }
+}
+
+// Capture und Cancel landen als Befehle in der Warteschlange und werden von der
+// Nachrichtenschleife abgearbeitet
+fn handle_input(controller: &WindowController, event: InputEvent) {
+    if let Err(e) = controller.handle_input(event) {
+        errorhandler::report(e.context("Failed to handle input").as_ref());
+        unsafe { PostQuitMessage(1) };
+    }
+}
+
 pub extern "system" fn win_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
     unsafe {
         let controll_ptr = GetWindowLongPtrA(hwnd, GWLP_USERDATA) as *const WindowController;
@@ -45,17 +63,15 @@ pub extern "system" fn win_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LP
 
         let controller = &*controll_ptr;
 
-        static mut RECT: Option<D2D_RECT_F> = None;
-
         match msg {
             WM_CREATE => {
-                RECT = None;
+                let _ = controller.reset_selection();
 
                 LRESULT(0)
             }
             WM_KEYDOWN => {
-                if wparam.0 == VK_ESCAPE.0 as usize {
-                    PostQuitMessage(0);
+                if let Some(key) = key_from_vk(wparam.0) {
+                    handle_input(controller, InputEvent::KeyDown(key, modifiers()));
                 }
                 LRESULT(0)
             }
@@ -63,36 +79,33 @@ pub extern "system" fn win_proc(hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LP
                 let x = get_x_lparam!(lparam.0) as f32;
                 let y = get_y_lparam!(lparam.0) as f32;
 
-                if msg == WM_LBUTTONDOWN {
-                    RECT = Some(D2D_RECT_F {
-                        left: x,
-                        top: y,
-                        right: x,
-                        bottom: y,
-                    });
-                } else if msg == WM_MOUSEMOVE && (wparam.0 & MK_LBUTTON.0 as usize) != 0 {
-                    if let Some(ref mut rect) = RECT {
-                        update_rect(rect, x, y);
-
-                        controller.dispatch(WindowType::Transparent, Command::RedrawWindow);
-                    }
-                }
+                let event = match msg {
+                    WM_LBUTTONDOWN => InputEvent::MouseDown { x, y },
+                    WM_LBUTTONUP => InputEvent::MouseUp { x, y },
+                    _ => InputEvent::MouseMove {
+                        x,
+                        y,
+                        pressed: (wparam.0 & MK_LBUTTON.0 as usize) != 0,
+                    },
+                };
+                handle_input(controller, event);
                 LRESULT(0)
             }
 
             WM_PAINT => {
-                controller.dispatch(WindowType::Transparent, Command::DrawOverlay(RECT));
+                // Beim Annotieren das eingefrorene Bild mit den Formen statt der Auswahl
+                let frame = if controller.is_annotating() {
+                    Command::DrawAnnotations(controller.annotation_shapes())
+                } else {
+                    Command::DrawOverlay(controller.overlay())
+                };
+                let _ = controller.dispatch(WindowType::Transparent, frame);
                 LRESULT(0)
             }
             WM_ERASEBKGND => {
                 let _ = controller.dispatch(
                     WindowType::Transparent,
-                    Command::FillBackground(D2D1_COLOR_F {
-                        r: 0.0,
-                        g: 0.0,
-                        b: 0.0,
-                        a: 0.6,
-                    }),
+                    Command::FillBackground(Color::new(0.0, 0.0, 0.0, 0.6)),
                 );
                 LRESULT(1)
             }
@@ -121,39 +134,29 @@ pub extern "system" fn opaque_handler(
         let controller = &*controll_ptr;
         match message {
             WM_KEYDOWN => {
-                if wparam.0 == VK_ESCAPE.0 as usize {
-                    PostQuitMessage(0);
+                match key_from_vk(wparam.0) {
+                    Some(Key::Escape) => PostQuitMessage(0),
+                    Some(key) => {
+                        controller.handle_shortcut(key, modifiers());
+                    }
+                    None => {}
                 }
                 LRESULT(0)
             }
             WM_DESTROY => {
-                println!("WM_DESTROY");
                 PostQuitMessage(0);
                 LRESULT(0)
             }
             WM_PAINT => {
-                println!("WM_PAINT");
                 let _ = controller.dispatch(
                     WindowType::Opaque,
-                    Command::FillBackground(D2D1_COLOR_F {
-                        r: 1.0,
-                        g: 1.0,
-                        b: 1.0,
-                        a: 1.0,
-                    }),
+                    Command::FillBackground(Color::new(1.0, 1.0, 1.0, 1.0)),
                 );
                 LRESULT(0)
             }
             WM_ERASEBKGND => {
                 LRESULT(1) // Return 1 to indicate that the background has been erased
             }
-            WM_ERASEBKGND => {
-                //if FIRST_PAINT.swap(false, Ordering::SeqCst) {
-
-                //}
-                LRESULT(0)
-            }
-
             _ => DefWindowProcW(window, message, wparam, lparam),
         }
     }
@@ -0,0 +1,564 @@
+use crate::modules::annotation;
+use crate::modules::backend::{Backend, Color, Frame, PixelRect, Surface, WindowType};
+use crate::modules::controller::WindowController;
+use crate::modules::raster::{self, OverlayStyle};
+use anyhow::{anyhow, Result};
+use image::RgbaImage;
+use std::sync::{Arc, Mutex};
+
+struct HeadlessState {
+    buffer: RgbaImage,
+    backdrop: Option<RgbaImage>,
+    visible: bool,
+    redraws: usize,
+}
+
+// Fenster ohne Bildschirm: jeder Frame landet in einem RGBA-Puffer
+pub struct HeadlessWindow {
+    window_type: WindowType,
+    style: OverlayStyle,
+    state: Arc<Mutex<HeadlessState>>,
+}
+
+impl HeadlessWindow {
+    fn with_state<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut HeadlessState) -> T,
+    {
+        self.state
+            .lock()
+            .map(|mut state| f(&mut state))
+            .map_err(|_| anyhow!("Failed to lock headless window state"))
+    }
+}
+
+impl Surface for HeadlessWindow {
+    fn window_type(&self) -> WindowType {
+        self.window_type
+    }
+
+    fn show(&self) {
+        let _ = self.with_state(|state| state.visible = true);
+    }
+
+    fn hide(&self) {
+        let _ = self.with_state(|state| state.visible = false);
+    }
+
+    fn redraw(&self) {
+        let _ = self.with_state(|state| state.redraws += 1);
+    }
+
+    fn set_backdrop(&self, image: &RgbaImage) -> Result<()> {
+        self.with_state(|state| state.backdrop = Some(image.clone()))
+    }
+
+    fn paint(&self, frame: &Frame) -> Result<()> {
+        self.with_state(|state| match frame {
+            Frame::Overlay(overlay) => raster::draw_overlay_on(
+                &mut state.buffer,
+                state.backdrop.as_ref(),
+                overlay,
+                &self.style,
+            ),
+            Frame::Background(color) => raster::fill(&mut state.buffer, *color),
+            Frame::Annotations(shapes) => {
+                raster::fill(&mut state.buffer, Color::new(0.0, 0.0, 0.0, 0.0));
+                if let Some(backdrop) = &state.backdrop {
+                    image::imageops::replace(&mut state.buffer, backdrop, 0, 0);
+                }
+                annotation::render_shapes(&mut state.buffer, shapes);
+            }
+        })
+    }
+}
+
+type HeadlessWindows = Vec<(WindowType, Arc<Mutex<HeadlessState>>)>;
+
+// Klone teilen sich die Fenster, so lässt sich ein Backend an den WindowController übergeben
+// und trotzdem noch auswerten
+#[derive(Clone)]
+pub struct HeadlessBackend {
+    width: u32,
+    height: u32,
+    style: OverlayStyle,
+    windows: Arc<Mutex<HeadlessWindows>>,
+}
+
+impl HeadlessBackend {
+    pub fn new(width: u32, height: u32) -> Self {
+        HeadlessBackend {
+            width,
+            height,
+            style: OverlayStyle::default(),
+            windows: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn with_overlay_style(mut self, style: OverlayStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    fn state(&self, window_type: WindowType) -> Option<Arc<Mutex<HeadlessState>>> {
+        let windows = self.windows.lock().ok()?;
+        windows
+            .iter()
+            .find(|(kind, _)| *kind == window_type)
+            .map(|(_, state)| state.clone())
+    }
+
+    // Kopie des zuletzt gezeichneten Frames
+    pub fn frame(&self, window_type: WindowType) -> Option<RgbaImage> {
+        let state = self.state(window_type)?;
+        let state = state.lock().ok()?;
+        Some(state.buffer.clone())
+    }
+
+    pub fn is_visible(&self, window_type: WindowType) -> bool {
+        self.state(window_type)
+            .and_then(|state| state.lock().ok().map(|state| state.visible))
+            .unwrap_or(false)
+    }
+
+    pub fn redraw_count(&self, window_type: WindowType) -> usize {
+        self.state(window_type)
+            .and_then(|state| state.lock().ok().map(|state| state.redraws))
+            .unwrap_or(0)
+    }
+}
+
+impl Backend for HeadlessBackend {
+    fn screen_bounds(&self) -> PixelRect {
+        PixelRect::new(0, 0, self.width, self.height)
+    }
+
+    fn create_window(
+        &self,
+        window_type: WindowType,
+        _controller: &WindowController,
+    ) -> Result<Box<dyn Surface>> {
+        if window_type == WindowType::None {
+            return Err(anyhow!("Invalid window type"));
+        }
+
+        let state = Arc::new(Mutex::new(HeadlessState {
+            buffer: RgbaImage::new(self.width, self.height),
+            backdrop: None,
+            visible: false,
+            redraws: 0,
+        }));
+
+        let mut windows = self
+            .windows
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock headless windows"))?;
+        windows.retain(|(kind, _)| *kind != window_type);
+        windows.push((window_type, state.clone()));
+
+        Ok(Box::new(HeadlessWindow {
+            window_type,
+            style: self.style,
+            state,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::capture::{Capture, FrameSource, ImageFrameSource};
+    use crate::modules::commands::{AppCommand, CommandBus, CommandName, CommandSource};
+    use crate::modules::controller::Command;
+    use crate::modules::recording::RecordingOptions;
+    use crate::modules::selection::{InputEvent, Key, Modifiers, SelectionState};
+    use image::Rgba;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    fn desktop() -> RgbaImage {
+        RgbaImage::from_fn(40, 30, |x, y| Rgba([x as u8 * 6, y as u8 * 8, 100, 255]))
+    }
+
+    // Controller mit eingefrorenem Desktop und sichtbarem Overlay
+    fn overlay() -> (HeadlessBackend, WindowController) {
+        let backend = HeadlessBackend::new(40, 30);
+        let controller = WindowController::new(Box::new(backend.clone()));
+        controller
+            .set_capture(Capture::new(Box::new(ImageFrameSource::new(desktop()))))
+            .unwrap();
+        controller.freeze().unwrap();
+        controller.create_window(WindowType::Transparent).unwrap();
+        controller
+            .dispatch(WindowType::Transparent, Command::Show)
+            .unwrap();
+        (backend, controller)
+    }
+
+    fn drag(controller: &WindowController, from: (f32, f32), to: (f32, f32)) -> SelectionState {
+        let (x, y) = from;
+        controller
+            .handle_input(InputEvent::MouseDown { x, y })
+            .unwrap();
+        let (x, y) = to;
+        controller
+            .handle_input(InputEvent::MouseMove {
+                x,
+                y,
+                pressed: true,
+            })
+            .unwrap();
+        controller
+            .handle_input(InputEvent::MouseUp { x, y })
+            .unwrap()
+    }
+
+    #[test]
+    fn windows_are_created_per_type() {
+        let backend = HeadlessBackend::new(16, 8);
+        let controller = WindowController::new(Box::new(backend.clone()));
+
+        assert!(backend.frame(WindowType::Transparent).is_none());
+        assert!(controller.create_window(WindowType::None).is_err());
+
+        controller.create_window(WindowType::Opaque).unwrap();
+        assert_eq!(
+            backend.frame(WindowType::Opaque).unwrap().dimensions(),
+            (16, 8)
+        );
+        assert!(!backend.is_visible(WindowType::Opaque));
+        assert!(backend.frame(WindowType::Transparent).is_none());
+
+        controller
+            .dispatch(WindowType::Opaque, Command::Show)
+            .unwrap();
+        assert!(backend.is_visible(WindowType::Opaque));
+        controller
+            .dispatch(WindowType::Opaque, Command::Hide)
+            .unwrap();
+        assert!(!backend.is_visible(WindowType::Opaque));
+    }
+
+    #[test]
+    fn background_fills_the_whole_buffer() {
+        let backend = HeadlessBackend::new(4, 3);
+        let controller = WindowController::new(Box::new(backend.clone()));
+        controller.create_window(WindowType::Opaque).unwrap();
+        controller
+            .dispatch(
+                WindowType::Opaque,
+                Command::FillBackground(Color::new(1.0, 0.0, 0.0, 1.0)),
+            )
+            .unwrap();
+
+        let frame = backend.frame(WindowType::Opaque).unwrap();
+        assert!(frame.pixels().all(|p| *p == Rgba([255, 0, 0, 255])));
+    }
+
+    #[test]
+    fn dragging_redraws_and_paints_the_cut_out() {
+        let (backend, controller) = overlay();
+        assert!(backend.is_visible(WindowType::Transparent));
+
+        assert_eq!(
+            drag(&controller, (10.0, 5.0), (30.0, 20.0)),
+            SelectionState::Selected
+        );
+        assert!(backend.redraw_count(WindowType::Transparent) >= 3);
+
+        controller
+            .dispatch(
+                WindowType::Transparent,
+                Command::DrawOverlay(controller.overlay()),
+            )
+            .unwrap();
+        let frame = backend.frame(WindowType::Transparent).unwrap();
+        let desktop = desktop();
+
+        // Innen das eingefrorene Bild, außen abgedunkelt
+        assert_eq!(frame.get_pixel(20, 12), desktop.get_pixel(20, 12));
+        let outside = frame.get_pixel(35, 2);
+        let original = desktop.get_pixel(35, 2);
+        assert_eq!(outside[3], 255);
+        assert!(outside[0] < original[0] && outside[2] < original[2]);
+    }
+
+    #[test]
+    fn enter_queues_capture_of_the_frozen_frame() {
+        let (backend, controller) = overlay();
+        drag(&controller, (30.0, 20.0), (10.0, 5.0));
+
+        let state = controller
+            .handle_input(InputEvent::KeyDown(Key::Enter, Modifiers::default()))
+            .unwrap();
+        assert_eq!(state, SelectionState::Committed);
+        let queued = controller.commands().pop().unwrap();
+        assert_eq!(queued.source, CommandSource::Input);
+        assert_eq!(queued.command, AppCommand::Capture);
+
+        let image = controller.capture_selection().unwrap();
+        let expected = image::imageops::crop_imm(&desktop(), 10, 5, 20, 15).to_image();
+        assert_eq!(image, expected);
+        assert!(!backend.is_visible(WindowType::Transparent));
+    }
+
+    #[test]
+    fn escape_queues_cancel() {
+        let (_, controller) = overlay();
+        drag(&controller, (10.0, 5.0), (30.0, 20.0));
+
+        let state = controller
+            .handle_input(InputEvent::KeyDown(Key::Escape, Modifiers::default()))
+            .unwrap();
+        assert_eq!(state, SelectionState::Cancelled);
+        assert_eq!(
+            controller.commands().pop().map(|queued| queued.command),
+            Some(AppCommand::Cancel)
+        );
+        assert!(controller.commands().is_empty());
+    }
+
+    #[test]
+    fn ctrl_s_without_selection_keeps_the_overlay_open() {
+        let (backend, controller) = overlay();
+        let mut bus = CommandBus::new(controller.commands().clone());
+        bus.register(
+            CommandName::Save,
+            Box::new(|controller: &WindowController, _| controller.capture_selection().map(|_| ())),
+        );
+        let ctrl = Modifiers {
+            ctrl: true,
+            ..Modifiers::default()
+        };
+
+        // Die Tastenkombination wird ohne Auswahl gar nicht erst eingereiht
+        assert!(!controller.handle_shortcut(Key::Char('S'), ctrl));
+        assert_eq!(bus.run_pending(&controller).unwrap(), 0);
+        assert!(backend.is_visible(WindowType::Transparent));
+
+        // Von außen kommt der Befehl trotzdem an, schlägt fehl und lässt das Overlay stehen
+        controller
+            .commands()
+            .post(CommandSource::Cli, AppCommand::Save { path: None });
+        assert!(bus.run_pending(&controller).is_err());
+        assert!(backend.is_visible(WindowType::Transparent));
+
+        drag(&controller, (10.0, 5.0), (30.0, 20.0));
+        assert!(controller.handle_shortcut(Key::Char('S'), ctrl));
+        assert_eq!(bus.run_pending(&controller).unwrap(), 1);
+        assert!(!backend.is_visible(WindowType::Transparent));
+    }
+
+    #[test]
+    fn annotations_are_drawn_and_burned_into_the_capture() {
+        let (backend, controller) = overlay();
+        drag(&controller, (10.0, 5.0), (30.0, 25.0));
+        assert!(!controller.is_annotating());
+
+        let ctrl = Modifiers {
+            shift: false,
+            ctrl: true,
+        };
+        controller
+            .handle_input(InputEvent::KeyDown(Key::Char('E'), ctrl))
+            .unwrap();
+        assert_eq!(
+            controller.commands().pop().map(|queued| queued.command),
+            Some(AppCommand::Annotate)
+        );
+        assert!(controller.annotate().unwrap());
+        assert!(!controller.annotate().unwrap());
+
+        // Die Maus zeichnet jetzt, statt die Auswahl zu verändern
+        controller
+            .handle_input(InputEvent::KeyDown(Key::Char('H'), Modifiers::default()))
+            .unwrap();
+        controller
+            .handle_input(InputEvent::MouseDown { x: 12.0, y: 7.0 })
+            .unwrap();
+        controller
+            .handle_input(InputEvent::MouseUp { x: 12.0, y: 7.0 })
+            .unwrap();
+        assert!(controller.annotation_shapes().is_empty());
+        drag(&controller, (12.0, 7.0), (18.0, 12.0));
+        controller
+            .handle_input(InputEvent::KeyDown(Key::Left, Modifiers::default()))
+            .unwrap();
+        assert_eq!(
+            controller.selection_bounds(),
+            Some(PixelRect::new(10, 5, 20, 20))
+        );
+
+        // Im Overlay in Bildschirmkoordinaten
+        let shapes = controller.annotation_shapes();
+        assert_eq!(shapes.len(), 1);
+        assert_eq!(
+            shapes[0].kind,
+            annotation::ShapeKind::Highlight(crate::modules::backend::Rect::new(
+                12.0, 7.0, 18.0, 12.0
+            ))
+        );
+        controller
+            .dispatch(WindowType::Transparent, Command::DrawAnnotations(shapes))
+            .unwrap();
+        let frame = backend.frame(WindowType::Transparent).unwrap();
+        assert_ne!(frame.get_pixel(14, 9), desktop().get_pixel(14, 9));
+        assert_eq!(frame.get_pixel(25, 20), desktop().get_pixel(25, 20));
+
+        let state = controller
+            .handle_input(InputEvent::KeyDown(Key::Enter, Modifiers::default()))
+            .unwrap();
+        assert_eq!(state, SelectionState::Committed);
+        assert_eq!(
+            controller.commands().pop().map(|queued| queued.command),
+            Some(AppCommand::Capture)
+        );
+
+        // In der Aufnahme relativ zur Auswahl
+        let image = controller.capture_selection().unwrap();
+        let desktop = desktop();
+        assert_eq!(image.dimensions(), (20, 20));
+        assert_ne!(image.get_pixel(4, 4), desktop.get_pixel(14, 9));
+        assert_eq!(image.get_pixel(15, 15), desktop.get_pixel(25, 20));
+    }
+
+    #[test]
+    fn undo_and_redo_affect_the_annotation_while_annotating() {
+        let (_backend, controller) = overlay();
+        drag(&controller, (10.0, 5.0), (30.0, 25.0));
+        let ctrl = Modifiers {
+            shift: false,
+            ctrl: true,
+        };
+        assert!(controller.annotate().unwrap());
+        drag(&controller, (12.0, 7.0), (18.0, 12.0));
+        drag(&controller, (20.0, 15.0), (25.0, 20.0));
+        assert_eq!(controller.annotation_shapes().len(), 2);
+
+        // Strg+Z läuft über den Befehlsbus und nimmt die letzte Form zurück
+        controller
+            .handle_input(InputEvent::KeyDown(Key::Char('Z'), ctrl))
+            .unwrap();
+        assert_eq!(
+            controller.commands().pop().map(|queued| queued.command),
+            Some(AppCommand::Undo)
+        );
+        assert!(controller.undo().unwrap());
+        assert_eq!(controller.annotation_shapes().len(), 1);
+        assert!(controller.undo().unwrap());
+        assert!(!controller.undo().unwrap());
+        assert!(controller.annotation_shapes().is_empty());
+
+        // Die Auswahl bleibt dabei unverändert
+        assert_eq!(
+            controller.selection_bounds(),
+            Some(PixelRect::new(10, 5, 20, 20))
+        );
+
+        controller
+            .handle_input(InputEvent::KeyDown(Key::Char('Y'), ctrl))
+            .unwrap();
+        assert_eq!(
+            controller.commands().pop().map(|queued| queued.command),
+            Some(AppCommand::Redo)
+        );
+        assert!(controller.redo().unwrap());
+        assert_eq!(controller.annotation_shapes().len(), 1);
+    }
+
+    // Meldet jeden geholten Frame, damit Tests auf die Aufnahme warten statt zu schlafen
+    struct SignallingFrameSource {
+        image: RgbaImage,
+        grabbed: mpsc::Sender<()>,
+    }
+
+    impl FrameSource for SignallingFrameSource {
+        fn grab(&mut self) -> Result<RgbaImage> {
+            let _ = self.grabbed.send(());
+            Ok(self.image.clone())
+        }
+    }
+
+    fn recording_controller() -> (HeadlessBackend, WindowController, mpsc::Receiver<()>) {
+        let (backend, controller) = overlay();
+        drag(&controller, (10.0, 5.0), (30.0, 20.0));
+        let (grabbed, frames) = mpsc::channel();
+        controller
+            .set_recording_source(Arc::new(move || {
+                Ok(Box::new(SignallingFrameSource {
+                    image: desktop(),
+                    grabbed: grabbed.clone(),
+                }) as Box<dyn FrameSource>)
+            }))
+            .unwrap();
+        (backend, controller, frames)
+    }
+
+    #[test]
+    fn recording_runs_in_the_background_until_stopped() {
+        let (backend, controller, frames) = recording_controller();
+        let options = RecordingOptions {
+            max_duration: Duration::from_secs(30),
+            ..RecordingOptions::default()
+        };
+        let (done, finished) = mpsc::channel();
+        assert!(controller
+            .start_recording(&options, move || done.send(()).unwrap())
+            .unwrap());
+        assert!(!backend.is_visible(WindowType::Transparent));
+        assert!(controller.is_recording());
+        assert!(!controller.start_recording(&options, || ()).unwrap());
+
+        // Die Aufnahme sperrt weder den Aufrufer noch die eingefrorene Aufnahme
+        assert_eq!(controller.selection_image().unwrap().dimensions(), (20, 15));
+        assert_eq!(controller.finish_recording().unwrap(), None);
+
+        frames.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(controller.stop_recording().unwrap());
+        finished.recv_timeout(Duration::from_secs(5)).unwrap();
+        let recording = controller.finish_recording().unwrap().unwrap();
+        assert_eq!(recording.dimensions(), Some((20, 15)));
+        assert!(!controller.is_recording());
+        assert!(!controller.stop_recording().unwrap());
+    }
+
+    #[test]
+    fn recording_ends_at_the_maximum_duration() {
+        let (_backend, controller, frames) = recording_controller();
+        let options = RecordingOptions {
+            max_duration: Duration::from_millis(300),
+            ..RecordingOptions::default()
+        };
+        let (done, finished) = mpsc::channel();
+        controller
+            .start_recording(&options, move || done.send(()).unwrap())
+            .unwrap();
+
+        // Ohne stop_recording endet die Aufnahme von selbst
+        finished.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(frames.try_iter().count() >= 1);
+        let recording = controller.finish_recording().unwrap().unwrap();
+        assert_eq!(recording.dimensions(), Some((20, 15)));
+        assert!(recording.duration() <= options.max_duration);
+        assert!(!controller.is_recording());
+    }
+
+    #[test]
+    fn recording_needs_a_source_and_a_selection() {
+        let (_backend, controller) = overlay();
+        let options = RecordingOptions::default();
+        assert!(controller.start_recording(&options, || ()).is_err());
+
+        let (_backend, controller, _) = recording_controller();
+        controller
+            .set_recording_source(Arc::new(|| Err(anyhow!("no screen"))))
+            .unwrap();
+        let (done, finished) = mpsc::channel();
+        controller
+            .start_recording(&options, move || done.send(()).unwrap())
+            .unwrap();
+        finished.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(controller.finish_recording().is_err());
+        assert!(!controller.is_recording());
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::VecDeque;
+
+// Obergrenze für alle gespeicherten Schritte zusammen, in Bytes
+pub const DEFAULT_BUDGET: usize = 16 * 1024 * 1024;
+
+// Eine umkehrbare Änderung an `Target`
+pub trait Edit {
+    type Target;
+
+    fn apply(&self, target: &mut Self::Target);
+    fn revert(&self, target: &mut Self::Target);
+
+    // Nimmt die direkt folgende Änderung derselben Geste auf, z.B. beim Ziehen mit der Maus
+    fn merge(&mut self, _next: &Self) -> bool {
+        false
+    }
+
+    // Z.B. ein Ziehen, das wieder am Ausgangspunkt endet
+    fn is_noop(&self) -> bool {
+        false
+    }
+
+    // Geschätzter Speicherbedarf für das Budget
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct History<E> {
+    done: VecDeque<E>,
+    undone: Vec<E>,
+    budget: usize,
+    used: usize,
+    gesture: bool,
+    // Die erste Änderung einer Geste wird nie mit dem vorherigen Schritt zusammengefasst
+    gesture_started: bool,
+}
+
+impl<E: Edit> Default for History<E> {
+    fn default() -> Self {
+        History::with_budget(DEFAULT_BUDGET)
+    }
+}
+
+impl<E: Edit> History<E> {
+    pub fn new() -> Self {
+        History::default()
+    }
+
+    pub fn with_budget(budget: usize) -> Self {
+        History {
+            done: VecDeque::new(),
+            undone: Vec::new(),
+            budget,
+            used: 0,
+            gesture: false,
+            gesture_started: false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    pub fn undo_steps(&self) -> usize {
+        self.done.len()
+    }
+
+    pub fn redo_steps(&self) -> usize {
+        self.undone.len()
+    }
+
+    pub fn memory_used(&self) -> usize {
+        self.used
+    }
+
+    // Alle Änderungen bis end_gesture werden zu einem Schritt
+    pub fn begin_gesture(&mut self) {
+        self.gesture = true;
+        self.gesture_started = true;
+    }
+
+    pub fn end_gesture(&mut self) {
+        self.gesture = false;
+        self.gesture_started = false;
+    }
+
+    // Führt die Änderung aus und merkt sie sich
+    pub fn execute(&mut self, edit: E, target: &mut E::Target) {
+        edit.apply(target);
+        self.record(edit);
+    }
+
+    // Merkt sich eine Änderung, die bereits ausgeführt wurde
+    pub fn record(&mut self, edit: E) {
+        if edit.is_noop() {
+            return;
+        }
+        self.used -= self.undone.drain(..).map(|edit| edit.size()).sum::<usize>();
+
+        let continues_gesture = self.gesture && !self.gesture_started;
+        self.gesture_started = false;
+
+        match self.done.back_mut() {
+            Some(last) if continues_gesture => {
+                let before = last.size();
+                if last.merge(&edit) {
+                    self.used = self.used - before + last.size();
+                    if last.is_noop() {
+                        self.used -= last.size();
+                        self.done.pop_back();
+                    }
+                } else {
+                    self.used += edit.size();
+                    self.done.push_back(edit);
+                }
+            }
+            _ => {
+                self.used += edit.size();
+                self.done.push_back(edit);
+            }
+        }
+
+        self.trim();
+    }
+
+    // Verwirft die ältesten Schritte, bis das Budget passt; der letzte bleibt immer erhalten
+    fn trim(&mut self) {
+        while self.used > self.budget && self.done.len() > 1 {
+            if let Some(oldest) = self.done.pop_front() {
+                self.used -= oldest.size();
+            }
+        }
+    }
+
+    pub fn undo(&mut self, target: &mut E::Target) -> bool {
+        self.end_gesture();
+        match self.done.pop_back() {
+            Some(edit) => {
+                edit.revert(target);
+                self.undone.push(edit);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self, target: &mut E::Target) -> bool {
+        self.end_gesture();
+        match self.undone.pop() {
+            Some(edit) => {
+                edit.apply(target);
+                self.done.push_back(edit);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+        self.used = 0;
+        self.end_gesture();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Setzt eine Zahl; `size` steht für den Speicherbedarf
+    #[derive(Clone, Debug, PartialEq)]
+    struct Set {
+        before: i32,
+        after: i32,
+        size: usize,
+    }
+
+    fn set(before: i32, after: i32) -> Set {
+        Set {
+            before,
+            after,
+            size: 10,
+        }
+    }
+
+    impl Edit for Set {
+        type Target = i32;
+
+        fn apply(&self, target: &mut i32) {
+            *target = self.after;
+        }
+
+        fn revert(&self, target: &mut i32) {
+            *target = self.before;
+        }
+
+        fn merge(&mut self, next: &Self) -> bool {
+            self.after = next.after;
+            self.size += next.size;
+            true
+        }
+
+        fn is_noop(&self) -> bool {
+            self.before == self.after
+        }
+
+        fn size(&self) -> usize {
+            self.size
+        }
+    }
+
+    // Führt die Änderungen nacheinander aus, jede vom aktuellen Wert ausgehend
+    fn run(history: &mut History<Set>, value: &mut i32, steps: &[i32]) {
+        for &after in steps {
+            history.execute(set(*value, after), value);
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_steps() {
+        let mut history = History::new();
+        let mut value = 0;
+        assert!(!history.can_undo());
+        assert!(!history.undo(&mut value));
+
+        run(&mut history, &mut value, &[1, 2, 3]);
+        assert_eq!(value, 3);
+        assert_eq!(history.undo_steps(), 3);
+
+        assert!(history.undo(&mut value));
+        assert!(history.undo(&mut value));
+        assert_eq!(value, 1);
+        assert_eq!((history.undo_steps(), history.redo_steps()), (1, 2));
+
+        assert!(history.redo(&mut value));
+        assert_eq!(value, 2);
+        assert!(history.redo(&mut value));
+        assert!(!history.redo(&mut value));
+        assert_eq!(value, 3);
+        assert_eq!(history.memory_used(), 30);
+    }
+
+    #[test]
+    fn new_edits_drop_the_redo_steps() {
+        let mut history = History::new();
+        let mut value = 0;
+        run(&mut history, &mut value, &[1, 2, 3]);
+        history.undo(&mut value);
+        history.undo(&mut value);
+        assert!(history.can_redo());
+
+        run(&mut history, &mut value, &[7]);
+        assert!(!history.can_redo());
+        assert!(!history.redo(&mut value));
+        assert_eq!(value, 7);
+        assert_eq!(history.undo_steps(), 2);
+        assert_eq!(history.memory_used(), 20);
+
+        history.undo(&mut value);
+        assert_eq!(value, 1);
+    }
+
+    #[test]
+    fn gestures_coalesce_into_one_step() {
+        let mut history = History::new();
+        let mut value = 0;
+        run(&mut history, &mut value, &[1]);
+
+        // Die erste Änderung der Geste bleibt ein eigener Schritt
+        history.begin_gesture();
+        run(&mut history, &mut value, &[2, 3, 4, 5]);
+        history.end_gesture();
+        assert_eq!(history.undo_steps(), 2);
+        assert_eq!(history.memory_used(), 50);
+
+        // Außerhalb einer Geste wird nichts zusammengefasst
+        run(&mut history, &mut value, &[6, 7]);
+        assert_eq!(history.undo_steps(), 4);
+
+        history.undo(&mut value);
+        history.undo(&mut value);
+        assert_eq!(value, 5);
+        history.undo(&mut value);
+        assert_eq!(value, 1);
+        history.redo(&mut value);
+        assert_eq!(value, 5);
+    }
+
+    #[test]
+    fn gesture_back_to_the_start_leaves_no_step() {
+        let mut history = History::new();
+        let mut value = 0;
+        run(&mut history, &mut value, &[1]);
+
+        history.begin_gesture();
+        run(&mut history, &mut value, &[4, 9, 1]);
+        history.end_gesture();
+        assert_eq!(history.undo_steps(), 1);
+        assert_eq!(history.memory_used(), 10);
+
+        // Leere Änderungen werden gar nicht erst aufgenommen
+        history.record(set(1, 1));
+        assert_eq!(history.undo_steps(), 1);
+    }
+
+    #[test]
+    fn undo_ends_the_gesture() {
+        let mut history = History::new();
+        let mut value = 0;
+        history.begin_gesture();
+        run(&mut history, &mut value, &[1, 2]);
+        history.undo(&mut value);
+        assert_eq!(value, 0);
+
+        run(&mut history, &mut value, &[3, 4]);
+        assert_eq!(history.undo_steps(), 2);
+    }
+
+    #[test]
+    fn budget_evicts_the_oldest_steps() {
+        let mut history = History::with_budget(35);
+        let mut value = 0;
+        run(&mut history, &mut value, &[1, 2, 3]);
+        assert_eq!(history.undo_steps(), 3);
+
+        run(&mut history, &mut value, &[4, 5]);
+        assert_eq!(history.undo_steps(), 3);
+        assert_eq!(history.memory_used(), 30);
+        while history.undo(&mut value) {}
+        assert_eq!(value, 2);
+
+        // Der letzte Schritt bleibt, auch wenn er allein das Budget sprengt
+        let mut history = History::with_budget(5);
+        history.execute(set(value, 9), &mut value);
+        assert_eq!(history.undo_steps(), 1);
+        history.execute(
+            Set {
+                before: value,
+                after: 10,
+                size: 100,
+            },
+            &mut value,
+        );
+        assert_eq!(history.undo_steps(), 1);
+        assert_eq!(history.memory_used(), 100);
+        history.undo(&mut value);
+        assert_eq!(value, 9);
+    }
+
+    #[test]
+    fn merged_gestures_count_against_the_budget() {
+        let mut history = History::with_budget(45);
+        let mut value = 0;
+        run(&mut history, &mut value, &[1, 2]);
+
+        history.begin_gesture();
+        run(&mut history, &mut value, &[3, 4, 5]);
+        history.end_gesture();
+        // 10 + 10 + 30 übersteigt das Budget, der älteste Schritt fällt weg
+        assert_eq!(history.undo_steps(), 2);
+        assert_eq!(history.memory_used(), 40);
+    }
+
+    #[test]
+    fn clear_forgets_everything() {
+        let mut history = History::new();
+        let mut value = 0;
+        run(&mut history, &mut value, &[1, 2]);
+        history.undo(&mut value);
+        history.clear();
+        assert!(!history.can_undo() && !history.can_redo());
+        assert_eq!(history.memory_used(), 0);
+    }
+}
@@ -0,0 +1,900 @@
+use crate::modules::backend::PixelRect;
+use crate::modules::errorhandler::{ErrorContext, SnipError};
+use crate::modules::export::{self, ExportFormat};
+use crate::modules::naming::CaptureInfo;
+use crate::modules::ocr::OcrWord;
+use crate::modules::search::{SearchHit, SearchIndex};
+use chrono::{DateTime, Local};
+use image::RgbaImage;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Bibliothek aller Aufnahmen aus dem Overlay. Bilder und Vorschaubilder liegen als PNG in
+// Unterordnern, die Metadaten in einer Indexdatei mit einem Eintrag pro Zeile.
+// Der Index wird bei jeder Änderung vollständig neu geschrieben und dann umbenannt,
+// damit ein Absturz nie einen halb geschriebenen Index hinterlässt.
+// Mehrere Prozesse teilen sich die Bibliothek: Jede Änderung sperrt die Sperrdatei, liest den
+// Index neu ein und vergibt erst dann die nächste ID.
+// Der Volltextindex über den OCR-Text wird beim Einlesen im Speicher aufgebaut.
+
+const INDEX_FILE: &str = "library.idx";
+const LOCK_FILE: &str = "library.lock";
+const INDEX_HEADER: &str = "snipping_tool library 2";
+// Version 1 kannte noch keine Wortboxen
+const INDEX_HEADER_V1: &str = "snipping_tool library 1";
+const IMAGE_DIR: &str = "images";
+const THUMBNAIL_DIR: &str = "thumbnails";
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LibraryEntry {
+    pub id: u64,
+    pub timestamp: DateTime<Local>,
+    // Auswahl in Koordinaten des virtuellen Bildschirms
+    pub region: PixelRect,
+    pub monitor: Option<usize>,
+    pub window_title: Option<String>,
+    pub text: Option<String>,
+    // Erkannte Wörter in Bildkoordinaten, für die Trefferanzeige der Volltextsuche
+    pub words: Vec<OcrWord>,
+    pub tags: Vec<String>,
+    // Relativ zum Ordner der Bibliothek
+    pub image: PathBuf,
+    pub thumbnail: PathBuf,
+}
+
+impl LibraryEntry {
+    // Jeder Suchbegriff muss im Fenstertitel, im Text oder in einem Tag vorkommen;
+    // `tag:name` verlangt genau dieses Tag. Groß- und Kleinschreibung zählt nicht.
+    pub fn matches(&self, query: &str) -> bool {
+        query.split_whitespace().all(|term| {
+            let term = term.to_lowercase();
+            if let Some(tag) = term.strip_prefix("tag:") {
+                return self.tags.iter().any(|t| t.to_lowercase() == tag);
+            }
+            self.tags.iter().any(|t| t.to_lowercase().contains(&term))
+                || [&self.window_title, &self.text].iter().any(
+                    |field| matches!(field, Some(value) if value.to_lowercase().contains(&term)),
+                )
+        })
+    }
+}
+
+// Ein Suchergebnis; ohne Treffer im OCR-Text sind Markierungen und Bewertung leer
+#[derive(Clone, Debug, PartialEq)]
+pub struct LibraryHit<'a> {
+    pub entry: &'a LibraryEntry,
+    pub score: f32,
+    // Byte-Bereiche in entry.text
+    pub highlights: Vec<std::ops::Range<usize>>,
+    // Passende Wörter in Bildkoordinaten
+    pub regions: Vec<PixelRect>,
+}
+
+pub struct Library {
+    root: PathBuf,
+    entries: Vec<LibraryEntry>,
+    next_id: u64,
+    index: SearchIndex,
+}
+
+// Standardordner: %LOCALAPPDATA% unter Windows, sonst $XDG_DATA_HOME bzw. ~/.local/share
+pub fn default_directory() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+    };
+    base.map(|base| base.join("snipping_tool").join("library"))
+}
+
+// Tabs, Umbrüche, Backslashes und das jeweilige Trennzeichen werden maskiert
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            ',' => escaped.push_str("\\,"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('t') => unescaped.push('\t'),
+                Some('n') => unescaped.push('\n'),
+                Some('r') => unescaped.push('\r'),
+                Some(other) => unescaped.push(other),
+                None => unescaped.push('\\'),
+            },
+            c => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+// Trennt an nicht maskierten Trennzeichen; die Maskierung bleibt erhalten
+fn split_escaped(value: &str, separator: char) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (index, c) in value.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == separator {
+            fields.push(&value[start..index]);
+            start = index + c.len_utf8();
+        }
+    }
+    fields.push(&value[start..]);
+    fields
+}
+
+fn optional(value: &Option<String>) -> String {
+    value.as_deref().map(escape).unwrap_or_default()
+}
+
+// "x y w h Text" pro Wort, durch Kommas getrennt
+fn format_words(words: &[OcrWord]) -> String {
+    let words: Vec<String> = words
+        .iter()
+        .map(|word| {
+            let bounds = word.bounds;
+            let text = escape(&word.text);
+            format!(
+                "{} {} {} {} {}",
+                bounds.x, bounds.y, bounds.width, bounds.height, text
+            )
+        })
+        .collect();
+    words.join(",")
+}
+
+fn parse_words(value: &str) -> Option<Vec<OcrWord>> {
+    if value.is_empty() {
+        return Some(Vec::new());
+    }
+    split_escaped(value, ',')
+        .into_iter()
+        .map(|word| {
+            let mut fields = word.splitn(5, ' ');
+            let mut number = || fields.next()?.parse::<i64>().ok();
+            let (x, y) = (number()?, number()?);
+            let (width, height) = (number()?, number()?);
+            let bounds = PixelRect::new(
+                x.try_into().ok()?,
+                y.try_into().ok()?,
+                width.try_into().ok()?,
+                height.try_into().ok()?,
+            );
+            Some(OcrWord::new(unescape(fields.next()?), bounds))
+        })
+        .collect()
+}
+
+fn format_entry(entry: &LibraryEntry) -> String {
+    let tags: Vec<String> = entry.tags.iter().map(|tag| escape(tag)).collect();
+    [
+        entry.id.to_string(),
+        entry.timestamp.to_rfc3339(),
+        entry.region.to_string(),
+        entry.monitor.map(|m| m.to_string()).unwrap_or_default(),
+        optional(&entry.window_title),
+        tags.join(","),
+        escape(&entry.image.to_string_lossy()),
+        escape(&entry.thumbnail.to_string_lossy()),
+        optional(&entry.text),
+        format_words(&entry.words),
+    ]
+    .join("\t")
+}
+
+fn parse_entry(line: &str) -> Option<LibraryEntry> {
+    let mut fields = split_escaped(line, '\t');
+    if fields.len() == 9 {
+        // Eintrag aus Version 1
+        fields.push("");
+    }
+    let [id, timestamp, region, monitor, title, tags, image, thumbnail, text, words] =
+        <[&str; 10]>::try_from(fields).ok()?;
+    let non_empty = |value: &str| (!value.is_empty()).then(|| unescape(value));
+    let tags = match tags {
+        "" => Vec::new(),
+        tags => split_escaped(tags, ',').into_iter().map(unescape).collect(),
+    };
+
+    Some(LibraryEntry {
+        id: id.parse().ok()?,
+        timestamp: DateTime::parse_from_rfc3339(timestamp)
+            .ok()?
+            .with_timezone(&Local),
+        region: region.parse().ok()?,
+        monitor: match monitor {
+            "" => None,
+            monitor => Some(monitor.parse().ok()?),
+        },
+        window_title: non_empty(title),
+        text: non_empty(text),
+        words: parse_words(words)?,
+        tags,
+        image: PathBuf::from(unescape(image)),
+        thumbnail: PathBuf::from(unescape(thumbnail)),
+    })
+}
+
+// Höchstens THUMBNAIL_SIZE an der längeren Seite, nie vergrößert
+fn thumbnail_size(width: u32, height: u32) -> (u32, u32) {
+    let scale = (THUMBNAIL_SIZE as f32 / width.max(height).max(1) as f32).min(1.0);
+    let scaled = |side: u32| ((side as f32 * scale).round() as u32).max(1);
+    (scaled(width), scaled(height))
+}
+
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.iter().map(|tag| tag.trim()) {
+        if !tag.is_empty() && !normalized.iter().any(|t| t == tag) {
+            normalized.push(tag.to_string());
+        }
+    }
+    normalized
+}
+
+fn index_entry(index: &mut SearchIndex, entry: &LibraryEntry) {
+    if let Some(text) = &entry.text {
+        index.insert(entry.id, text, &entry.words);
+    }
+}
+
+// Ohne Indexdatei ist die Bibliothek leer
+fn read_index(root: &Path) -> Result<Vec<LibraryEntry>, SnipError> {
+    let index = root.join(INDEX_FILE);
+    let mut entries = Vec::new();
+    if !index.exists() {
+        return Ok(entries);
+    }
+
+    let content =
+        fs::read_to_string(&index).context(format!("Failed to read {}", index.display()))?;
+    let mut lines = content.lines();
+    if !matches!(lines.next(), Some(INDEX_HEADER | INDEX_HEADER_V1)) {
+        return Err(SnipError::Config(format!(
+            "{} is not a capture library index",
+            index.display()
+        )));
+    }
+    for (number, line) in lines.enumerate().filter(|(_, line)| !line.is_empty()) {
+        // Lieber abbrechen als beim nächsten Schreiben Einträge zu verlieren
+        let entry = parse_entry(line).ok_or_else(|| {
+            SnipError::Config(format!(
+                "Corrupt entry in {} at line {}",
+                index.display(),
+                number + 2
+            ))
+        })?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+impl Library {
+    // Legt den Ordner bei Bedarf an und liest den vorhandenen Index
+    pub fn open<P: AsRef<Path>>(root: P) -> Result<Library, SnipError> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(root.join(IMAGE_DIR))
+            .and_then(|_| fs::create_dir_all(root.join(THUMBNAIL_DIR)))
+            .context(format!("Failed to create library in {}", root.display()))?;
+
+        let mut library = Library {
+            root,
+            entries: Vec::new(),
+            next_id: 1,
+            index: SearchIndex::new(),
+        };
+        library.reload()?;
+        Ok(library)
+    }
+
+    // Übernimmt, was andere Prozesse inzwischen geschrieben haben; IDs laufen nie rückwärts
+    fn reload(&mut self) -> Result<(), SnipError> {
+        let entries = read_index(&self.root)?;
+        let next_id = entries.iter().map(|entry| entry.id + 1).max().unwrap_or(1);
+        let mut index = SearchIndex::new();
+        for entry in &entries {
+            index_entry(&mut index, entry);
+        }
+        self.next_id = self.next_id.max(next_id);
+        self.entries = entries;
+        self.index = index;
+        Ok(())
+    }
+
+    // Hält die Sperre, bis die Datei geschlossen wird
+    fn lock(&self) -> Result<File, SnipError> {
+        let path = self.root.join(LOCK_FILE);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.lock().map(|_| file))
+            .context(format!("Failed to lock {}", path.display()))?;
+        Ok(file)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // Älteste zuerst, in der Reihenfolge der Aufnahme
+    pub fn entries(&self) -> &[LibraryEntry] {
+        &self.entries
+    }
+
+    // Neueste zuerst
+    pub fn list(&self) -> Vec<&LibraryEntry> {
+        self.entries.iter().rev().collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<&LibraryEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    // `tag:name` filtert, der Rest wird im OCR-Text gesucht. Treffer im Text kommen nach
+    // Relevanz zuerst, danach die neuesten Aufnahmen, bei denen nur Fenstertitel oder Tags passen.
+    // Eine leere Suche liefert alles, neueste zuerst.
+    pub fn search(&self, query: &str) -> Vec<LibraryHit<'_>> {
+        let (filters, terms): (Vec<&str>, Vec<&str>) = query
+            .split_whitespace()
+            .partition(|term| term.to_lowercase().starts_with("tag:"));
+        let filter = filters.join(" ");
+        let text = terms.join(" ");
+        let candidates = || {
+            self.entries
+                .iter()
+                .rev()
+                .filter(|entry| entry.matches(&filter))
+        };
+
+        let mut hits: Vec<LibraryHit> = Vec::new();
+        if !text.is_empty() {
+            for SearchHit {
+                id,
+                score,
+                highlights,
+                regions,
+            } in self.index.search(&text)
+            {
+                if let Some(entry) = self.get(id).filter(|entry| entry.matches(&filter)) {
+                    hits.push(LibraryHit {
+                        entry,
+                        score,
+                        highlights,
+                        regions,
+                    });
+                }
+            }
+        }
+
+        for entry in candidates().filter(|entry| entry.matches(&text)) {
+            if !hits.iter().any(|hit| hit.entry.id == entry.id) {
+                hits.push(LibraryHit {
+                    entry,
+                    score: 0.0,
+                    highlights: Vec::new(),
+                    regions: Vec::new(),
+                });
+            }
+        }
+        hits
+    }
+
+    pub fn path_of(&self, relative: &Path) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    // Speichert Bild und Vorschaubild und nimmt die Aufnahme in den Index auf.
+    // Die Wörter stammen aus der OCR des Bildes und dürfen fehlen.
+    pub fn add(
+        &mut self,
+        image: &RgbaImage,
+        region: PixelRect,
+        info: &CaptureInfo,
+        words: &[OcrWord],
+        tags: &[String],
+    ) -> Result<&LibraryEntry, SnipError> {
+        let _lock = self.lock()?;
+        self.reload()?;
+
+        let id = self.next_id;
+        let name = format!("{:06}_{}.png", id, info.timestamp.format("%Y%m%d-%H%M%S"));
+        let entry = LibraryEntry {
+            id,
+            timestamp: info.timestamp,
+            region,
+            monitor: info.monitor,
+            window_title: info.window_title.clone(),
+            text: info.ocr_text.clone().filter(|text| !text.trim().is_empty()),
+            words: words.to_vec(),
+            tags: normalize_tags(tags),
+            image: Path::new(IMAGE_DIR).join(&name),
+            thumbnail: Path::new(THUMBNAIL_DIR).join(&name),
+        };
+
+        let (width, height) = thumbnail_size(image.width(), image.height());
+        let thumbnail = image::imageops::thumbnail(image, width, height);
+        for (path, image) in [(&entry.image, image), (&entry.thumbnail, &thumbnail)] {
+            let path = self.root.join(path);
+            let written = export::encode(image, ExportFormat::Png).and_then(|data| {
+                fs::write(&path, data).context(format!("Failed to write {}", path.display()))
+            });
+            // Ohne Vorschaubild bliebe sonst das Bild ohne Eintrag liegen
+            if let Err(e) = written {
+                self.remove_files(&entry);
+                return Err(e);
+            }
+        }
+
+        self.entries.push(entry);
+        self.next_id = id + 1;
+        if let Err(e) = self.write_index() {
+            let entry = self.entries.pop().unwrap();
+            self.remove_files(&entry);
+            return Err(e);
+        }
+        let entry = self.entries.last().unwrap();
+        index_entry(&mut self.index, entry);
+        Ok(entry)
+    }
+
+    // Lädt das gespeicherte Bild wieder, z.B. um es erneut zu kopieren oder zu bearbeiten
+    pub fn reopen(&self, id: u64) -> Result<RgbaImage, SnipError> {
+        let entry = self.require(id)?;
+        let path = self.root.join(&entry.image);
+        Ok(image::open(&path)
+            .context(format!("Failed to open {}", path.display()))?
+            .to_rgba8())
+    }
+
+    pub fn set_tags(&mut self, id: u64, tags: &[String]) -> Result<(), SnipError> {
+        let _lock = self.lock()?;
+        self.reload()?;
+
+        let index = self.position(id)?;
+        let previous = std::mem::replace(&mut self.entries[index].tags, normalize_tags(tags));
+        if let Err(e) = self.write_index() {
+            self.entries[index].tags = previous;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // Entfernt den Eintrag und seine Dateien; gibt den entfernten Eintrag zurück
+    pub fn delete(&mut self, id: u64) -> Result<LibraryEntry, SnipError> {
+        let _lock = self.lock()?;
+        self.reload()?;
+
+        let index = self.position(id)?;
+        let entry = self.entries.remove(index);
+        if let Err(e) = self.write_index() {
+            self.entries.insert(index, entry);
+            return Err(e);
+        }
+        self.index.remove(id);
+        // Erst nach dem Index, sonst zeigt ein Eintrag auf fehlende Dateien
+        self.remove_files(&entry);
+        Ok(entry)
+    }
+
+    fn remove_files(&self, entry: &LibraryEntry) {
+        let _ = fs::remove_file(self.root.join(&entry.image));
+        let _ = fs::remove_file(self.root.join(&entry.thumbnail));
+    }
+
+    fn position(&self, id: u64) -> Result<usize, SnipError> {
+        self.entries
+            .iter()
+            .position(|entry| entry.id == id)
+            .ok_or_else(|| SnipError::Config(format!("No capture with id {} in the library", id)))
+    }
+
+    fn require(&self, id: u64) -> Result<&LibraryEntry, SnipError> {
+        self.position(id).map(|index| &self.entries[index])
+    }
+
+    // Nur unter der Sperre aufrufen
+    fn write_index(&self) -> Result<(), SnipError> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let mut content = String::from(INDEX_HEADER);
+        content.push('\n');
+        for entry in &self.entries {
+            content.push_str(&format_entry(entry));
+            content.push('\n');
+        }
+
+        // Eigener Name pro Prozess und Aufruf, falls doch jemand ohne Sperre schreibt
+        let index = self.root.join(INDEX_FILE);
+        let temporary = self.root.join(format!(
+            "{}.{}_{}.tmp",
+            INDEX_FILE,
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let result = fs::write(&temporary, content).and_then(|_| fs::rename(&temporary, &index));
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        result.context(format!("Failed to write {}", index.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::testing::TempDir;
+    use image::Rgba;
+
+    fn capture(color: u8) -> RgbaImage {
+        RgbaImage::from_pixel(640, 320, Rgba([color, 0, 0, 255]))
+    }
+
+    fn info(title: &str, text: &str) -> CaptureInfo {
+        CaptureInfo {
+            monitor: Some(1),
+            window_title: Some(title.to_string()),
+            ocr_text: Some(text.to_string()),
+            ..CaptureInfo::new(640, 320)
+        }
+    }
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|tag| tag.to_string()).collect()
+    }
+
+    #[test]
+    fn entries_survive_reopening_the_library() {
+        let dir = TempDir::new("library");
+        let region = PixelRect::new(-1920, 40, 640, 320);
+        let first_info = info("Rechnung\t2024 – Editor", "Summe: 42,00 €\nDanke");
+        let words = [
+            OcrWord::new("Summe:", PixelRect::new(0, 0, 50, 12)),
+            OcrWord::new("42,00 €", PixelRect::new(54, 0, 60, 12)),
+            OcrWord::new("Danke", PixelRect::new(0, 16, 44, 12)),
+        ];
+        {
+            let mut library = Library::open(&dir.0).unwrap();
+            library
+                .add(
+                    &capture(10),
+                    region,
+                    &first_info,
+                    &words,
+                    &tags(&["work", "a,b", "work", " "]),
+                )
+                .unwrap();
+            library
+                .add(&capture(20), region, &CaptureInfo::new(640, 320), &[], &[])
+                .unwrap();
+        }
+
+        let library = Library::open(&dir.0).unwrap();
+        let entries = library.entries();
+        assert_eq!(entries.len(), 2);
+        let first = &entries[0];
+        assert_eq!(first.id, 1);
+        assert_eq!(first.region, region);
+        assert_eq!(first.monitor, Some(1));
+        assert_eq!(
+            first.window_title.as_deref(),
+            Some("Rechnung\t2024 – Editor")
+        );
+        assert_eq!(first.text.as_deref(), Some("Summe: 42,00 €\nDanke"));
+        assert_eq!(first.words, words);
+        assert_eq!(first.tags, tags(&["work", "a,b"]));
+        assert_eq!(
+            first.timestamp.timestamp(),
+            first_info.timestamp.timestamp()
+        );
+        assert_eq!(entries[1].window_title, None);
+        assert!(entries[1].tags.is_empty());
+
+        let thumbnail = image::open(library.path_of(&first.thumbnail))
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(thumbnail.dimensions(), (256, 128));
+        assert_eq!(library.reopen(1).unwrap(), capture(10));
+        assert_eq!(library.list()[0].id, 2);
+    }
+
+    #[test]
+    fn search_matches_text_title_and_tags() {
+        let dir = TempDir::new("library");
+        let mut library = Library::open(&dir.0).unwrap();
+        let region = PixelRect::new(0, 0, 640, 320);
+        library
+            .add(
+                &capture(1),
+                region,
+                &info("Outlook", "Meeting um 10 Uhr"),
+                &[],
+                &tags(&["Termine"]),
+            )
+            .unwrap();
+        library
+            .add(
+                &capture(2),
+                region,
+                &info("Firefox", "Error 404"),
+                &[],
+                &tags(&["bug"]),
+            )
+            .unwrap();
+
+        let ids = |query: &str| -> Vec<u64> {
+            library
+                .search(query)
+                .iter()
+                .map(|hit| hit.entry.id)
+                .collect()
+        };
+        assert_eq!(ids("meeting"), [1]);
+        assert_eq!(ids("firefox 404"), [2]);
+        assert_eq!(ids("tag:termine"), [1]);
+        assert_eq!(ids("tag:term"), Vec::<u64>::new());
+        assert_eq!(ids(""), [2, 1]);
+    }
+
+    #[test]
+    fn full_text_hits_are_ranked_and_highlighted() {
+        let dir = TempDir::new("library");
+        let mut library = Library::open(&dir.0).unwrap();
+        let region = PixelRect::new(0, 0, 640, 320);
+        let words = [
+            OcrWord::new("Rechnung", PixelRect::new(10, 10, 80, 14)),
+            OcrWord::new("Müller", PixelRect::new(94, 10, 50, 14)),
+        ];
+        library
+            .add(
+                &capture(1),
+                region,
+                &info("Editor", "Rechnung Müller"),
+                &words,
+                &tags(&["buchhaltung"]),
+            )
+            .unwrap();
+        library
+            .add(
+                &capture(2),
+                region,
+                &info("Rechnungen – Explorer", "Ordner"),
+                &[],
+                &[],
+            )
+            .unwrap();
+        library
+            .add(
+                &capture(3),
+                region,
+                &info("Mail", "Rechnung, Rechnung, Mahnung"),
+                &[],
+                &[],
+            )
+            .unwrap();
+
+        // Treffer im Text nach Relevanz, dann nur der Fenstertitel
+        let hits = library.search("rechnung");
+        let ids: Vec<u64> = hits.iter().map(|hit| hit.entry.id).collect();
+        assert_eq!(ids, [3, 1, 2]);
+        assert!(hits[2].highlights.is_empty());
+
+        let hits = library.search("muell tag:buchhaltung");
+        assert_eq!(hits.len(), 1);
+        let text = hits[0].entry.text.as_deref().unwrap();
+        assert_eq!(&text[hits[0].highlights[0].clone()], "Müller");
+        assert_eq!(hits[0].regions, [words[1].bounds]);
+
+        // Nach dem Löschen und erneuten Öffnen stimmt der Index noch
+        library.delete(3).unwrap();
+        let library = Library::open(&dir.0).unwrap();
+        let ids: Vec<u64> = library
+            .search("rechnung")
+            .iter()
+            .map(|hit| hit.entry.id)
+            .collect();
+        assert_eq!(ids, [1, 2]);
+    }
+
+    #[test]
+    fn version_one_index_is_still_read() {
+        let dir = TempDir::new("library");
+        Library::open(&dir.0).unwrap();
+        fs::write(
+            dir.0.join(INDEX_FILE),
+            format!(
+                "{}\n4\t2024-05-01T10:00:00+02:00\t0,0,10,10\t\tEditor\t\timages/a.png\tthumbnails/a.png\tAlter Text\n",
+                INDEX_HEADER_V1
+            ),
+        )
+        .unwrap();
+
+        let library = Library::open(&dir.0).unwrap();
+        assert!(library.get(4).unwrap().words.is_empty());
+        assert_eq!(library.search("alter")[0].entry.id, 4);
+    }
+
+    #[test]
+    fn delete_removes_entry_and_files() {
+        let dir = TempDir::new("library");
+        let mut library = Library::open(&dir.0).unwrap();
+        let region = PixelRect::new(0, 0, 640, 320);
+        let image = library
+            .add(&capture(1), region, &CaptureInfo::new(640, 320), &[], &[])
+            .unwrap()
+            .image
+            .clone();
+        library
+            .add(&capture(2), region, &CaptureInfo::new(640, 320), &[], &[])
+            .unwrap();
+
+        let deleted = library.delete(1).unwrap();
+        assert!(!library.path_of(&deleted.image).exists());
+        assert!(!library.path_of(&deleted.thumbnail).exists());
+        assert_eq!(deleted.image, image);
+        assert!(library.delete(1).is_err());
+        assert!(library.reopen(1).is_err());
+
+        // IDs werden nicht wiederverwendet
+        let library = Library::open(&dir.0).unwrap();
+        assert_eq!(library.entries().len(), 1);
+        let mut library = library;
+        let id = library
+            .add(&capture(3), region, &CaptureInfo::new(640, 320), &[], &[])
+            .unwrap()
+            .id;
+        assert_eq!(id, 3);
+    }
+
+    #[test]
+    fn failed_thumbnail_leaves_no_image_behind() {
+        let dir = TempDir::new("library");
+        let mut library = Library::open(&dir.0).unwrap();
+        // Eine Datei statt des Ordners lässt das Schreiben des Vorschaubilds scheitern
+        fs::remove_dir(dir.join(THUMBNAIL_DIR)).unwrap();
+        fs::write(dir.join(THUMBNAIL_DIR), b"").unwrap();
+
+        let region = PixelRect::new(0, 0, 640, 320);
+        assert!(library
+            .add(&capture(1), region, &CaptureInfo::new(640, 320), &[], &[])
+            .is_err());
+        assert!(library.entries().is_empty());
+        assert_eq!(fs::read_dir(dir.join(IMAGE_DIR)).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn tags_can_be_changed() {
+        let dir = TempDir::new("library");
+        let mut library = Library::open(&dir.0).unwrap();
+        library
+            .add(
+                &capture(1),
+                PixelRect::new(0, 0, 640, 320),
+                &CaptureInfo::new(640, 320),
+                &[],
+                &[],
+            )
+            .unwrap();
+        library.set_tags(1, &tags(&["archiv", "2024"])).unwrap();
+
+        let library = Library::open(&dir.0).unwrap();
+        assert_eq!(library.get(1).unwrap().tags, tags(&["archiv", "2024"]));
+    }
+
+    #[test]
+    fn changes_of_other_instances_are_kept() {
+        let dir = TempDir::new("library");
+        let region = PixelRect::new(0, 0, 4, 4);
+        let small = RgbaImage::new(4, 4);
+        let mut first = Library::open(&dir.0).unwrap();
+        let mut second = Library::open(&dir.0).unwrap();
+
+        // Beide wurden vor der ersten Aufnahme geöffnet und bekommen trotzdem eigene IDs
+        let a = first
+            .add(&small, region, &info("A", "eins"), &[], &[])
+            .unwrap()
+            .id;
+        let b = second
+            .add(&small, region, &info("B", "zwei"), &[], &[])
+            .unwrap()
+            .id;
+        assert_eq!((a, b), (1, 2));
+        assert_eq!(second.entries().len(), 2);
+        assert_eq!(second.search("eins")[0].entry.id, 1);
+
+        first.set_tags(2, &tags(&["fremd"])).unwrap();
+        second.delete(1).unwrap();
+        let c = first
+            .add(&small, region, &CaptureInfo::new(4, 4), &[], &[])
+            .unwrap()
+            .id;
+        assert_eq!(c, 3);
+
+        let library = Library::open(&dir.0).unwrap();
+        let ids: Vec<u64> = library.entries().iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, [2, 3]);
+        assert_eq!(library.get(2).unwrap().tags, tags(&["fremd"]));
+        assert!(first.set_tags(1, &[]).is_err());
+    }
+
+    #[test]
+    fn concurrent_writers_do_not_lose_entries() {
+        let dir = TempDir::new("library");
+        Library::open(&dir.0).unwrap();
+
+        let writers: Vec<_> = (0..4)
+            .map(|writer| {
+                let root = dir.0.clone();
+                std::thread::spawn(move || {
+                    let mut library = Library::open(&root).unwrap();
+                    for _ in 0..5 {
+                        let info = info(&format!("writer {}", writer), "");
+                        library
+                            .add(
+                                &RgbaImage::new(4, 4),
+                                PixelRect::new(0, 0, 4, 4),
+                                &info,
+                                &[],
+                                &[],
+                            )
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let library = Library::open(&dir.0).unwrap();
+        let mut ids: Vec<u64> = library.entries().iter().map(|entry| entry.id).collect();
+        ids.sort();
+        assert_eq!(ids, (1..=20).collect::<Vec<u64>>());
+        for entry in library.entries() {
+            assert!(library.path_of(&entry.image).exists());
+        }
+        let leftovers = fs::read_dir(&dir.0)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn corrupt_index_is_reported() {
+        let dir = TempDir::new("library");
+        Library::open(&dir.0).unwrap();
+        fs::write(
+            dir.0.join(INDEX_FILE),
+            format!("{}\nnot an entry\n", INDEX_HEADER),
+        )
+        .unwrap();
+        assert!(Library::open(&dir.0).is_err());
+
+        fs::write(dir.0.join(INDEX_FILE), "something else\n").unwrap();
+        assert!(Library::open(&dir.0).is_err());
+    }
+}
@@ -1,8 +1,43 @@
+pub mod annotation;
+pub mod backend;
+pub mod barcode;
+pub mod capture;
+pub mod cli;
+#[cfg(windows)]
+pub mod clipboard;
 pub mod commands;
 pub mod controller;
+#[cfg(windows)]
 pub mod drawing;
+#[cfg(windows)]
+pub mod dxgi_source;
 pub mod errorhandler;
+pub mod export;
+pub mod font;
+#[cfg(windows)]
 pub mod handler;
+pub mod headless;
+pub mod history;
+pub mod library;
+pub mod naming;
+pub mod ocr;
+pub mod pdf;
+pub mod pii;
+pub mod qr;
+pub mod raster;
+pub mod recording;
+pub mod redact;
+#[cfg(windows)]
 pub mod renderer;
+#[cfg(windows)]
 pub mod resource_manager;
+pub mod search;
+pub mod selection;
+pub mod stitch;
+pub mod table;
+#[cfg(test)]
+pub mod testing;
+pub mod text;
+pub mod webp;
+#[cfg(windows)]
 pub mod win_fact;
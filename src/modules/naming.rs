@@ -0,0 +1,471 @@
+use crate::modules::errorhandler::SnipError;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use std::fs::{self, OpenOptions};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+pub const DEFAULT_TEMPLATE: &str = "{date:%Y-%m-%d}_{time}_{w}x{h}_{counter}.png";
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+const DEFAULT_TIME_FORMAT: &str = "%H-%M-%S";
+// Fenstertitel und OCR-Text werden gekürzt, damit der Dateiname handlich bleibt
+const MAX_TEXT_LENGTH: usize = 48;
+const MAX_COUNTER: u32 = 99_999;
+
+// Standardordner für Aufnahmen aus dem Overlay: Bilder/Screenshots im Benutzerordner
+pub fn default_directory() -> Option<PathBuf> {
+    let home = if cfg!(windows) {
+        std::env::var_os("USERPROFILE")
+    } else {
+        std::env::var_os("HOME")
+    };
+    home.map(|home| PathBuf::from(home).join("Pictures").join("Screenshots"))
+}
+
+// Alles, was ein Dateiname über eine Aufnahme aussagen kann
+#[derive(Clone, Debug)]
+pub struct CaptureInfo {
+    pub timestamp: DateTime<Local>,
+    pub width: u32,
+    pub height: u32,
+    pub monitor: Option<usize>,
+    pub window_title: Option<String>,
+    pub ocr_text: Option<String>,
+}
+
+impl CaptureInfo {
+    pub fn new(width: u32, height: u32) -> Self {
+        CaptureInfo {
+            timestamp: Local::now(),
+            width,
+            height,
+            monitor: None,
+            window_title: None,
+            ocr_text: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Date(String),
+    Time(String),
+    Width,
+    Height,
+    Monitor,
+    Title,
+    Ocr,
+    Counter(usize),
+}
+
+// Vorlage wie `{date:%Y-%m-%d}_{time}_{w}x{h}_{counter}.png`; `{{` und `}}` stehen für Klammern
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilenameTemplate {
+    segments: Vec<Segment>,
+}
+
+fn check_format(format: &str) -> Result<String, SnipError> {
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(SnipError::Config(format!(
+            "Invalid date/time format '{}'",
+            format
+        )));
+    }
+    Ok(format.to_string())
+}
+
+fn parse_token(token: &str) -> Result<Segment, SnipError> {
+    let (name, argument) = match token.split_once(':') {
+        Some((name, argument)) => (name, Some(argument)),
+        None => (token, None),
+    };
+
+    match (name, argument) {
+        ("date", format) => Ok(Segment::Date(check_format(
+            format.unwrap_or(DEFAULT_DATE_FORMAT),
+        )?)),
+        ("time", format) => Ok(Segment::Time(check_format(
+            format.unwrap_or(DEFAULT_TIME_FORMAT),
+        )?)),
+        ("w", None) => Ok(Segment::Width),
+        ("h", None) => Ok(Segment::Height),
+        ("monitor", None) => Ok(Segment::Monitor),
+        ("title", None) => Ok(Segment::Title),
+        ("ocr", None) => Ok(Segment::Ocr),
+        ("counter", digits) => {
+            let digits = match digits {
+                Some(digits) => digits.parse().map_err(|_| {
+                    SnipError::Config(format!("Invalid counter width '{}'", digits))
+                })?,
+                None => 3,
+            };
+            Ok(Segment::Counter(digits))
+        }
+        _ => Err(SnipError::Config(format!(
+            "Unknown template token '{{{}}}'",
+            token
+        ))),
+    }
+}
+
+impl FromStr for FilenameTemplate {
+    type Err = SnipError;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut token = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => token.push(c),
+                            None => {
+                                return Err(SnipError::Config(format!(
+                                    "Unclosed token in template '{}'",
+                                    template
+                                )))
+                            }
+                        }
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(parse_token(&token)?);
+                }
+                '}' => {
+                    return Err(SnipError::Config(format!(
+                        "Unmatched '}}' in template '{}'",
+                        template
+                    )))
+                }
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        if segments.is_empty() {
+            return Err(SnipError::Config("Empty filename template".to_string()));
+        }
+
+        Ok(FilenameTemplate { segments })
+    }
+}
+
+impl Default for FilenameTemplate {
+    fn default() -> Self {
+        DEFAULT_TEMPLATE
+            .parse()
+            .expect("Default template must be valid")
+    }
+}
+
+// Ersetzt Zeichen, die Windows in Dateinamen nicht erlaubt
+fn sanitize(value: &str, max_length: usize) -> String {
+    let cleaned: String = value
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .take(max_length)
+        .collect();
+    cleaned.trim().trim_end_matches('.').to_string()
+}
+
+impl FilenameTemplate {
+    pub fn has_counter(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Counter(_)))
+    }
+
+    // Nur dann muss vor dem Speichern Text erkannt werden
+    pub fn has_ocr(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Ocr))
+    }
+
+    pub fn render(&self, info: &CaptureInfo, counter: u32) -> String {
+        let mut name = String::new();
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(text) => name.push_str(text),
+                Segment::Date(format) | Segment::Time(format) => {
+                    name.push_str(&sanitize(
+                        &info.timestamp.format(format).to_string(),
+                        usize::MAX,
+                    ));
+                }
+                Segment::Width => name.push_str(&info.width.to_string()),
+                Segment::Height => name.push_str(&info.height.to_string()),
+                Segment::Monitor => {
+                    name.push_str(&info.monitor.map_or(String::new(), |m| m.to_string()))
+                }
+                Segment::Title => name.push_str(&sanitize(
+                    info.window_title.as_deref().unwrap_or(""),
+                    MAX_TEXT_LENGTH,
+                )),
+                Segment::Ocr => {
+                    let first_line = info
+                        .ocr_text
+                        .as_deref()
+                        .and_then(|text| text.lines().map(str::trim).find(|l| !l.is_empty()))
+                        .unwrap_or("");
+                    name.push_str(&sanitize(first_line, MAX_TEXT_LENGTH));
+                }
+                Segment::Counter(digits) => {
+                    name.push_str(&format!("{:0width$}", counter, width = *digits))
+                }
+            }
+        }
+
+        name
+    }
+}
+
+// Zielordner und Namensvorlage für gespeicherte Aufnahmen
+#[derive(Clone, Debug)]
+pub struct OutputPolicy {
+    pub directory: PathBuf,
+    pub template: FilenameTemplate,
+    // Ersetzt die Endung aus der Vorlage, z.B. wenn das Format explizit gewählt wurde
+    pub extension: Option<String>,
+}
+
+impl OutputPolicy {
+    pub fn new<P: Into<PathBuf>>(directory: P, template: FilenameTemplate) -> Self {
+        OutputPolicy {
+            directory: directory.into(),
+            template,
+            extension: None,
+        }
+    }
+
+    pub fn with_extension<S: Into<String>>(mut self, extension: S) -> Self {
+        self.extension = Some(extension.into());
+        self
+    }
+
+    // Ohne {counter} in der Vorlage wird bei Kollisionen `_2`, `_3`, ... angehängt
+    fn candidate(&self, info: &CaptureInfo, counter: u32) -> PathBuf {
+        let mut name = PathBuf::from(self.template.render(info, counter));
+        if let Some(extension) = &self.extension {
+            name.set_extension(extension);
+        }
+        if self.template.has_counter() || counter == 1 {
+            return self.directory.join(name);
+        }
+
+        let path = name.as_path();
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match path.extension() {
+            Some(extension) => self.directory.join(format!(
+                "{}_{}.{}",
+                stem,
+                counter,
+                extension.to_string_lossy()
+            )),
+            None => self.directory.join(format!("{}_{}", stem, counter)),
+        }
+    }
+
+    // Legt den Ordner bei Bedarf an und reserviert den ersten freien Dateinamen,
+    // indem die Datei exklusiv angelegt wird; so überschreiben sich parallele Aufnahmen nicht
+    pub fn reserve(&self, info: &CaptureInfo) -> Result<PathBuf, SnipError> {
+        fs::create_dir_all(&self.directory)?;
+
+        for counter in 1..=MAX_COUNTER {
+            let path = self.candidate(info, counter);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(path),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Err(SnipError::Config(format!(
+            "No free file name left in {}",
+            self.directory.display()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::testing::TempDir;
+    use chrono::TimeZone;
+
+    fn info() -> CaptureInfo {
+        CaptureInfo {
+            timestamp: Local.with_ymd_and_hms(2024, 3, 9, 14, 5, 7).unwrap(),
+            monitor: Some(1),
+            window_title: Some("Bericht: Q1/Q2 <Entwurf>".to_string()),
+            ocr_text: Some("\n  Rechnung Nr. 42  \nzweite Zeile".to_string()),
+            ..CaptureInfo::new(640, 480)
+        }
+    }
+
+    fn render(template: &str, counter: u32) -> String {
+        template
+            .parse::<FilenameTemplate>()
+            .unwrap()
+            .render(&info(), counter)
+    }
+
+    #[test]
+    fn default_template_renders() {
+        assert_eq!(
+            FilenameTemplate::default().render(&info(), 7),
+            "2024-03-09_14-05-07_640x480_007.png"
+        );
+    }
+
+    #[test]
+    fn placeholders_are_replaced() {
+        assert_eq!(render("{date:%d.%m.%y}", 1), "09.03.24");
+        assert_eq!(render("{time:%H%M}", 1), "1405");
+        assert_eq!(render("{w}-{h}-{monitor}", 1), "640-480-1");
+        assert_eq!(render("{ocr}.png", 1), "Rechnung Nr. 42.png");
+        assert_eq!(render("{{{w}}}", 1), "{640}");
+        assert_eq!(render("ohne Platzhalter", 1), "ohne Platzhalter");
+    }
+
+    #[test]
+    fn missing_values_render_empty() {
+        let info = CaptureInfo::new(1, 2);
+        let template: FilenameTemplate = "a{monitor}{title}{ocr}b".parse().unwrap();
+        assert_eq!(template.render(&info, 1), "ab");
+    }
+
+    #[test]
+    fn ocr_placeholder_is_detected() {
+        let with_ocr: FilenameTemplate = "{date:%Y}_{ocr}.png".parse().unwrap();
+        let without: FilenameTemplate = "{date:%Y}_{{ocr}}.png".parse().unwrap();
+        assert!(with_ocr.has_ocr());
+        assert!(!without.has_ocr());
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        for template in [
+            "",
+            "{unknown}",
+            "{w:3}",
+            "{counter:x}",
+            "{date:%Q}",
+            "{w",
+            "w}",
+        ] {
+            assert!(
+                matches!(
+                    template.parse::<FilenameTemplate>(),
+                    Err(SnipError::Config(_))
+                ),
+                "{:?}",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn counter_is_zero_padded() {
+        assert_eq!(render("{counter}", 5), "005");
+        assert_eq!(render("{counter:5}", 42), "00042");
+        assert_eq!(render("{counter:1}", 1234), "1234");
+        assert!(FilenameTemplate::default().has_counter());
+        assert!(!"{w}".parse::<FilenameTemplate>().unwrap().has_counter());
+    }
+
+    #[test]
+    fn text_is_sanitized() {
+        assert_eq!(render("{title}", 1), "Bericht_ Q1_Q2 _Entwurf_");
+        assert_eq!(sanitize("a\tb|c?*", usize::MAX), "a_b_c__");
+        // Windows verbietet Punkte und Leerzeichen am Ende
+        assert_eq!(sanitize(" name. ", usize::MAX), "name");
+        assert_eq!(sanitize("äöü€", usize::MAX), "äöü€");
+        assert_eq!(
+            sanitize(&"x".repeat(100), MAX_TEXT_LENGTH).len(),
+            MAX_TEXT_LENGTH
+        );
+    }
+
+    #[test]
+    fn collisions_get_a_suffix() {
+        let dir = TempDir::new("naming_suffix");
+        let policy = OutputPolicy::new(&dir.0, "{w}x{h}.png".parse().unwrap());
+
+        assert_eq!(policy.reserve(&info()).unwrap(), dir.join("640x480.png"));
+        assert_eq!(policy.reserve(&info()).unwrap(), dir.join("640x480_2.png"));
+        assert_eq!(policy.reserve(&info()).unwrap(), dir.join("640x480_3.png"));
+
+        let policy = OutputPolicy::new(&dir.0, "{w}x{h}".parse().unwrap());
+        assert_eq!(policy.reserve(&info()).unwrap(), dir.join("640x480"));
+        assert_eq!(policy.reserve(&info()).unwrap(), dir.join("640x480_2"));
+    }
+
+    #[test]
+    fn counter_counts_up_instead_of_suffix() {
+        let dir = TempDir::new("naming_counter");
+        let policy = OutputPolicy::new(&dir.0, "shot{counter:2}.png".parse().unwrap());
+
+        assert_eq!(policy.reserve(&info()).unwrap(), dir.join("shot01.png"));
+        assert_eq!(policy.reserve(&info()).unwrap(), dir.join("shot02.png"));
+        // Eine Lücke wird wieder gefüllt
+        fs::remove_file(dir.join("shot01.png")).unwrap();
+        assert_eq!(policy.reserve(&info()).unwrap(), dir.join("shot01.png"));
+    }
+
+    #[test]
+    fn extension_overrides_template() {
+        let dir = TempDir::new("naming_extension");
+        let policy = OutputPolicy::new(&dir.0, "{w}.png".parse().unwrap()).with_extension("webp");
+        assert_eq!(policy.reserve(&info()).unwrap(), dir.join("640.webp"));
+        assert_eq!(policy.reserve(&info()).unwrap(), dir.join("640_2.webp"));
+    }
+
+    #[test]
+    fn reserve_creates_the_directory_and_never_overwrites() {
+        let dir = TempDir::new("naming_reserve");
+        let nested = dir.join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("shot.png"), b"vorhanden").unwrap();
+
+        let policy = OutputPolicy::new(&nested, "shot.png".parse().unwrap());
+        let path = policy.reserve(&info()).unwrap();
+        assert_eq!(path, nested.join("shot_2.png"));
+        // Die reservierte Datei existiert leer, die alte ist unverändert
+        assert_eq!(fs::read(&path).unwrap(), b"");
+        assert_eq!(fs::read(nested.join("shot.png")).unwrap(), b"vorhanden");
+
+        let fresh = OutputPolicy::new(dir.join("neu"), "shot.png".parse().unwrap());
+        assert_eq!(
+            fresh.reserve(&info()).unwrap(),
+            dir.join("neu").join("shot.png")
+        );
+    }
+}
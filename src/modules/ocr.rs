@@ -0,0 +1,404 @@
+use crate::modules::backend::PixelRect;
+use crate::modules::errorhandler::SnipError;
+use image::RgbaImage;
+
+// Ein erkanntes Wort; `bounds` in Pixeln relativ zum erkannten Bild
+#[derive(Clone, Debug, PartialEq)]
+pub struct OcrWord {
+    pub text: String,
+    pub bounds: PixelRect,
+    // Zwischen 0 und 1; None, wenn die Engine keine Sicherheit liefert (z.B. Windows.Media.Ocr)
+    pub confidence: Option<f32>,
+}
+
+impl OcrWord {
+    pub fn new<S: Into<String>>(text: S, bounds: PixelRect) -> Self {
+        OcrWord {
+            text: text.into(),
+            bounds,
+            confidence: None,
+        }
+    }
+
+    pub fn with_confidence(mut self, confidence: f32) -> Self {
+        self.confidence = Some(confidence.clamp(0.0, 1.0));
+        self
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OcrLine {
+    pub words: Vec<OcrWord>,
+}
+
+impl OcrLine {
+    pub fn new(words: Vec<OcrWord>) -> Self {
+        OcrLine { words }
+    }
+
+    pub fn bounds(&self) -> Option<PixelRect> {
+        let (first, rest) = self.words.split_first()?;
+        Some(
+            rest.iter()
+                .fold(first.bounds, |bounds, word| bounds.union(&word.bounds)),
+        )
+    }
+
+    pub fn text(&self) -> String {
+        self.words
+            .iter()
+            .map(|word| word.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    // Mittelwert der Wörter, die eine Sicherheit angeben
+    pub fn confidence(&self) -> Option<f32> {
+        let known: Vec<f32> = self
+            .words
+            .iter()
+            .filter_map(|word| word.confidence)
+            .collect();
+        (!known.is_empty()).then(|| known.iter().sum::<f32>() / known.len() as f32)
+    }
+}
+
+// Zeilen in der Reihenfolge, die die Engine liefert
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OcrResult {
+    pub lines: Vec<OcrLine>,
+}
+
+impl OcrResult {
+    pub fn new(lines: Vec<OcrLine>) -> Self {
+        OcrResult { lines }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.iter().all(|line| line.words.is_empty())
+    }
+
+    pub fn words(&self) -> impl Iterator<Item = &OcrWord> {
+        self.lines.iter().flat_map(|line| line.words.iter())
+    }
+
+    pub fn into_words(self) -> Vec<OcrWord> {
+        self.lines.into_iter().flat_map(|line| line.words).collect()
+    }
+
+    // Zeilen durch Zeilenumbrüche getrennt, ohne weitere Aufbereitung
+    pub fn text(&self) -> String {
+        self.lines
+            .iter()
+            .map(OcrLine::text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    // Verwirft Wörter unterhalb der Schwelle; Wörter ohne Angabe bleiben erhalten
+    pub fn filter_confidence(mut self, minimum: f32) -> Self {
+        for line in &mut self.lines {
+            line.words.retain(
+                |word| !matches!(word.confidence, Some(confidence) if confidence < minimum),
+            );
+        }
+        self.lines.retain(|line| !line.words.is_empty());
+        self
+    }
+}
+
+// Texterkennung hinter einer Schnittstelle, damit sich die Auswertung ohne Windows testen lässt
+pub trait OcrEngine {
+    fn recognize(&self, image: &RgbaImage) -> Result<OcrResult, SnipError>;
+
+    // Wörter in Lesereihenfolge
+    fn recognize_words(&self, image: &RgbaImage) -> Result<Vec<OcrWord>, SnipError> {
+        Ok(self.recognize(image)?.into_words())
+    }
+}
+
+// Liefert unabhängig vom Bild immer dasselbe Ergebnis, z.B. für Tests ohne Windows
+#[derive(Clone, Debug, Default)]
+pub struct StubOcrEngine {
+    result: OcrResult,
+}
+
+// Maße, mit denen from_text die Wortboxen auslegt
+const STUB_CHAR_WIDTH: u32 = 8;
+const STUB_LINE_HEIGHT: u32 = 16;
+const STUB_TEXT_HEIGHT: u32 = 12;
+
+impl StubOcrEngine {
+    pub fn new(result: OcrResult) -> Self {
+        StubOcrEngine { result }
+    }
+
+    // Legt den Text zeilenweise als Wörter mit fester Zeichenbreite aus; Sicherheit 1.0
+    pub fn from_text(text: &str) -> Self {
+        let lines = text
+            .lines()
+            .enumerate()
+            .map(|(row, line)| {
+                let y = (row as u32 * STUB_LINE_HEIGHT) as i32;
+                let chars: Vec<char> = line.chars().collect();
+                let mut words = Vec::new();
+                let mut column = 0;
+                while column < chars.len() {
+                    if chars[column].is_whitespace() {
+                        column += 1;
+                        continue;
+                    }
+                    let start = column;
+                    while column < chars.len() && !chars[column].is_whitespace() {
+                        column += 1;
+                    }
+                    let bounds = PixelRect::new(
+                        (start as u32 * STUB_CHAR_WIDTH) as i32,
+                        y,
+                        (column - start) as u32 * STUB_CHAR_WIDTH,
+                        STUB_TEXT_HEIGHT,
+                    );
+                    let word: String = chars[start..column].iter().collect();
+                    words.push(OcrWord::new(word, bounds).with_confidence(1.0));
+                }
+                OcrLine::new(words)
+            })
+            .collect();
+        StubOcrEngine::new(OcrResult::new(lines))
+    }
+}
+
+impl OcrEngine for StubOcrEngine {
+    fn recognize(&self, _image: &RgbaImage) -> Result<OcrResult, SnipError> {
+        Ok(self.result.clone())
+    }
+}
+
+#[cfg(windows)]
+pub use windows_engine::WindowsOcrEngine;
+
+#[cfg(windows)]
+mod windows_engine {
+    use super::{OcrEngine, OcrLine, OcrResult, OcrWord};
+    use crate::modules::backend::PixelRect;
+    use crate::modules::errorhandler::SnipError;
+    use image::imageops::{self, FilterType};
+    use image::RgbaImage;
+    use windows::core::HSTRING;
+    use windows::Globalization::Language;
+    use windows::Graphics::Imaging::{BitmapAlphaMode, BitmapPixelFormat, SoftwareBitmap};
+    use windows::Media::Ocr;
+    use windows::Storage::Streams::DataWriter;
+
+    // Windows.Media.Ocr direkt, da nur diese API Zeilen und Wortboxen liefert
+    pub struct WindowsOcrEngine {
+        engine: Ocr::OcrEngine,
+    }
+
+    fn ocr_error(message: &str) -> impl Fn(windows::core::Error) -> SnipError + '_ {
+        move |e| SnipError::ocr(message).caused_by(e)
+    }
+
+    impl WindowsOcrEngine {
+        // Erste installierte Sprache aus dem Benutzerprofil
+        pub fn new() -> Result<Self, SnipError> {
+            let engine = Ocr::OcrEngine::TryCreateFromUserProfileLanguages()
+                .map_err(ocr_error("No OCR language installed for the user profile"))?;
+            Ok(WindowsOcrEngine { engine })
+        }
+
+        // BCP-47-Kennung, z.B. "de-DE"
+        pub fn for_language(tag: &str) -> Result<Self, SnipError> {
+            let language = Language::CreateLanguage(&HSTRING::from(tag))
+                .map_err(ocr_error("Invalid OCR language"))?;
+            let engine = Ocr::OcrEngine::TryCreateFromLanguage(&language).map_err(|e| {
+                SnipError::ocr(format!("OCR language {} is not installed", tag)).caused_by(e)
+            })?;
+            Ok(WindowsOcrEngine { engine })
+        }
+
+        // RgbaImage ist nicht vormultipliziert, daher Straight
+        fn bitmap(image: &RgbaImage) -> windows::core::Result<SoftwareBitmap> {
+            let bgra: Vec<u8> = image
+                .pixels()
+                .flat_map(|p| [p[2], p[1], p[0], p[3]])
+                .collect();
+
+            let writer = DataWriter::new()?;
+            writer.WriteBytes(&bgra)?;
+            let buffer = writer.DetachBuffer()?;
+
+            SoftwareBitmap::CreateCopyWithAlphaFromBuffer(
+                &buffer,
+                BitmapPixelFormat::Bgra8,
+                image.width() as i32,
+                image.height() as i32,
+                BitmapAlphaMode::Straight,
+            )
+        }
+    }
+
+    impl OcrEngine for WindowsOcrEngine {
+        fn recognize(&self, image: &RgbaImage) -> Result<OcrResult, SnipError> {
+            // Größere Bilder lehnt die Engine ab; die Boxen werden danach zurückskaliert
+            let max = Ocr::OcrEngine::MaxImageDimension()
+                .map_err(ocr_error("Failed to query the OCR size limit"))?;
+            let longest = image.width().max(image.height());
+            let (scale, scaled) = if longest > max {
+                let scale = max as f32 / longest as f32;
+                let width = ((image.width() as f32 * scale) as u32).max(1);
+                let height = ((image.height() as f32 * scale) as u32).max(1);
+                (
+                    scale,
+                    Some(imageops::resize(image, width, height, FilterType::Triangle)),
+                )
+            } else {
+                (1.0, None)
+            };
+
+            let bitmap = Self::bitmap(scaled.as_ref().unwrap_or(image))
+                .map_err(ocr_error("Failed to convert the image for OCR"))?;
+            let result = self
+                .engine
+                .RecognizeAsync(&bitmap)
+                .and_then(|operation| operation.get())
+                .map_err(ocr_error("Text recognition failed"))?;
+
+            let mut lines = Vec::new();
+            for line in result
+                .Lines()
+                .map_err(ocr_error("Failed to read OCR lines"))?
+            {
+                let mut words = Vec::new();
+                for word in line
+                    .Words()
+                    .map_err(ocr_error("Failed to read OCR words"))?
+                {
+                    let text = word
+                        .Text()
+                        .map_err(ocr_error("Failed to read OCR word"))?
+                        .to_string_lossy();
+                    let rect = word
+                        .BoundingRect()
+                        .map_err(ocr_error("Failed to read OCR word bounds"))?;
+
+                    let left = (rect.X / scale).floor() as i32;
+                    let top = (rect.Y / scale).floor() as i32;
+                    let right = ((rect.X + rect.Width) / scale).ceil() as i32;
+                    let bottom = ((rect.Y + rect.Height) / scale).ceil() as i32;
+                    let bounds = PixelRect::new(
+                        left,
+                        top,
+                        (right - left).max(0) as u32,
+                        (bottom - top).max(0) as u32,
+                    );
+                    words.push(OcrWord::new(text, bounds));
+                }
+                lines.push(OcrLine::new(words));
+            }
+
+            Ok(OcrResult::new(lines))
+        }
+    }
+}
+
+// Die Engine des Systems; außerhalb von Windows gibt es keine
+#[cfg(windows)]
+pub fn system_engine() -> Result<Box<dyn OcrEngine>, SnipError> {
+    Ok(Box::new(WindowsOcrEngine::new()?))
+}
+
+#[cfg(not(windows))]
+pub fn system_engine() -> Result<Box<dyn OcrEngine>, SnipError> {
+    Err(SnipError::ocr(
+        "Text recognition is only available on Windows",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str, x: i32, confidence: Option<f32>) -> OcrWord {
+        let word = OcrWord::new(text, PixelRect::new(x, 0, 10, 12));
+        match confidence {
+            Some(confidence) => word.with_confidence(confidence),
+            None => word,
+        }
+    }
+
+    #[test]
+    fn stub_lays_out_words_per_line() {
+        let engine = StubOcrEngine::from_text("Hallo  Welt\n\nzweite Zeile");
+        let result = engine.recognize(&RgbaImage::new(1, 1)).unwrap();
+        assert_eq!(result.lines.len(), 3);
+        assert!(result.lines[1].words.is_empty());
+        assert_eq!(result.text(), "Hallo Welt\n\nzweite Zeile");
+
+        // Acht Pixel pro Zeichen, 16 Pixel pro Zeile
+        let words = &result.lines[0].words;
+        assert_eq!(words[0].bounds, PixelRect::new(0, 0, 40, 12));
+        assert_eq!(words[1].bounds, PixelRect::new(56, 0, 32, 12));
+        assert_eq!(words[1].confidence, Some(1.0));
+        assert_eq!(
+            result.lines[2].words[1].bounds,
+            PixelRect::new(56, 32, 40, 12)
+        );
+        assert_eq!(result.lines[0].bounds(), Some(PixelRect::new(0, 0, 88, 12)));
+        assert_eq!(result.lines[1].bounds(), None);
+    }
+
+    #[test]
+    fn stub_ignores_the_image() {
+        let engine = StubOcrEngine::from_text("eins zwei\ndrei");
+        let small = engine.recognize_words(&RgbaImage::new(1, 1)).unwrap();
+        let large = engine.recognize_words(&RgbaImage::new(500, 300)).unwrap();
+        assert_eq!(small, large);
+        let texts: Vec<&str> = small.iter().map(|word| word.text.as_str()).collect();
+        assert_eq!(texts, ["eins", "zwei", "drei"]);
+
+        let empty = StubOcrEngine::default()
+            .recognize(&RgbaImage::new(1, 1))
+            .unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.text(), "");
+    }
+
+    #[test]
+    fn confidence_is_clamped_and_averaged() {
+        assert_eq!(word("a", 0, Some(1.5)).confidence, Some(1.0));
+        assert_eq!(word("a", 0, Some(-0.5)).confidence, Some(0.0));
+
+        let line = OcrLine::new(vec![
+            word("a", 0, Some(0.5)),
+            word("b", 20, None),
+            word("c", 40, Some(1.0)),
+        ]);
+        assert_eq!(line.confidence(), Some(0.75));
+        assert_eq!(line.text(), "a b c");
+        assert_eq!(OcrLine::new(vec![word("a", 0, None)]).confidence(), None);
+    }
+
+    #[test]
+    fn filter_confidence_keeps_unknown_and_drops_empty_lines() {
+        let result = OcrResult::new(vec![
+            OcrLine::new(vec![
+                word("sicher", 0, Some(0.9)),
+                word("unsicher", 20, Some(0.3)),
+                word("ohne", 40, None),
+            ]),
+            OcrLine::new(vec![word("rauschen", 0, Some(0.1))]),
+            OcrLine::new(vec![word("grenze", 0, Some(0.5))]),
+        ]);
+
+        let filtered = result.clone().filter_confidence(0.5);
+        assert_eq!(filtered.lines.len(), 2);
+        assert_eq!(filtered.text(), "sicher ohne\ngrenze");
+
+        assert_eq!(result.clone().filter_confidence(0.0), result);
+        assert_eq!(
+            result.filter_confidence(1.0).into_words(),
+            vec![word("ohne", 40, None)]
+        );
+    }
+}
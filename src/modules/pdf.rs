@@ -0,0 +1,496 @@
+use crate::modules::errorhandler::SnipError;
+use crate::modules::export::DEFAULT_JPEG_QUALITY;
+use crate::modules::ocr::OcrWord;
+use image::codecs::jpeg::JpegEncoder;
+use image::{ColorType, DynamicImage, RgbaImage};
+use std::fmt::Write;
+
+// Einseitiges, durchsuchbares PDF: das Bild als JPEG (DCTDecode) füllt die Seite, darüber liegt
+// unsichtbarer Text (Rendermodus 3) an den Positionen der OCR-Wortboxen. Helvetica gehört zu den
+// Standardschriften jedes Betrachters und muss nicht eingebettet werden.
+//
+// Die Schrift nutzt WinAnsiEncoding und deckt damit nur westeuropäische Zeichen ab. Alles andere,
+// z.B. Kyrillisch oder CJK, landet als '?' im Text und ist nicht auffindbar; dafür bräuchte es
+// eine eingebettete CID-Schrift mit ToUnicode-Tabelle.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PdfOptions {
+    // Bestimmt die Seitengröße; bei 96 dpi wird ein Pixel zu 0,75 Punkt
+    pub dpi: f32,
+    pub jpeg_quality: u8,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        PdfOptions {
+            dpi: 96.0,
+            jpeg_quality: DEFAULT_JPEG_QUALITY,
+        }
+    }
+}
+
+// Zeichenbreiten von Helvetica für 0x20 bis 0x7e, in Tausendstel der Schriftgröße
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+// Für Umlaute und Sonderzeichen reicht eine mittlere Breite, der Text ist ohnehin unsichtbar
+const DEFAULT_WIDTH: u16 = 556;
+const ASCENT: f32 = 0.718;
+const DESCENT: f32 = 0.207;
+
+// Objektnummern in der Reihenfolge, in der sie geschrieben werden
+const CATALOG: usize = 1;
+const PAGES: usize = 2;
+const PAGE: usize = 3;
+const FONT: usize = 4;
+const IMAGE: usize = 5;
+const CONTENT: usize = 6;
+
+// Zeichen in WinAnsiEncoding; was dort fehlt, wird zum Fragezeichen
+fn win_ansi(c: char) -> u8 {
+    match c {
+        ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+        '€' => 0x80,
+        '‚' => 0x82,
+        '„' => 0x84,
+        '…' => 0x85,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        '™' => 0x99,
+        _ => b'?',
+    }
+}
+
+fn char_width(byte: u8) -> u16 {
+    match byte {
+        0x20..=0x7e => HELVETICA_WIDTHS[(byte - 0x20) as usize],
+        _ => DEFAULT_WIDTH,
+    }
+}
+
+// Zwei Nachkommastellen genügen; ohne überflüssige Nullen
+fn number(value: f32) -> String {
+    let text = format!("{:.2}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "" | "-0" => "0".to_string(),
+        text => text.to_string(),
+    }
+}
+
+// Das nächste Wort steht rechts daneben auf derselben Zeile
+fn same_line(word: &OcrWord, next: &OcrWord) -> bool {
+    next.bounds.x >= word.bounds.x
+        && next.bounds.y < word.bounds.bottom()
+        && word.bounds.y < next.bounds.bottom()
+}
+
+fn content_stream(width: f32, height: f32, words: &[OcrWord], scale: f32) -> String {
+    let mut content = String::new();
+    let _ = writeln!(
+        content,
+        "q {} 0 0 {} 0 0 cm /Im0 Do Q",
+        number(width),
+        number(height)
+    );
+
+    let words: Vec<&OcrWord> = words
+        .iter()
+        .filter(|word| !word.text.trim().is_empty() && word.bounds.width > 0)
+        .filter(|word| word.bounds.height > 0)
+        .collect();
+    if words.is_empty() {
+        return content;
+    }
+
+    content.push_str("BT\n3 Tr\n");
+    for (index, word) in words.iter().enumerate() {
+        let mut encoded: Vec<u8> = word.text.chars().map(win_ansi).collect();
+        let advance: u32 = encoded.iter().map(|byte| char_width(*byte) as u32).sum();
+        // Ohne Leerzeichen kleben Textextraktoren die Wörter einer Zeile aneinander. Es zählt
+        // nicht zur Breite und liegt damit rechts außerhalb der Box.
+        if words
+            .get(index + 1)
+            .is_some_and(|next| same_line(word, next))
+        {
+            encoded.push(b' ');
+        }
+
+        // Die Box reicht von der Ober- bis zur Unterlänge; die Grundlinie liegt darüber
+        let size = word.bounds.height as f32 * scale / (ASCENT + DESCENT);
+        let x = word.bounds.x as f32 * scale;
+        let baseline = height - word.bounds.bottom() as f32 * scale + DESCENT * size;
+        // Horizontal so gestreckt, dass markierter Text genau die Box abdeckt
+        let natural = advance as f32 * size / 1000.0;
+        let stretch = word.bounds.width as f32 * scale / natural * 100.0;
+
+        let hex: String = encoded.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let _ = writeln!(
+            content,
+            "/F1 {} Tf {} Tz 1 0 0 1 {} {} Tm <{}> Tj",
+            number(size),
+            number(stretch),
+            number(x),
+            number(baseline),
+            hex
+        );
+    }
+    content.push_str("ET\n");
+    content
+}
+
+struct Writer {
+    buffer: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl Writer {
+    fn object(&mut self, number: usize, dictionary: &str, stream: Option<&[u8]>) {
+        debug_assert_eq!(number, self.offsets.len() + 1);
+        self.offsets.push(self.buffer.len());
+        self.buffer
+            .extend_from_slice(format!("{} 0 obj\n{}\n", number, dictionary).as_bytes());
+        if let Some(stream) = stream {
+            self.buffer.extend_from_slice(b"stream\n");
+            self.buffer.extend_from_slice(stream);
+            self.buffer.extend_from_slice(b"\nendstream\n");
+        }
+        self.buffer.extend_from_slice(b"endobj\n");
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let xref = self.buffer.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            // Jeder Eintrag ist genau 20 Bytes lang, einschließlich " \n"
+            let _ = writeln!(table, "{:010} 00000 n ", offset);
+        }
+        let _ = write!(
+            table,
+            "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            self.offsets.len() + 1,
+            CATALOG,
+            xref
+        );
+        self.buffer.extend_from_slice(table.as_bytes());
+        self.buffer
+    }
+}
+
+// Wortboxen in Pixeln des Bildes; ohne Wörter entsteht ein reines Bild-PDF
+pub fn encode(
+    image: &RgbaImage,
+    words: &[OcrWord],
+    options: &PdfOptions,
+) -> Result<Vec<u8>, SnipError> {
+    let (width, height) = image.dimensions();
+
+    // JPEG kennt keinen Alphakanal
+    let rgb = DynamicImage::ImageRgba8(image.clone()).to_rgb8();
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, options.jpeg_quality.clamp(1, 100))
+        .encode(rgb.as_raw(), width, height, ColorType::Rgb8)
+        .map_err(|e| SnipError::Encode {
+            format: "PDF",
+            message: e.to_string(),
+        })?;
+
+    let scale = 72.0 / options.dpi;
+    let page_width = width as f32 * scale;
+    let page_height = height as f32 * scale;
+    let content = content_stream(page_width, page_height, words, scale);
+
+    let mut writer = Writer {
+        // Die Bytes über 127 in der Kommentarzeile kennzeichnen die Datei als binär
+        buffer: b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec(),
+        offsets: Vec::new(),
+    };
+    writer.object(
+        CATALOG,
+        &format!("<< /Type /Catalog /Pages {} 0 R >>", PAGES),
+        None,
+    );
+    writer.object(
+        PAGES,
+        &format!("<< /Type /Pages /Kids [{} 0 R] /Count 1 >>", PAGE),
+        None,
+    );
+    writer.object(
+        PAGE,
+        &format!(
+            "<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /XObject << /Im0 {} 0 R >> /Font << /F1 {} 0 R >> >> \
+             /Contents {} 0 R >>",
+            PAGES,
+            number(page_width),
+            number(page_height),
+            IMAGE,
+            FONT,
+            CONTENT
+        ),
+        None,
+    );
+    writer.object(
+        FONT,
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+        None,
+    );
+    writer.object(
+        IMAGE,
+        &format!(
+            "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB \
+             /BitsPerComponent 8 /Filter /DCTDecode /Length {} >>",
+            width,
+            height,
+            jpeg.len()
+        ),
+        Some(&jpeg),
+    );
+    writer.object(
+        CONTENT,
+        &format!("<< /Length {} >>", content.len()),
+        Some(content.as_bytes()),
+    );
+    Ok(writer.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::backend::PixelRect;
+    use image::Rgba;
+
+    fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+        haystack[from..]
+            .windows(needle.len())
+            .position(|window| window == needle)
+            .map(|position| position + from)
+    }
+
+    fn line_after(pdf: &[u8], marker: &[u8]) -> String {
+        let start = find(pdf, marker, 0).unwrap() + marker.len();
+        let end = find(pdf, b"\n", start).unwrap();
+        String::from_utf8_lossy(&pdf[start..end]).into_owned()
+    }
+
+    // Liest ein Objekt über die Querverweistabelle, wie es ein Betrachter tut
+    fn object(pdf: &[u8], number: usize) -> (String, Vec<u8>) {
+        let startxref = find(pdf, b"startxref\n", 0).unwrap();
+        let xref: usize = line_after(&pdf[startxref..], b"startxref\n")
+            .parse()
+            .unwrap();
+        assert_eq!(&pdf[xref..xref + 4], b"xref");
+
+        let entry = xref + "xref\n0 7\n".len() + number * 20;
+        let offset: usize = std::str::from_utf8(&pdf[entry..entry + 10])
+            .unwrap()
+            .parse()
+            .unwrap();
+        let header = format!("{} 0 obj\n", number);
+        assert!(pdf[offset..].starts_with(header.as_bytes()));
+
+        let dictionary_start = offset + header.len();
+        let dictionary_end = find(pdf, b"\n", dictionary_start).unwrap();
+        let dictionary = String::from_utf8(pdf[dictionary_start..dictionary_end].to_vec()).unwrap();
+
+        let mut stream = Vec::new();
+        if let Some(length) = dictionary.split("/Length ").nth(1) {
+            let length: usize = length.split(' ').next().unwrap().parse().unwrap();
+            let start = dictionary_end + 1 + "stream\n".len();
+            assert!(pdf[start + length..].starts_with(b"\nendstream"));
+            stream = pdf[start..start + length].to_vec();
+        }
+        (dictionary, stream)
+    }
+
+    // Minimaler Textextraktor: Tm setzt die Position, Tj zeigt einen Hex-String
+    fn extract_text(pdf: &[u8]) -> Vec<(String, f32, f32)> {
+        let (_, content) = object(pdf, CONTENT);
+        let content = String::from_utf8(content).unwrap();
+        let mut operands: Vec<&str> = Vec::new();
+        let mut position = (0.0, 0.0);
+        let mut words = Vec::new();
+        for token in content.split_whitespace() {
+            match token {
+                "Tm" => {
+                    let n = operands.len();
+                    position = (
+                        operands[n - 2].parse().unwrap(),
+                        operands[n - 1].parse().unwrap(),
+                    );
+                }
+                "Tj" => {
+                    let hex = operands.last().unwrap().trim_matches(['<', '>']);
+                    let text = (0..hex.len())
+                        .step_by(2)
+                        .map(|i| char::from(u8::from_str_radix(&hex[i..i + 2], 16).unwrap()))
+                        .collect();
+                    words.push((text, position.0, position.1));
+                }
+                _ => {
+                    operands.push(token);
+                    continue;
+                }
+            }
+            operands.clear();
+        }
+        words
+    }
+
+    fn page() -> RgbaImage {
+        RgbaImage::from_pixel(400, 200, Rgba([250, 250, 250, 255]))
+    }
+
+    #[test]
+    fn text_layer_round_trips() {
+        let words = vec![
+            OcrWord::new("Rechnung", PixelRect::new(20, 20, 120, 24)),
+            OcrWord::new("Straße", PixelRect::new(160, 20, 90, 24)),
+            OcrWord::new("42,00 €", PixelRect::new(20, 120, 100, 20)),
+        ];
+        let pdf = encode(&page(), &words, &PdfOptions::default()).unwrap();
+
+        let extracted = extract_text(&pdf);
+        let texts: Vec<&str> = extracted.iter().map(|(text, _, _)| text.as_str()).collect();
+        assert_eq!(texts, ["Rechnung ", "Straße", "42,00 \u{80}"]);
+
+        // 96 dpi: Pixel mal 0,75; y zählt im PDF von unten
+        let (_, x, y) = &extracted[0];
+        assert_eq!(*x, 15.0);
+        assert!(*y > 150.0 - 44.0 * 0.75 && *y < 150.0 - 15.0);
+        assert!(extracted[2].2 < extracted[0].2);
+    }
+
+    // Setzt die Wörter wie ein Textextraktor zusammen: neue Zeile, sobald sich y ändert
+    fn extract_lines(pdf: &[u8]) -> Vec<String> {
+        let mut lines: Vec<(String, f32)> = Vec::new();
+        for (text, _, y) in extract_text(pdf) {
+            match lines.last_mut() {
+                Some((line, baseline)) if (*baseline - y).abs() < 1.0 => line.push_str(&text),
+                _ => lines.push((text, y)),
+            }
+        }
+        lines.into_iter().map(|(line, _)| line).collect()
+    }
+
+    #[test]
+    fn words_of_a_line_are_separated_by_spaces() {
+        let words = vec![
+            OcrWord::new("Gesamt", PixelRect::new(20, 20, 70, 20)),
+            OcrWord::new("zu", PixelRect::new(100, 21, 20, 19)),
+            OcrWord::new("zahlen:", PixelRect::new(130, 20, 80, 20)),
+            OcrWord::new("42,00", PixelRect::new(20, 60, 60, 20)),
+            OcrWord::new("Euro", PixelRect::new(90, 60, 50, 20)),
+        ];
+        let pdf = encode(&page(), &words, &PdfOptions::default()).unwrap();
+        assert_eq!(extract_lines(&pdf), ["Gesamt zu zahlen:", "42,00 Euro"]);
+
+        // Das Leerzeichen verändert die Streckung des Wortes nicht
+        let alone = content_stream(300.0, 150.0, &words[..1], 0.75);
+        let spaced = content_stream(300.0, 150.0, &words[..2], 0.75);
+        let stretch = |content: &str| {
+            content
+                .lines()
+                .nth(2)
+                .unwrap()
+                .split(" Tz")
+                .next()
+                .map(str::to_string)
+        };
+        assert_eq!(stretch(&alone), stretch(&spaced));
+    }
+
+    #[test]
+    fn text_is_invisible_helvetica() {
+        let words = vec![OcrWord::new("hidden", PixelRect::new(0, 0, 60, 16))];
+        let pdf = encode(&page(), &words, &PdfOptions::default()).unwrap();
+
+        let (_, content) = object(&pdf, CONTENT);
+        let content = String::from_utf8(content).unwrap();
+        assert!(content.contains("BT\n3 Tr\n"));
+        let (font, _) = object(&pdf, FONT);
+        assert!(font.contains("/BaseFont /Helvetica"));
+    }
+
+    #[test]
+    fn image_is_embedded_as_jpeg_filling_the_page() {
+        let options = PdfOptions {
+            dpi: 144.0,
+            ..PdfOptions::default()
+        };
+        let pdf = encode(&page(), &[], &options).unwrap();
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+
+        let (page, _) = object(&pdf, PAGE);
+        assert!(page.contains("/MediaBox [0 0 200 100]"));
+        let (dictionary, jpeg) = object(&pdf, IMAGE);
+        assert!(dictionary.contains("/Width 400 /Height 200"));
+        assert!(dictionary.contains("/Filter /DCTDecode"));
+        assert!(jpeg.starts_with(&[0xff, 0xd8]));
+        assert_eq!(
+            image::load_from_memory(&jpeg).unwrap().to_rgb8().width(),
+            400
+        );
+
+        let (_, content) = object(&pdf, CONTENT);
+        assert_eq!(content, b"q 200 0 0 100 0 0 cm /Im0 Do Q\n");
+        assert!(extract_text(&pdf).is_empty());
+    }
+
+    #[test]
+    fn characters_outside_win_ansi_become_question_marks() {
+        let words = vec![
+            OcrWord::new("日本", PixelRect::new(0, 0, 40, 20)),
+            OcrWord::new(" ", PixelRect::new(50, 0, 10, 20)),
+        ];
+        let pdf = encode(&page(), &words, &PdfOptions::default()).unwrap();
+        let texts: Vec<String> = extract_text(&pdf).into_iter().map(|w| w.0).collect();
+        assert_eq!(texts, ["??"]);
+    }
+
+    #[test]
+    fn redacted_words_are_left_out() {
+        use crate::modules::export::{self, ExportFormat, ExportOptions};
+        use crate::modules::redact::{Redaction, RedactionStyle};
+
+        let options = ExportOptions {
+            format: Some(ExportFormat::Pdf),
+            redactions: vec![Redaction::new(
+                PixelRect::new(100, 0, 100, 40),
+                RedactionStyle::default(),
+            )],
+            text_layer: vec![
+                OcrWord::new("IBAN", PixelRect::new(20, 10, 50, 20)),
+                OcrWord::new("DE89370400440532013000", PixelRect::new(110, 10, 80, 20)),
+            ],
+        };
+        let pdf = export::encode_with_text(
+            &options.prepare(&page()),
+            ExportFormat::Pdf,
+            &options.visible_text(),
+        )
+        .unwrap();
+        let texts: Vec<String> = extract_text(&pdf).into_iter().map(|w| w.0).collect();
+        assert_eq!(texts, ["IBAN"]);
+    }
+
+    #[test]
+    fn stretch_matches_box_width() {
+        let words = vec![OcrWord::new("mmmm", PixelRect::new(0, 0, 100, 20))];
+        let content = content_stream(75.0, 75.0, &words, 0.75);
+        let size = 20.0 * 0.75 / (ASCENT + DESCENT);
+        let natural = 4.0 * 833.0 * size / 1000.0;
+        let expected = number(75.0 / natural * 100.0);
+        assert!(content.contains(&format!("{} Tz", expected)), "{}", content);
+    }
+}
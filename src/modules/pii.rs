@@ -0,0 +1,459 @@
+use crate::modules::backend::PixelRect;
+use crate::modules::errorhandler::SnipError;
+use crate::modules::ocr::{OcrEngine, OcrWord};
+use crate::modules::redact::{self, Redaction, RedactionStyle};
+use image::RgbaImage;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+// IBANs und Kartennummern werden oft in Gruppen geschrieben und als mehrere Wörter erkannt
+const MAX_GROUPED_WORDS: usize = 9;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PiiKind {
+    Email,
+    Iban,
+    CreditCard,
+    IpAddress,
+    ApiKey,
+}
+
+impl PiiKind {
+    pub const ALL: [PiiKind; 5] = [
+        PiiKind::Email,
+        PiiKind::Iban,
+        PiiKind::CreditCard,
+        PiiKind::IpAddress,
+        PiiKind::ApiKey,
+    ];
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PiiRules {
+    pub kinds: Vec<PiiKind>,
+    // Kürzere Zeichenketten gelten nicht als Schlüssel
+    pub api_key_min_length: usize,
+    // Mindestentropie in Bit pro Zeichen, damit normale Wörter nicht anschlagen
+    pub api_key_min_entropy: f32,
+    pub style: RedactionStyle,
+    // Rand um die Wortboxen, da OCR die Zeichen oft knapp umschließt
+    pub padding: u32,
+}
+
+impl Default for PiiRules {
+    fn default() -> Self {
+        PiiRules {
+            kinds: PiiKind::ALL.to_vec(),
+            api_key_min_length: 20,
+            api_key_min_entropy: 3.0,
+            style: RedactionStyle::default(),
+            padding: 2,
+        }
+    }
+}
+
+impl PiiRules {
+    pub fn only(kinds: &[PiiKind]) -> Self {
+        PiiRules {
+            kinds: kinds.to_vec(),
+            ..PiiRules::default()
+        }
+    }
+
+    fn enabled(&self, kind: PiiKind) -> bool {
+        self.kinds.contains(&kind)
+    }
+
+    // Prüft eine einzelne, bereits zusammengesetzte Zeichenkette
+    pub fn classify(&self, text: &str) -> Option<PiiKind> {
+        let text = trim_punctuation(text);
+        if text.is_empty() {
+            return None;
+        }
+
+        PiiKind::ALL
+            .iter()
+            .copied()
+            .filter(|kind| self.enabled(*kind))
+            .find(|kind| match kind {
+                PiiKind::Email => is_email(text),
+                PiiKind::Iban => is_iban(text),
+                PiiKind::CreditCard => is_credit_card(text),
+                PiiKind::IpAddress => is_ip_address(text),
+                PiiKind::ApiKey => {
+                    is_api_key(text, self.api_key_min_length, self.api_key_min_entropy)
+                }
+            })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PiiMatch {
+    pub kind: PiiKind,
+    pub text: String,
+    pub bounds: PixelRect,
+}
+
+fn trim_punctuation(text: &str) -> &str {
+    text.trim_matches(|c: char| c.is_whitespace() || "\"'()[]<>{},;:!?".contains(c))
+        .trim_end_matches('.')
+}
+
+fn is_email(text: &str) -> bool {
+    let (local, domain) = match text.split_once('@') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let local_ok = !local.is_empty()
+        && local
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._%+-".contains(c));
+
+    let labels: Vec<&str> = domain.split('.').collect();
+    let domain_ok = labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        && labels
+            .last()
+            .is_some_and(|tld| tld.len() >= 2 && tld.chars().all(|c| c.is_ascii_alphabetic()));
+
+    local_ok && domain_ok
+}
+
+// Leerzeichen und Bindestriche zwischen Zifferngruppen sind erlaubt
+fn compact(text: &str) -> String {
+    text.chars().filter(|c| *c != ' ' && *c != '-').collect()
+}
+
+// ISO 13616: Länderkennung und Prüfziffern nach hinten, Buchstaben als Zahlen, Rest mod 97 = 1
+fn is_iban(text: &str) -> bool {
+    let iban = compact(text).to_ascii_uppercase();
+    let bytes = iban.as_bytes();
+    if !(15..=34).contains(&bytes.len())
+        || !bytes[..2].iter().all(u8::is_ascii_alphabetic)
+        || !bytes[2..4].iter().all(u8::is_ascii_digit)
+        || !bytes.iter().all(u8::is_ascii_alphanumeric)
+    {
+        return false;
+    }
+
+    let mut remainder = 0u32;
+    for &byte in bytes[4..].iter().chain(&bytes[..4]) {
+        let value = match byte {
+            b'0'..=b'9' => (byte - b'0') as u32,
+            _ => (byte - b'A') as u32 + 10,
+        };
+        remainder = if value < 10 {
+            (remainder * 10 + value) % 97
+        } else {
+            (remainder * 100 + value) % 97
+        };
+    }
+    remainder == 1
+}
+
+pub fn luhn_valid(digits: &str) -> bool {
+    let mut sum = 0;
+    for (i, c) in digits.chars().rev().enumerate() {
+        let mut digit = match c.to_digit(10) {
+            Some(digit) => digit,
+            None => return false,
+        };
+        if i % 2 == 1 {
+            digit *= 2;
+            if digit > 9 {
+                digit -= 9;
+            }
+        }
+        sum += digit;
+    }
+    sum % 10 == 0
+}
+
+fn is_credit_card(text: &str) -> bool {
+    let digits = compact(text);
+    (13..=19).contains(&digits.len())
+        && digits.chars().all(|c| c.is_ascii_digit())
+        && luhn_valid(&digits)
+}
+
+fn is_ip_address(text: &str) -> bool {
+    // Portangaben wie 10.0.0.1:8080 gehören dazu
+    let host = match text.rsplit_once(':') {
+        Some((host, port)) if host.contains('.') && port.parse::<u16>().is_ok() => host,
+        _ => text,
+    };
+    host.parse::<Ipv4Addr>().is_ok()
+        || (host.matches(':').count() >= 2
+            && host.chars().any(|c| c.is_ascii_hexdigit())
+            && host.parse::<Ipv6Addr>().is_ok())
+}
+
+fn entropy(text: &str) -> f32 {
+    let mut counts = [0u32; 128];
+    for byte in text.bytes() {
+        counts[(byte & 0x7F) as usize] += 1;
+    }
+    let length = text.len() as f32;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f32 / length;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+// Wechsel zwischen Groß-, Kleinbuchstaben, Ziffern und Sonderzeichen
+fn class_changes(text: &str) -> usize {
+    let class = |c: char| match c {
+        'A'..='Z' => 0,
+        'a'..='z' => 1,
+        '0'..='9' => 2,
+        _ => 3,
+    };
+    text.chars()
+        .map(class)
+        .collect::<Vec<_>>()
+        .windows(2)
+        .filter(|pair| pair[0] != pair[1])
+        .count()
+}
+
+// Lange, zufällig wirkende Zeichenketten, z.B. `sk_live_…` oder `ghp_…`. Zusammengesetzte
+// Wörter mit angehängter Jahreszahl haben kaum Klassenwechsel und fallen heraus.
+fn is_api_key(text: &str, min_length: usize, min_entropy: f32) -> bool {
+    text.len() >= min_length
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && text.chars().any(|c| c.is_ascii_digit())
+        && text.chars().any(|c| c.is_ascii_alphabetic())
+        && class_changes(text) * 5 >= text.len()
+        && entropy(text) >= min_entropy
+}
+
+// Zwei Wörter gehören zur selben Gruppe, wenn sie sich vertikal überlappen und dicht beieinander stehen
+fn adjacent(previous: &PixelRect, next: &PixelRect) -> bool {
+    let overlap = previous.bottom().min(next.bottom()) - previous.y.max(next.y);
+    let height = previous.height.min(next.height) as i32;
+    let gap = next.x - previous.right();
+    overlap * 2 >= height && gap >= -height && gap <= height * 2
+}
+
+// Sucht in den Wörtern nach Treffern; zusammengehörige Wortgruppen werden als eine Zeichenkette geprüft
+pub fn find_pii(words: &[OcrWord], rules: &PiiRules) -> Vec<PiiMatch> {
+    let mut matches = Vec::new();
+    let mut start = 0;
+
+    while start < words.len() {
+        let mut end = start + 1;
+        while end < words.len()
+            && end - start < MAX_GROUPED_WORDS
+            && adjacent(&words[end - 1].bounds, &words[end].bounds)
+        {
+            end += 1;
+        }
+
+        // Die längste Gruppe ab `start`, die einer Regel entspricht
+        let found = (start + 1..=end).rev().find_map(|end| {
+            let group = &words[start..end];
+            let text = group
+                .iter()
+                .map(|word| word.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let kind = if group.len() == 1 {
+                rules.classify(&text)
+            } else {
+                // Nur IBANs und Kartennummern erstrecken sich über mehrere Wörter
+                rules
+                    .classify(&text)
+                    .filter(|kind| matches!(kind, PiiKind::Iban | PiiKind::CreditCard))
+            };
+            kind.map(|kind| (end, kind, text))
+        });
+
+        match found {
+            Some((end, kind, text)) => {
+                let bounds = words[start + 1..end]
+                    .iter()
+                    .fold(words[start].bounds, |bounds, word| {
+                        bounds.union(&word.bounds)
+                    });
+                matches.push(PiiMatch { kind, text, bounds });
+                start = end;
+            }
+            None => start += 1,
+        }
+    }
+
+    matches
+}
+
+fn pad(bounds: PixelRect, padding: u32) -> PixelRect {
+    PixelRect::new(
+        bounds.x - padding as i32,
+        bounds.y - padding as i32,
+        bounds.width + padding * 2,
+        bounds.height + padding * 2,
+    )
+}
+
+pub fn redactions(matches: &[PiiMatch], rules: &PiiRules) -> Vec<Redaction> {
+    matches
+        .iter()
+        .map(|found| Redaction::new(pad(found.bounds, rules.padding), rules.style))
+        .collect()
+}
+
+// Erkennt den Text, schwärzt alle Treffer im Bild und gibt sie zurück
+pub fn redact_pii(
+    image: &mut RgbaImage,
+    engine: &dyn OcrEngine,
+    rules: &PiiRules,
+) -> Result<Vec<PiiMatch>, SnipError> {
+    let words = engine
+        .recognize_words(image)
+        .map_err(|e| e.context("Failed to recognize text for PII redaction"))?;
+    let matches = find_pii(&words, rules);
+    redact::apply_all(image, &redactions(&matches, rules));
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::ocr::{OcrLine, OcrResult, StubOcrEngine};
+    use image::Rgba;
+
+    // Wörter einer Zeile, jeweils 8 Pixel pro Zeichen und ein Leerzeichen Abstand
+    fn line(y: i32, text: &str) -> Vec<OcrWord> {
+        let mut x = 0;
+        text.split(' ')
+            .map(|word| {
+                let width = word.len() as u32 * 8;
+                let bounds = PixelRect::new(x, y, width, 12);
+                x += width as i32 + 8;
+                OcrWord::new(word, bounds)
+            })
+            .collect()
+    }
+
+    fn kinds(text: &str) -> Vec<(PiiKind, String)> {
+        find_pii(&line(0, text), &PiiRules::default())
+            .into_iter()
+            .map(|found| (found.kind, found.text))
+            .collect()
+    }
+
+    #[test]
+    fn detects_email_with_punctuation() {
+        assert_eq!(
+            kinds("Mail an (max.mustermann@example.co.uk), danke"),
+            vec![(
+                PiiKind::Email,
+                "(max.mustermann@example.co.uk),".to_string()
+            )]
+        );
+        assert!(kinds("user@localhost oder @handle").is_empty());
+    }
+
+    #[test]
+    fn detects_grouped_iban_with_checksum() {
+        assert_eq!(
+            kinds("IBAN: DE89 3704 0044 0532 0130 00 bitte"),
+            vec![(PiiKind::Iban, "DE89 3704 0044 0532 0130 00".to_string())]
+        );
+        // Eine falsche Prüfziffer
+        assert!(kinds("DE88 3704 0044 0532 0130 00").is_empty());
+    }
+
+    #[test]
+    fn detects_credit_card_only_with_luhn() {
+        assert_eq!(
+            kinds("Karte 4111 1111 1111 1111 gültig"),
+            vec![(PiiKind::CreditCard, "4111 1111 1111 1111".to_string())]
+        );
+        assert!(kinds("Karte 4111 1111 1111 1112").is_empty());
+        assert!(luhn_valid("79927398713"));
+        assert!(!luhn_valid("79927398710"));
+    }
+
+    #[test]
+    fn detects_ip_addresses() {
+        assert_eq!(
+            kinds("Server 192.168.10.4:8080 und 2001:db8::8a2e:370:7334."),
+            vec![
+                (PiiKind::IpAddress, "192.168.10.4:8080".to_string()),
+                (PiiKind::IpAddress, "2001:db8::8a2e:370:7334.".to_string()),
+            ]
+        );
+        assert!(kinds("Version 1.2.3 um 12:30:45 oder 300.1.1.1").is_empty());
+    }
+
+    #[test]
+    fn detects_api_keys_but_not_long_words() {
+        assert_eq!(
+            kinds("token ghp_9fT2kLq8Zx1VbN4mRw7YcA0sDe3 ok"),
+            vec![(
+                PiiKind::ApiKey,
+                "ghp_9fT2kLq8Zx1VbN4mRw7YcA0sDe3".to_string()
+            )]
+        );
+        assert!(kinds("Donaudampfschifffahrtsgesellschaft2024").is_empty());
+        assert!(kinds("aaaaaaaaaaaaaaaaaaaaaaaa1111").is_empty());
+    }
+
+    #[test]
+    fn disabled_rules_do_not_match() {
+        let rules = PiiRules::only(&[PiiKind::Email]);
+        assert!(find_pii(&line(0, "4111 1111 1111 1111"), &rules).is_empty());
+    }
+
+    #[test]
+    fn groups_do_not_cross_lines() {
+        let mut words = line(0, "4111 1111");
+        words.extend(line(40, "1111 1111"));
+        assert!(find_pii(&words, &PiiRules::default()).is_empty());
+    }
+
+    #[test]
+    fn redacts_matching_word_boxes_only() {
+        let words = line(20, "Kontakt jane@example.org heute");
+        let email = words[1].bounds;
+        let engine = StubOcrEngine::new(OcrResult::new(vec![OcrLine::new(words)]));
+
+        let original = RgbaImage::from_fn(200, 60, |x, y| {
+            Rgba([
+                (x * 5 % 256) as u8,
+                (y * 7 % 256) as u8,
+                ((x + y) % 2 * 255) as u8,
+                255,
+            ])
+        });
+        let mut image = original.clone();
+        let rules = PiiRules {
+            style: RedactionStyle::Fill(crate::modules::backend::Color::new(0.0, 0.0, 0.0, 1.0)),
+            ..PiiRules::default()
+        };
+
+        let matches = redact_pii(&mut image, &engine, &rules).unwrap();
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].kind, PiiKind::Email);
+
+        let padded = pad(email, rules.padding);
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let inside = padded.to_rect().contains(x as f32 + 0.5, y as f32 + 0.5);
+            if inside {
+                assert_eq!(*pixel, Rgba([0, 0, 0, 255]));
+            } else {
+                assert_eq!(pixel, original.get_pixel(x, y));
+            }
+        }
+    }
+}
@@ -0,0 +1,1146 @@
+use crate::modules::backend::PixelRect;
+use crate::modules::barcode::Bitmap;
+use once_cell::sync::Lazy;
+
+// QR-Decoder für Bildschirminhalte: Finder-Muster suchen, das Modulraster affin abtasten,
+// Formatinformation lesen, Maske entfernen, Blöcke per Reed-Solomon korrigieren, Segmente lesen.
+// Perspektivische Verzerrung wird nicht ausgeglichen.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EcLevel {
+    L,
+    M,
+    Q,
+    H,
+}
+
+impl EcLevel {
+    const ALL: [EcLevel; 4] = [EcLevel::L, EcLevel::M, EcLevel::Q, EcLevel::H];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    // Kennung in der Formatinformation
+    fn format_bits(self) -> u32 {
+        match self {
+            EcLevel::L => 1,
+            EcLevel::M => 0,
+            EcLevel::Q => 3,
+            EcLevel::H => 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QrCode {
+    pub version: u8,
+    pub ec_level: EcLevel,
+    pub payload: Vec<u8>,
+    pub bounds: PixelRect,
+}
+
+impl QrCode {
+    // Byte-Segmente sind meist UTF-8; alles andere wird als ISO-8859-1 gelesen
+    pub fn text(&self) -> String {
+        match std::str::from_utf8(&self.payload) {
+            Ok(text) => text.to_string(),
+            Err(_) => self.payload.iter().map(|byte| char::from(*byte)).collect(),
+        }
+    }
+}
+
+// Nach Version (Index 1 bis 40) und Korrekturstufe
+const ECC_CODEWORDS_PER_BLOCK: [[u8; 41]; 4] = [
+    [
+        0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28,
+        30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28,
+        28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+    ],
+    [
+        0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30,
+        30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24,
+        30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+];
+
+const ERROR_CORRECTION_BLOCKS: [[u8; 41]; 4] = [
+    [
+        0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13,
+        14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25,
+    ],
+    [
+        0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21,
+        23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49,
+    ],
+    [
+        0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29,
+        34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68,
+    ],
+    [
+        0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32,
+        35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81,
+    ],
+];
+
+const ALPHANUMERIC: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+// Rechnen im Galoiskörper GF(256) mit dem Polynom x^8 + x^4 + x^3 + x^2 + 1
+struct Galois {
+    exp: [u8; 512],
+    log: [u8; 256],
+}
+
+static GF: Lazy<Galois> = Lazy::new(|| {
+    let mut exp = [0u8; 512];
+    let mut log = [0u8; 256];
+    let mut x: u16 = 1;
+    for (i, entry) in exp.iter_mut().take(255).enumerate() {
+        *entry = x as u8;
+        log[x as usize] = i as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= 0x11d;
+        }
+    }
+    for i in 255..512 {
+        exp[i] = exp[i - 255];
+    }
+    Galois { exp, log }
+});
+
+fn mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        0
+    } else {
+        GF.exp[GF.log[a as usize] as usize + GF.log[b as usize] as usize]
+    }
+}
+
+fn div(a: u8, b: u8) -> u8 {
+    if a == 0 {
+        0
+    } else {
+        GF.exp[GF.log[a as usize] as usize + 255 - GF.log[b as usize] as usize]
+    }
+}
+
+// α^power, auch für negative Exponenten modulo 255
+fn alpha_pow(power: isize) -> u8 {
+    GF.exp[power.rem_euclid(255) as usize]
+}
+
+// Koeffizienten mit dem niedrigsten Grad zuerst
+fn poly_eval(poly: &[u8], x: u8) -> u8 {
+    poly.iter().rev().fold(0, |acc, c| mul(acc, x) ^ c)
+}
+
+// Das erste Codewort ist der Koeffizient mit dem höchsten Grad
+fn syndromes(block: &[u8], ecc_len: usize) -> Vec<u8> {
+    (0..ecc_len)
+        .map(|i| {
+            let x = alpha_pow(i as isize);
+            block.iter().fold(0, |acc, c| mul(acc, x) ^ c)
+        })
+        .collect()
+}
+
+// Korrigiert bis zu ecc_len / 2 fehlerhafte Codewörter (Berlekamp-Massey, Chien, Forney).
+// Gibt false zurück, wenn der Block nicht zu retten ist.
+pub fn correct_errors(block: &mut [u8], ecc_len: usize) -> bool {
+    let syndromes = syndromes(block, ecc_len);
+    if syndromes.iter().all(|s| *s == 0) {
+        return true;
+    }
+
+    let mut locator = vec![1u8];
+    let mut previous = vec![1u8];
+    let mut errors = 0;
+    let mut shift = 1;
+    let mut previous_delta = 1u8;
+    for k in 0..ecc_len {
+        let mut delta = syndromes[k];
+        for i in 1..=errors.min(locator.len() - 1) {
+            delta ^= mul(locator[i], syndromes[k - i]);
+        }
+        if delta == 0 {
+            shift += 1;
+            continue;
+        }
+
+        let factor = div(delta, previous_delta);
+        let mut next = locator.clone();
+        next.resize(next.len().max(previous.len() + shift), 0);
+        for (i, coefficient) in previous.iter().enumerate() {
+            next[i + shift] ^= mul(factor, *coefficient);
+        }
+
+        if 2 * errors <= k {
+            previous = std::mem::replace(&mut locator, next);
+            errors = k + 1 - errors;
+            previous_delta = delta;
+            shift = 1;
+        } else {
+            locator = next;
+            shift += 1;
+        }
+    }
+    while locator.len() > 1 && locator.last() == Some(&0) {
+        locator.pop();
+    }
+    if locator.len() - 1 != errors || 2 * errors > ecc_len {
+        return false;
+    }
+
+    // Nullstellen des Lokatorpolynoms ergeben die Fehlerstellen
+    let n = block.len();
+    let positions: Vec<usize> = (0..n)
+        .filter(|j| poly_eval(&locator, alpha_pow(-((n - 1 - j) as isize))) == 0)
+        .collect();
+    if positions.len() != errors {
+        return false;
+    }
+
+    let mut evaluator = vec![0u8; ecc_len];
+    for (i, s) in syndromes.iter().enumerate() {
+        for (j, c) in locator.iter().enumerate().take(ecc_len - i) {
+            evaluator[i + j] ^= mul(*s, *c);
+        }
+    }
+    // Formale Ableitung: in Charakteristik 2 bleiben nur die ungeraden Potenzen
+    let derivative: Vec<u8> = locator
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, c)| if i % 2 == 1 { *c } else { 0 })
+        .collect();
+
+    for j in positions {
+        let power = (n - 1 - j) as isize;
+        let inverse = alpha_pow(-power);
+        let denominator = poly_eval(&derivative, inverse);
+        if denominator == 0 {
+            return false;
+        }
+        let magnitude = mul(
+            alpha_pow(power),
+            div(poly_eval(&evaluator, inverse), denominator),
+        );
+        block[j] ^= magnitude;
+    }
+
+    self::syndromes(block, ecc_len).iter().all(|s| *s == 0)
+}
+
+pub fn size(version: u8) -> usize {
+    17 + 4 * version as usize
+}
+
+pub fn alignment_positions(version: u8) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let count = version as usize / 7 + 2;
+    let step = if version == 32 {
+        26
+    } else {
+        (version as usize * 4 + count * 2 + 1) / (count * 2 - 2) * 2
+    };
+    let last = size(version) - 7;
+    let mut positions = vec![6];
+    positions.extend((0..count - 1).rev().map(|i| last - i * step));
+    positions
+}
+
+// 15 Bit Formatinformation mit BCH-Code und fester Maske
+pub fn format_bits(ec_level: EcLevel, mask: u8) -> u32 {
+    let data = ec_level.format_bits() << 3 | mask as u32;
+    let mut remainder = data;
+    for _ in 0..10 {
+        remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+    }
+    (data << 10 | remainder) ^ 0x5412
+}
+
+// 18 Bit Versionsinformation ab Version 7
+pub fn version_bits(version: u8) -> u32 {
+    let mut remainder = version as u32;
+    for _ in 0..12 {
+        remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1f25);
+    }
+    (version as u32) << 12 | remainder
+}
+
+// Positionen (x, y) der beiden Kopien der Formatinformation, Bit 0 zuerst
+pub fn format_positions(size: usize) -> [Vec<(usize, usize)>; 2] {
+    let mut first: Vec<(usize, usize)> = (0..=5).map(|i| (8, i)).collect();
+    first.extend([(8, 7), (8, 8), (7, 8)]);
+    first.extend((9..15).map(|i| (14 - i, 8)));
+
+    let mut second: Vec<(usize, usize)> = (0..8).map(|i| (size - 1 - i, 8)).collect();
+    second.extend((8..15).map(|i| (8, size - 15 + i)));
+    [first, second]
+}
+
+// Module, die keine Daten tragen: Finder, Trenner, Timing, Alignment, Format und Version
+pub fn function_modules(version: u8) -> Vec<bool> {
+    let size = size(version);
+    let mut function = vec![false; size * size];
+    let mut mark = |x: usize, y: usize| function[y * size + x] = true;
+
+    for i in 0..size {
+        mark(6, i);
+        mark(i, 6);
+    }
+    for (cx, cy) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+        for dy in -4isize..=4 {
+            for dx in -4isize..=4 {
+                let (x, y) = (cx as isize + dx, cy as isize + dy);
+                if (0..size as isize).contains(&x) && (0..size as isize).contains(&y) {
+                    mark(x as usize, y as usize);
+                }
+            }
+        }
+    }
+
+    let positions = alignment_positions(version);
+    let last = positions.len().saturating_sub(1);
+    for (i, cy) in positions.iter().enumerate() {
+        for (j, cx) in positions.iter().enumerate() {
+            // Die Ecken mit Finder-Mustern bleiben frei
+            if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                continue;
+            }
+            for y in cy - 2..=cy + 2 {
+                for x in cx - 2..=cx + 2 {
+                    mark(x, y);
+                }
+            }
+        }
+    }
+
+    for copy in format_positions(size) {
+        for (x, y) in copy {
+            mark(x, y);
+        }
+    }
+    // Das immer dunkle Modul neben der zweiten Kopie
+    mark(8, size - 8);
+
+    if version >= 7 {
+        for i in 0..18 {
+            let (a, b) = (size - 11 + i % 3, i / 3);
+            mark(a, b);
+            mark(b, a);
+        }
+    }
+    function
+}
+
+// Reihenfolge der Datenmodule: in Zweierspalten von rechts nach links, abwechselnd auf- und abwärts
+pub fn data_positions(version: u8, function: &[bool]) -> Vec<(usize, usize)> {
+    let size = size(version);
+    let mut positions = Vec::new();
+    let mut right = size as isize - 1;
+    while right >= 1 {
+        if right == 6 {
+            right = 5;
+        }
+        let upward = (right + 1) & 2 == 0;
+        for vertical in 0..size {
+            let y = if upward {
+                size - 1 - vertical
+            } else {
+                vertical
+            };
+            for column in 0..2 {
+                let x = right as usize - column;
+                if !function[y * size + x] {
+                    positions.push((x, y));
+                }
+            }
+        }
+        right -= 2;
+    }
+    positions
+}
+
+pub fn mask_applies(mask: u8, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (x / 3 + y / 2).is_multiple_of(2),
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + x * y % 3).is_multiple_of(2),
+    }
+}
+
+// Anzahl der Blöcke und Korrektur-Codewörter je Block
+pub fn block_layout(version: u8, ec_level: EcLevel) -> (usize, usize) {
+    (
+        ERROR_CORRECTION_BLOCKS[ec_level.index()][version as usize] as usize,
+        ECC_CODEWORDS_PER_BLOCK[ec_level.index()][version as usize] as usize,
+    )
+}
+
+// Länge jedes Blocks (Daten und Korrektur); die kurzen Blöcke stehen vorne
+pub fn block_lengths(total: usize, blocks: usize) -> Vec<usize> {
+    let short = total / blocks;
+    let short_blocks = blocks - total % blocks;
+    (0..blocks)
+        .map(|index| short + usize::from(index >= short_blocks))
+        .collect()
+}
+
+// Macht die Verschachtelung der Blöcke rückgängig
+fn deinterleave(codewords: &[u8], version: u8, ec_level: EcLevel) -> Vec<Vec<u8>> {
+    let (blocks, ecc_len) = block_layout(version, ec_level);
+    let lengths = block_lengths(codewords.len(), blocks);
+    let mut result: Vec<Vec<u8>> = lengths.iter().map(|len| Vec::with_capacity(*len)).collect();
+    let mut input = codewords.iter();
+
+    let longest_data = lengths[blocks - 1] - ecc_len;
+    for i in 0..longest_data {
+        for (block, len) in result.iter_mut().zip(&lengths) {
+            if i < len - ecc_len {
+                block.extend(input.next());
+            }
+        }
+    }
+    for _ in 0..ecc_len {
+        for block in result.iter_mut() {
+            block.extend(input.next());
+        }
+    }
+    result
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, bits: usize) -> Option<u32> {
+        if self.position + bits > self.data.len() * 8 {
+            return None;
+        }
+        let mut value = 0;
+        for _ in 0..bits {
+            let byte = self.data[self.position / 8];
+            value = value << 1 | ((byte >> (7 - self.position % 8)) & 1) as u32;
+            self.position += 1;
+        }
+        Some(value)
+    }
+}
+
+// Liest die Segmente; Kanji wird nicht unterstützt
+fn parse_segments(data: &[u8], version: u8) -> Option<Vec<u8>> {
+    let group = match version {
+        1..=9 => 0,
+        10..=26 => 1,
+        _ => 2,
+    };
+    let mut reader = BitReader { data, position: 0 };
+    let mut payload = Vec::new();
+
+    // Weniger als vier Restbits gelten als Endmarke
+    while let Some(mode) = reader.read(4) {
+        match mode {
+            0 => break,
+            1 => {
+                let mut count = reader.read([10, 12, 14][group])?;
+                while count > 0 {
+                    let (bits, digits) = match count {
+                        1 => (4, 1),
+                        2 => (7, 2),
+                        _ => (10, 3),
+                    };
+                    let value = reader.read(bits)?;
+                    let text = format!("{:0width$}", value, width = digits);
+                    if text.len() != digits {
+                        return None;
+                    }
+                    payload.extend(text.bytes());
+                    count -= digits as u32;
+                }
+            }
+            2 => {
+                let mut count = reader.read([9, 11, 13][group])?;
+                while count >= 2 {
+                    let value = reader.read(11)? as usize;
+                    payload.push(*ALPHANUMERIC.get(value / 45)?);
+                    payload.push(ALPHANUMERIC[value % 45]);
+                    count -= 2;
+                }
+                if count == 1 {
+                    payload.push(*ALPHANUMERIC.get(reader.read(6)? as usize)?);
+                }
+            }
+            4 => {
+                let count = reader.read([8, 16, 16][group])?;
+                for _ in 0..count {
+                    payload.push(reader.read(8)? as u8);
+                }
+            }
+            // ECI: nur die Kennung überspringen
+            7 => {
+                let first = reader.read(8)?;
+                match first {
+                    0x00..=0x7f => {}
+                    0x80..=0xbf => {
+                        reader.read(8)?;
+                    }
+                    0xc0..=0xdf => {
+                        reader.read(16)?;
+                    }
+                    _ => return None,
+                }
+            }
+            // Structured Append und FNC1 tragen keinen Text
+            3 => {
+                reader.read(16)?;
+            }
+            5 => {}
+            9 => {
+                reader.read(8)?;
+            }
+            _ => return None,
+        }
+    }
+    Some(payload)
+}
+
+// Abgetastetes Modulraster, Zeile für Zeile
+struct Grid {
+    size: usize,
+    modules: Vec<bool>,
+}
+
+impl Grid {
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+}
+
+fn read_format(grid: &Grid) -> Option<(EcLevel, u8)> {
+    let mut best: Option<(u32, EcLevel, u8)> = None;
+    for copy in format_positions(grid.size) {
+        let bits = copy.iter().enumerate().fold(0u32, |bits, (i, (x, y))| {
+            bits | (grid.get(*x, *y) as u32) << i
+        });
+        for ec_level in EcLevel::ALL {
+            for mask in 0..8 {
+                let distance = (format_bits(ec_level, mask) ^ bits).count_ones();
+                if !matches!(best, Some((best, _, _)) if best <= distance) {
+                    best = Some((distance, ec_level, mask));
+                }
+            }
+        }
+    }
+    // Der BCH-Code korrigiert bis zu drei falsche Bits
+    best.filter(|(distance, _, _)| *distance <= 3)
+        .map(|(_, ec_level, mask)| (ec_level, mask))
+}
+
+fn decode_grid(grid: &Grid, version: u8) -> Option<(EcLevel, Vec<u8>)> {
+    let (ec_level, mask) = read_format(grid)?;
+    let function = function_modules(version);
+    let positions = data_positions(version, &function);
+
+    let codewords: Vec<u8> = positions
+        .chunks_exact(8)
+        .map(|byte| {
+            byte.iter().fold(0u8, |value, (x, y)| {
+                value << 1 | (grid.get(*x, *y) ^ mask_applies(mask, *x, *y)) as u8
+            })
+        })
+        .collect();
+
+    let (_, ecc_len) = block_layout(version, ec_level);
+    let mut data = Vec::new();
+    for mut block in deinterleave(&codewords, version, ec_level) {
+        if !correct_errors(&mut block, ecc_len) {
+            return None;
+        }
+        data.extend_from_slice(&block[..block.len() - ecc_len]);
+    }
+    Some((ec_level, parse_segments(&data, version)?))
+}
+
+// Mittelpunkt eines Finder-Musters und die geschätzte Modulgröße
+#[derive(Clone, Copy, Debug)]
+struct Finder {
+    x: f32,
+    y: f32,
+    module: f32,
+    hits: u32,
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+// Dunkel, hell, dunkel, hell, dunkel im Verhältnis 1:1:3:1:1; liefert die Modulgröße
+fn finder_ratio(widths: [u32; 5]) -> Option<f32> {
+    let total: u32 = widths.iter().sum();
+    if total < 7 {
+        return None;
+    }
+    let unit = total as f32 / 7.0;
+    let tolerance = unit / 2.0;
+    let fits = widths
+        .iter()
+        .zip([1.0, 1.0, 3.0, 1.0, 1.0])
+        .all(|(width, expected)| (*width as f32 - unit * expected).abs() < tolerance * expected);
+    fits.then_some(unit)
+}
+
+// Prüft das Verhältnis entlang einer Achse durch (x, y); liefert Mittelpunkt und Modulgröße
+fn cross_check(bitmap: &Bitmap, x: i32, y: i32, vertical: bool, limit: u32) -> Option<(f32, f32)> {
+    let at = |offset: i32| {
+        if vertical {
+            bitmap.is_dark(x, y + offset)
+        } else {
+            bitmap.is_dark(x + offset, y)
+        }
+    };
+    if !at(0) {
+        return None;
+    }
+
+    let count = |from: i32, step: i32, dark: bool| {
+        let mut length = 0;
+        while length < limit && at(from + step * length as i32) == dark {
+            length += 1;
+        }
+        length
+    };
+
+    let center_back = count(0, -1, true);
+    let center_forward = count(1, 1, true);
+    let light_back = count(-(center_back as i32), -1, false);
+    let outer_back = count(-((center_back + light_back) as i32), -1, true);
+    let light_forward = count(1 + center_forward as i32, 1, false);
+    let outer_forward = count(1 + (center_forward + light_forward) as i32, 1, true);
+
+    let center = center_back + center_forward;
+    let unit = finder_ratio([outer_back, light_back, center, light_forward, outer_forward])?;
+    let start = if vertical { y } else { x } - center_back as i32 + 1;
+    Some((start as f32 + center as f32 / 2.0, unit))
+}
+
+fn find_finders(bitmap: &Bitmap) -> Vec<Finder> {
+    let mut finders: Vec<Finder> = Vec::new();
+    for y in 0..bitmap.height() {
+        let runs = bitmap.row_runs(y);
+        for window in runs.windows(5) {
+            if !window[0].dark {
+                continue;
+            }
+            let widths = [0, 1, 2, 3, 4].map(|i| window[i].len);
+            let unit = match finder_ratio(widths) {
+                Some(unit) => unit,
+                None => continue,
+            };
+            let limit = (unit * 14.0).ceil() as u32;
+            let x = window[2].start as f32 + window[2].len as f32 / 2.0;
+
+            let (cy, vertical_unit) = match cross_check(bitmap, x as i32, y as i32, true, limit) {
+                Some(found) => found,
+                None => continue,
+            };
+            let (cx, horizontal_unit) = match cross_check(bitmap, x as i32, cy as i32, false, limit)
+            {
+                Some(found) => found,
+                None => continue,
+            };
+            let module = (unit + vertical_unit + horizontal_unit) / 3.0;
+
+            // Dasselbe Muster wird in mehreren Zeilen gefunden
+            let existing = finders.iter_mut().find(|finder| {
+                (finder.x - cx).abs() <= finder.module * 2.0
+                    && (finder.y - cy).abs() <= finder.module * 2.0
+            });
+            match existing {
+                Some(finder) => {
+                    let hits = finder.hits as f32;
+                    finder.x = (finder.x * hits + cx) / (hits + 1.0);
+                    finder.y = (finder.y * hits + cy) / (hits + 1.0);
+                    finder.module = (finder.module * hits + module) / (hits + 1.0);
+                    finder.hits += 1;
+                }
+                None => finders.push(Finder {
+                    x: cx,
+                    y: cy,
+                    module,
+                    hits: 1,
+                }),
+            }
+        }
+    }
+    finders
+}
+
+// Ordnet drei Finder als oben links, oben rechts, unten links, wenn sie ein Quadrat aufspannen
+fn arrange(finders: [Finder; 3]) -> Option<[Finder; 3]> {
+    let modules: Vec<f32> = finders.iter().map(|finder| finder.module).collect();
+    let smallest = modules.iter().copied().fold(f32::MAX, f32::min);
+    let largest = modules.iter().copied().fold(0.0, f32::max);
+    if largest > smallest * 1.5 {
+        return None;
+    }
+
+    let point = |finder: &Finder| (finder.x, finder.y);
+    // Die Ecke gegenüber der längsten Seite ist oben links
+    let corner = (0..3).max_by(|a, b| {
+        let opposite =
+            |i: usize| distance(point(&finders[(i + 1) % 3]), point(&finders[(i + 2) % 3]));
+        opposite(*a).total_cmp(&opposite(*b))
+    })?;
+    let top_left = finders[corner];
+    let mut top_right = finders[(corner + 1) % 3];
+    let mut bottom_left = finders[(corner + 2) % 3];
+
+    let right = distance(point(&top_left), point(&top_right));
+    let down = distance(point(&top_left), point(&bottom_left));
+    let diagonal = distance(point(&top_right), point(&bottom_left));
+    if right.max(down) > right.min(down) * 1.2
+        || (diagonal.powi(2) - right.powi(2) - down.powi(2)).abs() > diagonal.powi(2) * 0.2
+    {
+        return None;
+    }
+
+    // Im Uhrzeigersinn: von oben links nach oben rechts, dann nach unten links
+    let cross = (top_right.x - top_left.x) * (bottom_left.y - top_left.y)
+        - (top_right.y - top_left.y) * (bottom_left.x - top_left.x);
+    if cross < 0.0 {
+        std::mem::swap(&mut top_right, &mut bottom_left);
+    }
+    Some([top_left, top_right, bottom_left])
+}
+
+fn decode_at(bitmap: &Bitmap, [top_left, top_right, bottom_left]: [Finder; 3]) -> Option<QrCode> {
+    let module = (top_left.module + top_right.module + bottom_left.module) / 3.0;
+    let right = distance((top_left.x, top_left.y), (top_right.x, top_right.y));
+    let down = distance((top_left.x, top_left.y), (bottom_left.x, bottom_left.y));
+    let modules = (right + down) / 2.0 / module + 7.0;
+    let estimate = ((modules - 17.0) / 4.0).round() as i32;
+
+    // Die Modulgröße ist nur geschätzt, daher auch die Nachbarversionen versuchen
+    for version in [0, -1, 1, -2, 2].map(|offset| estimate + offset) {
+        if !(1..=40).contains(&version) {
+            continue;
+        }
+        let version = version as u8;
+        let size = size(version);
+
+        // Affine Abbildung von Modul- auf Bildkoordinaten; die Finder-Mitten liegen bei 3.5
+        let span = (size - 7) as f32;
+        let map = |mx: f32, my: f32| {
+            let u = (mx - 3.5) / span;
+            let v = (my - 3.5) / span;
+            (
+                top_left.x + u * (top_right.x - top_left.x) + v * (bottom_left.x - top_left.x),
+                top_left.y + u * (top_right.y - top_left.y) + v * (bottom_left.y - top_left.y),
+            )
+        };
+
+        let mut modules = Vec::with_capacity(size * size);
+        for y in 0..size {
+            for x in 0..size {
+                let (px, py) = map(x as f32 + 0.5, y as f32 + 0.5);
+                modules.push(bitmap.is_dark(px.floor() as i32, py.floor() as i32));
+            }
+        }
+
+        if let Some((ec_level, payload)) = decode_grid(&Grid { size, modules }, version) {
+            let corners = [
+                (0.0, 0.0),
+                (size as f32, 0.0),
+                (0.0, size as f32),
+                (size as f32, size as f32),
+            ]
+            .map(|(mx, my)| map(mx, my));
+            let left = corners.iter().map(|c| c.0).fold(f32::MAX, f32::min).floor();
+            let top = corners.iter().map(|c| c.1).fold(f32::MAX, f32::min).floor();
+            let right = corners.iter().map(|c| c.0).fold(f32::MIN, f32::max).ceil();
+            let bottom = corners.iter().map(|c| c.1).fold(f32::MIN, f32::max).ceil();
+            return Some(QrCode {
+                version,
+                ec_level,
+                payload,
+                bounds: PixelRect::new(
+                    left as i32,
+                    top as i32,
+                    (right - left) as u32,
+                    (bottom - top) as u32,
+                ),
+            });
+        }
+    }
+    None
+}
+
+// Höchstzahl der Finder-Kandidaten, die zu Dreiergruppen kombiniert werden
+const MAX_FINDERS: usize = 24;
+
+pub fn decode(bitmap: &Bitmap) -> Vec<QrCode> {
+    let mut finders = find_finders(bitmap);
+    // Einzelne Treffer sind meist Zufall, z.B. in Text
+    finders.retain(|finder| finder.hits >= 2);
+    finders.sort_by_key(|finder| std::cmp::Reverse(finder.hits));
+    finders.truncate(MAX_FINDERS);
+
+    let mut used = vec![false; finders.len()];
+    let mut codes = Vec::new();
+    for a in 0..finders.len() {
+        for b in a + 1..finders.len() {
+            for c in b + 1..finders.len() {
+                if used[a] || used[b] || used[c] {
+                    continue;
+                }
+                let arranged = match arrange([finders[a], finders[b], finders[c]]) {
+                    Some(arranged) => arranged,
+                    None => continue,
+                };
+                if let Some(code) = decode_at(bitmap, arranged) {
+                    used[a] = true;
+                    used[b] = true;
+                    used[c] = true;
+                    codes.push(code);
+                }
+            }
+        }
+    }
+    codes.sort_by_key(|code| (code.bounds.y, code.bounds.x));
+    codes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    fn ecc(data: &[u8], degree: usize) -> Vec<u8> {
+        let mut divisor = vec![0u8; degree];
+        divisor[degree - 1] = 1;
+        let mut root = 1u8;
+        for _ in 0..degree {
+            for j in 0..degree {
+                divisor[j] = mul(divisor[j], root);
+                if j + 1 < degree {
+                    divisor[j] ^= divisor[j + 1];
+                }
+            }
+            root = mul(root, 2);
+        }
+
+        let mut remainder = vec![0u8; degree];
+        for byte in data {
+            let factor = byte ^ remainder.remove(0);
+            remainder.push(0);
+            for (r, d) in remainder.iter_mut().zip(&divisor) {
+                *r ^= mul(*d, factor);
+            }
+        }
+        remainder
+    }
+
+    // Erzeugt ein Symbol mit einem Byte-Segment; Zeilen aus Modulen, true = dunkel
+    fn encode(payload: &[u8], version: u8, ec_level: EcLevel, mask: u8) -> Vec<Vec<bool>> {
+        let size = size(version);
+        let function = function_modules(version);
+        let positions = data_positions(version, &function);
+        let total = positions.len() / 8;
+        let (blocks, ecc_len) = block_layout(version, ec_level);
+        let capacity = total - blocks * ecc_len;
+
+        let mut bits: Vec<bool> = Vec::new();
+        let mut push = |value: u32, count: usize| {
+            for i in (0..count).rev() {
+                bits.push(value >> i & 1 == 1);
+            }
+        };
+        push(4, 4);
+        push(payload.len() as u32, if version < 10 { 8 } else { 16 });
+        for byte in payload {
+            push(*byte as u32, 8);
+        }
+        assert!(bits.len() <= capacity * 8, "payload too long");
+        let terminator = (capacity * 8 - bits.len()).min(4);
+        bits.extend(std::iter::repeat_n(false, terminator));
+        while !bits.len().is_multiple_of(8) {
+            bits.push(false);
+        }
+        let mut data: Vec<u8> = bits
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0, |value, bit| value << 1 | *bit as u8))
+            .collect();
+        for pad in [0xec, 0x11].iter().cycle() {
+            if data.len() == capacity {
+                break;
+            }
+            data.push(*pad);
+        }
+
+        let lengths = block_lengths(total, blocks);
+        let mut rest = &data[..];
+        let mut split: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+        for len in &lengths {
+            let (block, tail) = rest.split_at(len - ecc_len);
+            split.push((block.to_vec(), ecc(block, ecc_len)));
+            rest = tail;
+        }
+        let mut codewords = Vec::new();
+        for i in 0..lengths[blocks - 1] - ecc_len {
+            codewords.extend(split.iter().filter_map(|(data, _)| data.get(i)));
+        }
+        for i in 0..ecc_len {
+            codewords.extend(split.iter().map(|(_, ecc)| ecc[i]));
+        }
+
+        let mut grid = vec![vec![false; size]; size];
+        for (i, row) in grid.iter_mut().enumerate() {
+            row[6] = i.is_multiple_of(2);
+        }
+        grid[6] = (0..size).map(|i| i.is_multiple_of(2)).collect();
+        for (cx, cy) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4isize..=4 {
+                for dx in -4isize..=4 {
+                    let (x, y) = (cx as isize + dx, cy as isize + dy);
+                    if (0..size as isize).contains(&x) && (0..size as isize).contains(&y) {
+                        let ring = dx.abs().max(dy.abs());
+                        grid[y as usize][x as usize] = ring != 2 && ring != 4;
+                    }
+                }
+            }
+        }
+        let alignment = alignment_positions(version);
+        let last = alignment.len().saturating_sub(1);
+        for (i, cy) in alignment.iter().enumerate() {
+            for (j, cx) in alignment.iter().enumerate() {
+                if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                    continue;
+                }
+                for dy in -2isize..=2 {
+                    for dx in -2isize..=2 {
+                        let (x, y) = ((*cx as isize + dx) as usize, (*cy as isize + dy) as usize);
+                        grid[y][x] = dx.abs().max(dy.abs()) != 1;
+                    }
+                }
+            }
+        }
+        let format = format_bits(ec_level, mask);
+        for copy in format_positions(size) {
+            for (i, (x, y)) in copy.iter().enumerate() {
+                grid[*y][*x] = format >> i & 1 == 1;
+            }
+        }
+        grid[size - 8][8] = true;
+        if version >= 7 {
+            let bits = version_bits(version);
+            for i in 0..18 {
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                grid[b][a] = bits >> i & 1 == 1;
+                grid[a][b] = bits >> i & 1 == 1;
+            }
+        }
+
+        for (index, (x, y)) in positions.iter().enumerate() {
+            let bit = codewords
+                .get(index / 8)
+                .is_some_and(|byte| byte >> (7 - index % 8) & 1 == 1);
+            grid[*y][*x] = bit ^ mask_applies(mask, *x, *y);
+        }
+        grid
+    }
+
+    fn render(grid: &[Vec<bool>], scale: u32) -> RgbaImage {
+        let quiet = 4 * scale;
+        let side = grid.len() as u32 * scale + 2 * quiet;
+        RgbaImage::from_fn(side, side, |x, y| {
+            let module = |v: u32| (v >= quiet).then(|| ((v - quiet) / scale) as usize);
+            let dark = match (module(x), module(y)) {
+                (Some(mx), Some(my)) => grid.get(my).and_then(|row| row.get(mx)) == Some(&true),
+                _ => false,
+            };
+            if dark {
+                Rgba([0, 0, 0, 255])
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
+        })
+    }
+
+    fn decode_image(image: &RgbaImage) -> Vec<QrCode> {
+        decode(&Bitmap::from_image(image))
+    }
+
+    #[test]
+    fn data_module_count_matches_the_specification() {
+        for version in 1..=40u8 {
+            let v = version as usize;
+            let mut expected = (16 * v + 128) * v + 64;
+            if version >= 2 {
+                let count = v / 7 + 2;
+                expected -= (25 * count - 10) * count - 55;
+                if version >= 7 {
+                    expected -= 36;
+                }
+            }
+            let function = function_modules(version);
+            assert_eq!(
+                data_positions(version, &function).len(),
+                expected,
+                "{}",
+                version
+            );
+        }
+    }
+
+    #[test]
+    fn reed_solomon_corrects_up_to_half_the_ecc() {
+        let data: Vec<u8> = (0..40).map(|i| (i * 7 + 3) as u8).collect();
+        let mut block = data.clone();
+        block.extend(ecc(&data, 10));
+
+        let mut damaged = block.clone();
+        for (position, value) in [(0, 0xff), (11, 0x00), (29, 0x5a), (44, 0x01), (49, 0x80)] {
+            damaged[position] ^= value;
+        }
+        assert!(correct_errors(&mut damaged, 10));
+        assert_eq!(damaged, block);
+
+        for position in [1, 5, 9, 13, 17, 21] {
+            damaged[position] ^= 0x42;
+        }
+        assert!(!correct_errors(&mut damaged, 10) || damaged != block);
+    }
+
+    #[test]
+    fn decodes_small_code() {
+        let image = render(&encode(b"Hello, snip!", 1, EcLevel::M, 2), 4);
+        let codes = decode_image(&image);
+        assert_eq!(codes.len(), 1);
+        assert_eq!(codes[0].payload, b"Hello, snip!");
+        assert_eq!(codes[0].version, 1);
+        assert_eq!(codes[0].ec_level, EcLevel::M);
+        assert_eq!(codes[0].bounds, PixelRect::new(16, 16, 21 * 4, 21 * 4));
+    }
+
+    #[test]
+    fn decodes_every_mask_and_level() {
+        for (mask, ec_level) in (0..8).zip(EcLevel::ALL.iter().cycle()) {
+            let text = format!("mask {} level {:?}", mask, ec_level);
+            let image = render(&encode(text.as_bytes(), 3, *ec_level, mask), 3);
+            let codes = decode_image(&image);
+            assert_eq!(codes.len(), 1, "{}", text);
+            assert_eq!(codes[0].text(), text);
+        }
+    }
+
+    #[test]
+    fn decodes_larger_version_with_multiple_blocks() {
+        let text = "https://example.com/snips/2024/ä-ö-ü?query=".repeat(2);
+        let image = render(&encode(text.as_bytes(), 9, EcLevel::Q, 5), 3);
+        let codes = decode_image(&image);
+        assert_eq!(codes.len(), 1);
+        assert_eq!(codes[0].version, 9);
+        assert_eq!(codes[0].text(), text);
+    }
+
+    #[test]
+    fn decodes_version_with_version_information() {
+        let text = "0123456789abcdef".repeat(8);
+        let image = render(&encode(text.as_bytes(), 7, EcLevel::L, 3), 2);
+        let codes = decode_image(&image);
+        assert_eq!(codes.len(), 1);
+        assert_eq!(codes[0].version, 7);
+        assert_eq!(codes[0].text(), text);
+    }
+
+    #[test]
+    fn decodes_rotated_code() {
+        let image = render(&encode(b"rotated", 2, EcLevel::L, 0), 3);
+        for rotated in [
+            image::imageops::rotate90(&image),
+            image::imageops::rotate180(&image),
+            image::imageops::rotate270(&image),
+        ] {
+            let codes = decode_image(&rotated);
+            assert_eq!(codes.len(), 1);
+            assert_eq!(codes[0].payload, b"rotated");
+        }
+    }
+
+    #[test]
+    fn corrects_damaged_modules() {
+        let mut grid = encode(b"still readable", 2, EcLevel::H, 4);
+        // Ein Fleck in der unteren rechten Ecke trifft nur Datenmodule
+        for row in grid.iter_mut().skip(18).take(4) {
+            for module in row.iter_mut().skip(18).take(4) {
+                *module = !*module;
+            }
+        }
+        let codes = decode_image(&render(&grid, 3));
+        assert_eq!(codes.len(), 1);
+        assert_eq!(codes[0].payload, b"still readable");
+    }
+
+    #[test]
+    fn finds_two_codes_side_by_side() {
+        let first = render(&encode(b"left", 1, EcLevel::L, 1), 3);
+        let second = render(&encode(b"right", 2, EcLevel::M, 6), 4);
+        let mut image = RgbaImage::from_pixel(
+            first.width() + second.width() + 20,
+            second.height(),
+            Rgba([255, 255, 255, 255]),
+        );
+        image::imageops::replace(&mut image, &first, 0, 0);
+        image::imageops::replace(&mut image, &second, first.width() + 20, 0);
+
+        let mut payloads: Vec<Vec<u8>> = decode_image(&image)
+            .into_iter()
+            .map(|code| code.payload)
+            .collect();
+        payloads.sort();
+        assert_eq!(payloads, vec![b"left".to_vec(), b"right".to_vec()]);
+    }
+
+    #[test]
+    fn parses_numeric_and_alphanumeric_segments() {
+        // Numerisch "01234567", danach alphanumerisch "AC-42"
+        let mut bits = String::new();
+        bits.push_str("0001");
+        bits.push_str("0000001000");
+        bits.push_str("0000001100");
+        bits.push_str("0101011001");
+        bits.push_str("1000011");
+        bits.push_str("0010");
+        bits.push_str("000000101");
+        bits.push_str("00111001110");
+        bits.push_str("11100111001");
+        bits.push_str("000010");
+        bits.push_str("0000");
+        while !bits.len().is_multiple_of(8) {
+            bits.push('0');
+        }
+        let data: Vec<u8> = bits
+            .as_bytes()
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0, |value, bit| value << 1 | (bit - b'0')))
+            .collect();
+        assert_eq!(parse_segments(&data, 1).unwrap(), b"01234567AC-42");
+    }
+}
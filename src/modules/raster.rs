@@ -0,0 +1,628 @@
+use crate::modules::backend::{Color, Overlay, Rect};
+use image::{Rgba, RgbaImage};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Border {
+    pub color: Color,
+    pub width: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OverlayStyle {
+    pub dim: Color,
+    pub border: Option<Border>,
+    pub handle_fill: Color,
+    pub handle_outline: Color,
+    pub highlight: Color,
+}
+
+impl Default for OverlayStyle {
+    // Entspricht der Ebene mit Deckkraft 0.6 aus Drawing::draw_overlay
+    fn default() -> Self {
+        OverlayStyle {
+            dim: Color::new(0.0, 0.0, 0.0, 0.6),
+            border: None,
+            handle_fill: Color::new(1.0, 1.0, 1.0, 1.0),
+            handle_outline: Color::new(0.0, 0.0, 0.0, 1.0),
+            highlight: Color::new(0.0, 0.8, 0.3, 1.0),
+        }
+    }
+}
+
+pub fn to_rgba(color: Color) -> Rgba<u8> {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Rgba([
+        channel(color.r),
+        channel(color.g),
+        channel(color.b),
+        channel(color.a),
+    ])
+}
+
+pub fn fill(target: &mut RgbaImage, color: Color) {
+    let pixel = to_rgba(color);
+    for p in target.pixels_mut() {
+        *p = pixel;
+    }
+}
+
+// Zeichnet `color` mit der gegebenen Abdeckung (0..1) über das Pixel (source-over, nicht vormultipliert)
+pub fn blend(dst: &mut Rgba<u8>, color: Color, coverage: f32) {
+    let src_a = (color.a * coverage).clamp(0.0, 1.0);
+    if src_a <= 0.0 {
+        return;
+    }
+
+    let dst_a = dst[3] as f32 / 255.0;
+    let out_a = src_a + dst_a * (1.0 - src_a);
+    let mix = |src: f32, dst: u8| {
+        let dst = dst as f32 / 255.0;
+        let value = (src * src_a + dst * dst_a * (1.0 - src_a)) / out_a;
+        (value.clamp(0.0, 1.0) * 255.0).round() as u8
+    };
+
+    *dst = Rgba([
+        mix(color.r, dst[0]),
+        mix(color.g, dst[1]),
+        mix(color.b, dst[2]),
+        (out_a * 255.0).round() as u8,
+    ]);
+}
+
+// Anteil des Pixels (x, y) der vom Rechteck bedeckt wird, für Kantenglättung
+pub fn coverage(rect: &Rect, x: u32, y: u32) -> f32 {
+    let (px, py) = (x as f32, y as f32);
+    let w = (rect.right.min(px + 1.0) - rect.left.max(px)).max(0.0);
+    let h = (rect.bottom.min(py + 1.0) - rect.top.max(py)).max(0.0);
+    w * h
+}
+
+pub fn render_overlay(
+    width: u32,
+    height: u32,
+    overlay: &Overlay,
+    style: &OverlayStyle,
+) -> RgbaImage {
+    let mut target = RgbaImage::new(width, height);
+    draw_overlay(&mut target, overlay, style);
+    target
+}
+
+// Software-Variante von Drawing::draw_overlay: außerhalb der Auswahl abdunkeln,
+// innen transparent lassen, optional mit Rahmen und Anfassern um die Auswahl
+pub fn draw_overlay(target: &mut RgbaImage, overlay: &Overlay, style: &OverlayStyle) {
+    draw_overlay_on(target, None, overlay, style);
+}
+
+// Wie draw_overlay, aber über einem eingefrorenen Bild des Desktops
+pub fn draw_overlay_on(
+    target: &mut RgbaImage,
+    backdrop: Option<&RgbaImage>,
+    overlay: &Overlay,
+    style: &OverlayStyle,
+) {
+    fill(target, Color::new(0.0, 0.0, 0.0, 0.0));
+    if let Some(backdrop) = backdrop {
+        image::imageops::replace(target, backdrop, 0, 0);
+    }
+
+    // Ohne Auswahl bleibt das Overlay leer, ein eingefrorenes Bild wird aber ganz abgedunkelt
+    let rect = match (overlay.selection, backdrop) {
+        (Some(rect), _) => rect.normalized(),
+        (None, Some(_)) => Rect::default(),
+        (None, None) => return,
+    };
+
+    for (x, y, p) in target.enumerate_pixels_mut() {
+        let hole = coverage(&rect, x, y);
+        blend(p, style.dim, 1.0 - hole);
+    }
+
+    if overlay.selection.is_none() {
+        return;
+    }
+
+    if let Some(border) = style.border {
+        stroke_rect(target, &rect, border.color, border.width);
+    }
+
+    for handle in &overlay.handles {
+        fill_rect(target, handle, style.handle_fill);
+        stroke_rect(target, handle, style.handle_outline, 1.0);
+    }
+
+    for highlight in &overlay.highlights {
+        stroke_rect(target, highlight, style.highlight, 2.0);
+    }
+}
+
+// Pixelbereich, den ein Rechteck im Bild berührt
+fn pixel_span(target: &RgbaImage, rect: &Rect) -> (u32, u32, u32, u32) {
+    let x0 = rect.left.floor().max(0.0) as u32;
+    let y0 = rect.top.floor().max(0.0) as u32;
+    let x1 = (rect.right.ceil().max(0.0) as u32).min(target.width());
+    let y1 = (rect.bottom.ceil().max(0.0) as u32).min(target.height());
+    (x0, y0, x1, y1)
+}
+
+pub fn fill_rect(target: &mut RgbaImage, rect: &Rect, color: Color) {
+    let rect = rect.normalized();
+    let (x0, y0, x1, y1) = pixel_span(target, &rect);
+
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let amount = coverage(&rect, x, y);
+            blend(target.get_pixel_mut(x, y), color, amount);
+        }
+    }
+}
+
+// Rahmen mittig auf den Kanten des Rechtecks
+pub fn stroke_rect(target: &mut RgbaImage, rect: &Rect, color: Color, width: f32) {
+    let rect = rect.normalized();
+    let half = width / 2.0;
+    let outer = Rect::new(
+        rect.left - half,
+        rect.top - half,
+        rect.right + half,
+        rect.bottom + half,
+    );
+    let inner = Rect::new(
+        rect.left + half,
+        rect.top + half,
+        (rect.right - half).max(rect.left + half),
+        (rect.bottom - half).max(rect.top + half),
+    );
+
+    let (x0, y0, x1, y1) = pixel_span(target, &outer);
+
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let amount = coverage(&outer, x, y) - coverage(&inner, x, y);
+            blend(target.get_pixel_mut(x, y), color, amount);
+        }
+    }
+}
+
+// Deckt die Pixel in `bounds` anhand des Abstands der Pixelmitte zur Kante der Form ab
+// (negativ = innen); ergibt etwa einen Pixel Kantenglättung
+fn fill_distance<F>(target: &mut RgbaImage, bounds: &Rect, color: Color, distance: F)
+where
+    F: Fn(f32, f32) -> f32,
+{
+    let bounds = bounds.normalized();
+    let bounds = Rect::new(
+        bounds.left - 1.0,
+        bounds.top - 1.0,
+        bounds.right + 1.0,
+        bounds.bottom + 1.0,
+    );
+    let (x0, y0, x1, y1) = pixel_span(target, &bounds);
+
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let amount = (0.5 - distance(x as f32 + 0.5, y as f32 + 0.5)).clamp(0.0, 1.0);
+            if amount > 0.0 {
+                blend(target.get_pixel_mut(x, y), color, amount);
+            }
+        }
+    }
+}
+
+fn segment_distance(x: f32, y: f32, from: (f32, f32), to: (f32, f32)) -> f32 {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 {
+        (((x - from.0) * dx + (y - from.1) * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (px, py) = (from.0 + t * dx - x, from.1 + t * dy - y);
+    (px * px + py * py).sqrt()
+}
+
+fn points_bounds(points: &[(f32, f32)], margin: f32) -> Rect {
+    let mut bounds = Rect::new(f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for &(x, y) in points {
+        bounds.left = bounds.left.min(x - margin);
+        bounds.top = bounds.top.min(y - margin);
+        bounds.right = bounds.right.max(x + margin);
+        bounds.bottom = bounds.bottom.max(y + margin);
+    }
+    bounds
+}
+
+// Linienzug mit runden Enden und Ecken; ein einzelner Punkt ergibt einen Kreis
+pub fn stroke_polyline(target: &mut RgbaImage, points: &[(f32, f32)], color: Color, width: f32) {
+    let segments: Vec<((f32, f32), (f32, f32))> = match points {
+        [] => return,
+        [point] => vec![(*point, *point)],
+        _ => points.windows(2).map(|pair| (pair[0], pair[1])).collect(),
+    };
+
+    let half = width / 2.0;
+    fill_distance(target, &points_bounds(points, half), color, |x, y| {
+        segments
+            .iter()
+            .map(|&(from, to)| segment_distance(x, y, from, to))
+            .fold(f32::INFINITY, f32::min)
+            - half
+    });
+}
+
+pub fn stroke_line(
+    target: &mut RgbaImage,
+    from: (f32, f32),
+    to: (f32, f32),
+    color: Color,
+    width: f32,
+) {
+    stroke_polyline(target, &[from, to], color, width);
+}
+
+// Gerade-Ungerade-Regel, wie D2D1_FILL_MODE_ALTERNATE
+fn polygon_contains(points: &[(f32, f32)], x: f32, y: f32) -> bool {
+    let mut inside = false;
+    let mut previous = points[points.len() - 1];
+    for &point in points {
+        if (point.1 > y) != (previous.1 > y)
+            && x < (previous.0 - point.0) * (y - point.1) / (previous.1 - point.1) + point.0
+        {
+            inside = !inside;
+        }
+        previous = point;
+    }
+    inside
+}
+
+pub fn fill_polygon(target: &mut RgbaImage, points: &[(f32, f32)], color: Color) {
+    if points.len() < 3 {
+        return;
+    }
+
+    fill_distance(target, &points_bounds(points, 0.0), color, |x, y| {
+        let edge = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(&from, &to)| segment_distance(x, y, from, to))
+            .fold(f32::INFINITY, f32::min);
+        if polygon_contains(points, x, y) {
+            -edge
+        } else {
+            edge
+        }
+    });
+}
+
+// Näherung des Abstands zur Ellipse über den Gradienten der impliziten Gleichung
+fn ellipse_distance(rect: &Rect, x: f32, y: f32) -> f32 {
+    let (a, b) = (rect.width() / 2.0, rect.height() / 2.0);
+    let (dx, dy) = (x - rect.left - a, y - rect.top - b);
+
+    let f = ((dx / a).powi(2) + (dy / b).powi(2)).sqrt();
+    if f < 1e-6 {
+        return -a.min(b);
+    }
+    let (gx, gy) = (dx / (a * a * f), dy / (b * b * f));
+    (f - 1.0) / (gx * gx + gy * gy).sqrt()
+}
+
+pub fn fill_ellipse(target: &mut RgbaImage, rect: &Rect, color: Color) {
+    let rect = rect.normalized();
+    if rect.width() <= 0.0 || rect.height() <= 0.0 {
+        return;
+    }
+    fill_distance(target, &rect, color, |x, y| ellipse_distance(&rect, x, y));
+}
+
+// Rahmen mittig auf der Ellipse, wie bei stroke_rect
+pub fn stroke_ellipse(target: &mut RgbaImage, rect: &Rect, color: Color, width: f32) {
+    let rect = rect.normalized();
+    if rect.width() <= 0.0 || rect.height() <= 0.0 {
+        return;
+    }
+
+    let half = width / 2.0;
+    let bounds = Rect::new(
+        rect.left - half,
+        rect.top - half,
+        rect.right + half,
+        rect.bottom + half,
+    );
+    fill_distance(target, &bounds, color, |x, y| {
+        ellipse_distance(&rect, x, y).abs() - half
+    });
+}
+
+// Textmarker: dunkelt nur ab (Minimum je Kanal), entspricht D2D1_PRIMITIVE_BLEND_MIN
+pub fn darken_rect(target: &mut RgbaImage, rect: &Rect, color: Color) {
+    let rect = rect.normalized();
+    let marker = to_rgba(color);
+    let (x0, y0, x1, y1) = pixel_span(target, &rect);
+
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let amount = coverage(&rect, x, y) * color.a.clamp(0.0, 1.0);
+            let p = target.get_pixel_mut(x, y);
+            for channel in 0..3 {
+                let dst = p[channel] as f32;
+                let darker = dst.min(marker[channel] as f32);
+                p[channel] = (dst + (darker - dst) * amount).round() as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deckkraft je Pixel, zeilenweise; Vergleichsbilder werden so von Hand lesbar
+    fn alpha_rows(image: &RgbaImage) -> Vec<Vec<u8>> {
+        image
+            .rows()
+            .map(|row| row.map(|p| p[3]).collect())
+            .collect()
+    }
+
+    fn selection(rect: Rect) -> Overlay {
+        Overlay {
+            selection: Some(rect),
+            ..Overlay::default()
+        }
+    }
+
+    #[test]
+    fn dims_everything_outside_the_cut_out() {
+        let image = render_overlay(
+            6,
+            4,
+            &selection(Rect::new(1.0, 1.0, 4.0, 3.0)),
+            &OverlayStyle::default(),
+        );
+
+        assert_eq!(
+            alpha_rows(&image),
+            vec![
+                vec![153, 153, 153, 153, 153, 153],
+                vec![153, 0, 0, 0, 153, 153],
+                vec![153, 0, 0, 0, 153, 153],
+                vec![153, 153, 153, 153, 153, 153],
+            ]
+        );
+        assert!(image.pixels().all(|p| p[0] == 0 && p[1] == 0 && p[2] == 0));
+    }
+
+    #[test]
+    fn fractional_cut_out_is_anti_aliased() {
+        // Halb bedeckte Pixel bekommen die halbe Abdunklung, die Ecke drei Viertel
+        let image = render_overlay(
+            6,
+            4,
+            &selection(Rect::new(4.0, 2.5, 1.5, 1.0)),
+            &OverlayStyle::default(),
+        );
+
+        assert_eq!(
+            alpha_rows(&image),
+            vec![
+                vec![153, 153, 153, 153, 153, 153],
+                vec![153, 77, 0, 0, 153, 153],
+                vec![153, 115, 77, 77, 153, 153],
+                vec![153, 153, 153, 153, 153, 153],
+            ]
+        );
+    }
+
+    #[test]
+    fn border_covers_pixels_centered_on_the_edge() {
+        let style = OverlayStyle {
+            dim: Color::new(0.0, 0.0, 0.0, 0.0),
+            border: Some(Border {
+                color: Color::new(1.0, 1.0, 1.0, 1.0),
+                width: 1.0,
+            }),
+            ..OverlayStyle::default()
+        };
+        let image = render_overlay(8, 8, &selection(Rect::new(2.0, 2.0, 6.0, 6.0)), &style);
+
+        assert_eq!(
+            alpha_rows(&image),
+            vec![
+                vec![0, 0, 0, 0, 0, 0, 0, 0],
+                vec![0, 64, 128, 128, 128, 128, 64, 0],
+                vec![0, 128, 191, 128, 128, 191, 128, 0],
+                vec![0, 128, 128, 0, 0, 128, 128, 0],
+                vec![0, 128, 128, 0, 0, 128, 128, 0],
+                vec![0, 128, 191, 128, 128, 191, 128, 0],
+                vec![0, 64, 128, 128, 128, 128, 64, 0],
+                vec![0, 0, 0, 0, 0, 0, 0, 0],
+            ]
+        );
+        // Teilweise bedeckte Pixel behalten die volle Farbe, nur die Deckkraft sinkt
+        assert!(image.pixels().all(|p| p[3] == 0 || p[0] == 255));
+    }
+
+    #[test]
+    fn frozen_backdrop_shows_through_the_cut_out() {
+        let backdrop = RgbaImage::from_pixel(5, 3, Rgba([200, 100, 50, 255]));
+        let mut target = RgbaImage::new(5, 3);
+
+        draw_overlay_on(
+            &mut target,
+            Some(&backdrop),
+            &selection(Rect::new(1.0, 1.0, 3.0, 2.0)),
+            &OverlayStyle::default(),
+        );
+
+        let dimmed = Rgba([80, 40, 20, 255]);
+        for (x, y, p) in target.enumerate_pixels() {
+            let inside = (1..3).contains(&x) && y == 1;
+            assert_eq!(
+                *p,
+                if inside { backdrop[(x, y)] } else { dimmed },
+                "({}, {})",
+                x,
+                y
+            );
+        }
+
+        // Ohne Auswahl wird das ganze Bild abgedunkelt
+        draw_overlay_on(
+            &mut target,
+            Some(&backdrop),
+            &Overlay::default(),
+            &OverlayStyle::default(),
+        );
+        assert!(target.pixels().all(|p| *p == dimmed));
+    }
+
+    #[test]
+    fn empty_overlay_without_backdrop_stays_transparent() {
+        let image = render_overlay(3, 3, &Overlay::default(), &OverlayStyle::default());
+        assert!(image.pixels().all(|p| *p == Rgba([0, 0, 0, 0])));
+    }
+
+    const BLACK: Color = Color::new(0.0, 0.0, 0.0, 1.0);
+
+    fn white(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, Rgba([255, 255, 255, 255]))
+    }
+
+    // Deckung von Schwarz auf Weiß, 0 bis 255
+    fn ink(image: &RgbaImage, x: u32, y: u32) -> u8 {
+        255 - image.get_pixel(x, y)[0]
+    }
+
+    fn ink_rows(image: &RgbaImage) -> Vec<Vec<u8>> {
+        (0..image.height())
+            .map(|y| (0..image.width()).map(|x| ink(image, x, y)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn fill_rect_blends_partial_pixels() {
+        let mut image = white(5, 3);
+        // Vertauschte Kanten werden normalisiert
+        fill_rect(&mut image, &Rect::new(3.5, 2.0, 1.0, 1.0), BLACK);
+        assert_eq!(
+            ink_rows(&image),
+            vec![
+                vec![0, 0, 0, 0, 0],
+                vec![0, 255, 255, 127, 0],
+                vec![0, 0, 0, 0, 0],
+            ]
+        );
+    }
+
+    #[test]
+    fn stroke_rect_is_centered_on_the_edge() {
+        let mut image = white(8, 8);
+        stroke_rect(&mut image, &Rect::new(2.0, 2.0, 6.0, 6.0), BLACK, 2.0);
+        assert_eq!(
+            ink_rows(&image),
+            vec![
+                vec![0, 0, 0, 0, 0, 0, 0, 0],
+                vec![0, 255, 255, 255, 255, 255, 255, 0],
+                vec![0, 255, 255, 255, 255, 255, 255, 0],
+                vec![0, 255, 255, 0, 0, 255, 255, 0],
+                vec![0, 255, 255, 0, 0, 255, 255, 0],
+                vec![0, 255, 255, 255, 255, 255, 255, 0],
+                vec![0, 255, 255, 255, 255, 255, 255, 0],
+                vec![0, 0, 0, 0, 0, 0, 0, 0],
+            ]
+        );
+    }
+
+    #[test]
+    fn lines_have_round_caps() {
+        let mut image = white(10, 5);
+        stroke_line(&mut image, (2.0, 2.5), (7.0, 2.5), BLACK, 1.0);
+        // Die Enden ragen um die halbe Breite über die Punkte hinaus
+        assert_eq!(
+            ink_rows(&image),
+            vec![
+                vec![0; 10],
+                vec![0; 10],
+                vec![0, 127, 255, 255, 255, 255, 255, 127, 0, 0],
+                vec![0; 10],
+                vec![0; 10],
+            ]
+        );
+
+        // Ein einzelner Punkt wird zum Kreis
+        let mut image = white(7, 7);
+        stroke_polyline(&mut image, &[(3.5, 3.5)], BLACK, 4.0);
+        assert_eq!(ink(&image, 3, 3), 255);
+        assert_eq!(ink(&image, 3, 2), 255);
+        assert_eq!(ink(&image, 3, 1), 127);
+        assert_eq!(ink(&image, 0, 0), 0);
+        assert_eq!(ink(&image, 6, 6), 0);
+
+        // Ohne Punkte passiert nichts
+        let mut image = white(3, 3);
+        stroke_polyline(&mut image, &[], BLACK, 4.0);
+        assert_eq!(image, white(3, 3));
+    }
+
+    #[test]
+    fn polygons_fill_their_inside() {
+        let mut image = white(10, 10);
+        fill_polygon(&mut image, &[(1.0, 1.0), (9.0, 1.0), (1.0, 9.0)], BLACK);
+        assert_eq!(ink(&image, 2, 2), 255);
+        assert_eq!(ink(&image, 3, 5), 255);
+        assert_eq!(ink(&image, 8, 8), 0);
+        assert_eq!(ink(&image, 6, 6), 0);
+        // Die Pixelmitte liegt auf der Diagonalen
+        assert_eq!(ink(&image, 4, 5), 127);
+
+        // Weniger als drei Punkte sind keine Fläche
+        let mut image = white(4, 4);
+        fill_polygon(&mut image, &[(0.0, 0.0), (4.0, 4.0)], BLACK);
+        assert_eq!(image, white(4, 4));
+    }
+
+    #[test]
+    fn ellipses_fill_and_stroke() {
+        let rect = Rect::new(1.0, 1.0, 11.0, 11.0);
+
+        let mut image = white(12, 12);
+        fill_ellipse(&mut image, &rect, BLACK);
+        assert_eq!(ink(&image, 6, 6), 255);
+        assert_eq!(ink(&image, 2, 6), 255);
+        assert!(ink(&image, 1, 6) > 240);
+        assert_eq!(ink(&image, 1, 1), 0);
+        assert_eq!(ink(&image, 11, 11), 0);
+
+        let mut image = white(12, 12);
+        stroke_ellipse(&mut image, &rect, BLACK, 2.0);
+        assert_eq!(ink(&image, 6, 6), 0);
+        assert_eq!(ink(&image, 1, 6), 255);
+        assert_eq!(ink(&image, 6, 10), 255);
+        assert_eq!(ink(&image, 0, 0), 0);
+
+        // Ellipsen ohne Fläche zeichnen nichts
+        let mut image = white(4, 4);
+        fill_ellipse(&mut image, &Rect::new(1.0, 1.0, 1.0, 3.0), BLACK);
+        stroke_ellipse(&mut image, &Rect::new(1.0, 1.0, 3.0, 1.0), BLACK, 2.0);
+        assert_eq!(image, white(4, 4));
+    }
+
+    #[test]
+    fn highlight_only_darkens() {
+        let mut image = RgbaImage::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([10, 200, 10, 255])
+            }
+        });
+        darken_rect(
+            &mut image,
+            &Rect::new(0.0, 0.0, 2.0, 1.0),
+            Color::new(1.0, 0.5, 0.2, 1.0),
+        );
+        assert_eq!(image.get_pixel(0, 0), &Rgba([255, 128, 51, 255]));
+        assert_eq!(image.get_pixel(1, 0), &Rgba([10, 128, 10, 255]));
+    }
+}
@@ -0,0 +1,706 @@
+use crate::modules::errorhandler::SnipError;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::codecs::png::PngEncoder;
+use image::{ColorType, Delay, ImageError, RgbaImage};
+use once_cell::sync::Lazy;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Kurze Animationen eines Bildschirmbereichs, z.B. für Fehlerberichte. Der Recorder nimmt
+// Frames mit Zeitstempel entgegen und fasst identische Frames zusammen; kodiert wird erst am
+// Ende. Passt das Ergebnis nicht ins Größenbudget, wird jeder zweite Frame verworfen.
+//
+// APNG wird von Hand zusammengesetzt: image 0.23 kodiert jeden Frame als PNG, dessen
+// IDAT-Daten als fdAT-Chunks übernommen werden. Ab dem zweiten Frame wird nur das
+// Rechteck mit Änderungen gespeichert.
+
+pub const MAX_FPS: f32 = 50.0;
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+const APNG_DISPOSE_NONE: u8 = 0;
+const APNG_BLEND_SOURCE: u8 = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+}
+
+impl AnimationFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "gif" => Some(AnimationFormat::Gif),
+            "png" | "apng" => Some(AnimationFormat::Apng),
+            _ => None,
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        path.as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(Self::from_extension)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "GIF",
+            AnimationFormat::Apng => "APNG",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecordingOptions {
+    // Frames pro Sekunde, höchstens MAX_FPS
+    pub fps: f32,
+    pub max_duration: Duration,
+    // Höchstgröße der kodierten Datei in Bytes
+    pub max_bytes: usize,
+    // 1 bis 30; kleiner heißt bessere Palette, aber langsamer (NeuQuant)
+    pub gif_speed: i32,
+}
+
+impl Default for RecordingOptions {
+    fn default() -> Self {
+        RecordingOptions {
+            fps: 10.0,
+            max_duration: Duration::from_secs(10),
+            max_bytes: 8 * 1024 * 1024,
+            gif_speed: 10,
+        }
+    }
+}
+
+impl RecordingOptions {
+    pub fn validate(&self) -> Result<(), SnipError> {
+        if !(self.fps > 0.0 && self.fps <= MAX_FPS) {
+            return Err(SnipError::Config(format!(
+                "Frame rate {} must be between 0 and {}",
+                self.fps, MAX_FPS
+            )));
+        }
+        if self.max_duration.is_zero() {
+            return Err(SnipError::Config("Recording duration is zero".to_string()));
+        }
+        if self.max_bytes == 0 {
+            return Err(SnipError::Config("Size budget is zero".to_string()));
+        }
+        if !(1..=30).contains(&self.gif_speed) {
+            return Err(SnipError::Config(format!(
+                "GIF speed {} must be between 1 and 30",
+                self.gif_speed
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_nanos((1e9 / self.fps as f64).round() as u64)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedFrame {
+    pub image: RgbaImage,
+    // Wie lange der Frame angezeigt wird
+    pub delay: Duration,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recording {
+    pub frames: Vec<RecordedFrame>,
+}
+
+// Sammelt Frames; Zeitstempel zählen ab Beginn der Aufnahme
+pub struct Recorder {
+    options: RecordingOptions,
+    frames: Vec<(RgbaImage, Duration)>,
+    last: Duration,
+}
+
+impl Recorder {
+    pub fn new(options: RecordingOptions) -> Self {
+        Recorder {
+            options,
+            frames: Vec::new(),
+            last: Duration::ZERO,
+        }
+    }
+
+    // Gibt false zurück, sobald die Höchstdauer erreicht ist; der Frame wird dann verworfen.
+    // Ein Frame, der dem vorigen gleicht, verlängert nur dessen Anzeigedauer.
+    pub fn push(&mut self, image: RgbaImage, at: Duration) -> bool {
+        if self.is_full(at) {
+            return false;
+        }
+        self.last = self.last.max(at);
+        let unchanged = self.frames.last().is_some_and(|(previous, _)| {
+            previous.dimensions() == image.dimensions() && previous.as_raw() == image.as_raw()
+        });
+        if !unchanged {
+            self.frames.push((image, at));
+        }
+        true
+    }
+
+    pub fn is_full(&self, at: Duration) -> bool {
+        at >= self.options.max_duration
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // Der letzte Frame bleibt bis `end` stehen, mindestens aber ein Intervall lang
+    pub fn finish(self, end: Duration) -> Recording {
+        let end = end
+            .max(self.last + self.options.interval())
+            .min(self.options.max_duration);
+        let starts: Vec<Duration> = self.frames.iter().map(|(_, at)| *at).collect();
+        let frames = self
+            .frames
+            .into_iter()
+            .enumerate()
+            .map(|(index, (image, at))| RecordedFrame {
+                image,
+                delay: starts
+                    .get(index + 1)
+                    .copied()
+                    .unwrap_or(end)
+                    .saturating_sub(at),
+            })
+            .collect();
+        Recording { frames }
+    }
+}
+
+// Nimmt mit der eingestellten Bildrate auf, bis die Höchstdauer erreicht ist oder `stop` true
+// liefert. Verpasste Takte werden übersprungen statt nachgeholt.
+pub fn record<G, S>(
+    mut grab: G,
+    options: &RecordingOptions,
+    mut stop: S,
+) -> Result<Recording, SnipError>
+where
+    G: FnMut() -> anyhow::Result<RgbaImage>,
+    S: FnMut() -> bool,
+{
+    options.validate()?;
+    let interval = options.interval();
+    let mut recorder = Recorder::new(*options);
+    let started = Instant::now();
+    let mut tick = Duration::ZERO;
+
+    while !stop() {
+        let image =
+            grab().map_err(|e| SnipError::capture("Failed to grab a frame").caused_by(e))?;
+        if !recorder.push(image, started.elapsed()) {
+            break;
+        }
+
+        let elapsed = started.elapsed();
+        while tick <= elapsed {
+            tick += interval;
+        }
+        if recorder.is_full(tick) {
+            break;
+        }
+        thread::sleep(tick - elapsed);
+    }
+
+    Ok(recorder.finish(started.elapsed()))
+}
+
+impl Recording {
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|frame| frame.delay).sum()
+    }
+
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        self.frames.first().map(|frame| frame.image.dimensions())
+    }
+
+    // Halbiert die Bildrate: jeweils zwei Frames werden zu einem mit der Summe beider Dauern
+    pub fn decimate(&self) -> Recording {
+        let frames = self
+            .frames
+            .chunks(2)
+            .map(|pair| RecordedFrame {
+                image: pair[0].image.clone(),
+                delay: pair.iter().map(|frame| frame.delay).sum(),
+            })
+            .collect();
+        Recording { frames }
+    }
+
+    fn encode_once(
+        &self,
+        format: AnimationFormat,
+        options: &RecordingOptions,
+    ) -> Result<Vec<u8>, SnipError> {
+        match format {
+            AnimationFormat::Gif => encode_gif(&self.frames, options.gif_speed),
+            AnimationFormat::Apng => encode_apng(&self.frames),
+        }
+    }
+
+    // Verwirft so lange jeden zweiten Frame, bis die Datei ins Budget passt
+    pub fn encode(
+        &self,
+        format: AnimationFormat,
+        options: &RecordingOptions,
+    ) -> Result<Vec<u8>, SnipError> {
+        let size = self
+            .dimensions()
+            .ok_or_else(|| SnipError::capture("The recording has no frames"))?;
+        if self
+            .frames
+            .iter()
+            .any(|frame| frame.image.dimensions() != size)
+        {
+            return Err(SnipError::Config(
+                "All frames of a recording must have the same size".to_string(),
+            ));
+        }
+
+        let mut decimated;
+        let mut recording = self;
+        loop {
+            let data = recording.encode_once(format, options)?;
+            if data.len() <= options.max_bytes {
+                return Ok(data);
+            }
+            if recording.frames.len() == 1 {
+                return Err(SnipError::Encode {
+                    format: format.name(),
+                    message: format!(
+                        "a single frame needs {} bytes, the budget is {}",
+                        data.len(),
+                        options.max_bytes
+                    ),
+                });
+            }
+            decimated = recording.decimate();
+            recording = &decimated;
+        }
+    }
+}
+
+fn encode_error(format: AnimationFormat) -> impl Fn(ImageError) -> SnipError {
+    move |e| SnipError::Encode {
+        format: format.name(),
+        message: e.to_string(),
+    }
+}
+
+fn millis(delay: Duration) -> u32 {
+    delay.as_millis().min(u32::MAX as u128) as u32
+}
+
+// Jeder Frame bekommt eine eigene Palette aus NeuQuant
+pub fn encode_gif(frames: &[RecordedFrame], speed: i32) -> Result<Vec<u8>, SnipError> {
+    let error = encode_error(AnimationFormat::Gif);
+    let mut buffer = Vec::new();
+    {
+        let mut encoder = GifEncoder::new_with_speed(&mut buffer, speed.clamp(1, 30));
+        encoder.set_repeat(Repeat::Infinite).map_err(&error)?;
+        for frame in frames {
+            let delay = Delay::from_numer_denom_ms(millis(frame.delay), 1);
+            let frame = image::Frame::from_parts(frame.image.clone(), 0, 0, delay);
+            encoder.encode_frame(frame).map_err(&error)?;
+        }
+    }
+    Ok(buffer)
+}
+
+static CRC_TABLE: Lazy<[u32; 256]> = Lazy::new(|| {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+    table
+});
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+// Chunks einer PNG-Datei als (Typ, Daten)
+fn chunks(png: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut chunks = Vec::new();
+    let mut position = PNG_SIGNATURE.len();
+    while position + 12 <= png.len() {
+        let length = u32::from_be_bytes(png[position..position + 4].try_into().unwrap()) as usize;
+        let kind: [u8; 4] = png[position + 4..position + 8].try_into().unwrap();
+        let end = (position + 8 + length).min(png.len());
+        chunks.push((kind, &png[position + 8..end]));
+        position = end + 4;
+    }
+    chunks
+}
+
+fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, SnipError> {
+    let mut buffer = Vec::new();
+    PngEncoder::new(&mut buffer)
+        .encode(
+            image.as_raw(),
+            image.width(),
+            image.height(),
+            ColorType::Rgba8,
+        )
+        .map_err(encode_error(AnimationFormat::Apng))?;
+    Ok(buffer)
+}
+
+// Kleinstes Rechteck, in dem sich die Frames unterscheiden, als (x, y, Breite, Höhe)
+fn changed_area(previous: &RgbaImage, current: &RgbaImage) -> (u32, u32, u32, u32) {
+    let (mut left, mut top) = (u32::MAX, u32::MAX);
+    let (mut right, mut bottom) = (0, 0);
+    for (x, y, pixel) in current.enumerate_pixels() {
+        if previous.get_pixel(x, y) != pixel {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x + 1);
+            bottom = bottom.max(y + 1);
+        }
+    }
+    // Gleiche Frames nach dem Ausdünnen: ein Pixel reicht als Platzhalter
+    if left == u32::MAX {
+        return (0, 0, 1, 1);
+    }
+    (left, top, right - left, bottom - top)
+}
+
+// Verzögerung als Bruch mit 16-Bit-Zähler und -Nenner
+fn apng_delay(delay: Duration) -> (u16, u16) {
+    let millis = millis(delay);
+    match u16::try_from(millis) {
+        Ok(millis) => (millis, 1000),
+        Err(_) => ((millis / 100).min(u16::MAX as u32) as u16, 10),
+    }
+}
+
+pub fn encode_apng(frames: &[RecordedFrame]) -> Result<Vec<u8>, SnipError> {
+    let error = |message: &str| SnipError::Encode {
+        format: AnimationFormat::Apng.name(),
+        message: message.to_string(),
+    };
+    if frames.is_empty() {
+        return Err(error("no frames"));
+    }
+
+    let mut out = PNG_SIGNATURE.to_vec();
+    let mut sequence = 0u32;
+    let mut previous: Option<&RgbaImage> = None;
+
+    for (index, frame) in frames.iter().enumerate() {
+        let (x, y, width, height) = match previous {
+            Some(previous) => changed_area(previous, &frame.image),
+            None => (0, 0, frame.image.width(), frame.image.height()),
+        };
+        let png = if previous.is_none() {
+            encode_png(&frame.image)?
+        } else {
+            let area = image::imageops::crop_imm(&frame.image, x, y, width, height).to_image();
+            encode_png(&area)?
+        };
+        let chunks = chunks(&png);
+
+        if index == 0 {
+            let (_, header) = chunks
+                .iter()
+                .find(|(kind, _)| kind == b"IHDR")
+                .ok_or_else(|| error("PNG encoder wrote no IHDR"))?;
+            write_chunk(&mut out, b"IHDR", header);
+            let mut control = (frames.len() as u32).to_be_bytes().to_vec();
+            // 0 = Endlosschleife
+            control.extend_from_slice(&0u32.to_be_bytes());
+            write_chunk(&mut out, b"acTL", &control);
+        }
+
+        let (numerator, denominator) = apng_delay(frame.delay);
+        let mut control = Vec::with_capacity(26);
+        for value in [sequence, width, height, x, y] {
+            control.extend_from_slice(&value.to_be_bytes());
+        }
+        control.extend_from_slice(&numerator.to_be_bytes());
+        control.extend_from_slice(&denominator.to_be_bytes());
+        control.extend_from_slice(&[APNG_DISPOSE_NONE, APNG_BLEND_SOURCE]);
+        write_chunk(&mut out, b"fcTL", &control);
+        sequence += 1;
+
+        // Der erste Frame ist zugleich das Standbild für Programme ohne APNG
+        for (_, data) in chunks.iter().filter(|(kind, _)| kind == b"IDAT") {
+            if index == 0 {
+                write_chunk(&mut out, b"IDAT", data);
+            } else {
+                let mut frame_data = sequence.to_be_bytes().to_vec();
+                frame_data.extend_from_slice(data);
+                write_chunk(&mut out, b"fdAT", &frame_data);
+                sequence += 1;
+            }
+        }
+        previous = Some(&frame.image);
+    }
+
+    write_chunk(&mut out, b"IEND", &[]);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifDecoder;
+    use image::{AnimationDecoder, Rgba};
+
+    // Ein wandernder Balken auf weißem Grund
+    fn frame(step: u32) -> RgbaImage {
+        RgbaImage::from_fn(64, 32, |x, _| {
+            if x / 8 == step % 8 {
+                Rgba([200, 30, 30, 255])
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
+        })
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn recording(steps: &[u32], delay: u64) -> Recording {
+        let mut recorder = Recorder::new(RecordingOptions::default());
+        for (index, step) in steps.iter().enumerate() {
+            recorder.push(frame(*step), ms(index as u64 * delay));
+        }
+        recorder.finish(ms(steps.len() as u64 * delay))
+    }
+
+    // Setzt die APNG-Frames wieder zu vollständigen Bildern zusammen
+    fn decode_apng(data: &[u8]) -> Vec<(RgbaImage, Duration)> {
+        assert_eq!(data[..8], PNG_SIGNATURE);
+        let mut position = 8;
+        let mut parsed = Vec::new();
+        while position < data.len() {
+            let length =
+                u32::from_be_bytes(data[position..position + 4].try_into().unwrap()) as usize;
+            let body = &data[position + 4..position + 8 + length];
+            let crc = u32::from_be_bytes(
+                data[position + 8 + length..position + 12 + length]
+                    .try_into()
+                    .unwrap(),
+            );
+            assert_eq!(crc32(body), crc);
+            let kind: [u8; 4] = body[..4].try_into().unwrap();
+            parsed.push((kind, body[4..].to_vec()));
+            position += 12 + length;
+        }
+
+        let be = |bytes: &[u8]| u32::from_be_bytes(bytes[..4].try_into().unwrap());
+        let header = &parsed.iter().find(|(kind, _)| kind == b"IHDR").unwrap().1;
+        let control = &parsed.iter().find(|(kind, _)| kind == b"acTL").unwrap().1;
+        let mut canvas = RgbaImage::new(be(&header[0..]), be(&header[4..]));
+        let mut frames = Vec::new();
+        let mut sequence = 0;
+        let mut current: Option<(Vec<u8>, Vec<u8>)> = None;
+
+        let mut flush = |current: Option<(Vec<u8>, Vec<u8>)>, frames: &mut Vec<_>| {
+            let Some((control, data)) = current else {
+                return;
+            };
+            let mut area_header = header.clone();
+            area_header[..8].copy_from_slice(&control[4..12]);
+            let mut png = PNG_SIGNATURE.to_vec();
+            write_chunk(&mut png, b"IHDR", &area_header);
+            write_chunk(&mut png, b"IDAT", &data);
+            write_chunk(&mut png, b"IEND", &[]);
+            let area = image::load_from_memory(&png).unwrap().to_rgba8();
+            image::imageops::replace(&mut canvas, &area, be(&control[12..]), be(&control[16..]));
+            let numerator = u16::from_be_bytes([control[20], control[21]]) as u64;
+            let denominator = u16::from_be_bytes([control[22], control[23]]) as u64;
+            frames.push((canvas.clone(), ms(numerator * 1000 / denominator)));
+        };
+
+        for (kind, body) in &parsed {
+            match kind {
+                b"fcTL" => {
+                    assert_eq!(be(body), sequence);
+                    sequence += 1;
+                    flush(current.take(), &mut frames);
+                    current = Some((body.clone(), Vec::new()));
+                }
+                b"IDAT" => current.as_mut().unwrap().1.extend_from_slice(body),
+                b"fdAT" => {
+                    assert_eq!(be(body), sequence);
+                    sequence += 1;
+                    current.as_mut().unwrap().1.extend_from_slice(&body[4..]);
+                }
+                _ => {}
+            }
+        }
+        flush(current.take(), &mut frames);
+        assert_eq!(be(control) as usize, frames.len());
+        frames
+    }
+
+    #[test]
+    fn identical_frames_are_merged() {
+        let recording = recording(&[0, 0, 0, 1, 1, 2], 100);
+        let delays: Vec<Duration> = recording.frames.iter().map(|f| f.delay).collect();
+        assert_eq!(delays, [ms(300), ms(200), ms(100)]);
+        assert_eq!(recording.frames[1].image, frame(1));
+        assert_eq!(recording.duration(), ms(600));
+    }
+
+    #[test]
+    fn recorder_stops_at_max_duration() {
+        let options = RecordingOptions {
+            max_duration: ms(250),
+            ..RecordingOptions::default()
+        };
+        let mut recorder = Recorder::new(options);
+        let accepted: Vec<bool> = (0..5)
+            .map(|step| recorder.push(frame(step), ms(step as u64 * 100)))
+            .collect();
+        assert_eq!(accepted, [true, true, true, false, false]);
+        let recording = recorder.finish(ms(400));
+        assert_eq!(recording.frames.len(), 3);
+        assert_eq!(recording.duration(), ms(250));
+    }
+
+    #[test]
+    fn apng_frames_round_trip_losslessly() {
+        let recording = recording(&[0, 1, 1, 5, 0], 100);
+        let options = RecordingOptions::default();
+        let data = recording.encode(AnimationFormat::Apng, &options).unwrap();
+
+        // Programme ohne APNG zeigen den ersten Frame
+        assert_eq!(image::load_from_memory(&data).unwrap().to_rgba8(), frame(0));
+
+        let decoded = decode_apng(&data);
+        let expected = [(0, 100), (1, 200), (5, 100), (0, 100)];
+        assert_eq!(decoded.len(), expected.len());
+        for ((image, delay), (step, millis)) in decoded.iter().zip(expected) {
+            assert_eq!(*image, frame(step));
+            assert_eq!(*delay, ms(millis));
+        }
+    }
+
+    #[test]
+    fn gif_keeps_frames_delays_and_colors() {
+        let recording = recording(&[0, 1, 2, 2], 100);
+        let data = recording
+            .encode(AnimationFormat::Gif, &RecordingOptions::default())
+            .unwrap();
+
+        let frames = GifDecoder::new(&data[..])
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(frames.len(), 3);
+        let delays: Vec<(u32, u32)> = frames
+            .iter()
+            .map(|frame| frame.delay().numer_denom_ms())
+            .collect();
+        assert_eq!(delays, [(100, 1), (100, 1), (200, 1)]);
+
+        // Nach der Palettenreduktion bleiben die Farben nahe am Original
+        let close = |a: &Rgba<u8>, b: &Rgba<u8>| (0..3).all(|c| a[c].abs_diff(b[c]) <= 8);
+        for (decoded, step) in frames.iter().zip([0, 1, 2]) {
+            let original = frame(step);
+            assert!(decoded
+                .buffer()
+                .pixels()
+                .zip(original.pixels())
+                .all(|(a, b)| close(a, b)));
+        }
+    }
+
+    #[test]
+    fn size_budget_drops_frames() {
+        let recording = recording(&[0, 1, 2, 3, 4, 5, 6, 7], 100);
+        let full = recording
+            .encode(AnimationFormat::Gif, &RecordingOptions::default())
+            .unwrap();
+
+        let options = RecordingOptions {
+            max_bytes: full.len() * 2 / 3,
+            ..RecordingOptions::default()
+        };
+        let data = recording.encode(AnimationFormat::Gif, &options).unwrap();
+        assert!(data.len() <= options.max_bytes);
+        let frames = GifDecoder::new(&data[..])
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert!(frames.len() < 8);
+        let total: u32 = frames
+            .iter()
+            .map(|frame| frame.delay().numer_denom_ms().0)
+            .sum();
+        assert_eq!(total, 800);
+
+        let options = RecordingOptions {
+            max_bytes: 16,
+            ..RecordingOptions::default()
+        };
+        assert!(recording.encode(AnimationFormat::Apng, &options).is_err());
+    }
+
+    #[test]
+    fn record_samples_until_stopped() {
+        let mut step = 0;
+        let mut grabs = 0;
+        let options = RecordingOptions {
+            fps: MAX_FPS,
+            ..RecordingOptions::default()
+        };
+        let recording = record(
+            || {
+                step += 1;
+                Ok(frame(step / 2))
+            },
+            &options,
+            || {
+                grabs += 1;
+                grabs > 6
+            },
+        )
+        .unwrap();
+        // Sechs Frames, von denen jeweils zwei gleich sind
+        assert_eq!(recording.frames.len(), 4);
+
+        assert!(RecordingOptions {
+            fps: 0.0,
+            ..RecordingOptions::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
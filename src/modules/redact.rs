@@ -0,0 +1,340 @@
+use crate::modules::backend::{Color, PixelRect};
+use crate::modules::errorhandler::SnipError;
+use crate::modules::raster;
+use image::{Rgba, RgbaImage};
+use std::fmt;
+use std::str::FromStr;
+
+pub const DEFAULT_BLOCK_SIZE: u32 = 12;
+pub const DEFAULT_BLUR_SIGMA: f32 = 8.0;
+// Kleinere Blöcke bzw. ein schwächerer Weichzeichner ließen das Original erkennen
+const MIN_BLOCK_SIZE: u32 = 2;
+const MIN_BLUR_SIGMA: f32 = 1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RedactionStyle {
+    // Mosaik aus Blöcken mit dem Mittelwert ihrer Pixel
+    Pixelate { block_size: u32 },
+    Blur { sigma: f32 },
+    Fill(Color),
+}
+
+impl Default for RedactionStyle {
+    fn default() -> Self {
+        RedactionStyle::Pixelate {
+            block_size: DEFAULT_BLOCK_SIZE,
+        }
+    }
+}
+
+// Textform `pixelate[:block]`, `blur[:sigma]` oder `fill[:RRGGBB]`
+impl FromStr for RedactionStyle {
+    type Err = SnipError;
+
+    fn from_str(s: &str) -> Result<Self, SnipError> {
+        let (name, argument) = match s.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (s, None),
+        };
+        let invalid = || SnipError::Config(format!("Invalid redaction style '{}'", s));
+
+        match (name.to_ascii_lowercase().as_str(), argument) {
+            ("pixelate", None) => Ok(RedactionStyle::default()),
+            ("pixelate", Some(size)) => Ok(RedactionStyle::Pixelate {
+                block_size: size.parse().map_err(|_| invalid())?,
+            }),
+            ("blur", None) => Ok(RedactionStyle::Blur {
+                sigma: DEFAULT_BLUR_SIGMA,
+            }),
+            ("blur", Some(sigma)) => Ok(RedactionStyle::Blur {
+                sigma: sigma.parse().map_err(|_| invalid())?,
+            }),
+            ("fill", None) => Ok(RedactionStyle::Fill(Color::new(0.0, 0.0, 0.0, 1.0))),
+            ("fill", Some(hex)) => parse_hex(hex).map(RedactionStyle::Fill).ok_or_else(invalid),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    let hex = hex.trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16)
+            .ok()
+            .map(|value| value as f32 / 255.0)
+    };
+    Some(Color::new(channel(0)?, channel(2)?, channel(4)?, 1.0))
+}
+
+impl fmt::Display for RedactionStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedactionStyle::Pixelate { block_size } => write!(f, "pixelate:{}", block_size),
+            RedactionStyle::Blur { sigma } => write!(f, "blur:{}", sigma),
+            RedactionStyle::Fill(color) => {
+                let Rgba([r, g, b, _]) = raster::to_rgba(*color);
+                write!(f, "fill:{:02x}{:02x}{:02x}", r, g, b)
+            }
+        }
+    }
+}
+
+// Bereich relativ zur linken oberen Ecke des Bildes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Redaction {
+    pub region: PixelRect,
+    pub style: RedactionStyle,
+}
+
+impl Redaction {
+    pub fn new(region: PixelRect, style: RedactionStyle) -> Self {
+        Redaction { region, style }
+    }
+}
+
+// Textform `x,y,w,h[=style]`, z.B. `10,10,200,20=blur:6`
+impl FromStr for Redaction {
+    type Err = SnipError;
+
+    fn from_str(s: &str) -> Result<Self, SnipError> {
+        let (region, style) = match s.split_once('=') {
+            Some((region, style)) => (region, style.parse()?),
+            None => (s, RedactionStyle::default()),
+        };
+        let region = region
+            .parse::<PixelRect>()
+            .map_err(|e| SnipError::Config(e.to_string()))?;
+        Ok(Redaction::new(region, style))
+    }
+}
+
+// Schnittmenge mit dem Bild als halboffene Pixelgrenzen
+fn clip(region: PixelRect, image: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
+    let left = region.x.max(0) as i64;
+    let top = region.y.max(0) as i64;
+    let right = (region.x as i64 + region.width as i64).min(image.width() as i64);
+    let bottom = (region.y as i64 + region.height as i64).min(image.height() as i64);
+    (left < right && top < bottom).then_some((left as u32, top as u32, right as u32, bottom as u32))
+}
+
+// Überschreibt die Pixel im Bereich; das Original lässt sich daraus nicht zurückgewinnen
+pub fn apply(image: &mut RgbaImage, redaction: &Redaction) {
+    let bounds = match clip(redaction.region, image) {
+        Some(bounds) => bounds,
+        None => return,
+    };
+
+    match redaction.style {
+        RedactionStyle::Pixelate { block_size } => pixelate(image, bounds, block_size),
+        RedactionStyle::Blur { sigma } => blur(image, bounds, sigma),
+        RedactionStyle::Fill(color) => {
+            let (left, top, right, bottom) = bounds;
+            let color = raster::to_rgba(color);
+            for y in top..bottom {
+                for x in left..right {
+                    image.put_pixel(x, y, color);
+                }
+            }
+        }
+    }
+}
+
+pub fn apply_all(image: &mut RgbaImage, redactions: &[Redaction]) {
+    for redaction in redactions {
+        apply(image, redaction);
+    }
+}
+
+// Teilt [start, end) in gleich große Abschnitte von mindestens `size` Pixeln,
+// damit am Rand kein einzelner, unveränderter Pixelstreifen übrig bleibt
+fn blocks(start: u32, end: u32, size: u32) -> impl Iterator<Item = (u32, u32)> {
+    let length = end - start;
+    let count = (length / size).max(1);
+    (0..count).map(move |i| {
+        (
+            start + (i as u64 * length as u64 / count as u64) as u32,
+            start + ((i as u64 + 1) * length as u64 / count as u64) as u32,
+        )
+    })
+}
+
+fn pixelate(image: &mut RgbaImage, (left, top, right, bottom): (u32, u32, u32, u32), size: u32) {
+    let size = size.max(MIN_BLOCK_SIZE);
+
+    for (y0, y1) in blocks(top, bottom, size) {
+        for (x0, x1) in blocks(left, right, size) {
+            let mut sum = [0u64; 4];
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    for (total, value) in sum.iter_mut().zip(image.get_pixel(x, y).0) {
+                        *total += value as u64;
+                    }
+                }
+            }
+
+            let count = ((x1 - x0) * (y1 - y0)) as u64;
+            let average = Rgba(sum.map(|total| ((total + count / 2) / count) as u8));
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    image.put_pixel(x, y, average);
+                }
+            }
+        }
+    }
+}
+
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let radius = (sigma * 3.0).ceil() as i32;
+    let weights: Vec<f32> = (-radius..=radius)
+        .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = weights.iter().sum();
+    weights.into_iter().map(|weight| weight / total).collect()
+}
+
+// Getrennt in horizontalen und vertikalen Durchgang; gelesen wird nur innerhalb des Bereichs,
+// am Rand wird der letzte Pixel wiederholt
+fn blur(image: &mut RgbaImage, (left, top, right, bottom): (u32, u32, u32, u32), sigma: f32) {
+    let kernel = gaussian_kernel(sigma.max(MIN_BLUR_SIGMA));
+    let radius = (kernel.len() / 2) as i64;
+    let (width, height) = ((right - left) as usize, (bottom - top) as usize);
+
+    let mut buffer: Vec<[f32; 4]> = Vec::with_capacity(width * height);
+    for y in top..bottom {
+        for x in left..right {
+            buffer.push(image.get_pixel(x, y).0.map(f32::from));
+        }
+    }
+
+    let convolve = |buffer: &[[f32; 4]], horizontal: bool| -> Vec<[f32; 4]> {
+        let mut output = vec![[0.0; 4]; buffer.len()];
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0.0f32; 4];
+                for (k, weight) in kernel.iter().enumerate() {
+                    let offset = k as i64 - radius;
+                    let (sx, sy) = if horizontal {
+                        ((x as i64 + offset).clamp(0, width as i64 - 1) as usize, y)
+                    } else {
+                        (x, (y as i64 + offset).clamp(0, height as i64 - 1) as usize)
+                    };
+                    for (total, value) in sum.iter_mut().zip(buffer[sy * width + sx]) {
+                        *total += value * weight;
+                    }
+                }
+                output[y * width + x] = sum;
+            }
+        }
+        output
+    };
+
+    let blurred = convolve(&convolve(&buffer, true), false);
+    for (i, pixel) in blurred.iter().enumerate() {
+        let (x, y) = (left + (i % width) as u32, top + (i / width) as u32);
+        image.put_pixel(
+            x,
+            y,
+            Rgba(pixel.map(|value| value.round().clamp(0.0, 255.0) as u8)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Schachbrett aus hellen und dunklen Pixeln mit leichtem Verlauf; jeder Mittelwert
+    // über Nachbarn liegt dazwischen und trifft keinen der ursprünglichen Werte
+    fn pattern(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let shade = ((x * 7 + y * 3) % 40) as u8;
+            if (x + y) % 2 == 0 {
+                Rgba([shade, shade / 2, 40 - shade, 255])
+            } else {
+                Rgba([215 + shade, 255 - shade, 200 + shade / 2, 255])
+            }
+        })
+    }
+
+    fn assert_redacted(style: RedactionStyle, region: PixelRect) {
+        let original = pattern(64, 48);
+        let mut image = original.clone();
+        apply(&mut image, &Redaction::new(region, style));
+
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let inside = region.to_rect().contains(x as f32 + 0.5, y as f32 + 0.5);
+            let unchanged = pixel == original.get_pixel(x, y);
+            if inside {
+                assert!(!unchanged, "{} kept the pixel at {},{}", style, x, y);
+            } else {
+                assert!(unchanged, "{} touched the pixel at {},{}", style, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn pixelate_replaces_every_pixel() {
+        assert_redacted(
+            RedactionStyle::Pixelate { block_size: 8 },
+            PixelRect::new(5, 7, 30, 21),
+        );
+    }
+
+    #[test]
+    fn pixelate_with_remainder_leaves_no_original_strip() {
+        // 17 = 2 * 8 + 1: der letzte Pixel wird dem vorherigen Block zugeschlagen
+        assert_redacted(
+            RedactionStyle::Pixelate { block_size: 8 },
+            PixelRect::new(0, 0, 17, 17),
+        );
+    }
+
+    #[test]
+    fn pixelate_block_size_below_minimum_still_redacts() {
+        assert_redacted(
+            RedactionStyle::Pixelate { block_size: 1 },
+            PixelRect::new(10, 10, 9, 9),
+        );
+    }
+
+    #[test]
+    fn blur_replaces_every_pixel() {
+        assert_redacted(
+            RedactionStyle::Blur { sigma: 4.0 },
+            PixelRect::new(3, 2, 40, 30),
+        );
+        assert_redacted(
+            RedactionStyle::Blur { sigma: 0.1 },
+            PixelRect::new(3, 2, 40, 30),
+        );
+    }
+
+    #[test]
+    fn fill_replaces_every_pixel() {
+        assert_redacted(
+            RedactionStyle::Fill(Color::new(0.5, 0.0, 0.5, 1.0)),
+            PixelRect::new(0, 0, 64, 48),
+        );
+    }
+
+    #[test]
+    fn region_is_clipped_to_the_image() {
+        assert_redacted(RedactionStyle::default(), PixelRect::new(50, 40, 100, 100));
+        assert_redacted(RedactionStyle::default(), PixelRect::new(-10, -10, 30, 25));
+    }
+
+    #[test]
+    fn parse_redaction() {
+        let redaction: Redaction = "10,20,30,40=blur:6".parse().unwrap();
+        assert_eq!(redaction.region, PixelRect::new(10, 20, 30, 40));
+        assert_eq!(redaction.style, RedactionStyle::Blur { sigma: 6.0 });
+
+        let fill: RedactionStyle = "fill:#ff0000".parse().unwrap();
+        assert_eq!(fill.to_string(), "fill:ff0000");
+        assert!("mosaic".parse::<RedactionStyle>().is_err());
+        assert!("fill:red".parse::<RedactionStyle>().is_err());
+    }
+}
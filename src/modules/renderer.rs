@@ -1,3 +1,4 @@
+use crate::modules::errorhandler::{ErrorContext, SnipError};
 use crate::modules::resource_manager::ResourceManager;
 
 use std::mem::ManuallyDrop;
@@ -27,7 +28,10 @@ pub struct Render {
 }
 
 impl Render {
-    pub fn new(hwnd: HWND, resource_manager: &ResourceManager) -> Result<Self> {
+    pub fn new(
+        hwnd: HWND,
+        resource_manager: &ResourceManager,
+    ) -> std::result::Result<Self, SnipError> {
         let d2d_factory = &resource_manager.d2d_factory;
         let dcomp_device = &resource_manager.dcomp_device;
         let dxgi_factory = &resource_manager.dxgi_factory;
@@ -35,8 +39,6 @@ impl Render {
         unsafe {
             let mut d3d_device: Option<ID3D11Device> = None;
             let mut d3d_context: Option<ID3D11DeviceContext> = None;
-            let mut dxgi_device: Option<IDXGIDevice> = None;
-            let mut dxgi_adapter: Option<IDXGIAdapter> = None;
 
             let feature_levels = [D3D_FEATURE_LEVEL_11_0];
             D3D11CreateDevice(
@@ -50,10 +52,15 @@ impl Render {
                 None,
                 Some(&mut d3d_context),
             )
-            .unwrap();
+            .map_err(|e| {
+                SnipError::backend_init("No Direct3D 11 hardware device available").caused_by(e)
+            })?;
 
-            dxgi_device = Some(d3d_device.as_ref().unwrap().cast::<IDXGIDevice>().unwrap());
-            dxgi_adapter = Some(dxgi_device.as_ref().unwrap().GetAdapter().unwrap());
+            let d3d_device = d3d_device
+                .ok_or_else(|| SnipError::backend_init("D3D11CreateDevice returned no device"))?;
+            let dxgi_device: IDXGIDevice = d3d_device
+                .cast()
+                .context("Failed to query the DXGI device")?;
 
             let mut rect = RECT::default();
             GetClientRect(hwnd, &mut rect);
@@ -79,18 +86,20 @@ impl Render {
 
             let swap_chain = resource_manager
                 .dxgi_factory
-                .CreateSwapChainForComposition(
-                    dxgi_device.as_ref().unwrap(),
-                    &swap_chain_desc,
-                    None::<&IDXGIOutput>,
-                )
-                .unwrap();
-
-            let d2d_device = d2d_factory.CreateDevice(dxgi_device.as_ref().unwrap())?;
-            let d2d_context1 = d2d_device.CreateDeviceContext(D2D1_DEVICE_CONTEXT_OPTIONS_NONE)?;
+                .CreateSwapChainForComposition(&dxgi_device, &swap_chain_desc, None::<&IDXGIOutput>)
+                .context("Failed to create the swap chain")?;
+
+            let d2d_device = d2d_factory
+                .CreateDevice(&dxgi_device)
+                .context("Failed to create the Direct2D device")?;
+            let d2d_context1 = d2d_device
+                .CreateDeviceContext(D2D1_DEVICE_CONTEXT_OPTIONS_NONE)
+                .context("Failed to create the Direct2D device context")?;
             let d2d_context: ID2D1DeviceContext = d2d_context1.cast()?;
 
-            let dcomp_target = dcomp_device.CreateTargetForHwnd(hwnd, true)?;
+            let dcomp_target = dcomp_device
+                .CreateTargetForHwnd(hwnd, true)
+                .context("Failed to create the composition target")?;
             let dcomp_visual = dcomp_device.CreateVisual()?;
 
             dcomp_visual.SetContent(&swap_chain)?;
@@ -98,7 +107,6 @@ impl Render {
             dcomp_device.Commit()?;
 
             let (dpi_x, dpi_y) = get_dpi_for_window(hwnd);
-            println!("DPI: ({}, {})", dpi_x, dpi_y);
             d2d_context.SetDpi(dpi_x, dpi_y);
 
             Ok(Render {
@@ -123,7 +131,7 @@ impl Render {
             let d2d_context = &self.d2d_context;
             let swap_chain = &self.swap_chain;
 
-            let dxgi_back_buffer = swap_chain.GetBuffer::<IDXGISurface>(0).unwrap();
+            let dxgi_back_buffer = swap_chain.GetBuffer::<IDXGISurface>(0)?;
 
             let bitmap_properties = D2D1_BITMAP_PROPERTIES1 {
                 pixelFormat: D2D1_PIXEL_FORMAT {
@@ -137,8 +145,7 @@ impl Render {
             };
 
             let target_bitmap = d2d_context
-                .CreateBitmapFromDxgiSurface(&dxgi_back_buffer, Some(&bitmap_properties))
-                .unwrap();
+                .CreateBitmapFromDxgiSurface(&dxgi_back_buffer, Some(&bitmap_properties))?;
 
             d2d_context.SetTarget(&target_bitmap);
 
@@ -152,8 +159,8 @@ impl Render {
 
             render_fn(d2d_context)?;
 
-            d2d_context.EndDraw(None, None).unwrap();
-            swap_chain.Present(1, 0).unwrap();
+            d2d_context.EndDraw(None, None)?;
+            swap_chain.Present(1, 0).ok()?;
         }
         Ok(())
     }
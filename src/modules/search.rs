@@ -0,0 +1,449 @@
+use crate::modules::backend::PixelRect;
+use crate::modules::ocr::OcrWord;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::{Bound, Range};
+
+// Invertierter Index über den OCR-Text der Aufnahmen. Begriffe werden für Deutsch und Englisch
+// normalisiert (Umlaute, ß, Akzente, Bindestrich-Komposita, Apostrophe), gesucht wird mit
+// Präfixen, gewichtet nach BM25. Treffer liefern Byte-Bereiche im Text und Wortboxen im Bild.
+
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+// Kürzere Suchbegriffe müssen exakt passen, sonst passt fast alles
+const MIN_PREFIX: usize = 2;
+// Ein Präfixtreffer zählt weniger als ein exakter
+const PREFIX_WEIGHT: f32 = 0.5;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub term: String,
+    // Byte-Bereich im Originaltext
+    pub range: Range<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchHit {
+    pub id: u64,
+    pub score: f32,
+    // Sortierte, nicht überlappende Byte-Bereiche im indexierten Text
+    pub highlights: Vec<Range<usize>>,
+    // Boxen der passenden Wörter in Bildkoordinaten
+    pub regions: Vec<PixelRect>,
+}
+
+// Wandelt in Kleinbuchstaben und faltet Sonderzeichen: "Größe" → "groesse", "Café" → "cafe"
+pub fn fold(word: &str) -> String {
+    let mut folded = String::with_capacity(word.len());
+    for c in word.chars().flat_map(char::to_lowercase) {
+        match c {
+            'ä' | 'æ' => folded.push_str("ae"),
+            'ö' | 'ø' | 'œ' => folded.push_str("oe"),
+            'ü' => folded.push_str("ue"),
+            'ß' => folded.push_str("ss"),
+            'à' | 'á' | 'â' | 'ã' | 'å' => folded.push('a'),
+            'ç' => folded.push('c'),
+            'è' | 'é' | 'ê' | 'ë' => folded.push('e'),
+            'ì' | 'í' | 'î' | 'ï' => folded.push('i'),
+            'ñ' => folded.push('n'),
+            'ò' | 'ó' | 'ô' | 'õ' => folded.push('o'),
+            'ù' | 'ú' | 'û' => folded.push('u'),
+            'ý' | 'ÿ' => folded.push('y'),
+            c => folded.push(c),
+        }
+    }
+    folded
+}
+
+// "Mueller" und "Muller" sollen beide "Müller" finden
+fn without_umlauts(word: &str) -> Option<String> {
+    let lower = word.to_lowercase();
+    if !lower.contains(['ä', 'ö', 'ü']) {
+        return None;
+    }
+    Some(fold(
+        &lower.replace('ä', "a").replace('ö', "o").replace('ü', "u"),
+    ))
+}
+
+fn is_joiner(c: char) -> bool {
+    matches!(c, '-' | '\u{2010}' | '\u{2011}' | '\'' | '\u{2019}')
+}
+
+fn is_apostrophe(c: char) -> bool {
+    matches!(c, '\'' | '\u{2019}')
+}
+
+// Wortgruppen aus Buchstaben und Ziffern, verbunden durch einzelne Bindestriche oder Apostrophe
+fn groups(text: &str) -> Vec<(Range<usize>, Vec<Range<usize>>)> {
+    let mut groups: Vec<(Range<usize>, Vec<Range<usize>>)> = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    let mut joined = false;
+
+    while let Some((start, c)) = chars.next() {
+        if !c.is_alphanumeric() {
+            // Nur ein Verbinder direkt zwischen zwei Wörtern hält die Gruppe zusammen
+            joined = is_joiner(c)
+                && groups.last().is_some_and(|(range, _)| range.end == start)
+                && chars.peek().is_some_and(|(_, next)| next.is_alphanumeric());
+            continue;
+        }
+        let mut end = start + c.len_utf8();
+        while let Some(&(index, next)) = chars.peek() {
+            if !next.is_alphanumeric() {
+                break;
+            }
+            end = index + next.len_utf8();
+            chars.next();
+        }
+
+        match groups.last_mut() {
+            Some((range, parts)) if joined => {
+                range.end = end;
+                parts.push(start..end);
+            }
+            _ => {
+                let range = start..end;
+                groups.push((range.clone(), vec![range]));
+            }
+        }
+        joined = false;
+    }
+    groups
+}
+
+// Normalisierte Begriffe zum Indexieren. Komposita liefern ihre Teile und die zusammengezogene
+// Form ("E-Mail-Adresse" → e, mail, adresse, emailadresse); "Peter's" liefert peters und peter.
+pub fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let push = |tokens: &mut Vec<Token>, word: &str, range: Range<usize>| {
+        tokens.push(Token {
+            term: fold(word),
+            range: range.clone(),
+        });
+        if let Some(variant) = without_umlauts(word) {
+            tokens.push(Token {
+                term: variant,
+                range,
+            });
+        }
+    };
+
+    for (range, parts) in groups(text) {
+        if parts.len() > 1 {
+            let joined: String = parts.iter().map(|part| &text[part.clone()]).collect();
+            push(&mut tokens, &joined, range.clone());
+        }
+
+        let apostrophe = text[range.clone()].contains(is_apostrophe);
+        for (index, part) in parts.iter().enumerate() {
+            // Bei "it's" oder "geht's" trägt das Anhängsel keine Bedeutung
+            if apostrophe && index > 0 && text[part.clone()].chars().count() <= 2 {
+                continue;
+            }
+            push(&mut tokens, &text[part.clone()], part.clone());
+        }
+    }
+    tokens
+}
+
+// Ein Begriff pro Wortgruppe, damit "E-Mail" als Ganzes gesucht wird
+fn query_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for (_, parts) in groups(query) {
+        let joined: String = parts.iter().map(|part| &query[part.clone()]).collect();
+        let term = fold(&joined);
+        if !terms.contains(&term) {
+            terms.push(term);
+        }
+    }
+    terms
+}
+
+// Umschließt die Treffer mit Markierungen, z.B. "[" und "]" für die Konsole
+pub fn mark(text: &str, highlights: &[Range<usize>], open: &str, close: &str) -> String {
+    let mut marked = String::with_capacity(text.len());
+    let mut position = 0;
+    for range in highlights {
+        if range.start < position || range.end > text.len() {
+            continue;
+        }
+        marked.push_str(&text[position..range.start]);
+        marked.push_str(open);
+        marked.push_str(&text[range.clone()]);
+        marked.push_str(close);
+        position = range.end;
+    }
+    marked.push_str(&text[position..]);
+    marked
+}
+
+#[derive(Clone, Debug, Default)]
+struct Document {
+    // Anzahl der Wörter, für die Längennormalisierung
+    length: usize,
+    terms: BTreeSet<String>,
+    words: Vec<(PixelRect, BTreeSet<String>)>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SearchIndex {
+    documents: BTreeMap<u64, Document>,
+    // Begriff → Dokument → Fundstellen im Text
+    postings: BTreeMap<String, BTreeMap<u64, Vec<Range<usize>>>>,
+    total_length: usize,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        SearchIndex::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    // Ersetzt ein vorhandenes Dokument mit derselben ID. Die Wörter liefern die Boxen der Treffer.
+    pub fn insert(&mut self, id: u64, text: &str, words: &[OcrWord]) {
+        self.remove(id);
+
+        let tokens = tokenize(text);
+        let mut document = Document {
+            length: groups(text).len(),
+            ..Document::default()
+        };
+        for token in tokens {
+            let ranges = self
+                .postings
+                .entry(token.term.clone())
+                .or_default()
+                .entry(id)
+                .or_default();
+            if !ranges.contains(&token.range) {
+                ranges.push(token.range);
+            }
+            document.terms.insert(token.term);
+        }
+        document.words = words
+            .iter()
+            .map(|word| {
+                let terms = tokenize(&word.text).into_iter().map(|t| t.term).collect();
+                (word.bounds, terms)
+            })
+            .collect();
+
+        self.total_length += document.length;
+        self.documents.insert(id, document);
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        let Some(document) = self.documents.remove(&id) else {
+            return;
+        };
+        self.total_length -= document.length;
+        for term in &document.terms {
+            if let Some(postings) = self.postings.get_mut(term) {
+                postings.remove(&id);
+                if postings.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+    }
+
+    // Indexierte Begriffe, die zum Suchbegriff passen, mit ihrem Gewicht
+    fn expand<'a>(&'a self, term: &'a str) -> impl Iterator<Item = (&'a String, f32)> + 'a {
+        let prefix = term.chars().count() >= MIN_PREFIX;
+        self.postings
+            .range::<str, _>((Bound::Included(term), Bound::Unbounded))
+            .map(|(indexed, _)| indexed)
+            .take_while(move |indexed| {
+                indexed.as_str() == term || (prefix && indexed.starts_with(term))
+            })
+            .map(move |indexed| {
+                let weight = if indexed == term { 1.0 } else { PREFIX_WEIGHT };
+                (indexed, weight)
+            })
+    }
+
+    // Jedes Wort der Suche muss vorkommen, als ganzer Begriff oder als Präfix.
+    // Beste Treffer zuerst, bei gleicher Bewertung die neuere Aufnahme.
+    pub fn search(&self, query: &str) -> Vec<SearchHit> {
+        let terms = query_terms(query);
+        if terms.is_empty() || self.documents.is_empty() {
+            return Vec::new();
+        }
+
+        let count = self.documents.len() as f32;
+        let average = (self.total_length as f32 / count).max(1.0);
+        let mut scores: BTreeMap<u64, (f32, usize, BTreeSet<&String>)> = BTreeMap::new();
+
+        for term in &terms {
+            let mut frequencies: BTreeMap<u64, (f32, Vec<&String>)> = BTreeMap::new();
+            for (indexed, weight) in self.expand(term) {
+                for (id, ranges) in &self.postings[indexed] {
+                    let (frequency, matched) = frequencies.entry(*id).or_default();
+                    *frequency += ranges.len() as f32 * weight;
+                    matched.push(indexed);
+                }
+            }
+
+            let matching = frequencies.len() as f32;
+            let idf = ((count - matching + 0.5) / (matching + 0.5) + 1.0).ln();
+            for (id, (frequency, matched)) in frequencies {
+                let length = self.documents[&id].length as f32;
+                let norm = K1 * (1.0 - B + B * length / average);
+                let (score, found, matched_terms) = scores.entry(id).or_default();
+                *score += idf * frequency * (K1 + 1.0) / (frequency + norm);
+                *found += 1;
+                matched_terms.extend(matched);
+            }
+        }
+
+        let mut hits: Vec<SearchHit> = scores
+            .into_iter()
+            .filter(|(_, (_, found, _))| *found == terms.len())
+            .map(|(id, (score, _, matched))| self.hit(id, score, &matched))
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(b.id.cmp(&a.id)));
+        hits
+    }
+
+    fn hit(&self, id: u64, score: f32, matched: &BTreeSet<&String>) -> SearchHit {
+        let mut ranges: Vec<Range<usize>> = matched
+            .iter()
+            .flat_map(|term| self.postings[*term][&id].iter().cloned())
+            .collect();
+        ranges.sort_by_key(|range| (range.start, range.end));
+
+        let mut highlights: Vec<Range<usize>> = Vec::new();
+        for range in ranges {
+            match highlights.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => highlights.push(range),
+            }
+        }
+
+        let regions = self.documents[&id]
+            .words
+            .iter()
+            .filter(|(_, terms)| terms.iter().any(|term| matched.contains(term)))
+            .map(|(bounds, _)| *bounds)
+            .collect();
+
+        SearchHit {
+            id,
+            score,
+            highlights,
+            regions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(text: &str) -> Vec<String> {
+        tokenize(text).into_iter().map(|token| token.term).collect()
+    }
+
+    fn ids(index: &SearchIndex, query: &str) -> Vec<u64> {
+        index.search(query).iter().map(|hit| hit.id).collect()
+    }
+
+    #[test]
+    fn tokenizer_folds_german_and_english() {
+        assert_eq!(terms("Größe: 42,00 €"), ["groesse", "grosse", "42", "00"]);
+        assert_eq!(
+            terms("E-Mail-Adresse"),
+            ["emailadresse", "e", "mail", "adresse"]
+        );
+        assert_eq!(
+            terms("Peter’s café, it's"),
+            ["peters", "peter", "cafe", "its", "it"]
+        );
+        assert_eq!(terms("Ende - Anfang"), ["ende", "anfang"]);
+
+        let text = "Straße-Nr. 5";
+        let tokens = tokenize(text);
+        assert_eq!(&text[tokens[0].range.clone()], "Straße-Nr");
+        assert_eq!(&text[tokens.last().unwrap().range.clone()], "5");
+    }
+
+    #[test]
+    fn prefixes_and_spelling_variants_match() {
+        let mut index = SearchIndex::new();
+        index.insert(1, "Rechnung von Müller GmbH", &[]);
+        index.insert(2, "Invoice for the e-mail campaign", &[]);
+
+        assert_eq!(ids(&index, "rech"), [1]);
+        assert_eq!(ids(&index, "MUELLER"), [1]);
+        assert_eq!(ids(&index, "muller rechnung"), [1]);
+        assert_eq!(ids(&index, "Email"), [2]);
+        assert_eq!(ids(&index, "e-mail invoice"), [2]);
+        assert_eq!(ids(&index, "mail"), [2]);
+        // Ein Buchstabe allein ist kein Präfix
+        assert!(ids(&index, "r").is_empty());
+        assert!(ids(&index, "rechnung campaign").is_empty());
+    }
+
+    #[test]
+    fn exact_and_frequent_matches_rank_first() {
+        let mut index = SearchIndex::new();
+        index.insert(1, "Termine und Terminplanung", &[]);
+        index.insert(2, "Terminal öffnen", &[]);
+        index.insert(3, "Termin verschoben, neuer Termin am Montag", &[]);
+        index.insert(4, "Kein Treffer hier", &[]);
+
+        assert_eq!(ids(&index, "termin"), [3, 1, 2]);
+        // Gleiche Bewertung: die neuere Aufnahme zuerst
+        index.insert(5, "Terminal öffnen", &[]);
+        assert_eq!(ids(&index, "terminal"), [5, 2]);
+    }
+
+    #[test]
+    fn highlights_point_into_text_and_image() {
+        let mut index = SearchIndex::new();
+        let text = "Bitte die E-Mail-Adresse prüfen";
+        let words = [
+            OcrWord::new("Bitte", PixelRect::new(0, 0, 40, 12)),
+            OcrWord::new("die", PixelRect::new(44, 0, 20, 12)),
+            OcrWord::new("E-Mail-Adresse", PixelRect::new(68, 0, 100, 12)),
+            OcrWord::new("prüfen", PixelRect::new(172, 0, 44, 12)),
+        ];
+        index.insert(7, text, &words);
+
+        let hits = index.search("mail PRÜF");
+        assert_eq!(hits.len(), 1);
+        let found: Vec<&str> = hits[0]
+            .highlights
+            .iter()
+            .map(|range| &text[range.clone()])
+            .collect();
+        assert_eq!(found, ["Mail", "prüfen"]);
+        assert_eq!(
+            mark(text, &hits[0].highlights, "[", "]"),
+            "Bitte die E-[Mail]-Adresse [prüfen]"
+        );
+        assert_eq!(hits[0].regions, [words[2].bounds, words[3].bounds]);
+    }
+
+    #[test]
+    fn removed_documents_are_not_found() {
+        let mut index = SearchIndex::new();
+        index.insert(1, "Projektplan", &[]);
+        index.insert(2, "Projektbericht", &[]);
+        index.remove(1);
+        assert_eq!(ids(&index, "projekt"), [2]);
+
+        // Erneutes Einfügen ersetzt den alten Text
+        index.insert(2, "Urlaubsantrag", &[]);
+        assert!(ids(&index, "projekt").is_empty());
+        assert_eq!(index.len(), 1);
+        assert!(index.postings.keys().all(|term| term.starts_with("urlaub")));
+    }
+}
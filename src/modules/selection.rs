@@ -0,0 +1,875 @@
+use crate::modules::backend::{PixelRect, Rect};
+use crate::modules::history::Edit;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Escape,
+    Enter,
+    Left,
+    Right,
+    Up,
+    Down,
+    // Buchstaben und Ziffern, immer als Großbuchstabe
+    Char(char),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+}
+
+// Plattformunabhängige Eingaben, in die win_proc die Fensternachrichten übersetzt
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEvent {
+    MouseDown { x: f32, y: f32 },
+    MouseMove { x: f32, y: f32, pressed: bool },
+    MouseUp { x: f32, y: f32 },
+    KeyDown(Key, Modifiers),
+}
+
+// Kantenlänge der Anfasser in Pixeln
+pub const HANDLE_SIZE: f32 = 8.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Handle {
+    TopLeft,
+    Top,
+    TopRight,
+    Right,
+    BottomRight,
+    Bottom,
+    BottomLeft,
+    Left,
+}
+
+impl Handle {
+    pub const ALL: [Handle; 8] = [
+        Handle::TopLeft,
+        Handle::Top,
+        Handle::TopRight,
+        Handle::Right,
+        Handle::BottomRight,
+        Handle::Bottom,
+        Handle::BottomLeft,
+        Handle::Left,
+    ];
+
+    // Mittelpunkt des Anfassers auf dem (normalisierten) Rechteck
+    fn anchor(&self, rect: &Rect) -> (f32, f32) {
+        let cx = (rect.left + rect.right) / 2.0;
+        let cy = (rect.top + rect.bottom) / 2.0;
+        match self {
+            Handle::TopLeft => (rect.left, rect.top),
+            Handle::Top => (cx, rect.top),
+            Handle::TopRight => (rect.right, rect.top),
+            Handle::Right => (rect.right, cy),
+            Handle::BottomRight => (rect.right, rect.bottom),
+            Handle::Bottom => (cx, rect.bottom),
+            Handle::BottomLeft => (rect.left, rect.bottom),
+            Handle::Left => (rect.left, cy),
+        }
+    }
+
+    pub fn rect(&self, rect: &Rect) -> Rect {
+        let (x, y) = self.anchor(&rect.normalized());
+        let half = HANDLE_SIZE / 2.0;
+        Rect::new(x - half, y - half, x + half, y + half)
+    }
+
+    fn resize(&self, rect: &mut Rect, x: f32, y: f32) {
+        match self {
+            Handle::TopLeft => {
+                rect.left = x;
+                rect.top = y;
+            }
+            Handle::Top => rect.top = y,
+            Handle::TopRight => {
+                rect.right = x;
+                rect.top = y;
+            }
+            Handle::Right => rect.right = x,
+            Handle::BottomRight => {
+                rect.right = x;
+                rect.bottom = y;
+            }
+            Handle::Bottom => rect.bottom = y,
+            Handle::BottomLeft => {
+                rect.left = x;
+                rect.bottom = y;
+            }
+            Handle::Left => rect.left = x,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Adjustment {
+    Move,
+    Resize(Handle),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelectionState {
+    Idle,
+    Dragging,
+    Selected,
+    Adjusting,
+    Committed,
+    Cancelled,
+}
+
+fn clamp_point(bounds: &Rect, x: f32, y: f32) -> (f32, f32) {
+    (
+        x.clamp(bounds.left, bounds.right),
+        y.clamp(bounds.top, bounds.bottom),
+    )
+}
+
+// Auswahl aus Ankerpunkt (Mausklick) und Cursor; liefert immer ein normalisiertes,
+// auf den virtuellen Bildschirm begrenztes Rechteck, egal in welche Richtung gezogen wird
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Selection {
+    anchor: (f32, f32),
+    cursor: (f32, f32),
+    bounds: Rect,
+}
+
+impl Selection {
+    pub fn new(x: f32, y: f32, bounds: Rect) -> Self {
+        let bounds = bounds.normalized();
+        let anchor = clamp_point(&bounds, x, y);
+        Selection {
+            anchor,
+            cursor: anchor,
+            bounds,
+        }
+    }
+
+    pub fn from_rect(rect: Rect, bounds: Rect) -> Self {
+        let mut selection = Selection::new(rect.left, rect.top, bounds);
+        selection.drag_to(rect.right, rect.bottom);
+        selection
+    }
+
+    pub fn drag_to(&mut self, x: f32, y: f32) {
+        self.cursor = clamp_point(&self.bounds, x, y);
+    }
+
+    pub fn bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.anchor.0, self.anchor.1, self.cursor.0, self.cursor.1).normalized()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let pixels = self.pixel_bounds();
+        pixels.width == 0 || pixels.height == 0
+    }
+
+    // Verschiebt die Auswahl, ohne sie über den Rand des Bildschirms hinauszuschieben
+    pub fn translate(&self, dx: f32, dy: f32) -> Self {
+        let rect = self.rect();
+        let dx = dx.clamp(self.bounds.left - rect.left, self.bounds.right - rect.right);
+        let dy = dy.clamp(self.bounds.top - rect.top, self.bounds.bottom - rect.bottom);
+        Selection::from_rect(rect.offset(dx, dy), self.bounds)
+    }
+
+    // Ganzzahlige Pixelgrenzen zum Zuschneiden; angeschnittene Pixel gehören dazu
+    pub fn pixel_bounds(&self) -> PixelRect {
+        let rect = self.rect();
+        let left = rect.left.floor() as i32;
+        let top = rect.top.floor() as i32;
+        let right = (rect.right.ceil() as i32).min(self.bounds.right.floor() as i32);
+        let bottom = (rect.bottom.ceil() as i32).min(self.bounds.bottom.floor() as i32);
+        PixelRect {
+            x: left,
+            y: top,
+            width: (right - left).max(0) as u32,
+            height: (bottom - top).max(0) as u32,
+        }
+    }
+}
+
+pub struct SelectionSession {
+    state: SelectionState,
+    bounds: Rect,
+    selection: Option<Selection>,
+    adjustment: Option<Adjustment>,
+    grab: Option<(f32, f32, Selection)>,
+}
+
+impl SelectionSession {
+    pub fn new(bounds: Rect) -> Self {
+        SelectionSession {
+            state: SelectionState::Idle,
+            bounds,
+            selection: None,
+            adjustment: None,
+            grab: None,
+        }
+    }
+
+    pub fn state(&self) -> SelectionState {
+        self.state
+    }
+
+    pub fn selection(&self) -> Option<Selection> {
+        self.selection
+    }
+
+    pub fn rect(&self) -> Option<Rect> {
+        self.selection.map(|selection| selection.rect())
+    }
+
+    pub fn pixel_bounds(&self) -> Option<PixelRect> {
+        self.selection.map(|selection| selection.pixel_bounds())
+    }
+
+    // Anfasser werden nur auf einer fertigen Auswahl angezeigt
+    pub fn handles(&self) -> Vec<Rect> {
+        match (self.state, self.rect()) {
+            (SelectionState::Selected | SelectionState::Adjusting, Some(rect)) => Handle::ALL
+                .iter()
+                .map(|handle| handle.rect(&rect))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn hit_test(&self, x: f32, y: f32) -> Option<Adjustment> {
+        let rect = self.rect()?;
+        Handle::ALL
+            .iter()
+            .find(|handle| handle.rect(&rect).contains(x, y))
+            .map(|handle| Adjustment::Resize(*handle))
+            .or_else(|| rect.contains(x, y).then_some(Adjustment::Move))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.state,
+            SelectionState::Committed | SelectionState::Cancelled
+        )
+    }
+
+    pub fn reset(&mut self) {
+        *self = SelectionSession::new(self.bounds);
+    }
+
+    // Setzt die Auswahl direkt, z.B. per Befehl von der Kommandozeile
+    pub fn select(&mut self, rect: Rect) {
+        let selection = Selection::from_rect(rect, self.bounds);
+        self.adjustment = None;
+        self.grab = None;
+        if selection.is_empty() {
+            self.state = SelectionState::Idle;
+            self.selection = None;
+        } else {
+            self.state = SelectionState::Selected;
+            self.selection = Some(selection);
+        }
+    }
+
+    // Stellt eine Auswahl aus dem Verlauf wieder her
+    pub fn restore(&mut self, rect: Option<Rect>) {
+        match rect {
+            Some(rect) => self.select(rect),
+            None => self.reset(),
+        }
+    }
+
+    // Schließt eine fertige Auswahl ohne Enter ab; gibt false zurück, wenn keine vorliegt
+    pub fn commit(&mut self) -> bool {
+        if self.state != SelectionState::Selected {
+            return false;
+        }
+        self.state = SelectionState::Committed;
+        true
+    }
+
+    // Während eines Ziehens ist die Auswahl noch nicht fertig
+    pub fn in_gesture(&self) -> bool {
+        matches!(
+            self.state,
+            SelectionState::Dragging | SelectionState::Adjusting
+        )
+    }
+
+    // Verarbeitet ein Ereignis; gibt true zurück, wenn das Overlay neu gezeichnet werden muss
+    pub fn handle(&mut self, event: InputEvent) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+
+        if let InputEvent::KeyDown(Key::Escape, _) = event {
+            self.state = SelectionState::Cancelled;
+            self.adjustment = None;
+            self.grab = None;
+            return true;
+        }
+
+        match (self.state, event) {
+            (SelectionState::Idle, InputEvent::MouseDown { x, y }) => {
+                self.start_drag(x, y);
+                true
+            }
+            (SelectionState::Dragging, InputEvent::MouseMove { x, y, pressed }) => {
+                self.drag_to(x, y);
+                if !pressed {
+                    self.finish_drag();
+                }
+                true
+            }
+            (SelectionState::Dragging, InputEvent::MouseUp { x, y }) => {
+                self.drag_to(x, y);
+                self.finish_drag();
+                true
+            }
+            (SelectionState::Selected, InputEvent::MouseDown { x, y }) => {
+                match (self.hit_test(x, y), self.selection) {
+                    (Some(adjustment), Some(selection)) => {
+                        self.state = SelectionState::Adjusting;
+                        self.adjustment = Some(adjustment);
+                        self.grab = Some((x, y, selection));
+                    }
+                    _ => self.start_drag(x, y),
+                }
+                true
+            }
+            (SelectionState::Selected, InputEvent::KeyDown(Key::Enter, _)) => {
+                self.state = SelectionState::Committed;
+                true
+            }
+            (SelectionState::Selected, InputEvent::KeyDown(key, modifiers)) => {
+                self.nudge(key, modifiers)
+            }
+            (SelectionState::Adjusting, InputEvent::MouseMove { x, y, pressed }) => {
+                self.adjust_to(x, y);
+                if !pressed {
+                    self.finish_adjustment();
+                }
+                true
+            }
+            (SelectionState::Adjusting, InputEvent::MouseUp { x, y }) => {
+                self.adjust_to(x, y);
+                self.finish_adjustment();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn start_drag(&mut self, x: f32, y: f32) {
+        self.state = SelectionState::Dragging;
+        self.selection = Some(Selection::new(x, y, self.bounds));
+    }
+
+    fn drag_to(&mut self, x: f32, y: f32) {
+        if let Some(ref mut selection) = self.selection {
+            selection.drag_to(x, y);
+        }
+    }
+
+    fn finish_drag(&mut self) {
+        match self.selection {
+            Some(selection) if !selection.is_empty() => {
+                self.state = SelectionState::Selected;
+            }
+            _ => {
+                self.state = SelectionState::Idle;
+                self.selection = None;
+            }
+        }
+    }
+
+    // Verschiebt bzw. skaliert ausgehend von der Auswahl beim Mausklick
+    fn adjust_to(&mut self, x: f32, y: f32) {
+        let ((gx, gy, origin), adjustment) = match (self.grab, self.adjustment) {
+            (Some(grab), Some(adjustment)) => (grab, adjustment),
+            _ => return,
+        };
+
+        self.selection = Some(match adjustment {
+            Adjustment::Move => origin.translate(x - gx, y - gy),
+            Adjustment::Resize(handle) => {
+                let mut rect = origin.rect();
+                handle.resize(&mut rect, x, y);
+                Selection::from_rect(rect, self.bounds)
+            }
+        });
+    }
+
+    fn finish_adjustment(&mut self) {
+        if let (Some(selection), Some((_, _, origin))) = (self.selection, self.grab) {
+            if selection.is_empty() {
+                self.selection = Some(origin);
+            }
+        }
+        self.state = SelectionState::Selected;
+        self.adjustment = None;
+        self.grab = None;
+    }
+
+    // Pfeiltasten verschieben die Auswahl um 1px, mit Shift um 10px
+    fn nudge(&mut self, key: Key, modifiers: Modifiers) -> bool {
+        let step = if modifiers.shift { 10.0 } else { 1.0 };
+        let (dx, dy) = match key {
+            Key::Left => (-step, 0.0),
+            Key::Right => (step, 0.0),
+            Key::Up => (0.0, -step),
+            Key::Down => (0.0, step),
+            _ => return false,
+        };
+
+        match self.selection {
+            Some(selection) => {
+                self.selection = Some(selection.translate(dx, dy));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// Änderung der Auswahl für den Verlauf; None steht für keine Auswahl
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SelectionEdit {
+    pub before: Option<Rect>,
+    pub after: Option<Rect>,
+}
+
+impl Edit for SelectionEdit {
+    type Target = SelectionSession;
+
+    fn apply(&self, session: &mut SelectionSession) {
+        session.restore(self.after);
+    }
+
+    fn revert(&self, session: &mut SelectionSession) {
+        session.restore(self.before);
+    }
+
+    fn merge(&mut self, next: &Self) -> bool {
+        self.after = next.after;
+        true
+    }
+
+    fn is_noop(&self) -> bool {
+        self.before == self.after
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screen() -> Rect {
+        Rect::new(0.0, 0.0, 200.0, 100.0)
+    }
+
+    fn key(key: Key) -> InputEvent {
+        InputEvent::KeyDown(key, Modifiers::default())
+    }
+
+    fn down(x: f32, y: f32) -> InputEvent {
+        InputEvent::MouseDown { x, y }
+    }
+
+    fn moved(x: f32, y: f32) -> InputEvent {
+        InputEvent::MouseMove {
+            x,
+            y,
+            pressed: true,
+        }
+    }
+
+    fn up(x: f32, y: f32) -> InputEvent {
+        InputEvent::MouseUp { x, y }
+    }
+
+    // Sitzung mit fertiger Auswahl von (10, 10) bis (50, 40)
+    fn selected() -> SelectionSession {
+        let mut session = SelectionSession::new(screen());
+        session.handle(down(10.0, 10.0));
+        session.handle(moved(30.0, 20.0));
+        session.handle(up(50.0, 40.0));
+        assert_eq!(session.state(), SelectionState::Selected);
+        session
+    }
+
+    #[test]
+    fn drag_goes_from_idle_to_selected_and_enter_commits() {
+        let mut session = SelectionSession::new(screen());
+        assert_eq!(session.state(), SelectionState::Idle);
+        assert_eq!(session.rect(), None);
+
+        assert!(session.handle(down(10.0, 10.0)));
+        assert_eq!(session.state(), SelectionState::Dragging);
+        assert!(session.in_gesture());
+
+        assert!(session.handle(moved(30.0, 20.0)));
+        assert_eq!(session.state(), SelectionState::Dragging);
+        assert_eq!(session.rect(), Some(Rect::new(10.0, 10.0, 30.0, 20.0)));
+
+        assert!(session.handle(up(50.0, 40.0)));
+        assert_eq!(session.state(), SelectionState::Selected);
+        assert!(!session.in_gesture());
+        assert_eq!(session.rect(), Some(Rect::new(10.0, 10.0, 50.0, 40.0)));
+        assert_eq!(session.handles().len(), Handle::ALL.len());
+
+        assert!(session.handle(key(Key::Enter)));
+        assert_eq!(session.state(), SelectionState::Committed);
+        assert!(session.is_finished());
+        assert!(session.handles().is_empty());
+        assert_eq!(session.pixel_bounds(), Some(PixelRect::new(10, 10, 40, 30)));
+    }
+
+    #[test]
+    fn release_reported_by_a_move_ends_the_drag() {
+        // Geht das MouseUp außerhalb des Fensters verloren, kommt nur ein Move ohne Taste
+        let mut session = SelectionSession::new(screen());
+        session.handle(down(10.0, 10.0));
+        session.handle(InputEvent::MouseMove {
+            x: 40.0,
+            y: 30.0,
+            pressed: false,
+        });
+        assert_eq!(session.state(), SelectionState::Selected);
+        assert_eq!(session.rect(), Some(Rect::new(10.0, 10.0, 40.0, 30.0)));
+    }
+
+    #[test]
+    fn escape_cancels_from_every_state() {
+        let mut idle = SelectionSession::new(screen());
+        assert!(idle.handle(key(Key::Escape)));
+        assert_eq!(idle.state(), SelectionState::Cancelled);
+
+        let mut dragging = SelectionSession::new(screen());
+        dragging.handle(down(10.0, 10.0));
+        dragging.handle(key(Key::Escape));
+        assert_eq!(dragging.state(), SelectionState::Cancelled);
+
+        let mut session = selected();
+        session.handle(key(Key::Escape));
+        assert_eq!(session.state(), SelectionState::Cancelled);
+        assert!(session.is_finished());
+    }
+
+    #[test]
+    fn click_without_drag_returns_to_idle() {
+        let mut session = SelectionSession::new(screen());
+        session.handle(down(10.0, 10.0));
+        session.handle(up(10.4, 10.0));
+        assert_eq!(session.state(), SelectionState::Idle);
+        assert_eq!(session.rect(), None);
+
+        // Enter ohne Auswahl schließt nichts ab
+        assert!(!session.handle(key(Key::Enter)));
+        assert_eq!(session.state(), SelectionState::Idle);
+        assert!(!session.commit());
+    }
+
+    #[test]
+    fn finished_session_ignores_further_input() {
+        let mut session = selected();
+        assert!(session.commit());
+        assert_eq!(session.state(), SelectionState::Committed);
+
+        assert!(!session.handle(down(100.0, 50.0)));
+        assert!(!session.handle(key(Key::Escape)));
+        assert_eq!(session.state(), SelectionState::Committed);
+        assert_eq!(session.rect(), Some(Rect::new(10.0, 10.0, 50.0, 40.0)));
+
+        session.reset();
+        assert_eq!(session.state(), SelectionState::Idle);
+        assert_eq!(session.rect(), None);
+    }
+
+    #[test]
+    fn clicking_outside_the_selection_starts_a_new_one() {
+        let mut session = selected();
+        session.handle(down(150.0, 80.0));
+        assert_eq!(session.state(), SelectionState::Dragging);
+        session.handle(up(120.0, 60.0));
+        assert_eq!(session.rect(), Some(Rect::new(120.0, 60.0, 150.0, 80.0)));
+    }
+
+    #[test]
+    fn select_and_restore_set_the_state_directly() {
+        let mut session = SelectionSession::new(screen());
+        session.select(Rect::new(5.0, 5.0, 25.0, 15.0));
+        assert_eq!(session.state(), SelectionState::Selected);
+
+        // Eine leere Auswahl zählt als keine Auswahl
+        session.select(Rect::new(5.0, 5.0, 5.0, 15.0));
+        assert_eq!(session.state(), SelectionState::Idle);
+        assert_eq!(session.rect(), None);
+
+        session.restore(Some(Rect::new(1.0, 2.0, 3.0, 4.0)));
+        assert_eq!(session.rect(), Some(Rect::new(1.0, 2.0, 3.0, 4.0)));
+        session.restore(None);
+        assert_eq!(session.state(), SelectionState::Idle);
+    }
+
+    #[test]
+    fn hit_test_finds_handles_before_the_inside() {
+        let session = selected();
+        let anchors = [
+            (Handle::TopLeft, 10.0, 10.0),
+            (Handle::Top, 30.0, 10.0),
+            (Handle::TopRight, 50.0, 10.0),
+            (Handle::Right, 50.0, 25.0),
+            (Handle::BottomRight, 50.0, 40.0),
+            (Handle::Bottom, 30.0, 40.0),
+            (Handle::BottomLeft, 10.0, 40.0),
+            (Handle::Left, 10.0, 25.0),
+        ];
+        for (handle, x, y) in anchors {
+            assert_eq!(session.hit_test(x, y), Some(Adjustment::Resize(handle)));
+            // Anfasser reichen eine halbe Kantenlänge über die Auswahl hinaus
+            let (dx, dy) = (x - 30.0, y - 25.0);
+            let outward = (
+                x + dx.signum() * (HANDLE_SIZE / 2.0 - 0.5),
+                y + dy.signum() * (HANDLE_SIZE / 2.0 - 0.5),
+            );
+            assert_eq!(
+                session.hit_test(outward.0, outward.1),
+                Some(Adjustment::Resize(handle))
+            );
+        }
+
+        assert_eq!(session.hit_test(30.0, 25.0), Some(Adjustment::Move));
+        assert_eq!(session.hit_test(20.0, 30.0), Some(Adjustment::Move));
+        assert_eq!(session.hit_test(100.0, 25.0), None);
+        assert_eq!(session.hit_test(30.0, 45.0), None);
+        assert_eq!(SelectionSession::new(screen()).hit_test(30.0, 25.0), None);
+    }
+
+    #[test]
+    fn resizing_through_zero_flips_the_selection() {
+        let mut session = selected();
+        session.handle(down(10.0, 10.0));
+        assert_eq!(session.state(), SelectionState::Adjusting);
+        assert!(session.in_gesture());
+
+        session.handle(moved(70.0, 60.0));
+        assert_eq!(session.rect(), Some(Rect::new(50.0, 40.0, 70.0, 60.0)));
+        session.handle(up(70.0, 60.0));
+        assert_eq!(session.state(), SelectionState::Selected);
+        assert_eq!(session.rect(), Some(Rect::new(50.0, 40.0, 70.0, 60.0)));
+    }
+
+    #[test]
+    fn resizing_to_zero_keeps_the_previous_selection() {
+        let mut session = selected();
+        session.handle(down(50.0, 25.0));
+        session.handle(moved(10.0, 25.0));
+        session.handle(up(10.0, 25.0));
+        assert_eq!(session.state(), SelectionState::Selected);
+        assert_eq!(session.rect(), Some(Rect::new(10.0, 10.0, 50.0, 40.0)));
+    }
+
+    #[test]
+    fn edge_handles_change_one_side_only() {
+        let mut session = selected();
+        session.handle(down(30.0, 40.0));
+        session.handle(up(80.0, 90.0));
+        assert_eq!(session.rect(), Some(Rect::new(10.0, 10.0, 50.0, 90.0)));
+
+        // Über den Bildschirmrand hinaus wird begrenzt
+        session.handle(down(10.0, 50.0));
+        session.handle(up(-30.0, 0.0));
+        assert_eq!(session.rect(), Some(Rect::new(0.0, 10.0, 50.0, 90.0)));
+    }
+
+    #[test]
+    fn dragging_the_inside_moves_within_the_screen() {
+        let mut session = selected();
+        session.handle(down(30.0, 25.0));
+        session.handle(moved(40.0, 30.0));
+        assert_eq!(session.rect(), Some(Rect::new(20.0, 15.0, 60.0, 45.0)));
+
+        // Größe bleibt erhalten, die Auswahl stößt an den Rand
+        session.handle(up(300.0, 300.0));
+        assert_eq!(session.state(), SelectionState::Selected);
+        assert_eq!(session.rect(), Some(Rect::new(160.0, 70.0, 200.0, 100.0)));
+    }
+
+    #[test]
+    fn arrows_nudge_by_one_and_shift_arrows_by_ten() {
+        let shift = Modifiers {
+            shift: true,
+            ctrl: false,
+        };
+        let mut session = selected();
+
+        assert!(session.handle(key(Key::Right)));
+        assert_eq!(session.rect(), Some(Rect::new(11.0, 10.0, 51.0, 40.0)));
+        assert!(session.handle(key(Key::Down)));
+        assert!(session.handle(key(Key::Left)));
+        assert_eq!(session.rect(), Some(Rect::new(10.0, 11.0, 50.0, 41.0)));
+
+        assert!(session.handle(InputEvent::KeyDown(Key::Up, shift)));
+        assert_eq!(session.rect(), Some(Rect::new(10.0, 1.0, 50.0, 31.0)));
+        assert!(session.handle(InputEvent::KeyDown(Key::Up, shift)));
+        assert_eq!(session.rect(), Some(Rect::new(10.0, 0.0, 50.0, 30.0)));
+
+        assert!(session.handle(InputEvent::KeyDown(Key::Right, shift)));
+        assert_eq!(session.rect(), Some(Rect::new(20.0, 0.0, 60.0, 30.0)));
+
+        assert!(!session.handle(key(Key::Char('A'))));
+        assert_eq!(session.state(), SelectionState::Selected);
+    }
+
+    #[test]
+    fn arrows_do_nothing_without_a_selection() {
+        let mut session = SelectionSession::new(screen());
+        assert!(!session.handle(key(Key::Left)));
+        assert_eq!(session.rect(), None);
+    }
+
+    // Virtueller Bildschirm mit einem Monitor links vom und einem über dem Hauptmonitor
+    fn virtual_screen() -> Rect {
+        Rect::new(-1920.0, -300.0, 1920.0, 1080.0)
+    }
+
+    // Punkte innerhalb, auf dem Rand und außerhalb des virtuellen Bildschirms
+    fn sample_points() -> Vec<(f32, f32)> {
+        let xs = [
+            -5000.0, -1920.0, -1000.5, -0.25, 0.0, 0.75, 960.0, 1919.5, 1920.0, 4000.0,
+        ];
+        let ys = [
+            -800.0, -300.0, -150.25, 0.0, 0.5, 540.0, 1079.75, 1080.0, 2000.0,
+        ];
+        xs.iter()
+            .flat_map(|&x| ys.iter().map(move |&y| (x, y)))
+            .collect()
+    }
+
+    #[test]
+    fn drag_in_each_quadrant_gives_the_same_normalized_rect() {
+        let cases = [
+            ((80.0, 70.0), Rect::new(50.0, 50.0, 80.0, 70.0)),
+            ((20.0, 70.0), Rect::new(20.0, 50.0, 50.0, 70.0)),
+            ((20.0, 30.0), Rect::new(20.0, 30.0, 50.0, 50.0)),
+            ((80.0, 30.0), Rect::new(50.0, 30.0, 80.0, 50.0)),
+        ];
+        for ((x, y), rect) in cases {
+            let mut selection = Selection::new(50.0, 50.0, screen());
+            selection.drag_to(x, y);
+            assert_eq!(selection.rect(), rect, "drag to ({}, {})", x, y);
+            assert!(!selection.is_empty());
+        }
+    }
+
+    #[test]
+    fn rect_is_normalized_clamped_and_direction_independent() {
+        let bounds = virtual_screen();
+        let points = sample_points();
+        for &(ax, ay) in &points {
+            for &(cx, cy) in &points {
+                let mut forward = Selection::new(ax, ay, bounds);
+                forward.drag_to(cx, cy);
+                let mut backward = Selection::new(cx, cy, bounds);
+                backward.drag_to(ax, ay);
+
+                let rect = forward.rect();
+                let context = format!("({}, {}) -> ({}, {})", ax, ay, cx, cy);
+                assert_eq!(rect, backward.rect(), "{}", context);
+                assert!(
+                    rect.left <= rect.right && rect.top <= rect.bottom,
+                    "{}",
+                    context
+                );
+                assert!(
+                    rect.left >= bounds.left
+                        && rect.top >= bounds.top
+                        && rect.right <= bounds.right
+                        && rect.bottom <= bounds.bottom,
+                    "{}",
+                    context
+                );
+                assert_eq!(
+                    rect.left,
+                    ax.min(cx).clamp(bounds.left, bounds.right),
+                    "{}",
+                    context
+                );
+                assert_eq!(
+                    rect.bottom,
+                    ay.max(cy).clamp(bounds.top, bounds.bottom),
+                    "{}",
+                    context
+                );
+
+                // Die Pixelgrenzen schließen angeschnittene Pixel ein und bleiben auf dem Bildschirm
+                let pixels = forward.pixel_bounds();
+                assert!(pixels.x as f32 <= rect.left && pixels.y as f32 <= rect.top);
+                assert!(pixels.right() as f32 >= rect.right.min(bounds.right));
+                assert!(pixels.right() as f32 <= bounds.right, "{}", context);
+                assert!(pixels.bottom() as f32 <= bounds.bottom, "{}", context);
+                assert_eq!(
+                    forward.is_empty(),
+                    pixels.width == 0 || pixels.height == 0,
+                    "{}",
+                    context
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn zero_size_selections_are_empty() {
+        let point = Selection::new(40.0, 40.0, screen());
+        assert!(point.is_empty());
+        assert_eq!(point.pixel_bounds(), PixelRect::new(40, 40, 0, 0));
+
+        let mut line = Selection::new(40.0, 40.0, screen());
+        line.drag_to(90.0, 40.0);
+        assert!(line.is_empty());
+        assert_eq!(line.rect(), Rect::new(40.0, 40.0, 90.0, 40.0));
+
+        // Ganz außerhalb gezogen bleibt nur eine Linie auf dem Rand übrig
+        let mut outside = Selection::new(-50.0, 10.0, screen());
+        outside.drag_to(-10.0, 60.0);
+        assert!(outside.is_empty());
+        assert_eq!(outside.rect(), Rect::new(0.0, 10.0, 0.0, 60.0));
+    }
+
+    #[test]
+    fn negative_coordinates_left_of_the_primary_monitor() {
+        let mut selection = Selection::new(-100.5, -20.25, virtual_screen());
+        selection.drag_to(-300.0, 40.5);
+        assert_eq!(selection.rect(), Rect::new(-300.0, -20.25, -100.5, 40.5));
+        assert_eq!(selection.pixel_bounds(), PixelRect::new(-300, -21, 200, 62));
+    }
+
+    #[test]
+    fn dragging_past_the_screen_edge_is_clamped() {
+        let mut selection = Selection::new(150.0, 20.0, screen());
+        selection.drag_to(500.0, -40.0);
+        assert_eq!(selection.rect(), Rect::new(150.0, 0.0, 200.0, 20.0));
+        assert_eq!(selection.pixel_bounds(), PixelRect::new(150, 0, 50, 20));
+
+        // Auch ein Anker außerhalb wird an den Rand gesetzt
+        let mut selection = Selection::new(-40.0, 250.0, screen());
+        selection.drag_to(30.0, 50.0);
+        assert_eq!(selection.rect(), Rect::new(0.0, 50.0, 30.0, 100.0));
+    }
+
+    #[test]
+    fn translation_keeps_the_size_inside_the_screen() {
+        let bounds = virtual_screen();
+        let selection = Selection::from_rect(Rect::new(-10.0, -10.0, 290.0, 190.0), bounds);
+        for &(dx, dy) in &sample_points() {
+            let rect = selection.translate(dx, dy).rect();
+            assert_eq!(rect.width(), 300.0, "by ({}, {})", dx, dy);
+            assert_eq!(rect.height(), 200.0, "by ({}, {})", dx, dy);
+            assert!(rect.left >= bounds.left && rect.right <= bounds.right);
+            assert!(rect.top >= bounds.top && rect.bottom <= bounds.bottom);
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use crate::modules::errorhandler::SnipError;
+use image::RgbaImage;
+
+// Setzt Aufnahmen desselben Bereichs, zwischen denen der Inhalt nach unten gescrollt wurde, zu
+// einem hohen Bild zusammen. Jede Zeile wird gehasht; Zeilen, die in zwei Frames an derselben
+// Stelle gleich sind, gelten als feste Kopf- bzw. Fußzeile (z.B. Menüleisten). Im Bereich
+// dazwischen wird die Verschiebung gesucht, bei der sich die meisten Zeilen decken.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StitchOptions {
+    // Niederwertige Bits jedes Farbkanals, die beim Vergleich ignoriert werden (Dithering, Rauschen)
+    pub ignored_bits: u8,
+    // Mindestzahl aussagekräftiger Zeilen, die sich überlappen müssen
+    pub min_overlap: u32,
+    // Anteil der überlappenden Zeilen, der übereinstimmen muss; der Rest darf sich ändern,
+    // z.B. ein blinkender Cursor
+    pub match_ratio: f32,
+    // Kopf- und Fußzeile dürfen zusammen höchstens diesen Anteil der Höhe einnehmen
+    pub max_sticky: f32,
+}
+
+impl Default for StitchOptions {
+    fn default() -> Self {
+        StitchOptions {
+            ignored_bits: 2,
+            min_overlap: 8,
+            match_ratio: 0.9,
+            max_sticky: 0.5,
+        }
+    }
+}
+
+// Ergebnis des Vergleichs zweier aufeinanderfolgender Frames
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Overlap {
+    // Feste Zeilen oben und unten
+    pub header: u32,
+    pub footer: u32,
+    // Um so viele Zeilen ist der Inhalt weitergescrollt; 0 heißt unverändert
+    pub shift: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Row {
+    hash: u64,
+    // Einfarbige Zeilen passen überall und zählen beim Suchen nicht mit
+    uniform: bool,
+}
+
+fn row_hashes(image: &RgbaImage, ignored_bits: u8) -> Vec<Row> {
+    let mask = 0xffu8.checked_shl(ignored_bits.min(7) as u32).unwrap_or(0);
+    let width = image.width() as usize * 4;
+    image
+        .as_raw()
+        .chunks_exact(width.max(1))
+        .map(|row| {
+            // FNV-1a
+            let mut hash = 0xcbf2_9ce4_8422_2325u64;
+            for byte in row {
+                hash ^= (byte & mask) as u64;
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+            let first = &row[..4];
+            let uniform = row
+                .chunks_exact(4)
+                .all(|pixel| pixel.iter().zip(first).all(|(a, b)| a & mask == b & mask));
+            Row { hash, uniform }
+        })
+        .collect()
+}
+
+// Sucht die Verschiebung im Bereich zwischen Kopf- und Fußzeile. Fehlende Zeilen kosten mehr
+// als passende bringen, damit eine falsche, größere Überlappung nicht gewinnt.
+fn find_shift(previous: &[Row], current: &[Row], options: &StitchOptions) -> Option<u32> {
+    let band = previous.len();
+    let mut best: Option<(i64, usize)> = None;
+
+    for shift in 1..band {
+        let overlap = band - shift;
+        let mut matched = 0usize;
+        let mut mismatched = 0usize;
+        for (before, after) in previous[shift..].iter().zip(&current[..overlap]) {
+            if before.hash == after.hash {
+                if !after.uniform {
+                    matched += 1;
+                }
+            } else {
+                mismatched += 1;
+            }
+        }
+
+        let compared = matched + mismatched;
+        if compared < options.min_overlap as usize
+            || (matched as f32) < compared as f32 * options.match_ratio
+        {
+            continue;
+        }
+        let score = matched as i64 - 4 * mismatched as i64;
+        if best.is_none_or(|(best_score, _)| score > best_score) {
+            best = Some((score, shift));
+        }
+    }
+    best.map(|(_, shift)| shift as u32)
+}
+
+fn sticky_rows<'a>(
+    previous: impl Iterator<Item = &'a Row>,
+    current: impl Iterator<Item = &'a Row>,
+) -> u32 {
+    previous
+        .zip(current)
+        .take_while(|(before, after)| before.hash == after.hash)
+        .count() as u32
+}
+
+// Vergleicht zwei gleich große Frames. None, wenn sich keine Überlappung findet, z.B. weil zu
+// weit oder nach oben gescrollt wurde.
+pub fn overlap(
+    previous: &RgbaImage,
+    current: &RgbaImage,
+    options: &StitchOptions,
+) -> Option<Overlap> {
+    if previous.dimensions() != current.dimensions() {
+        return None;
+    }
+    let before = row_hashes(previous, options.ignored_bits);
+    let after = row_hashes(current, options.ignored_bits);
+    let height = before.len() as u32;
+    overlap_rows(&before, &after, height, options)
+}
+
+fn overlap_rows(
+    before: &[Row],
+    after: &[Row],
+    height: u32,
+    options: &StitchOptions,
+) -> Option<Overlap> {
+    if before == after {
+        return Some(Overlap {
+            header: 0,
+            footer: 0,
+            shift: 0,
+        });
+    }
+
+    let max_sticky = (height as f32 * options.max_sticky.clamp(0.0, 1.0)) as u32;
+    let header = sticky_rows(before.iter(), after.iter()).min(max_sticky);
+    let footer = sticky_rows(before.iter().rev(), after.iter().rev()).min(max_sticky - header);
+    let band = header as usize..(height - footer) as usize;
+
+    let shift = find_shift(&before[band.clone()], &after[band], options)?;
+    Some(Overlap {
+        header,
+        footer,
+        shift,
+    })
+}
+
+// Frames in Scrollreihenfolge; gleiche Frames (kein Scrollen) werden übersprungen.
+// Kopf- und Fußzeile kommen nur einmal ins Ergebnis: oben aus dem ersten, unten aus dem letzten Frame.
+pub fn stitch(frames: &[RgbaImage], options: &StitchOptions) -> Result<RgbaImage, SnipError> {
+    let first = frames
+        .first()
+        .ok_or_else(|| SnipError::Config("Nothing to stitch".to_string()))?;
+    let (width, height) = first.dimensions();
+    if let Some(index) = frames
+        .iter()
+        .position(|frame| frame.dimensions() != (width, height))
+    {
+        return Err(SnipError::Config(format!(
+            "Frame {} is {}x{}, expected {}x{} like the first frame",
+            index + 1,
+            frames[index].width(),
+            frames[index].height(),
+            width,
+            height
+        )));
+    }
+
+    let hashes: Vec<Vec<Row>> = frames
+        .iter()
+        .map(|frame| row_hashes(frame, options.ignored_bits))
+        .collect();
+
+    // Erst Kopf- und Fußzeile über alle Paare bestimmen, dann mit diesem Band die Verschiebungen
+    let mut pairs = Vec::new();
+    for index in 1..frames.len() {
+        let overlap = overlap_rows(&hashes[index - 1], &hashes[index], height, options)
+            .ok_or_else(|| {
+                SnipError::capture(format!(
+                    "Frames {} and {} do not overlap; scroll less between captures",
+                    index,
+                    index + 1
+                ))
+            })?;
+        if overlap.shift > 0 {
+            pairs.push((index, overlap));
+        }
+    }
+    let header = pairs.iter().map(|(_, o)| o.header).min().unwrap_or(0);
+    let footer = pairs.iter().map(|(_, o)| o.footer).min().unwrap_or(0);
+    let band = header as usize..(height - footer) as usize;
+
+    let mut shifts = Vec::with_capacity(pairs.len());
+    let mut previous = 0;
+    for (index, overlap) in &pairs {
+        // Mit einem schmaleren Band als beim Paar selbst kann sich die Verschiebung ändern
+        let shift = if (overlap.header, overlap.footer) == (header, footer) {
+            overlap.shift
+        } else {
+            find_shift(
+                &hashes[previous][band.clone()],
+                &hashes[*index][band.clone()],
+                options,
+            )
+            .ok_or_else(|| {
+                SnipError::capture(format!(
+                    "Frames {} and {} do not overlap below the fixed header",
+                    previous + 1,
+                    index + 1
+                ))
+            })?
+        };
+        shifts.push((*index, shift));
+        previous = *index;
+    }
+
+    let total = height + shifts.iter().map(|(_, shift)| shift).sum::<u32>();
+    let mut stitched = RgbaImage::new(width, total);
+    let copy_rows = |target: &mut RgbaImage, source: &RgbaImage, from: u32, rows: u32, to: u32| {
+        if rows == 0 {
+            return;
+        }
+        let view = image::imageops::crop_imm(source, 0, from, width, rows);
+        image::imageops::replace(target, &view.to_image(), 0, to);
+    };
+
+    let last = frames[shifts.last().map_or(0, |(index, _)| *index)].clone();
+    let content_end = height - footer;
+    copy_rows(&mut stitched, first, 0, content_end, 0);
+    let mut y = content_end;
+    for (index, shift) in &shifts {
+        // Neu ist nur, was unten im Band hereingescrollt ist
+        copy_rows(
+            &mut stitched,
+            &frames[*index],
+            content_end - shift,
+            *shift,
+            y,
+        );
+        y += shift;
+    }
+    copy_rows(&mut stitched, &last, content_end, footer, y);
+    Ok(stitched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    const WIDTH: u32 = 48;
+
+    // Zeilen mit Pseudozufallsmuster, dazwischen ein paar leere Zeilen
+    fn page(height: u32) -> RgbaImage {
+        RgbaImage::from_fn(WIDTH, height, |x, y| {
+            if y % 37 < 3 {
+                return Rgba([255, 255, 255, 255]);
+            }
+            let mut n = (x / 3 + 1).wrapping_mul(2_654_435_761) ^ (y + 7).wrapping_mul(40_503);
+            n ^= n >> 13;
+            n = n.wrapping_mul(0x5bd1_e995);
+            Rgba([n as u8, (n >> 8) as u8, (n >> 16) as u8, 255])
+        })
+    }
+
+    fn bar(height: u32, color: [u8; 3]) -> RgbaImage {
+        RgbaImage::from_pixel(WIDTH, height, Rgba([color[0], color[1], color[2], 255]))
+    }
+
+    // Fenster der Höhe `view` an den Scrollpositionen, mit fester Kopf- und Fußzeile darüber
+    fn frames(
+        content: &RgbaImage,
+        view: u32,
+        offsets: &[u32],
+        header: u32,
+        footer: u32,
+    ) -> Vec<RgbaImage> {
+        offsets
+            .iter()
+            .map(|&offset| {
+                let mut frame = RgbaImage::new(WIDTH, view);
+                let body = view - header - footer;
+                let slice = image::imageops::crop_imm(content, 0, offset, WIDTH, body).to_image();
+                image::imageops::replace(&mut frame, &slice, 0, header);
+                image::imageops::replace(&mut frame, &bar(header, [20, 40, 200]), 0, 0);
+                image::imageops::replace(&mut frame, &bar(footer, [90, 90, 90]), 0, view - footer);
+                frame
+            })
+            .collect()
+    }
+
+    fn rows(image: &RgbaImage, from: u32, count: u32) -> RgbaImage {
+        image::imageops::crop_imm(image, 0, from, image.width(), count).to_image()
+    }
+
+    #[test]
+    fn slices_are_stitched_back_together() {
+        let content = page(600);
+        // Bei 0 und 37 beginnen beide Frames mit leeren Zeilen, die wie eine Kopfzeile aussehen
+        let frames = frames(&content, 160, &[0, 37, 70, 70, 181, 300, 440], 0, 0);
+        let stitched = stitch(&frames, &StitchOptions::default()).unwrap();
+        assert_eq!(stitched, rows(&content, 0, 600));
+    }
+
+    #[test]
+    fn sticky_header_and_footer_appear_once() {
+        let content = page(500);
+        let (header, footer) = (24, 16);
+        let frames = frames(&content, 200, &[0, 90, 200, 340], header, footer);
+        let overlap = overlap(&frames[0], &frames[1], &StitchOptions::default()).unwrap();
+        assert_eq!(
+            overlap,
+            Overlap {
+                header,
+                footer,
+                shift: 90
+            }
+        );
+
+        let stitched = stitch(&frames, &StitchOptions::default()).unwrap();
+        assert_eq!(stitched.height(), header + 500 + footer);
+        assert_eq!(rows(&stitched, 0, header), bar(header, [20, 40, 200]));
+        assert_eq!(rows(&stitched, header, 500), content);
+        assert_eq!(
+            rows(&stitched, header + 500, footer),
+            bar(footer, [90, 90, 90])
+        );
+    }
+
+    #[test]
+    fn small_changes_between_frames_are_tolerated() {
+        let content = page(400);
+        let mut frames = frames(&content, 150, &[0, 60, 130, 250], 0, 0);
+        // Rauschen in den unteren Bits und ein blinkender Cursor im zweiten Frame
+        for pixel in frames[1].pixels_mut() {
+            pixel[0] ^= 1;
+        }
+        for x in 10..14 {
+            for y in 40..44 {
+                frames[1].put_pixel(x, y, Rgba([0, 0, 0, 255]));
+            }
+        }
+
+        let stitched = stitch(&frames, &StitchOptions::default()).unwrap();
+        assert_eq!(stitched.height(), 400);
+        // Ab Zeile 210 stammt alles aus den unveränderten Frames
+        assert_eq!(rows(&stitched, 0, 150), frames[0]);
+        assert_eq!(rows(&stitched, 210, 190), rows(&content, 210, 190));
+    }
+
+    #[test]
+    fn frames_without_overlap_are_rejected() {
+        let content = page(600);
+        let frames = frames(&content, 100, &[0, 300], 0, 0);
+        assert!(stitch(&frames, &StitchOptions::default()).is_err());
+
+        let mut frames = frames.clone();
+        frames.push(bar(50, [0, 0, 0]));
+        assert!(stitch(&frames, &StitchOptions::default()).is_err());
+        assert!(stitch(&[], &StitchOptions::default()).is_err());
+    }
+
+    #[test]
+    fn unchanged_frames_give_the_frame_itself() {
+        let content = page(200);
+        let frames = frames(&content, 120, &[30, 30, 30], 10, 0);
+        let stitched = stitch(&frames, &StitchOptions::default()).unwrap();
+        assert_eq!(stitched, frames[0]);
+    }
+}
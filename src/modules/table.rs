@@ -0,0 +1,349 @@
+use crate::modules::errorhandler::SnipError;
+use crate::modules::ocr::OcrWord;
+use crate::modules::text::group_lines;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+// Erkennt Tabellen allein aus der Lage der Wortboxen: Zeilen über die vertikale Überlappung,
+// Zellen über größere Lücken innerhalb einer Zeile, Spalten über die horizontale Überlappung der Zellen
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TableFormat {
+    #[default]
+    Csv,
+    Tsv,
+    Markdown,
+}
+
+impl TableFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TableFormat::Csv => "csv",
+            TableFormat::Tsv => "tsv",
+            TableFormat::Markdown => "markdown",
+        }
+    }
+}
+
+impl FromStr for TableFormat {
+    type Err = SnipError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(TableFormat::Csv),
+            "tsv" => Ok(TableFormat::Tsv),
+            "markdown" | "md" => Ok(TableFormat::Markdown),
+            _ => Err(SnipError::Config(format!(
+                "Unknown table format '{}', expected csv, tsv or markdown",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for TableFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TableOptions {
+    // Ab dieser Lücke, gemessen in Texthöhen, beginnt innerhalb einer Zeile eine neue Zelle
+    pub cell_gap: f32,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        TableOptions { cell_gap: 1.0 }
+    }
+}
+
+// Alle Zeilen haben gleich viele Spalten; fehlende Zellen sind leer
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Table {
+    pub rows: Vec<Vec<String>>,
+}
+
+struct Cell {
+    text: String,
+    left: i32,
+    right: i32,
+}
+
+fn split_cells(words: &[OcrWord], gap: i32) -> Vec<Cell> {
+    let mut cells: Vec<Cell> = Vec::new();
+    for word in words {
+        match cells.last_mut() {
+            Some(cell) if word.bounds.x - cell.right <= gap => {
+                cell.text.push(' ');
+                cell.text.push_str(&word.text);
+                cell.right = cell.right.max(word.bounds.right());
+            }
+            _ => cells.push(Cell {
+                text: word.text.clone(),
+                left: word.bounds.x,
+                right: word.bounds.right(),
+            }),
+        }
+    }
+    cells
+}
+
+// Spalten als zusammenhängende Bereiche überlappender Zellen. Zeilen mit nur einer Zelle,
+// z.B. Überschriften über die ganze Breite, würden sonst alle Spalten verschmelzen.
+fn column_spans(rows: &[Vec<Cell>]) -> Vec<(i32, i32)> {
+    let multi = rows.iter().any(|row| row.len() > 1);
+    let mut spans: Vec<(i32, i32)> = rows
+        .iter()
+        .filter(|row| !multi || row.len() > 1)
+        .flatten()
+        .map(|cell| (cell.left, cell.right))
+        .collect();
+    spans.sort_unstable();
+
+    let mut columns: Vec<(i32, i32)> = Vec::new();
+    for (left, right) in spans {
+        match columns.last_mut() {
+            Some(column) if left < column.1 => column.1 = column.1.max(right),
+            _ => columns.push((left, right)),
+        }
+    }
+    columns
+}
+
+// Spalte mit der größten Überlappung; bei Gleichstand zählt der Abstand der linken Kanten
+fn column_of(cell: &Cell, columns: &[(i32, i32)]) -> usize {
+    columns
+        .iter()
+        .enumerate()
+        .max_by_key(|(_, (left, right))| {
+            let overlap = cell.right.min(*right) - cell.left.max(*left);
+            (overlap.max(0), -(cell.left - left).abs())
+        })
+        .map(|(index, _)| index)
+        .unwrap_or(0)
+}
+
+impl Table {
+    pub fn detect(words: &[OcrWord], options: &TableOptions) -> Table {
+        let mut heights: Vec<u32> = words.iter().map(|word| word.bounds.height).collect();
+        heights.sort_unstable();
+        let text_height = heights.get(heights.len() / 2).copied().unwrap_or(0);
+        let gap = (text_height as f32 * options.cell_gap).round() as i32;
+
+        let rows: Vec<Vec<Cell>> = group_lines(words)
+            .iter()
+            .map(|line| split_cells(&line.words, gap))
+            .collect();
+        let columns = column_spans(&rows);
+
+        let rows = rows
+            .into_iter()
+            .map(|cells| {
+                let mut row = vec![String::new(); columns.len()];
+                for cell in cells {
+                    let text = &mut row[column_of(&cell, &columns)];
+                    if !text.is_empty() {
+                        text.push(' ');
+                    }
+                    text.push_str(&cell.text);
+                }
+                row
+            })
+            .collect();
+        Table { rows }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.rows.first().map_or(0, Vec::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn render(&self, format: TableFormat) -> String {
+        match format {
+            TableFormat::Csv => self.to_csv(),
+            TableFormat::Tsv => self.to_tsv(),
+            TableFormat::Markdown => self.to_markdown(),
+        }
+    }
+
+    // Nach RFC 4180: Felder mit Trennzeichen, Anführungszeichen oder Umbrüchen werden gequotet
+    pub fn to_csv(&self) -> String {
+        self.join_rows(",", |cell| {
+            if cell.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.to_string()
+            }
+        })
+    }
+
+    // TSV kennt kein Quoting, Tabs und Umbrüche in Zellen werden zu Leerzeichen
+    pub fn to_tsv(&self) -> String {
+        self.join_rows("\t", |cell| cell.replace(['\t', '\n', '\r'], " "))
+    }
+
+    // Die erste Zeile wird zur Kopfzeile
+    pub fn to_markdown(&self) -> String {
+        if self.is_empty() {
+            return String::new();
+        }
+
+        let line = |row: &[String]| {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|cell| cell.replace('|', "\\|").replace(['\n', '\r'], " "))
+                .collect();
+            format!("| {} |", cells.join(" | "))
+        };
+
+        let mut lines = vec![line(&self.rows[0])];
+        lines.push(format!("|{}", " --- |".repeat(self.columns())));
+        lines.extend(self.rows[1..].iter().map(|row| line(row)));
+        lines.join("\n")
+    }
+
+    fn join_rows<F: Fn(&str) -> String>(&self, separator: &str, escape: F) -> String {
+        self.rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| escape(cell))
+                    .collect::<Vec<_>>()
+                    .join(separator)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::backend::PixelRect;
+
+    fn word(text: &str, x: i32, y: i32) -> OcrWord {
+        OcrWord::new(
+            text,
+            PixelRect::new(x, y, text.chars().count() as u32 * 7, 12),
+        )
+    }
+
+    fn table(words: &[OcrWord]) -> Table {
+        Table::detect(words, &TableOptions::default())
+    }
+
+    fn rows(table: &Table) -> Vec<Vec<&str>> {
+        table
+            .rows
+            .iter()
+            .map(|row| row.iter().map(String::as_str).collect())
+            .collect()
+    }
+
+    // Ausschnitt eines Dashboards: mehrteilige Namen, rechtsbündige Zahlen, eine leere Zelle
+    fn dashboard() -> Vec<OcrWord> {
+        vec![
+            word("Service", 10, 5),
+            word("Requests", 160, 5),
+            word("Errors", 260, 5),
+            word("api", 10, 25),
+            word("gateway", 38, 25),
+            word("1.204.332", 160, 26),
+            word("17", 281, 24),
+            word("auth", 10, 45),
+            word("89.120", 181, 45),
+            word("search", 10, 65),
+            word("index", 59, 65),
+            word("5.002", 188, 66),
+            word("1.203", 260, 64),
+        ]
+    }
+
+    #[test]
+    fn dashboard_fixture_becomes_a_grid() {
+        assert_eq!(
+            rows(&table(&dashboard())),
+            vec![
+                vec!["Service", "Requests", "Errors"],
+                vec!["api gateway", "1.204.332", "17"],
+                vec!["auth", "89.120", ""],
+                vec!["search index", "5.002", "1.203"],
+            ]
+        );
+    }
+
+    #[test]
+    fn input_order_does_not_matter() {
+        let mut words = dashboard();
+        words.reverse();
+        assert_eq!(table(&words), table(&dashboard()));
+    }
+
+    #[test]
+    fn full_width_title_does_not_merge_columns() {
+        let mut words = vec![word("Quarterly", 10, 0), word("revenue", 80, 0)];
+        words.extend(
+            [("Q1", 10), ("Q2", 100), ("Q3", 190)]
+                .iter()
+                .map(|(text, x)| word(text, *x, 20)),
+        );
+        words.extend(
+            [("10", 10), ("12", 100), ("9", 190)]
+                .iter()
+                .map(|(text, x)| word(text, *x, 40)),
+        );
+
+        let table = table(&words);
+        assert_eq!(table.columns(), 3);
+        assert_eq!(table.rows[0], vec!["Quarterly revenue", "", ""]);
+        assert_eq!(table.rows[2], vec!["10", "12", "9"]);
+    }
+
+    #[test]
+    fn csv_quotes_only_where_needed() {
+        let table = Table {
+            rows: vec![
+                vec!["name".into(), "note".into()],
+                vec!["a,b".into(), "say \"hi\"".into()],
+            ],
+        };
+        assert_eq!(table.to_csv(), "name,note\n\"a,b\",\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn tsv_and_markdown_rendering() {
+        let table = Table {
+            rows: vec![
+                vec!["key".into(), "value".into()],
+                vec!["pipe".into(), "a|b".into()],
+                vec!["tab".into(), "x\ty".into()],
+            ],
+        };
+        assert_eq!(table.to_tsv(), "key\tvalue\npipe\ta|b\ntab\tx y");
+        assert_eq!(
+            table.to_markdown(),
+            "| key | value |\n| --- | --- |\n| pipe | a\\|b |\n| tab | x\ty |"
+        );
+    }
+
+    #[test]
+    fn format_names() {
+        assert_eq!("MD".parse::<TableFormat>().unwrap(), TableFormat::Markdown);
+        assert_eq!("tsv".parse::<TableFormat>().unwrap(), TableFormat::Tsv);
+        assert!("xlsx".parse::<TableFormat>().is_err());
+    }
+
+    #[test]
+    fn no_words_no_table() {
+        let table = table(&[]);
+        assert!(table.is_empty());
+        assert_eq!(table.render(TableFormat::Markdown), "");
+    }
+}
@@ -0,0 +1,253 @@
+use crate::modules::backend::PixelRect;
+use crate::modules::ocr::{OcrLine, OcrWord};
+
+// Setzt erkannte Wortboxen wieder zu Fließtext zusammen, unabhängig von der OCR-Engine.
+// Zeilen ergeben sich aus der vertikalen Überlappung, Absätze aus größeren Zeilenabständen.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextOptions {
+    // "Trenn-\nung" wird zu "Trennung" zusammengefügt
+    pub join_hyphenated: bool,
+    // Ab diesem Abstand zwischen zwei Zeilen, gemessen in Zeilenhöhen, beginnt ein neuer Absatz
+    pub paragraph_gap: f32,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions {
+            join_hyphenated: false,
+            paragraph_gap: 0.8,
+        }
+    }
+}
+
+fn center_y(bounds: &PixelRect) -> f32 {
+    bounds.y as f32 + bounds.height as f32 / 2.0
+}
+
+// Gehört das Wort zur Zeile? Die Boxen müssen sich um mindestens die halbe Höhe überlappen
+fn overlaps(line: &PixelRect, word: &PixelRect) -> i32 {
+    let overlap = line.bottom().min(word.bottom()) - line.y.max(word.y);
+    let height = line.height.min(word.height) as i32;
+    if overlap * 2 >= height {
+        overlap
+    } else {
+        0
+    }
+}
+
+// Ordnet die Wörter in Lesereihenfolge zu Zeilen: von oben nach unten, innerhalb der Zeile von links
+pub fn group_lines(words: &[OcrWord]) -> Vec<OcrLine> {
+    let mut sorted: Vec<&OcrWord> = words.iter().filter(|word| !word.text.is_empty()).collect();
+    sorted.sort_by(|a, b| {
+        center_y(&a.bounds)
+            .total_cmp(&center_y(&b.bounds))
+            .then(a.bounds.x.cmp(&b.bounds.x))
+    });
+
+    let mut lines: Vec<(PixelRect, Vec<OcrWord>)> = Vec::new();
+    for word in sorted {
+        let best = lines
+            .iter()
+            .enumerate()
+            .map(|(index, (bounds, _))| (index, overlaps(bounds, &word.bounds)))
+            .filter(|(_, overlap)| *overlap > 0)
+            .max_by_key(|(_, overlap)| *overlap);
+
+        match best {
+            Some((index, _)) => {
+                let (bounds, line) = &mut lines[index];
+                *bounds = bounds.union(&word.bounds);
+                line.push(word.clone());
+            }
+            None => lines.push((word.bounds, vec![word.clone()])),
+        }
+    }
+
+    lines.sort_by(|(a, _), (b, _)| center_y(a).total_cmp(&center_y(b)));
+    lines
+        .into_iter()
+        .map(|(_, mut words)| {
+            words.sort_by_key(|word| word.bounds.x);
+            OcrLine::new(words)
+        })
+        .collect()
+}
+
+// Fasst Zeilen zu Absätzen zusammen
+pub fn group_paragraphs(lines: Vec<OcrLine>, paragraph_gap: f32) -> Vec<Vec<OcrLine>> {
+    let mut heights: Vec<u32> = lines
+        .iter()
+        .filter_map(OcrLine::bounds)
+        .map(|bounds| bounds.height)
+        .collect();
+    heights.sort_unstable();
+    let line_height = heights.get(heights.len() / 2).copied().unwrap_or(0) as f32;
+
+    let mut paragraphs: Vec<Vec<OcrLine>> = Vec::new();
+    let mut previous: Option<PixelRect> = None;
+    for line in lines {
+        let bounds = match line.bounds() {
+            Some(bounds) => bounds,
+            None => continue,
+        };
+        let gap = previous.map(|previous| (bounds.y - previous.bottom()) as f32);
+
+        match (gap, paragraphs.last_mut()) {
+            (Some(gap), Some(paragraph)) if gap <= line_height * paragraph_gap => {
+                paragraph.push(line)
+            }
+            _ => paragraphs.push(vec![line]),
+        }
+        previous = Some(bounds);
+    }
+    paragraphs
+}
+
+// Trennstrich am Zeilenende nach einem Buchstaben, auch als weiches Trennzeichen
+fn strip_hyphen(word: &str) -> Option<&str> {
+    let stem = word
+        .strip_suffix('-')
+        .or_else(|| word.strip_suffix('\u{ad}'))?;
+    stem.chars()
+        .last()
+        .is_some_and(char::is_alphabetic)
+        .then_some(stem)
+}
+
+// Zieht den ersten Teil des getrennten Wortes aus der Folgezeile nach oben;
+// der Rest der Folgezeile bleibt eine eigene Zeile
+fn join_hyphenated(lines: &mut Vec<Vec<String>>) {
+    let mut index = 0;
+    while index + 1 < lines.len() {
+        let joined = match (lines[index].last(), lines[index + 1].first()) {
+            (Some(last), Some(next)) if next.starts_with(char::is_lowercase) => {
+                strip_hyphen(last).map(|stem| format!("{}{}", stem, next))
+            }
+            _ => None,
+        };
+
+        if let Some(word) = joined {
+            lines[index].pop();
+            lines[index].push(word);
+            lines[index + 1].remove(0);
+            if lines[index + 1].is_empty() {
+                lines.remove(index + 1);
+                // Die übernächste Zeile kann wieder mit einem getrennten Wort anschließen
+                continue;
+            }
+        }
+        index += 1;
+    }
+}
+
+// Zeilen durch Zeilenumbrüche, Absätze durch eine Leerzeile getrennt
+pub fn assemble(words: &[OcrWord], options: &TextOptions) -> String {
+    group_paragraphs(group_lines(words), options.paragraph_gap)
+        .into_iter()
+        .map(|paragraph| {
+            let mut lines: Vec<Vec<String>> = paragraph
+                .into_iter()
+                .map(|line| line.words.into_iter().map(|word| word.text).collect())
+                .collect();
+            if options.join_hyphenated {
+                join_hyphenated(&mut lines);
+            }
+            lines
+                .iter()
+                .map(|line| line.join(" "))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str, x: i32, y: i32) -> OcrWord {
+        OcrWord::new(text, PixelRect::new(x, y, text.len() as u32 * 8, 12))
+    }
+
+    #[test]
+    fn orders_shuffled_words_into_lines() {
+        let words = vec![
+            word("Welt", 48, 1),
+            word("zweite", 0, 18),
+            word("Hallo", 0, 0),
+            word("Zeile", 56, 17),
+        ];
+        assert_eq!(
+            assemble(&words, &TextOptions::default()),
+            "Hallo Welt\nzweite Zeile"
+        );
+    }
+
+    #[test]
+    fn slightly_skewed_words_stay_on_their_line() {
+        // Schräg eingescannt: jedes Wort liegt 3px tiefer als das vorherige
+        let words: Vec<OcrWord> = ["a", "b", "c", "d"]
+            .iter()
+            .enumerate()
+            .map(|(i, text)| word(text, i as i32 * 20, i as i32 * 3))
+            .chain(std::iter::once(word("next", 0, 20)))
+            .collect();
+        assert_eq!(assemble(&words, &TextOptions::default()), "a b c d\nnext");
+    }
+
+    #[test]
+    fn large_gaps_start_a_new_paragraph() {
+        let words = vec![word("eins", 0, 0), word("zwei", 0, 16), word("drei", 0, 60)];
+        assert_eq!(
+            assemble(&words, &TextOptions::default()),
+            "eins\nzwei\n\ndrei"
+        );
+    }
+
+    #[test]
+    fn joins_hyphenated_line_ends_only_when_enabled() {
+        let words = vec![
+            word("eine", 0, 0),
+            word("Silben-", 40, 0),
+            word("trennung", 0, 16),
+            word("hier", 72, 16),
+            word("Nord-", 0, 32),
+            word("Süd", 0, 48),
+        ];
+        let joined = TextOptions {
+            join_hyphenated: true,
+            ..TextOptions::default()
+        };
+
+        assert_eq!(
+            assemble(&words, &TextOptions::default()),
+            "eine Silben-\ntrennung hier\nNord-\nSüd"
+        );
+        // Großgeschriebene Fortsetzungen wie "Nord-\nSüd" bleiben getrennt
+        assert_eq!(
+            assemble(&words, &joined),
+            "eine Silbentrennung\nhier\nNord-\nSüd"
+        );
+    }
+
+    #[test]
+    fn joined_line_that_becomes_empty_is_dropped() {
+        let words = vec![
+            word("Donau-", 0, 0),
+            word("dampf-", 0, 16),
+            word("schiff", 0, 32),
+        ];
+        let options = TextOptions {
+            join_hyphenated: true,
+            ..TextOptions::default()
+        };
+        assert_eq!(assemble(&words, &options), "Donaudampfschiff");
+    }
+
+    #[test]
+    fn empty_input_gives_empty_text() {
+        assert_eq!(assemble(&[], &TextOptions::default()), "");
+    }
+}
@@ -0,0 +1,417 @@
+use crate::modules::errorhandler::SnipError;
+use image::RgbaImage;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+// Minimaler verlustfreier WebP-Encoder (VP8L): keine Transformationen, kein LZ77,
+// nur eine Huffman-Codierung pro Farbkanal. image 0.23 kann WebP nur lesen.
+
+const SIGNATURE: u32 = 0x2f;
+pub const MAX_DIMENSION: u32 = 1 << 14;
+const GREEN_ALPHABET: usize = 256 + 24;
+const CHANNEL_ALPHABET: usize = 256;
+const DISTANCE_ALPHABET: usize = 40;
+const MAX_CODE_LENGTH: u8 = 15;
+const MAX_CODE_LENGTH_CODE_LENGTH: u8 = 7;
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+// Der Decoder beginnt mit dieser Länge, wenn Symbol 16 vor einem Literal kommt
+const DEFAULT_CODE_LENGTH: u8 = 8;
+
+#[derive(Default)]
+struct BitWriter {
+    buffer: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    // VP8L schreibt die Bits beginnend mit dem niederwertigsten
+    fn write(&mut self, value: u32, n: u32) {
+        self.bits |= (value as u64) << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.buffer.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.buffer.push(self.bits as u8);
+        }
+        self.buffer
+    }
+}
+
+fn huffman_lengths(counts: &[u32]) -> Vec<u8> {
+    let mut lengths = vec![0u8; counts.len()];
+    let mut parent: Vec<usize> = Vec::new();
+    let mut heap = BinaryHeap::new();
+
+    for (symbol, &count) in counts.iter().enumerate() {
+        if count > 0 {
+            heap.push(Reverse((count as u64, parent.len(), symbol)));
+            parent.push(usize::MAX);
+        }
+    }
+
+    let leaves = parent.len();
+    if leaves == 1 {
+        if let Some(Reverse((_, _, symbol))) = heap.pop() {
+            lengths[symbol] = 1;
+        }
+        return lengths;
+    }
+
+    let mut symbols = vec![0usize; leaves];
+    for Reverse((_, node, symbol)) in heap.iter() {
+        symbols[*node] = *symbol;
+    }
+
+    while heap.len() > 1 {
+        let Reverse((a_weight, a, _)) = heap.pop().unwrap();
+        let Reverse((b_weight, b, _)) = heap.pop().unwrap();
+        let node = parent.len();
+        parent.push(usize::MAX);
+        parent[a] = node;
+        parent[b] = node;
+        heap.push(Reverse((a_weight + b_weight, node, usize::MAX)));
+    }
+
+    for (leaf, &symbol) in symbols.iter().enumerate() {
+        let mut depth = 0u8;
+        let mut node = leaf;
+        while parent[node] != usize::MAX {
+            node = parent[node];
+            depth += 1;
+        }
+        lengths[symbol] = depth;
+    }
+
+    lengths
+}
+
+// Flacht die Häufigkeiten ab, bis kein Code länger als `limit` ist
+fn code_lengths(histogram: &[u32], limit: u8) -> Vec<u8> {
+    let mut counts = histogram.to_vec();
+    loop {
+        let lengths = huffman_lengths(&counts);
+        if lengths.iter().all(|&length| length <= limit) {
+            return lengths;
+        }
+        for count in counts.iter_mut().filter(|count| **count > 0) {
+            *count = (*count >> 1).max(1);
+        }
+    }
+}
+
+struct PrefixCode {
+    lengths: Vec<u8>,
+    codes: Vec<u16>,
+    // Ein Code mit nur einem Symbol wird vom Decoder ohne Bits gelesen
+    single: bool,
+}
+
+impl PrefixCode {
+    fn new(lengths: Vec<u8>) -> Self {
+        let single = lengths.iter().filter(|&&length| length > 0).count() == 1;
+
+        let mut bl_count = [0u16; 16];
+        for &length in lengths.iter().filter(|&&length| length > 0) {
+            bl_count[length as usize] += 1;
+        }
+
+        let mut next_code = [0u16; 16];
+        let mut code = 0u16;
+        for bits in 1..16 {
+            code = (code + bl_count[bits - 1]) << 1;
+            next_code[bits] = code;
+        }
+
+        // Kanonische Codes, bitweise umgedreht, da der Decoder sie vom höchsten Bit an liest
+        let codes = lengths
+            .iter()
+            .map(|&length| {
+                if length == 0 {
+                    return 0;
+                }
+                let code = next_code[length as usize];
+                next_code[length as usize] += 1;
+                code.reverse_bits() >> (16 - length as u32)
+            })
+            .collect();
+
+        PrefixCode {
+            lengths,
+            codes,
+            single,
+        }
+    }
+
+    fn write_symbol(&self, writer: &mut BitWriter, symbol: usize) {
+        if !self.single {
+            writer.write(self.codes[symbol] as u32, self.lengths[symbol] as u32);
+        }
+    }
+}
+
+// (Symbol, Wert der Zusatzbits, Anzahl der Zusatzbits) für die Codelängen-Codierung
+fn run_length_tokens(lengths: &[u8]) -> Vec<(usize, u32, u32)> {
+    let mut tokens = Vec::new();
+    let mut previous = DEFAULT_CODE_LENGTH;
+    let mut i = 0;
+
+    while i < lengths.len() {
+        let value = lengths[i];
+        let mut run = 1;
+        while i + run < lengths.len() && lengths[i + run] == value {
+            run += 1;
+        }
+        i += run;
+
+        if value == 0 {
+            while run >= 11 {
+                let n = run.min(138);
+                tokens.push((18, (n - 11) as u32, 7));
+                run -= n;
+            }
+            if run >= 3 {
+                tokens.push((17, (run - 3) as u32, 3));
+                run = 0;
+            }
+            tokens.extend(std::iter::repeat_n((0, 0, 0), run));
+            continue;
+        }
+
+        if value != previous {
+            tokens.push((value as usize, 0, 0));
+            previous = value;
+            run -= 1;
+        }
+        while run >= 3 {
+            let n = run.min(6);
+            tokens.push((16, (n - 3) as u32, 2));
+            run -= n;
+        }
+        tokens.extend(std::iter::repeat_n((value as usize, 0, 0), run));
+    }
+
+    tokens
+}
+
+fn write_prefix_code(writer: &mut BitWriter, histogram: &[u32]) -> PrefixCode {
+    let used: Vec<usize> = (0..histogram.len()).filter(|&s| histogram[s] > 0).collect();
+
+    if used.len() <= 2 && used.iter().all(|&symbol| symbol < 256) {
+        // Einfacher Code mit einem oder zwei Symbolen
+        let symbols = if used.is_empty() { vec![0] } else { used };
+        writer.write(1, 1);
+        writer.write(symbols.len() as u32 - 1, 1);
+        if symbols[0] < 2 {
+            writer.write(0, 1);
+            writer.write(symbols[0] as u32, 1);
+        } else {
+            writer.write(1, 1);
+            writer.write(symbols[0] as u32, 8);
+        }
+        if let Some(&second) = symbols.get(1) {
+            writer.write(second as u32, 8);
+        }
+
+        let mut lengths = vec![0u8; histogram.len()];
+        if symbols.len() == 2 {
+            lengths[symbols[0]] = 1;
+            lengths[symbols[1]] = 1;
+        }
+        return PrefixCode::new(lengths);
+    }
+
+    writer.write(0, 1);
+    let lengths = code_lengths(histogram, MAX_CODE_LENGTH);
+    let tokens = run_length_tokens(&lengths);
+
+    let mut token_histogram = [0u32; 19];
+    for &(symbol, _, _) in &tokens {
+        token_histogram[symbol] += 1;
+    }
+    let length_code = PrefixCode::new(code_lengths(&token_histogram, MAX_CODE_LENGTH_CODE_LENGTH));
+
+    let written = CODE_LENGTH_ORDER
+        .iter()
+        .rposition(|&symbol| length_code.lengths[symbol] > 0)
+        .map_or(4, |last| (last + 1).max(4));
+    writer.write(written as u32 - 4, 4);
+    for &symbol in &CODE_LENGTH_ORDER[..written] {
+        writer.write(length_code.lengths[symbol] as u32, 3);
+    }
+
+    // Alle Symbole des Alphabets werden übertragen
+    writer.write(0, 1);
+    for &(symbol, extra, extra_bits) in &tokens {
+        length_code.write_symbol(writer, symbol);
+        writer.write(extra, extra_bits);
+    }
+
+    PrefixCode::new(lengths)
+}
+
+pub fn encode_lossless(image: &RgbaImage) -> Result<Vec<u8>, SnipError> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 || width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(SnipError::Encode {
+            format: "WebP",
+            message: format!("{}x{} exceeds the WebP size limits", width, height),
+        });
+    }
+
+    let mut green = vec![0u32; GREEN_ALPHABET];
+    let mut red = vec![0u32; CHANNEL_ALPHABET];
+    let mut blue = vec![0u32; CHANNEL_ALPHABET];
+    let mut alpha = vec![0u32; CHANNEL_ALPHABET];
+    for p in image.pixels() {
+        red[p[0] as usize] += 1;
+        green[p[1] as usize] += 1;
+        blue[p[2] as usize] += 1;
+        alpha[p[3] as usize] += 1;
+    }
+    let has_alpha = alpha[255] as u64 != width as u64 * height as u64;
+
+    let mut writer = BitWriter::default();
+    writer.write(SIGNATURE, 8);
+    writer.write(width - 1, 14);
+    writer.write(height - 1, 14);
+    writer.write(has_alpha as u32, 1);
+    writer.write(0, 3);
+
+    // Keine Transformation, kein Farbcache, kein Meta-Präfixbild
+    writer.write(0, 1);
+    writer.write(0, 1);
+    writer.write(0, 1);
+
+    let green_code = write_prefix_code(&mut writer, &green);
+    let red_code = write_prefix_code(&mut writer, &red);
+    let blue_code = write_prefix_code(&mut writer, &blue);
+    let alpha_code = write_prefix_code(&mut writer, &alpha);
+    write_prefix_code(&mut writer, &[0u32; DISTANCE_ALPHABET]);
+
+    for p in image.pixels() {
+        green_code.write_symbol(&mut writer, p[1] as usize);
+        red_code.write_symbol(&mut writer, p[0] as usize);
+        blue_code.write_symbol(&mut writer, p[2] as usize);
+        alpha_code.write_symbol(&mut writer, p[3] as usize);
+    }
+
+    let data = writer.finish();
+    let padding = data.len() % 2;
+
+    let mut riff = Vec::with_capacity(data.len() + 20 + padding);
+    riff.extend_from_slice(b"RIFF");
+    riff.extend_from_slice(&((4 + 8 + data.len() + padding) as u32).to_le_bytes());
+    riff.extend_from_slice(b"WEBP");
+    riff.extend_from_slice(b"VP8L");
+    riff.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    riff.extend_from_slice(&data);
+    if padding == 1 {
+        riff.push(0);
+    }
+
+    Ok(riff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+    use image_webp::WebPDecoder;
+    use std::io::Cursor;
+
+    // Dekodiert mit einem unabhängigen Decoder, immer als RGBA
+    fn decode(data: &[u8]) -> RgbaImage {
+        let mut decoder = WebPDecoder::new(Cursor::new(data)).unwrap();
+        let (width, height) = decoder.dimensions();
+        let mut buffer = vec![0; decoder.output_buffer_size().unwrap()];
+        decoder.read_image(&mut buffer).unwrap();
+        if decoder.has_alpha() {
+            return RgbaImage::from_raw(width, height, buffer).unwrap();
+        }
+        let rgba = buffer
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect();
+        RgbaImage::from_raw(width, height, rgba).unwrap()
+    }
+
+    fn round_trip(image: &RgbaImage) {
+        let data = encode_lossless(image).unwrap();
+        assert_eq!(&data[..4], b"RIFF");
+        assert_eq!(&data[8..16], b"WEBPVP8L");
+        assert_eq!(data.len() % 2, 0);
+        assert_eq!(&decode(&data), image);
+    }
+
+    #[test]
+    fn noisy_image_round_trips() {
+        // Fast alle Werte je Kanal kommen vor, dazu eine Alphastufe
+        let mut state = 0x2545_f491u32;
+        let image = RgbaImage::from_fn(67, 41, |x, _| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let [r, g, b, _] = state.to_le_bytes();
+            Rgba([r, g, b, if x % 7 == 0 { 128 } else { 255 }])
+        });
+        round_trip(&image);
+    }
+
+    #[test]
+    fn opaque_gradient_round_trips() {
+        let image = RgbaImage::from_fn(300, 20, |x, y| {
+            Rgba([(x % 256) as u8, (y * 12) as u8, (x / 2) as u8, 255])
+        });
+        round_trip(&image);
+    }
+
+    #[test]
+    fn single_and_two_colour_images_round_trip() {
+        round_trip(&RgbaImage::from_pixel(5, 3, Rgba([10, 200, 30, 255])));
+        round_trip(&RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 0])));
+        round_trip(&RgbaImage::from_fn(9, 4, |x, y| {
+            if (x + y) % 2 == 0 {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([0, 1, 0, 255])
+            }
+        }));
+    }
+
+    #[test]
+    fn code_lengths_are_limited() {
+        // Fibonacci-Häufigkeiten ergeben ohne Begrenzung Codes mit über 20 Bit
+        let mut histogram = vec![1u32, 1];
+        while histogram.len() < 30 {
+            let next = histogram[histogram.len() - 1] + histogram[histogram.len() - 2];
+            histogram.push(next);
+        }
+        assert!(huffman_lengths(&histogram)
+            .iter()
+            .any(|&length| length > 15));
+
+        let lengths = code_lengths(&histogram, MAX_CODE_LENGTH);
+        assert!(lengths.iter().all(|&length| (1..=15).contains(&length)));
+        // Kraft-Ungleichung: die Codes lassen sich präfixfrei vergeben
+        let kraft: f64 = lengths.iter().map(|&l| 0.5f64.powi(l as i32)).sum();
+        assert!(kraft <= 1.0);
+    }
+
+    #[test]
+    fn oversized_images_are_rejected() {
+        assert!(encode_lossless(&RgbaImage::new(MAX_DIMENSION + 1, 1)).is_err());
+        assert!(encode_lossless(&RgbaImage::new(0, 0)).is_err());
+        // image-webp liest 16384 als 0, daher ohne Dekodieren
+        assert!(encode_lossless(&RgbaImage::new(MAX_DIMENSION, 1)).is_ok());
+        round_trip(&RgbaImage::new(MAX_DIMENSION - 1, 2));
+    }
+}
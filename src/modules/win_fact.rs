@@ -1,21 +1,34 @@
+use crate::modules::annotation::Shape;
+use crate::modules::backend::{
+    Backend, Color, Frame, Overlay, PixelRect, Rect, Surface, WindowType,
+};
+use crate::modules::controller::WindowController;
 use crate::modules::drawing::Drawing;
+use crate::modules::errorhandler::{self, SnipError};
+use crate::modules::handler::{opaque_handler, win_proc};
+use crate::modules::resource_manager::ResourceManager;
 
+use image::RgbaImage;
 use std::os::raw::c_void;
+use std::sync::Arc;
 use windows::{
     core::{w, Error, PCWSTR},
     Win32::{
-        Foundation::{HINSTANCE, HWND, LPARAM, LRESULT, WPARAM},
+        Foundation::{BOOL, HINSTANCE, HWND, LPARAM, LRESULT, RECT, TRUE, WPARAM},
         Graphics::{
             Direct2D::Common::{D2D1_COLOR_F, D2D_RECT_F},
             Gdi::{
-                RedrawWindow, RDW_ERASE, RDW_INTERNALPAINT, RDW_INVALIDATE, RDW_NOINTERNALPAINT,
+                EnumDisplayMonitors, RedrawWindow, HDC, HMONITOR, RDW_ERASE, RDW_INTERNALPAINT,
+                RDW_INVALIDATE, RDW_NOINTERNALPAINT,
             },
         },
         System::LibraryLoader::GetModuleHandleW,
+        UI::HiDpi::{SetProcessDpiAwarenessContext, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2},
         UI::WindowsAndMessaging::{
-            CreateWindowExW, DefWindowProcW, DestroyWindow, GetSystemMetrics, LoadCursorW,
-            RegisterClassW, SetForegroundWindow, SetWindowPos, ShowWindow, CS_HREDRAW, CS_OWNDC,
-            CS_VREDRAW, CW_USEDEFAULT, HMENU, HWND_TOPMOST, IDC_ARROW, IDC_CROSS,
+            CreateWindowExW, DefWindowProcW, DestroyWindow, GetForegroundWindow, GetSystemMetrics,
+            GetWindowTextLengthW, GetWindowTextW, LoadCursorW, RegisterClassW, SetForegroundWindow,
+            SetWindowLongPtrA, SetWindowPos, ShowWindow, CS_HREDRAW, CS_OWNDC, CS_VREDRAW,
+            CW_USEDEFAULT, GWLP_USERDATA, HMENU, HWND_TOPMOST, IDC_ARROW, IDC_CROSS,
             SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN,
             SWP_NOMOVE, SWP_NOSIZE, SW_HIDE, SW_SHOW, WINDOW_EX_STYLE, WINDOW_STYLE, WNDCLASSW,
             WS_EX_COMPOSITED, WS_EX_NOREDIRECTIONBITMAP, WS_POPUP,
@@ -37,9 +50,23 @@ impl Window {
             drawing: None,
         }
     }
-    pub fn draw_overlay(&self, rect: Option<D2D_RECT_F>) -> Result<(), Error> {
+    pub fn draw_overlay(&self, overlay: &Overlay) -> Result<(), Error> {
+        match &self.drawing {
+            Some(drawing) => drawing.draw_overlay(self.hwnd, overlay),
+            None => Err(Error::from_win32()),
+        }
+    }
+
+    pub fn draw_annotations(&self, shapes: &[Shape]) -> Result<(), Error> {
+        match &self.drawing {
+            Some(drawing) => drawing.draw_annotations(self.hwnd, shapes),
+            None => Err(Error::from_win32()),
+        }
+    }
+
+    pub fn set_backdrop(&self, image: &RgbaImage) -> Result<(), Error> {
         match &self.drawing {
-            Some(drawing) => drawing.draw_overlay(self.hwnd, rect),
+            Some(drawing) => drawing.set_backdrop(image),
             None => Err(Error::from_win32()),
         }
     }
@@ -90,16 +117,16 @@ impl Window {
 
     pub fn trigger_screenshot(&self) {
         unsafe {
-            println!("Trigger Screenshot");
-            let res = RedrawWindow(
+            let redrawn = RedrawWindow(
                 self.hwnd,
                 None,
                 None,
                 RDW_INVALIDATE | RDW_ERASE | RDW_NOINTERNALPAINT,
             );
-            match res.as_bool() {
-                false => eprintln!("Error in RedrawWindow"),
-                _ => println!("RedrawWindow successful"),
+            if !redrawn.as_bool() {
+                let error = SnipError::from(windows::core::Error::from_win32())
+                    .context("Failed to redraw the window");
+                errorhandler::report(&error);
             }
         }
     }
@@ -111,12 +138,65 @@ impl Window {
     }
 }
 
+impl Surface for Window {
+    fn window_type(&self) -> WindowType {
+        self.window_type
+    }
+
+    fn show(&self) {
+        Window::show(self);
+    }
+
+    fn hide(&self) {
+        Window::hide(self);
+    }
+
+    fn redraw(&self) {
+        self.redraw_window();
+    }
+
+    fn set_backdrop(&self, image: &RgbaImage) -> Result<(), anyhow::Error> {
+        Window::set_backdrop(self, image)?;
+        Ok(())
+    }
+
+    fn paint(&self, frame: &Frame) -> Result<(), anyhow::Error> {
+        match frame {
+            Frame::Overlay(overlay) => self.draw_overlay(overlay)?,
+            Frame::Background(color) => self.fill_background(D2D1_COLOR_F::from(*color))?,
+            Frame::Annotations(shapes) => self.draw_annotations(shapes)?,
+        }
+        Ok(())
+    }
+}
+
+impl From<Rect> for D2D_RECT_F {
+    fn from(rect: Rect) -> Self {
+        D2D_RECT_F {
+            left: rect.left,
+            top: rect.top,
+            right: rect.right,
+            bottom: rect.bottom,
+        }
+    }
+}
+
+impl From<Color> for D2D1_COLOR_F {
+    fn from(color: Color) -> Self {
+        D2D1_COLOR_F {
+            r: color.r,
+            g: color.g,
+            b: color.b,
+            a: color.a,
+        }
+    }
+}
+
 impl Drop for Window {
     fn drop(&mut self) {
         unsafe {
             if let Err(e) = DestroyWindow(self.hwnd) {
-                let error = anyhow::anyhow!("Error destroying window: {:?}", e);
-                eprintln!("{}", error);
+                errorhandler::report(&SnipError::from(e).context("Failed to destroy the window"));
             }
         }
     }
@@ -217,21 +297,111 @@ pub struct TransparentWindowFactory;
 pub struct OpaqueWindowFactory;
 pub struct MainWindowFactory;
 
+// Ohne DPI-Awareness liefern GetSystemMetrics und die Fensterkoordinaten auf skalierten
+// Monitoren logische Pixel, Desktop Duplication aber physische. Muss vor dem ersten Fenster
+// und vor virtual_screen aufgerufen werden.
+pub fn enable_dpi_awareness() {
+    unsafe {
+        // Schlägt fehl, wenn die Awareness schon gesetzt ist, z.B. über ein Manifest
+        let _ = SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+    }
+}
+
+// Umfasst alle Monitore; der Ursprung kann bei Monitoren links/oberhalb des Hauptmonitors negativ sein
+pub fn virtual_screen() -> PixelRect {
+    unsafe {
+        PixelRect::new(
+            GetSystemMetrics(SM_XVIRTUALSCREEN),
+            GetSystemMetrics(SM_YVIRTUALSCREEN),
+            GetSystemMetrics(SM_CXVIRTUALSCREEN).max(0) as u32,
+            GetSystemMetrics(SM_CYVIRTUALSCREEN).max(0) as u32,
+        )
+    }
+}
+
+unsafe extern "system" fn collect_monitor(
+    _monitor: HMONITOR,
+    _hdc: HDC,
+    rect: *mut RECT,
+    data: LPARAM,
+) -> BOOL {
+    let monitors = &mut *(data.0 as *mut Vec<PixelRect>);
+    let rect = *rect;
+    monitors.push(PixelRect::new(
+        rect.left,
+        rect.top,
+        (rect.right - rect.left).max(0) as u32,
+        (rect.bottom - rect.top).max(0) as u32,
+    ));
+    TRUE
+}
+
+// Alle Monitore in Desktop-Koordinaten, in der Reihenfolge von EnumDisplayMonitors
+pub fn monitors() -> Vec<PixelRect> {
+    let mut monitors: Vec<PixelRect> = Vec::new();
+    unsafe {
+        let _ = EnumDisplayMonitors(
+            HDC::default(),
+            None,
+            Some(collect_monitor),
+            LPARAM(&mut monitors as *mut Vec<PixelRect> as isize),
+        );
+    }
+    monitors
+}
+
+// Index des Monitors, der den größten Teil des Bereichs zeigt, in der Reihenfolge von
+// EnumDisplayMonitors. Der Bereich ist relativ zum virtuellen Bildschirm wie die Auswahl.
+pub fn monitor_index(region: PixelRect) -> Option<usize> {
+    let screen = virtual_screen();
+    let region = PixelRect::new(
+        region.x + screen.x,
+        region.y + screen.y,
+        region.width,
+        region.height,
+    );
+
+    monitors()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, monitor)| {
+            let overlap = monitor.intersection(&region)?;
+            Some((index, overlap.width as u64 * overlap.height as u64))
+        })
+        .max_by_key(|(_, area)| *area)
+        .map(|(index, _)| index)
+}
+
+// Muss vor dem Anzeigen des Overlays abgefragt werden, danach ist es selbst im Vordergrund
+pub fn foreground_window_title() -> Option<String> {
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        let length = GetWindowTextLengthW(hwnd);
+        if hwnd.0 == 0 || length <= 0 {
+            return None;
+        }
+        let mut buffer = vec![0u16; length as usize + 1];
+        let copied = GetWindowTextW(hwnd, &mut buffer);
+        (copied > 0).then(|| String::from_utf16_lossy(&buffer[..copied as usize]))
+    }
+}
+
 impl WindowFactory for TransparentWindowFactory {
     fn create_window(&self, builder: &WindowBuilder) -> Result<Window, anyhow::Error> {
         let window;
         unsafe {
             let mut template = WindowTemplate::new();
+            let screen = virtual_screen();
 
             template.windowprops = WINDOWPROPS {
                 lpclassname: w!("TransparentWindowClass"),
                 lpwindowname: w!("TransparentWindow"),
                 dwexstyle: WS_EX_NOREDIRECTIONBITMAP,
                 dwstyle: WS_POPUP,
-                x: GetSystemMetrics(SM_XVIRTUALSCREEN),
-                y: GetSystemMetrics(SM_YVIRTUALSCREEN),
-                nwidth: GetSystemMetrics(SM_CXVIRTUALSCREEN),
-                nheight: GetSystemMetrics(SM_CYVIRTUALSCREEN),
+                x: screen.x,
+                y: screen.y,
+                nwidth: screen.width as i32,
+                nheight: screen.height as i32,
                 ..Default::default()
             };
 
@@ -254,16 +424,17 @@ impl WindowFactory for OpaqueWindowFactory {
         let res;
         unsafe {
             let mut template = WindowTemplate::new();
+            let screen = virtual_screen();
 
             template.windowprops = WINDOWPROPS {
                 lpclassname: w!("OpaqueWindowClass"),
                 lpwindowname: w!("OpaqueWindow"),
                 dwexstyle: WS_EX_COMPOSITED,
                 dwstyle: WS_POPUP,
-                x: GetSystemMetrics(SM_XVIRTUALSCREEN),
-                y: GetSystemMetrics(SM_YVIRTUALSCREEN),
-                nwidth: GetSystemMetrics(SM_CXVIRTUALSCREEN),
-                nheight: GetSystemMetrics(SM_CYVIRTUALSCREEN),
+                x: screen.x,
+                y: screen.y,
+                nwidth: screen.width as i32,
+                nheight: screen.height as i32,
                 ..Default::default()
             };
 
@@ -341,14 +512,6 @@ pub struct WindowBuilder {
     window_type: WindowType,
 }
 
-#[derive(Clone, Copy)]
-pub enum WindowType {
-    Transparent,
-    Opaque,
-    Main,
-    None,
-}
-
 unsafe extern "system" fn default_window_proc(
     hwnd: HWND,
     msg: u32,
@@ -398,3 +561,54 @@ impl WindowBuilder {
         Ok(window)
     }
 }
+
+pub struct Win32Backend {
+    resource_manager: Arc<ResourceManager>,
+}
+
+impl Win32Backend {
+    pub fn new() -> Result<Self, anyhow::Error> {
+        let resource_manager = ResourceManager::new().map_err(|e| {
+            SnipError::backend_init("Failed to create the Direct2D and DirectComposition factories")
+                .caused_by(e)
+        })?;
+        Ok(Win32Backend { resource_manager })
+    }
+}
+
+impl Backend for Win32Backend {
+    fn screen_bounds(&self) -> PixelRect {
+        virtual_screen()
+    }
+
+    fn create_window(
+        &self,
+        window_type: WindowType,
+        controller: &WindowController,
+    ) -> Result<Box<dyn Surface>, anyhow::Error> {
+        let mut builder = WindowBuilder::new();
+        builder.set_window_type(window_type);
+        match window_type {
+            WindowType::Transparent => {
+                builder.set_window_proc(win_proc);
+            }
+            WindowType::Opaque => {
+                builder.set_window_proc(opaque_handler);
+            }
+            _ => {}
+        }
+
+        let mut window = builder.build()?;
+        let hwnd = window.get_hwnd();
+        let drawing = Drawing::new(hwnd, &self.resource_manager)?;
+
+        window.set_drawing(drawing);
+
+        // Set the WindowController pointer to the window
+        unsafe {
+            SetWindowLongPtrA(hwnd, GWLP_USERDATA, controller as *const _ as isize);
+        }
+
+        Ok(Box::new(window))
+    }
+}